@@ -0,0 +1,188 @@
+use futures::future::LocalBoxFuture;
+use image::DynamicImage;
+use js_sys::Uint8Array;
+use std::cell::RefCell;
+use std::collections::HashMap;
+use std::rc::Rc;
+use wasm_bindgen::JsCast;
+use wasm_bindgen_futures::JsFuture;
+use web_sys::{Request, RequestInit, RequestMode, Response};
+
+/// How the bytes behind a URL should be decoded.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum AssetKind {
+    /// anything the `image` crate can decode
+    Image,
+    /// UTF-8 text, e.g. GLSL source
+    Text,
+    /// undecoded bytes, e.g. meshes, left for whoever asked for them to parse
+    Binary,
+}
+
+/// A decoded asset. Cloning is cheap; every clone shares the data.
+#[derive(Debug, Clone)]
+pub enum Asset {
+    Image(Rc<DynamicImage>),
+    Text(Rc<str>),
+    Binary(Rc<[u8]>),
+}
+
+impl Asset {
+    fn decode(kind: AssetKind, bytes: Vec<u8>) -> Result<Self, String> {
+        Ok(match kind {
+            AssetKind::Image => Asset::Image(Rc::new(
+                image::load_from_memory(&bytes).map_err(|e| e.to_string())?,
+            )),
+            AssetKind::Text => {
+                Asset::Text(String::from_utf8(bytes).map_err(|e| e.to_string())?.into())
+            }
+            AssetKind::Binary => Asset::Binary(bytes.into()),
+        })
+    }
+
+    pub fn as_image(&self) -> Option<&Rc<DynamicImage>> {
+        match self {
+            Asset::Image(image) => Some(image),
+            _ => None,
+        }
+    }
+
+    pub fn as_text(&self) -> Option<&Rc<str>> {
+        match self {
+            Asset::Text(text) => Some(text),
+            _ => None,
+        }
+    }
+
+    pub fn as_binary(&self) -> Option<&Rc<[u8]>> {
+        match self {
+            Asset::Binary(bytes) => Some(bytes),
+            _ => None,
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub enum AssetState {
+    Pending,
+    Loaded(Asset),
+    /// why the fetch or the decode failed
+    Failed(String),
+}
+
+/// Where [`AssetLoader`] gets bytes from. [`HttpFetch`] is the real one; tests substitute their own.
+pub trait Fetch {
+    fn fetch(&self, url: &str) -> LocalBoxFuture<'static, Result<Vec<u8>, String>>;
+}
+
+/// `window.fetch`, relative to the page.
+pub struct HttpFetch;
+
+impl HttpFetch {
+    async fn fetch_bytes(url: String) -> Result<Vec<u8>, String> {
+        let describe = |e: wasm_bindgen::JsValue| format!("{url}: {e:?}");
+
+        let opts = RequestInit::new();
+        opts.set_method("GET");
+        opts.set_mode(RequestMode::Cors);
+        let request = Request::new_with_str_and_init(&url, &opts).map_err(describe)?;
+
+        let window = web_sys::window().ok_or("no window")?;
+        let response: Response = JsFuture::from(window.fetch_with_request(&request))
+            .await
+            .map_err(describe)?
+            .unchecked_into();
+        if !response.ok() {
+            return Err(format!("{url}: HTTP {}", response.status()));
+        }
+        let buffer = JsFuture::from(response.array_buffer().map_err(describe)?)
+            .await
+            .map_err(describe)?;
+        Ok(Uint8Array::new(&buffer).to_vec())
+    }
+}
+
+impl Fetch for HttpFetch {
+    fn fetch(&self, url: &str) -> LocalBoxFuture<'static, Result<Vec<u8>, String>> {
+        Box::pin(Self::fetch_bytes(url.to_string()))
+    }
+}
+
+/// Fetches assets by URL in the background and keeps them, so each URL is only fetched once.
+/// Clones share the same cache.
+#[derive(Clone)]
+pub struct AssetLoader {
+    fetch: Rc<dyn Fetch>,
+    assets: Rc<RefCell<HashMap<String, AssetState>>>,
+}
+
+impl AssetLoader {
+    pub fn new() -> Self {
+        Self::with_fetch(HttpFetch)
+    }
+
+    pub fn with_fetch(fetch: impl Fetch + 'static) -> Self {
+        Self {
+            fetch: Rc::new(fetch),
+            assets: Default::default(),
+        }
+    }
+
+    /// Start fetching `url` on the browser's event loop, unless it has been requested before.
+    pub fn load(&self, url: &str, kind: AssetKind) {
+        if let Some(task) = self.start(url, kind) {
+            wasm_bindgen_futures::spawn_local(task);
+        }
+    }
+
+    /// Mark `url` pending and return the future which fetches and decodes it,
+    /// or `None` if `url` has been requested before. [`Self::load`] runs it in the background.
+    pub fn start(&self, url: &str, kind: AssetKind) -> Option<LocalBoxFuture<'static, ()>> {
+        if self.assets.borrow().contains_key(url) {
+            return None;
+        }
+        self.assets
+            .borrow_mut()
+            .insert(url.to_string(), AssetState::Pending);
+
+        let fetch = self.fetch.fetch(url);
+        let assets = self.assets.clone();
+        let url = url.to_string();
+        Some(Box::pin(async move {
+            let state = match fetch.await.and_then(|bytes| Asset::decode(kind, bytes)) {
+                Ok(asset) => AssetState::Loaded(asset),
+                Err(e) => AssetState::Failed(e),
+            };
+            // unless it was forgotten in the meantime
+            if let Some(entry) = assets.borrow_mut().get_mut(&url) {
+                *entry = state;
+            }
+        }))
+    }
+
+    /// `None` if `url` was never requested
+    pub fn state(&self, url: &str) -> Option<AssetState> {
+        self.assets.borrow().get(url).cloned()
+    }
+
+    /// the asset, once it has loaded
+    pub fn get(&self, url: &str) -> Option<Asset> {
+        match self.state(url)? {
+            AssetState::Loaded(asset) => Some(asset),
+            _ => None,
+        }
+    }
+
+    pub fn pending_count(&self) -> usize {
+        self.assets
+            .borrow()
+            .values()
+            .filter(|state| matches!(state, AssetState::Pending))
+            .count()
+    }
+
+    /// Drop `url` from the cache, so the next request fetches it again (e.g. to retry a failure).
+    pub fn forget(&self, url: &str) {
+        self.assets.borrow_mut().remove(url);
+    }
+}
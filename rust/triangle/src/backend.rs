@@ -0,0 +1,903 @@
+use js_sys::Int32Array;
+use wasm_bindgen::{JsCast, JsValue};
+use web_sys::{
+    HtmlVideoElement, OvrMultiview2, WebGl2RenderingContext, WebGlBuffer, WebGlFramebuffer,
+    WebGlProgram, WebGlQuery, WebGlRenderbuffer, WebGlShader, WebGlTexture, WebGlUniformLocation,
+    WebGlVertexArrayObject,
+};
+
+/// `EXT_disjoint_timer_query_webgl2`'s query target, which `web_sys` does not define
+pub const TIME_ELAPSED_EXT: u32 = 0x88BF;
+/// `EXT_disjoint_timer_query_webgl2`'s parameter for [`GlBackend::get_gpu_disjoint`]
+pub const GPU_DISJOINT_EXT: u32 = 0x8FBB;
+/// `EXT_texture_filter_anisotropic`'s texture parameter, which `web_sys` does not define
+pub const TEXTURE_MAX_ANISOTROPY_EXT: u32 = 0x84FE;
+/// the extension behind [`GlBackend::framebuffer_texture_multiview_ovr`] and `gl_ViewID_OVR`
+pub const MULTIVIEW_EXTENSION: &str = "OVR_multiview2";
+
+/// What `getActiveAttrib` and `getActiveUniform` report about one variable of a linked program.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ActiveInfo {
+    /// arrays are reported by their first element, e.g. `lights[0]`
+    pub name: String,
+    /// the array length, 1 for anything else
+    pub size: i32,
+    /// `FLOAT_VEC3`, `SAMPLER_2D`, ...
+    pub type_: u32,
+}
+
+/// The subset of the WebGL2 API that [`crate::gl_thin`], [`crate::shaders`] and [`crate::objects`] use.
+/// The method names and argument orders follow [`WebGl2RenderingContext`]
+/// so code written against the trait reads the same as code written against `web_sys`.
+///
+/// [`WebGl2RenderingContext`] is the real implementation.
+/// `RecordingGl` (in `recording`, built for tests only) captures a call log so draw code can be tested natively.
+///
+/// Enum-style arguments (`target`, `usage`, `type_`, ...) are the `WebGl2RenderingContext::*` constants.
+pub trait GlBackend {
+    type Buffer: Clone + PartialEq;
+    type Texture: Clone + PartialEq;
+    type VertexArray: Clone + PartialEq;
+    type Shader: Clone;
+    type Program: Clone + PartialEq;
+    type UniformLocation: Clone;
+    type Framebuffer: Clone + PartialEq;
+    type Renderbuffer: Clone + PartialEq;
+    type Query: Clone + PartialEq;
+
+    // The creation methods are #[track_caller] so
+    // crate::gl_thin::ResourceRegistry can report where leaked objects came from.
+
+    #[track_caller]
+    fn create_buffer(&self) -> Option<Self::Buffer>;
+    fn bind_buffer(&self, target: u32, buffer: Option<&Self::Buffer>);
+    fn buffer_data_with_u8_array(&self, target: u32, src_data: &[u8], usage: u32);
+    /// allocate `size` bytes of uninitialized storage
+    fn buffer_data_with_i32(&self, target: u32, size: i32, usage: u32);
+    fn buffer_sub_data_with_i32_and_u8_array(&self, target: u32, offset: i32, src_data: &[u8]);
+    fn bind_buffer_base(&self, target: u32, index: u32, buffer: Option<&Self::Buffer>);
+    fn delete_buffer(&self, buffer: Option<&Self::Buffer>);
+
+    #[track_caller]
+    fn create_vertex_array(&self) -> Option<Self::VertexArray>;
+    fn bind_vertex_array(&self, array: Option<&Self::VertexArray>);
+    fn delete_vertex_array(&self, array: Option<&Self::VertexArray>);
+    fn vertex_attrib_pointer_with_i32(
+        &self,
+        index: u32,
+        size: i32,
+        type_: u32,
+        normalized: bool,
+        stride: i32,
+        offset: i32,
+    );
+    fn enable_vertex_attrib_array(&self, index: u32);
+    fn vertex_attrib_divisor(&self, index: u32, divisor: u32);
+
+    fn create_shader(&self, type_: u32) -> Option<Self::Shader>;
+    fn shader_source(&self, shader: &Self::Shader, source: &str);
+    fn compile_shader(&self, shader: &Self::Shader);
+    /// `get_shader_parameter(shader, COMPILE_STATUS)`
+    fn get_shader_compile_status(&self, shader: &Self::Shader) -> bool;
+    fn get_shader_info_log(&self, shader: &Self::Shader) -> Option<String>;
+    fn delete_shader(&self, shader: Option<&Self::Shader>);
+
+    #[track_caller]
+    fn create_program(&self) -> Option<Self::Program>;
+    fn attach_shader(&self, program: &Self::Program, shader: &Self::Shader);
+    /// takes effect at the next [`link_program`](Self::link_program)
+    fn bind_attrib_location(&self, program: &Self::Program, index: u32, name: &str);
+    fn link_program(&self, program: &Self::Program);
+    /// `get_program_parameter(program, LINK_STATUS)`
+    fn get_program_link_status(&self, program: &Self::Program) -> bool;
+    fn get_program_info_log(&self, program: &Self::Program) -> Option<String>;
+    fn use_program(&self, program: Option<&Self::Program>);
+    fn delete_program(&self, program: Option<&Self::Program>);
+    fn get_attrib_location(&self, program: &Self::Program, name: &str) -> i32;
+    /// `get_program_parameter(program, ACTIVE_ATTRIBUTES)`
+    fn get_active_attrib_count(&self, program: &Self::Program) -> u32;
+    fn get_active_attrib(&self, program: &Self::Program, index: u32) -> Option<ActiveInfo>;
+    /// `get_program_parameter(program, ACTIVE_UNIFORMS)`, which counts the members of uniform blocks too
+    fn get_active_uniform_count(&self, program: &Self::Program) -> u32;
+    fn get_active_uniform(&self, program: &Self::Program, index: u32) -> Option<ActiveInfo>;
+    fn get_uniform_location(
+        &self,
+        program: &Self::Program,
+        name: &str,
+    ) -> Option<Self::UniformLocation>;
+
+    /// [`WebGl2RenderingContext::INVALID_INDEX`] if there is no such block
+    fn get_uniform_block_index(&self, program: &Self::Program, uniform_block_name: &str) -> u32;
+    fn uniform_block_binding(
+        &self,
+        program: &Self::Program,
+        uniform_block_index: u32,
+        uniform_block_binding: u32,
+    );
+
+    fn uniform1i(&self, location: Option<&Self::UniformLocation>, x: i32);
+    fn uniform1f(&self, location: Option<&Self::UniformLocation>, x: f32);
+    fn uniform3fv_with_f32_array(&self, location: Option<&Self::UniformLocation>, data: &[f32]);
+    fn uniform4fv_with_f32_array(&self, location: Option<&Self::UniformLocation>, data: &[f32]);
+    fn uniform_matrix3fv_with_f32_array(
+        &self,
+        location: Option<&Self::UniformLocation>,
+        transpose: bool,
+        data: &[f32],
+    );
+    fn uniform_matrix4fv_with_f32_array(
+        &self,
+        location: Option<&Self::UniformLocation>,
+        transpose: bool,
+        data: &[f32],
+    );
+
+    #[track_caller]
+    fn create_texture(&self) -> Option<Self::Texture>;
+    fn active_texture(&self, texture: u32);
+    fn bind_texture(&self, target: u32, texture: Option<&Self::Texture>);
+    /// `get_parameter(TEXTURE_BINDING_2D)` for the active texture unit
+    fn get_texture_binding_2d(&self) -> Option<Self::Texture>;
+    fn tex_image_2d_with_u8_array(
+        &self,
+        target: u32,
+        level: i32,
+        internalformat: i32,
+        width: i32,
+        height: i32,
+        border: i32,
+        format: u32,
+        type_: u32,
+        pixels: Option<&[u8]>,
+    ) -> Result<(), JsValue>;
+    fn compressed_tex_image_2d_with_u8_array(
+        &self,
+        target: u32,
+        level: i32,
+        internalformat: u32,
+        width: i32,
+        height: i32,
+        border: i32,
+        data: &[u8],
+    );
+    /// upload the video's current frame, at the video's size
+    fn tex_image_2d_with_u32_and_u32_and_html_video_element(
+        &self,
+        target: u32,
+        level: i32,
+        internalformat: i32,
+        format: u32,
+        type_: u32,
+        source: &HtmlVideoElement,
+    ) -> Result<(), JsValue>;
+    /// immutable storage for a `TEXTURE_2D_ARRAY` or `TEXTURE_3D`
+    fn tex_storage_3d(
+        &self,
+        target: u32,
+        levels: i32,
+        internalformat: u32,
+        width: i32,
+        height: i32,
+        depth: i32,
+    );
+    fn tex_parameteri(&self, target: u32, pname: u32, param: i32);
+    fn tex_parameterf(&self, target: u32, pname: u32, param: f32);
+    fn generate_mipmap(&self, target: u32);
+    fn delete_texture(&self, texture: Option<&Self::Texture>);
+
+    #[track_caller]
+    fn create_framebuffer(&self) -> Option<Self::Framebuffer>;
+
+    /// `None` is the default framebuffer (or, inside an XR session, whatever the caller bound)
+    fn bind_framebuffer(&self, target: u32, framebuffer: Option<&Self::Framebuffer>);
+    /// `get_parameter(DRAW_FRAMEBUFFER_BINDING)`
+    fn get_draw_framebuffer_binding(&self) -> Option<Self::Framebuffer>;
+    fn framebuffer_texture_2d(
+        &self,
+        target: u32,
+        attachment: u32,
+        textarget: u32,
+        texture: Option<&Self::Texture>,
+        level: i32,
+    );
+    fn framebuffer_renderbuffer(
+        &self,
+        target: u32,
+        attachment: u32,
+        renderbuffertarget: u32,
+        renderbuffer: Option<&Self::Renderbuffer>,
+    );
+    /// `OVR_multiview2`'s `framebufferTextureMultiviewOVR`: attach `num_views` layers at once,
+    /// one per view. The extension must have been enabled with [`has_extension`](Self::has_extension).
+    fn framebuffer_texture_multiview_ovr(
+        &self,
+        target: u32,
+        attachment: u32,
+        texture: Option<&Self::Texture>,
+        level: i32,
+        base_view_index: i32,
+        num_views: i32,
+    );
+    fn check_framebuffer_status(&self, target: u32) -> u32;
+    fn blit_framebuffer(
+        &self,
+        src_x0: i32,
+        src_y0: i32,
+        src_x1: i32,
+        src_y1: i32,
+        dst_x0: i32,
+        dst_y0: i32,
+        dst_x1: i32,
+        dst_y1: i32,
+        mask: u32,
+        filter: u32,
+    );
+    fn delete_framebuffer(&self, framebuffer: Option<&Self::Framebuffer>);
+
+    #[track_caller]
+    fn create_renderbuffer(&self) -> Option<Self::Renderbuffer>;
+    fn bind_renderbuffer(&self, target: u32, renderbuffer: Option<&Self::Renderbuffer>);
+    /// `get_parameter(RENDERBUFFER_BINDING)`
+    fn get_renderbuffer_binding(&self) -> Option<Self::Renderbuffer>;
+    fn renderbuffer_storage(&self, target: u32, internalformat: u32, width: i32, height: i32);
+    fn renderbuffer_storage_multisample(
+        &self,
+        target: u32,
+        samples: i32,
+        internalformat: u32,
+        width: i32,
+        height: i32,
+    );
+    fn delete_renderbuffer(&self, renderbuffer: Option<&Self::Renderbuffer>);
+
+    fn clear_color(&self, red: f32, green: f32, blue: f32, alpha: f32);
+    fn clear(&self, mask: u32);
+    fn viewport(&self, x: i32, y: i32, width: i32, height: i32);
+    /// `get_parameter(VIEWPORT)` as `[x, y, width, height]`
+    fn get_viewport(&self) -> [i32; 4];
+
+    fn enable(&self, cap: u32);
+    fn disable(&self, cap: u32);
+    fn blend_func(&self, sfactor: u32, dfactor: u32);
+    fn depth_func(&self, func: u32);
+    fn depth_mask(&self, flag: bool);
+
+    /// `get_extension(name)` succeeded, which in WebGL also enables the extension
+    fn has_extension(&self, name: &str) -> bool;
+
+    #[track_caller]
+    fn create_query(&self) -> Option<Self::Query>;
+    fn begin_query(&self, target: u32, query: &Self::Query);
+    fn end_query(&self, target: u32);
+    /// `get_query_parameter(query, QUERY_RESULT_AVAILABLE)`
+    fn get_query_result_available(&self, query: &Self::Query) -> bool;
+    /// `get_query_parameter(query, QUERY_RESULT)`; nanoseconds for [`TIME_ELAPSED_EXT`] queries
+    fn get_query_result(&self, query: &Self::Query) -> u64;
+    fn delete_query(&self, query: Option<&Self::Query>);
+    /// `get_parameter(GPU_DISJOINT_EXT)`: whether timer query results since the last check are garbage
+    fn get_gpu_disjoint(&self) -> bool;
+
+    fn draw_arrays(&self, mode: u32, first: i32, count: i32);
+    fn draw_elements_with_i32(&self, mode: u32, count: i32, type_: u32, offset: i32);
+    fn draw_arrays_instanced(&self, mode: u32, first: i32, count: i32, instance_count: i32);
+    fn draw_elements_instanced_with_i32(
+        &self,
+        mode: u32,
+        count: i32,
+        type_: u32,
+        offset: i32,
+        instance_count: i32,
+    );
+}
+
+//
+
+impl GlBackend for WebGl2RenderingContext {
+    type Buffer = WebGlBuffer;
+    type Texture = WebGlTexture;
+    type VertexArray = WebGlVertexArrayObject;
+    type Shader = WebGlShader;
+    type Program = WebGlProgram;
+    type UniformLocation = WebGlUniformLocation;
+    type Framebuffer = WebGlFramebuffer;
+    type Renderbuffer = WebGlRenderbuffer;
+    type Query = WebGlQuery;
+
+    fn create_buffer(&self) -> Option<WebGlBuffer> {
+        WebGl2RenderingContext::create_buffer(self)
+    }
+
+    fn bind_buffer(&self, target: u32, buffer: Option<&WebGlBuffer>) {
+        WebGl2RenderingContext::bind_buffer(self, target, buffer);
+    }
+
+    fn buffer_data_with_u8_array(&self, target: u32, src_data: &[u8], usage: u32) {
+        WebGl2RenderingContext::buffer_data_with_u8_array(self, target, src_data, usage);
+    }
+
+    fn buffer_data_with_i32(&self, target: u32, size: i32, usage: u32) {
+        WebGl2RenderingContext::buffer_data_with_i32(self, target, size, usage);
+    }
+
+    fn buffer_sub_data_with_i32_and_u8_array(&self, target: u32, offset: i32, src_data: &[u8]) {
+        WebGl2RenderingContext::buffer_sub_data_with_i32_and_u8_array(
+            self, target, offset, src_data,
+        );
+    }
+
+    fn bind_buffer_base(&self, target: u32, index: u32, buffer: Option<&WebGlBuffer>) {
+        WebGl2RenderingContext::bind_buffer_base(self, target, index, buffer);
+    }
+
+    fn delete_buffer(&self, buffer: Option<&WebGlBuffer>) {
+        WebGl2RenderingContext::delete_buffer(self, buffer);
+    }
+
+    fn create_vertex_array(&self) -> Option<WebGlVertexArrayObject> {
+        WebGl2RenderingContext::create_vertex_array(self)
+    }
+
+    fn bind_vertex_array(&self, array: Option<&WebGlVertexArrayObject>) {
+        WebGl2RenderingContext::bind_vertex_array(self, array);
+    }
+
+    fn delete_vertex_array(&self, array: Option<&WebGlVertexArrayObject>) {
+        WebGl2RenderingContext::delete_vertex_array(self, array);
+    }
+
+    fn vertex_attrib_pointer_with_i32(
+        &self,
+        index: u32,
+        size: i32,
+        type_: u32,
+        normalized: bool,
+        stride: i32,
+        offset: i32,
+    ) {
+        WebGl2RenderingContext::vertex_attrib_pointer_with_i32(
+            self, index, size, type_, normalized, stride, offset,
+        );
+    }
+
+    fn enable_vertex_attrib_array(&self, index: u32) {
+        WebGl2RenderingContext::enable_vertex_attrib_array(self, index);
+    }
+
+    fn vertex_attrib_divisor(&self, index: u32, divisor: u32) {
+        WebGl2RenderingContext::vertex_attrib_divisor(self, index, divisor);
+    }
+
+    fn create_shader(&self, type_: u32) -> Option<WebGlShader> {
+        WebGl2RenderingContext::create_shader(self, type_)
+    }
+
+    fn shader_source(&self, shader: &WebGlShader, source: &str) {
+        WebGl2RenderingContext::shader_source(self, shader, source);
+    }
+
+    fn compile_shader(&self, shader: &WebGlShader) {
+        WebGl2RenderingContext::compile_shader(self, shader);
+    }
+
+    fn get_shader_compile_status(&self, shader: &WebGlShader) -> bool {
+        self.get_shader_parameter(shader, WebGl2RenderingContext::COMPILE_STATUS)
+            .as_bool()
+            .unwrap_or(false)
+    }
+
+    fn get_shader_info_log(&self, shader: &WebGlShader) -> Option<String> {
+        WebGl2RenderingContext::get_shader_info_log(self, shader)
+    }
+
+    fn delete_shader(&self, shader: Option<&WebGlShader>) {
+        WebGl2RenderingContext::delete_shader(self, shader);
+    }
+
+    fn create_program(&self) -> Option<WebGlProgram> {
+        WebGl2RenderingContext::create_program(self)
+    }
+
+    fn attach_shader(&self, program: &WebGlProgram, shader: &WebGlShader) {
+        WebGl2RenderingContext::attach_shader(self, program, shader);
+    }
+
+    fn bind_attrib_location(&self, program: &WebGlProgram, index: u32, name: &str) {
+        WebGl2RenderingContext::bind_attrib_location(self, program, index, name);
+    }
+
+    fn link_program(&self, program: &WebGlProgram) {
+        WebGl2RenderingContext::link_program(self, program);
+    }
+
+    fn get_program_link_status(&self, program: &WebGlProgram) -> bool {
+        self.get_program_parameter(program, WebGl2RenderingContext::LINK_STATUS)
+            .as_bool()
+            .unwrap_or(false)
+    }
+
+    fn get_program_info_log(&self, program: &WebGlProgram) -> Option<String> {
+        WebGl2RenderingContext::get_program_info_log(self, program)
+    }
+
+    fn use_program(&self, program: Option<&WebGlProgram>) {
+        WebGl2RenderingContext::use_program(self, program);
+    }
+
+    fn delete_program(&self, program: Option<&WebGlProgram>) {
+        WebGl2RenderingContext::delete_program(self, program);
+    }
+
+    fn get_attrib_location(&self, program: &WebGlProgram, name: &str) -> i32 {
+        WebGl2RenderingContext::get_attrib_location(self, program, name)
+    }
+
+    fn get_active_attrib_count(&self, program: &WebGlProgram) -> u32 {
+        self.get_program_parameter(program, WebGl2RenderingContext::ACTIVE_ATTRIBUTES)
+            .as_f64()
+            .unwrap_or(0.0) as u32
+    }
+
+    fn get_active_attrib(&self, program: &WebGlProgram, index: u32) -> Option<ActiveInfo> {
+        WebGl2RenderingContext::get_active_attrib(self, program, index).map(|info| ActiveInfo {
+            name: info.name(),
+            size: info.size(),
+            type_: info.type_(),
+        })
+    }
+
+    fn get_active_uniform_count(&self, program: &WebGlProgram) -> u32 {
+        self.get_program_parameter(program, WebGl2RenderingContext::ACTIVE_UNIFORMS)
+            .as_f64()
+            .unwrap_or(0.0) as u32
+    }
+
+    fn get_active_uniform(&self, program: &WebGlProgram, index: u32) -> Option<ActiveInfo> {
+        WebGl2RenderingContext::get_active_uniform(self, program, index).map(|info| ActiveInfo {
+            name: info.name(),
+            size: info.size(),
+            type_: info.type_(),
+        })
+    }
+
+    fn get_uniform_location(
+        &self,
+        program: &WebGlProgram,
+        name: &str,
+    ) -> Option<WebGlUniformLocation> {
+        WebGl2RenderingContext::get_uniform_location(self, program, name)
+    }
+
+    fn get_uniform_block_index(&self, program: &WebGlProgram, uniform_block_name: &str) -> u32 {
+        WebGl2RenderingContext::get_uniform_block_index(self, program, uniform_block_name)
+    }
+
+    fn uniform_block_binding(
+        &self,
+        program: &WebGlProgram,
+        uniform_block_index: u32,
+        uniform_block_binding: u32,
+    ) {
+        WebGl2RenderingContext::uniform_block_binding(
+            self,
+            program,
+            uniform_block_index,
+            uniform_block_binding,
+        );
+    }
+
+    fn uniform1i(&self, location: Option<&WebGlUniformLocation>, x: i32) {
+        WebGl2RenderingContext::uniform1i(self, location, x);
+    }
+
+    fn uniform1f(&self, location: Option<&WebGlUniformLocation>, x: f32) {
+        WebGl2RenderingContext::uniform1f(self, location, x);
+    }
+
+    fn uniform3fv_with_f32_array(&self, location: Option<&WebGlUniformLocation>, data: &[f32]) {
+        WebGl2RenderingContext::uniform3fv_with_f32_array(self, location, data);
+    }
+
+    fn uniform4fv_with_f32_array(&self, location: Option<&WebGlUniformLocation>, data: &[f32]) {
+        WebGl2RenderingContext::uniform4fv_with_f32_array(self, location, data);
+    }
+
+    fn uniform_matrix3fv_with_f32_array(
+        &self,
+        location: Option<&WebGlUniformLocation>,
+        transpose: bool,
+        data: &[f32],
+    ) {
+        WebGl2RenderingContext::uniform_matrix3fv_with_f32_array(self, location, transpose, data);
+    }
+
+    fn uniform_matrix4fv_with_f32_array(
+        &self,
+        location: Option<&WebGlUniformLocation>,
+        transpose: bool,
+        data: &[f32],
+    ) {
+        WebGl2RenderingContext::uniform_matrix4fv_with_f32_array(self, location, transpose, data);
+    }
+
+    fn create_texture(&self) -> Option<WebGlTexture> {
+        WebGl2RenderingContext::create_texture(self)
+    }
+
+    fn active_texture(&self, texture: u32) {
+        WebGl2RenderingContext::active_texture(self, texture);
+    }
+
+    fn bind_texture(&self, target: u32, texture: Option<&WebGlTexture>) {
+        WebGl2RenderingContext::bind_texture(self, target, texture);
+    }
+
+    fn get_texture_binding_2d(&self) -> Option<WebGlTexture> {
+        WebGl2RenderingContext::get_parameter(self, WebGl2RenderingContext::TEXTURE_BINDING_2D)
+            .ok()?
+            .dyn_into()
+            .ok()
+    }
+
+    fn tex_image_2d_with_u8_array(
+        &self,
+        target: u32,
+        level: i32,
+        internalformat: i32,
+        width: i32,
+        height: i32,
+        border: i32,
+        format: u32,
+        type_: u32,
+        pixels: Option<&[u8]>,
+    ) -> Result<(), JsValue> {
+        self.tex_image_2d_with_i32_and_i32_and_i32_and_format_and_type_and_opt_u8_array(
+            target,
+            level,
+            internalformat,
+            width,
+            height,
+            border,
+            format,
+            type_,
+            pixels,
+        )
+    }
+
+    fn compressed_tex_image_2d_with_u8_array(
+        &self,
+        target: u32,
+        level: i32,
+        internalformat: u32,
+        width: i32,
+        height: i32,
+        border: i32,
+        data: &[u8],
+    ) {
+        WebGl2RenderingContext::compressed_tex_image_2d_with_u8_array(
+            self,
+            target,
+            level,
+            internalformat,
+            width,
+            height,
+            border,
+            data,
+        );
+    }
+
+    fn tex_image_2d_with_u32_and_u32_and_html_video_element(
+        &self,
+        target: u32,
+        level: i32,
+        internalformat: i32,
+        format: u32,
+        type_: u32,
+        source: &HtmlVideoElement,
+    ) -> Result<(), JsValue> {
+        WebGl2RenderingContext::tex_image_2d_with_u32_and_u32_and_html_video_element(
+            self,
+            target,
+            level,
+            internalformat,
+            format,
+            type_,
+            source,
+        )
+    }
+
+    fn tex_storage_3d(
+        &self,
+        target: u32,
+        levels: i32,
+        internalformat: u32,
+        width: i32,
+        height: i32,
+        depth: i32,
+    ) {
+        WebGl2RenderingContext::tex_storage_3d(
+            self,
+            target,
+            levels,
+            internalformat,
+            width,
+            height,
+            depth,
+        );
+    }
+
+    fn tex_parameteri(&self, target: u32, pname: u32, param: i32) {
+        WebGl2RenderingContext::tex_parameteri(self, target, pname, param);
+    }
+
+    fn tex_parameterf(&self, target: u32, pname: u32, param: f32) {
+        WebGl2RenderingContext::tex_parameterf(self, target, pname, param);
+    }
+
+    fn generate_mipmap(&self, target: u32) {
+        WebGl2RenderingContext::generate_mipmap(self, target);
+    }
+
+    fn delete_texture(&self, texture: Option<&WebGlTexture>) {
+        WebGl2RenderingContext::delete_texture(self, texture);
+    }
+
+    fn create_framebuffer(&self) -> Option<WebGlFramebuffer> {
+        WebGl2RenderingContext::create_framebuffer(self)
+    }
+
+    fn bind_framebuffer(&self, target: u32, framebuffer: Option<&WebGlFramebuffer>) {
+        WebGl2RenderingContext::bind_framebuffer(self, target, framebuffer);
+    }
+
+    fn get_draw_framebuffer_binding(&self) -> Option<WebGlFramebuffer> {
+        WebGl2RenderingContext::get_parameter(
+            self,
+            WebGl2RenderingContext::DRAW_FRAMEBUFFER_BINDING,
+        )
+        .ok()?
+        .dyn_into()
+        .ok()
+    }
+
+    fn framebuffer_texture_2d(
+        &self,
+        target: u32,
+        attachment: u32,
+        textarget: u32,
+        texture: Option<&WebGlTexture>,
+        level: i32,
+    ) {
+        WebGl2RenderingContext::framebuffer_texture_2d(
+            self, target, attachment, textarget, texture, level,
+        );
+    }
+
+    fn framebuffer_renderbuffer(
+        &self,
+        target: u32,
+        attachment: u32,
+        renderbuffertarget: u32,
+        renderbuffer: Option<&WebGlRenderbuffer>,
+    ) {
+        WebGl2RenderingContext::framebuffer_renderbuffer(
+            self,
+            target,
+            attachment,
+            renderbuffertarget,
+            renderbuffer,
+        );
+    }
+
+    fn framebuffer_texture_multiview_ovr(
+        &self,
+        target: u32,
+        attachment: u32,
+        texture: Option<&WebGlTexture>,
+        level: i32,
+        base_view_index: i32,
+        num_views: i32,
+    ) {
+        // the same object every time, once the extension is enabled
+        if let Ok(Some(extension)) = self.get_extension(MULTIVIEW_EXTENSION) {
+            extension
+                .unchecked_into::<OvrMultiview2>()
+                .framebuffer_texture_multiview_ovr(
+                    target,
+                    attachment,
+                    texture,
+                    level,
+                    base_view_index,
+                    num_views,
+                );
+        }
+    }
+
+    fn check_framebuffer_status(&self, target: u32) -> u32 {
+        WebGl2RenderingContext::check_framebuffer_status(self, target)
+    }
+
+    fn blit_framebuffer(
+        &self,
+        src_x0: i32,
+        src_y0: i32,
+        src_x1: i32,
+        src_y1: i32,
+        dst_x0: i32,
+        dst_y0: i32,
+        dst_x1: i32,
+        dst_y1: i32,
+        mask: u32,
+        filter: u32,
+    ) {
+        WebGl2RenderingContext::blit_framebuffer(
+            self, src_x0, src_y0, src_x1, src_y1, dst_x0, dst_y0, dst_x1, dst_y1, mask, filter,
+        );
+    }
+
+    fn delete_framebuffer(&self, framebuffer: Option<&WebGlFramebuffer>) {
+        WebGl2RenderingContext::delete_framebuffer(self, framebuffer);
+    }
+
+    fn create_renderbuffer(&self) -> Option<WebGlRenderbuffer> {
+        WebGl2RenderingContext::create_renderbuffer(self)
+    }
+
+    fn bind_renderbuffer(&self, target: u32, renderbuffer: Option<&WebGlRenderbuffer>) {
+        WebGl2RenderingContext::bind_renderbuffer(self, target, renderbuffer);
+    }
+
+    fn get_renderbuffer_binding(&self) -> Option<WebGlRenderbuffer> {
+        WebGl2RenderingContext::get_parameter(self, WebGl2RenderingContext::RENDERBUFFER_BINDING)
+            .ok()?
+            .dyn_into()
+            .ok()
+    }
+
+    fn renderbuffer_storage(&self, target: u32, internalformat: u32, width: i32, height: i32) {
+        WebGl2RenderingContext::renderbuffer_storage(self, target, internalformat, width, height);
+    }
+
+    fn renderbuffer_storage_multisample(
+        &self,
+        target: u32,
+        samples: i32,
+        internalformat: u32,
+        width: i32,
+        height: i32,
+    ) {
+        WebGl2RenderingContext::renderbuffer_storage_multisample(
+            self,
+            target,
+            samples,
+            internalformat,
+            width,
+            height,
+        );
+    }
+
+    fn delete_renderbuffer(&self, renderbuffer: Option<&WebGlRenderbuffer>) {
+        WebGl2RenderingContext::delete_renderbuffer(self, renderbuffer);
+    }
+
+    fn clear_color(&self, red: f32, green: f32, blue: f32, alpha: f32) {
+        WebGl2RenderingContext::clear_color(self, red, green, blue, alpha);
+    }
+
+    fn clear(&self, mask: u32) {
+        WebGl2RenderingContext::clear(self, mask);
+    }
+
+    fn viewport(&self, x: i32, y: i32, width: i32, height: i32) {
+        WebGl2RenderingContext::viewport(self, x, y, width, height);
+    }
+
+    fn get_viewport(&self) -> [i32; 4] {
+        let mut rval = [0; 4];
+        if let Ok(viewport) =
+            WebGl2RenderingContext::get_parameter(self, WebGl2RenderingContext::VIEWPORT)
+        {
+            viewport.unchecked_into::<Int32Array>().copy_to(&mut rval);
+        }
+        rval
+    }
+
+    fn enable(&self, cap: u32) {
+        WebGl2RenderingContext::enable(self, cap);
+    }
+
+    fn disable(&self, cap: u32) {
+        WebGl2RenderingContext::disable(self, cap);
+    }
+
+    fn blend_func(&self, sfactor: u32, dfactor: u32) {
+        WebGl2RenderingContext::blend_func(self, sfactor, dfactor);
+    }
+
+    fn depth_func(&self, func: u32) {
+        WebGl2RenderingContext::depth_func(self, func);
+    }
+
+    fn depth_mask(&self, flag: bool) {
+        WebGl2RenderingContext::depth_mask(self, flag);
+    }
+
+    fn has_extension(&self, name: &str) -> bool {
+        matches!(
+            WebGl2RenderingContext::get_extension(self, name),
+            Ok(Some(_))
+        )
+    }
+
+    fn create_query(&self) -> Option<WebGlQuery> {
+        WebGl2RenderingContext::create_query(self)
+    }
+
+    fn begin_query(&self, target: u32, query: &WebGlQuery) {
+        WebGl2RenderingContext::begin_query(self, target, query);
+    }
+
+    fn end_query(&self, target: u32) {
+        WebGl2RenderingContext::end_query(self, target);
+    }
+
+    fn get_query_result_available(&self, query: &WebGlQuery) -> bool {
+        WebGl2RenderingContext::get_query_parameter(
+            self,
+            query,
+            WebGl2RenderingContext::QUERY_RESULT_AVAILABLE,
+        )
+        .as_bool()
+        .unwrap_or(false)
+    }
+
+    fn get_query_result(&self, query: &WebGlQuery) -> u64 {
+        WebGl2RenderingContext::get_query_parameter(
+            self,
+            query,
+            WebGl2RenderingContext::QUERY_RESULT,
+        )
+        .as_f64()
+        .unwrap_or(0.0) as u64
+    }
+
+    fn delete_query(&self, query: Option<&WebGlQuery>) {
+        WebGl2RenderingContext::delete_query(self, query);
+    }
+
+    fn get_gpu_disjoint(&self) -> bool {
+        WebGl2RenderingContext::get_parameter(self, GPU_DISJOINT_EXT)
+            .ok()
+            .and_then(|disjoint| disjoint.as_bool())
+            .unwrap_or(false)
+    }
+
+    fn draw_arrays(&self, mode: u32, first: i32, count: i32) {
+        WebGl2RenderingContext::draw_arrays(self, mode, first, count);
+    }
+
+    fn draw_elements_with_i32(&self, mode: u32, count: i32, type_: u32, offset: i32) {
+        WebGl2RenderingContext::draw_elements_with_i32(self, mode, count, type_, offset);
+    }
+
+    fn draw_arrays_instanced(&self, mode: u32, first: i32, count: i32, instance_count: i32) {
+        WebGl2RenderingContext::draw_arrays_instanced(self, mode, first, count, instance_count);
+    }
+
+    fn draw_elements_instanced_with_i32(
+        &self,
+        mode: u32,
+        count: i32,
+        type_: u32,
+        offset: i32,
+        instance_count: i32,
+    ) {
+        WebGl2RenderingContext::draw_elements_instanced_with_i32(
+            self,
+            mode,
+            count,
+            type_,
+            offset,
+            instance_count,
+        );
+    }
+}
@@ -0,0 +1,8 @@
+#include "common.glsl"
+
+in vec4 color2;
+out vec4 color;
+
+void main() {
+    color = color2;
+}
@@ -0,0 +1,13 @@
+#include "camera.glsl"
+
+in vec2 corner;
+in vec4 rect;
+in vec4 color;
+uniform mat4 model;
+out vec4 color2;
+
+void main()
+{
+    gl_Position = model_to_clip(model, rect.xy + corner*rect.zw);
+    color2 = color;
+}
@@ -0,0 +1,36 @@
+#ifdef MULTIVIEW
+#extension GL_OVR_multiview2 : require
+#endif
+#include "common.glsl"
+
+#ifdef MULTIVIEW
+// both eyes in one draw; gl_ViewID_OVR picks the eye
+layout(num_views = 2) in;
+
+struct CameraView {
+    mat4 projection;
+    mat4 view;
+    mat4 view_projection;
+    vec3 eye_position;
+    int eye_index;
+};
+
+layout(std140) uniform Camera {
+    CameraView views[2];
+} cameras;
+
+#define camera cameras.views[gl_ViewID_OVR]
+#else
+layout(std140) uniform Camera {
+    mat4 projection;
+    mat4 view;
+    mat4 view_projection;
+    vec3 eye_position;
+    int eye_index;
+} camera;
+#endif
+
+vec4 model_to_clip(mat4 model, vec2 xy)
+{
+    return camera.view_projection*model*vec4(xy, 0.0, 1.0);
+}
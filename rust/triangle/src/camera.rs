@@ -0,0 +1,216 @@
+use crate::backend::GlBackend;
+use crate::gl_thin::HomogeneousGlBuffer;
+use crate::to_mat4;
+use glam::{Mat4, Vec3};
+use std::mem::offset_of;
+use wasm_bindgen::JsValue;
+use web_sys::{WebGl2RenderingContext, XrView};
+
+/// The uniform buffer binding point every program's `Camera` block is attached to.
+pub const CAMERA_BINDING: u32 = 0;
+
+/// The name of the uniform block in the shaders
+pub const CAMERA_BLOCK_NAME: &str = "Camera";
+
+/// How many views the shaders' `MULTIVIEW` variants draw at once.
+pub const MULTIVIEW_VIEWS: usize = 2;
+
+/// Whether a draw targets one view, or every layer of a multiview framebuffer at once.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum ViewMode {
+    /// one [`CameraBlock`], drawn with the ordinary programs
+    #[default]
+    Single,
+    /// [`MULTIVIEW_VIEWS`] [`CameraBlock`]s, drawn with the `MULTIVIEW` variants,
+    /// whose `Camera` block is an array indexed by `gl_ViewID_OVR`
+    Multiview,
+}
+
+/// Mirrors the std140 uniform block the shaders declare:
+/// ```glsl
+/// layout(std140) uniform Camera {
+///     mat4 projection;
+///     mat4 view;
+///     mat4 view_projection;
+///     vec3 eye_position;
+///     int eye_index;
+/// } camera;
+/// ```
+/// The layout is checked against the std140 rules at compile time (see the asserts below).
+#[repr(C)]
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct CameraBlock {
+    pub projection: [f32; 16],
+    pub view: [f32; 16],
+    pub view_projection: [f32; 16],
+    /// world space
+    pub eye_position: [f32; 3],
+    /// 0 for the left eye (or the only view), 1 for the right
+    pub eye_index: i32,
+}
+
+// std140: a mat4 is 4 vec4 columns (16-byte aligned), a vec3 is 16-byte aligned
+// and a scalar may pack into the 4 bytes after it.
+const _: () = assert!(offset_of!(CameraBlock, projection) == 0);
+const _: () = assert!(offset_of!(CameraBlock, view) == 64);
+const _: () = assert!(offset_of!(CameraBlock, view_projection) == 128);
+const _: () = assert!(offset_of!(CameraBlock, eye_position) == 192);
+const _: () = assert!(offset_of!(CameraBlock, eye_index) == 204);
+const _: () = assert!(size_of::<CameraBlock>() == 208);
+
+impl CameraBlock {
+    pub fn new(projection: &Mat4, view: &Mat4, eye_position: Vec3, eye_index: i32) -> Self {
+        Self {
+            projection: projection.to_cols_array(),
+            view: view.to_cols_array(),
+            view_projection: (*projection * *view).to_cols_array(),
+            eye_position: eye_position.to_array(),
+            eye_index,
+        }
+    }
+
+    /// Identity projection and view, for drawing straight into clip space (the non-XR page view).
+    pub fn identity() -> Self {
+        Self::new(&Mat4::IDENTITY, &Mat4::IDENTITY, Vec3::ZERO, 0)
+    }
+
+    pub fn from_xr_view(xr_view: &XrView, eye_index: i32) -> Self {
+        let projection = to_mat4(&xr_view.projection_matrix());
+        let transform = to_mat4(&xr_view.transform().matrix());
+        Self::new(
+            &projection,
+            &transform.inverse(),
+            transform.w_axis.truncate(),
+            eye_index,
+        )
+    }
+
+    pub fn view_projection(&self) -> Mat4 {
+        Mat4::from_cols_array(&self.view_projection)
+    }
+
+    /// the bytes for `bufferSubData`, in std140 layout
+    pub fn to_std140_bytes(&self) -> Vec<u8> {
+        let mut rval = vec![0; size_of::<Self>()];
+        write_f32s(&mut rval, offset_of!(Self, projection), &self.projection);
+        write_f32s(&mut rval, offset_of!(Self, view), &self.view);
+        write_f32s(
+            &mut rval,
+            offset_of!(Self, view_projection),
+            &self.view_projection,
+        );
+        write_f32s(
+            &mut rval,
+            offset_of!(Self, eye_position),
+            &self.eye_position,
+        );
+        let eye_index = offset_of!(Self, eye_index);
+        rval[eye_index..eye_index + 4].copy_from_slice(&self.eye_index.to_le_bytes());
+        rval
+    }
+}
+
+pub(crate) fn write_f32s(dst: &mut [u8], offset: usize, src: &[f32]) {
+    for (i, x) in src.iter().enumerate() {
+        let at = offset + i * 4;
+        dst[at..at + 4].copy_from_slice(&x.to_le_bytes());
+    }
+}
+
+/// One uniform buffer per view, so the right eye's upload never overwrites
+/// the left eye's camera while the GPU may still be drawing with it.
+pub struct CameraUniforms<G: GlBackend = WebGl2RenderingContext> {
+    buffers: Vec<HomogeneousGlBuffer<u8, G>>,
+    /// every view's block back to back, for [`ViewMode::Multiview`]; created on first use
+    multiview: Option<HomogeneousGlBuffer<u8, G>>,
+}
+
+impl<G: GlBackend> CameraUniforms<G> {
+    #[track_caller]
+    pub fn new(gl: &G) -> Result<Self, &'static str> {
+        Ok(Self {
+            buffers: vec![Self::new_buffer(gl, 1)?],
+            multiview: None,
+        })
+    }
+
+    #[track_caller]
+    fn new_buffer(gl: &G, views: usize) -> Result<HomogeneousGlBuffer<u8, G>, &'static str> {
+        HomogeneousGlBuffer::with_capacity(
+            gl,
+            views * size_of::<CameraBlock>(),
+            WebGl2RenderingContext::UNIFORM_BUFFER,
+            WebGl2RenderingContext::DYNAMIC_DRAW,
+        )
+    }
+
+    /// Upload `camera` into the buffer for its `eye_index` and attach that buffer to [`CAMERA_BINDING`].
+    pub fn upload_and_bind(&mut self, gl: &G, camera: &CameraBlock) -> Result<(), &'static str> {
+        let slot: usize = camera
+            .eye_index
+            .try_into()
+            .map_err(|_| "negative eye index")?;
+        while self.buffers.len() <= slot {
+            self.buffers.push(Self::new_buffer(gl, 1)?);
+        }
+        let buffer = &mut self.buffers[slot];
+        buffer.upload(gl, &camera.to_std140_bytes())?;
+        gl.bind_buffer_base(
+            WebGl2RenderingContext::UNIFORM_BUFFER,
+            CAMERA_BINDING,
+            Some(&buffer.buffer),
+        );
+        Ok(())
+    }
+
+    /// Upload one camera per view into a single buffer, as the `MULTIVIEW` shader variants
+    /// declare the block (an array of [`CameraBlock`]s, which std140 packs back to back),
+    /// and attach it to [`CAMERA_BINDING`].
+    pub fn upload_and_bind_views(
+        &mut self,
+        gl: &G,
+        cameras: &[CameraBlock; MULTIVIEW_VIEWS],
+    ) -> Result<(), &'static str> {
+        let buffer = match &mut self.multiview {
+            Some(buffer) => buffer,
+            None => self
+                .multiview
+                .insert(Self::new_buffer(gl, MULTIVIEW_VIEWS)?),
+        };
+        let bytes: Vec<u8> = cameras
+            .iter()
+            .flat_map(CameraBlock::to_std140_bytes)
+            .collect();
+        buffer.upload(gl, &bytes)?;
+        gl.bind_buffer_base(
+            WebGl2RenderingContext::UNIFORM_BUFFER,
+            CAMERA_BINDING,
+            Some(&buffer.buffer),
+        );
+        Ok(())
+    }
+
+    /// New buffers on a restored context; the old ones died with the lost context.
+    pub fn recreate(&mut self, gl: &G) -> Result<(), &'static str> {
+        *self = Self::new(gl)?;
+        Ok(())
+    }
+
+    pub fn release(self, gl: &G) {
+        for buffer in self.buffers.into_iter().chain(self.multiview) {
+            buffer.release(gl);
+        }
+    }
+}
+
+/// Attach `program`'s `Camera` block to [`CAMERA_BINDING`].
+pub fn bind_camera_block<G: GlBackend>(gl: &G, program: &G::Program) -> Result<(), JsValue> {
+    let index = gl.get_uniform_block_index(program, CAMERA_BLOCK_NAME);
+    if index == WebGl2RenderingContext::INVALID_INDEX {
+        return Err(JsValue::from(format!(
+            "missing uniform block {CAMERA_BLOCK_NAME}"
+        )));
+    }
+    gl.uniform_block_binding(program, index, CAMERA_BINDING);
+    Ok(())
+}
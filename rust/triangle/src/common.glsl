@@ -0,0 +1 @@
+precision highp float;
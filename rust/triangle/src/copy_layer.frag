@@ -0,0 +1,11 @@
+#include "common.glsl"
+precision highp sampler2DArray;
+
+in vec2 uv;
+uniform sampler2DArray views;
+uniform int layer;
+out vec4 color;
+
+void main() {
+    color = texture(views, vec3(uv, float(layer)));
+}
@@ -0,0 +1,11 @@
+#include "common.glsl"
+
+out vec2 uv;
+
+// one triangle covering the viewport, with no vertex buffer
+void main()
+{
+    vec2 xy = vec2(float((gl_VertexID & 1) << 2) - 1.0, float((gl_VertexID & 2) << 1) - 1.0);
+    uv = xy*0.5 + 0.5;
+    gl_Position = vec4(xy, 0.0, 1.0);
+}
@@ -1,8 +1,9 @@
-#version 300 es
-precision highp float;
+#include "common.glsl"
+
 in vec3 rgb2;
+in vec4 tint2;
 out vec4 color;
 
 void main() {
-    color = vec4(rgb2, 1.0);
+    color = vec4(rgb2, 1.0) * tint2;
 }
@@ -1,11 +1,23 @@
-#version 300 es
+#include "camera.glsl"
+
 in vec2 xy;
 in vec3 rgb;
-uniform mat4 mvp;
+#ifdef INSTANCED
+in mat4 model;
+in vec4 tint;
+#else
+uniform mat4 model;
+#endif
 out vec3 rgb2;
+out vec4 tint2;
 
 void main()
 {
-    gl_Position = mvp*vec4(xy,0.0, 1.0);
+    gl_Position = model_to_clip(model, xy);
     rgb2 = rgb;
+#ifdef INSTANCED
+    tint2 = tint;
+#else
+    tint2 = vec4(1.0);
+#endif
 }
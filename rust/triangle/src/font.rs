@@ -0,0 +1,603 @@
+use std::rc::Rc;
+
+/// A TrueType font with `glyf` outlines. Just enough of the format to lay out and rasterize text:
+/// the character map, horizontal metrics, `kern` pair kerning and quadratic outlines.
+/// No hinting, no `GPOS` and no shaping beyond one glyph per character.
+#[derive(Clone)]
+pub struct Font {
+    data: Rc<[u8]>,
+    pub units_per_em: u16,
+    pub ascender: i16,
+    pub descender: i16,
+    pub line_gap: i16,
+    num_glyphs: u16,
+    num_h_metrics: u16,
+    long_loca: bool,
+    /// the chosen `cmap` subtable and its format (4 or 12)
+    cmap: (usize, u16),
+    hmtx: usize,
+    loca: usize,
+    glyf: usize,
+    /// the first pair and the pair count of a format 0 `kern` subtable
+    kern: Option<(usize, usize)>,
+}
+
+fn u16_at(data: &[u8], offset: usize) -> Option<u16> {
+    Some(u16::from_be_bytes(
+        data.get(offset..offset + 2)?.try_into().ok()?,
+    ))
+}
+
+fn i16_at(data: &[u8], offset: usize) -> Option<i16> {
+    u16_at(data, offset).map(|v| v as i16)
+}
+
+fn u32_at(data: &[u8], offset: usize) -> Option<u32> {
+    Some(u32::from_be_bytes(
+        data.get(offset..offset + 4)?.try_into().ok()?,
+    ))
+}
+
+impl Font {
+    pub fn parse(data: impl Into<Rc<[u8]>>) -> Result<Self, &'static str> {
+        let data: Rc<[u8]> = data.into();
+        let d = &data[..];
+        const TRUNCATED: &str = "truncated font";
+
+        let num_tables = u16_at(d, 4).ok_or("not a TrueType font")?;
+        let table = |tag: &[u8; 4]| {
+            (0..num_tables as usize)
+                .map(|i| 12 + 16 * i)
+                .find(|record| d.get(*record..record + 4) == Some(tag))
+                .and_then(|record| u32_at(d, record + 8))
+                .map(|offset| offset as usize)
+        };
+        let head = table(b"head").ok_or("font has no head table")?;
+        let hhea = table(b"hhea").ok_or("font has no hhea table")?;
+        let maxp = table(b"maxp").ok_or("font has no maxp table")?;
+        let hmtx = table(b"hmtx").ok_or("font has no hmtx table")?;
+        let loca = table(b"loca").ok_or("font has no loca table (CFF fonts are not supported)")?;
+        let glyf = table(b"glyf").ok_or("font has no glyf table")?;
+        let cmap = table(b"cmap").ok_or("font has no cmap table")?;
+
+        let cmap = Self::find_cmap(d, cmap).ok_or("font has no Unicode cmap")?;
+        let kern = table(b"kern").and_then(|kern| Self::find_kern(d, kern));
+
+        Ok(Self {
+            units_per_em: u16_at(d, head + 18).ok_or(TRUNCATED)?,
+            long_loca: i16_at(d, head + 50).ok_or(TRUNCATED)? != 0,
+            ascender: i16_at(d, hhea + 4).ok_or(TRUNCATED)?,
+            descender: i16_at(d, hhea + 6).ok_or(TRUNCATED)?,
+            line_gap: i16_at(d, hhea + 8).ok_or(TRUNCATED)?,
+            num_h_metrics: u16_at(d, hhea + 34).ok_or(TRUNCATED)?,
+            num_glyphs: u16_at(d, maxp + 4).ok_or(TRUNCATED)?,
+            cmap,
+            hmtx,
+            loca,
+            glyf,
+            kern,
+            data,
+        })
+    }
+
+    /// prefer the full Unicode (format 12) subtable, then the BMP (format 4) one
+    fn find_cmap(d: &[u8], cmap: usize) -> Option<(usize, u16)> {
+        let count = u16_at(d, cmap + 2)? as usize;
+        let subtables: Vec<_> = (0..count)
+            .filter_map(|i| {
+                let record = cmap + 4 + 8 * i;
+                let platform = u16_at(d, record)?;
+                let encoding = u16_at(d, record + 2)?;
+                let offset = cmap + u32_at(d, record + 4)? as usize;
+                let unicode = platform == 0 || (platform == 3 && matches!(encoding, 1 | 10));
+                unicode.then_some((offset, u16_at(d, offset)?))
+            })
+            .collect();
+        subtables
+            .iter()
+            .find(|(_, format)| *format == 12)
+            .or_else(|| subtables.iter().find(|(_, format)| *format == 4))
+            .copied()
+    }
+
+    /// the first horizontal format 0 subtable of a version 0 `kern` table
+    fn find_kern(d: &[u8], kern: usize) -> Option<(usize, usize)> {
+        if u16_at(d, kern)? != 0 {
+            return None;
+        }
+        let mut subtable = kern + 4;
+        for _ in 0..u16_at(d, kern + 2)? {
+            let length = u16_at(d, subtable + 2)? as usize;
+            let coverage = u16_at(d, subtable + 4)?;
+            if coverage >> 8 == 0 && coverage & 1 != 0 {
+                return Some((subtable + 14, u16_at(d, subtable + 6)? as usize));
+            }
+            subtable += length;
+        }
+        None
+    }
+
+    /// `None` for characters the font has no glyph for
+    pub fn glyph_index(&self, c: char) -> Option<u16> {
+        let d = &self.data[..];
+        let c = c as u32;
+        let (subtable, format) = self.cmap;
+        let glyph = if format == 12 {
+            let groups = u32_at(d, subtable + 12)? as usize;
+            (0..groups).find_map(|i| {
+                let group = subtable + 16 + 12 * i;
+                let start = u32_at(d, group)?;
+                let end = u32_at(d, group + 4)?;
+                (start..=end)
+                    .contains(&c)
+                    .then(|| u32_at(d, group + 8).map(|glyph| glyph + c - start))?
+            })? as u16
+        } else {
+            let c = u16::try_from(c).ok()?;
+            let seg_count = u16_at(d, subtable + 6)? as usize / 2;
+            let ends = subtable + 14;
+            let starts = ends + 2 * seg_count + 2;
+            let deltas = starts + 2 * seg_count;
+            let range_offsets = deltas + 2 * seg_count;
+            let segment =
+                (0..seg_count).find(|i| u16_at(d, ends + 2 * i).is_some_and(|end| end >= c))?;
+            let start = u16_at(d, starts + 2 * segment)?;
+            if start > c {
+                return None;
+            }
+            let delta = u16_at(d, deltas + 2 * segment)?;
+            let range_offset_at = range_offsets + 2 * segment;
+            match u16_at(d, range_offset_at)? {
+                0 => c.wrapping_add(delta),
+                range_offset => {
+                    let at = range_offset_at + range_offset as usize + 2 * (c - start) as usize;
+                    match u16_at(d, at)? {
+                        0 => 0,
+                        glyph => glyph.wrapping_add(delta),
+                    }
+                }
+            }
+        };
+        (glyph != 0 && glyph < self.num_glyphs).then_some(glyph)
+    }
+
+    /// in font units
+    pub fn advance(&self, glyph: u16) -> u16 {
+        let metric = glyph.min(self.num_h_metrics.saturating_sub(1)) as usize;
+        u16_at(&self.data, self.hmtx + 4 * metric).unwrap_or(0)
+    }
+
+    /// the adjustment to the advance between `left` and `right`, in font units
+    pub fn kerning(&self, left: u16, right: u16) -> i16 {
+        let Some((pairs, count)) = self.kern else {
+            return 0;
+        };
+        let d = &self.data[..];
+        let key = (left as u32) << 16 | right as u32;
+        let (mut lo, mut hi) = (0, count);
+        while lo < hi {
+            let mid = (lo + hi) / 2;
+            let pair = pairs + 6 * mid;
+            let Some(pair_key) = u32_at(d, pair) else {
+                return 0;
+            };
+            match pair_key.cmp(&key) {
+                std::cmp::Ordering::Less => lo = mid + 1,
+                std::cmp::Ordering::Greater => hi = mid,
+                std::cmp::Ordering::Equal => return i16_at(d, pair + 4).unwrap_or(0),
+            }
+        }
+        0
+    }
+
+    /// the byte range of `glyph` in the `glyf` table; empty for glyphs without an outline
+    fn glyph_data(&self, glyph: u16) -> Option<&[u8]> {
+        let d = &self.data[..];
+        let (start, end) = if self.long_loca {
+            let at = self.loca + 4 * glyph as usize;
+            (u32_at(d, at)? as usize, u32_at(d, at + 4)? as usize)
+        } else {
+            let at = self.loca + 2 * glyph as usize;
+            (2 * u16_at(d, at)? as usize, 2 * u16_at(d, at + 2)? as usize)
+        };
+        d.get(self.glyf + start..self.glyf + end.max(start))
+    }
+
+    /// The outline of `glyph` in font units, y up. Empty for glyphs like the space.
+    pub fn outline(&self, glyph: u16) -> GlyphOutline {
+        let mut outline = GlyphOutline::default();
+        self.append_outline(glyph, [1.0, 0.0, 0.0, 1.0, 0.0, 0.0], 0, &mut outline);
+        outline
+    }
+
+    /// `transform` is `[xx, xy, yx, yy, dx, dy]`
+    fn append_outline(
+        &self,
+        glyph: u16,
+        transform: [f32; 6],
+        depth: u32,
+        outline: &mut GlyphOutline,
+    ) {
+        let Some(g) = self.glyph_data(glyph).filter(|g| g.len() >= 10) else {
+            return;
+        };
+        let contours = i16_at(g, 0).unwrap_or(0);
+        if depth == 0 {
+            outline.bounds = [0, 2, 4, 6].map(|i| i16_at(g, 2 + i).unwrap_or(0));
+        }
+        let apply = |x: f32, y: f32| {
+            let [xx, xy, yx, yy, dx, dy] = transform;
+            (xx * x + yx * y + dx, xy * x + yy * y + dy)
+        };
+        if contours >= 0 {
+            if let Some(points) = Self::simple_glyph_points(g, contours as usize) {
+                outline.contours.extend(points.into_iter().map(|contour| {
+                    contour
+                        .into_iter()
+                        .map(|point| {
+                            let (x, y) = apply(point.x, point.y);
+                            OutlinePoint { x, y, ..point }
+                        })
+                        .collect()
+                }));
+            }
+        } else if depth < 8 {
+            self.append_composite(g, transform, depth, outline);
+        }
+    }
+
+    fn simple_glyph_points(g: &[u8], contours: usize) -> Option<Vec<Vec<OutlinePoint>>> {
+        const ON_CURVE: u8 = 1;
+        const X_SHORT: u8 = 2;
+        const Y_SHORT: u8 = 4;
+        const REPEAT: u8 = 8;
+        const X_SAME_OR_POSITIVE: u8 = 16;
+        const Y_SAME_OR_POSITIVE: u8 = 32;
+
+        let ends: Vec<usize> = (0..contours)
+            .map(|i| u16_at(g, 10 + 2 * i).map(|end| end as usize))
+            .collect::<Option<_>>()?;
+        let point_count = ends.last().map_or(0, |end| end + 1);
+        let instructions = u16_at(g, 10 + 2 * contours)? as usize;
+        let mut at = 12 + 2 * contours + instructions;
+
+        let mut flags = Vec::with_capacity(point_count);
+        while flags.len() < point_count {
+            let flag = *g.get(at)?;
+            at += 1;
+            flags.push(flag);
+            if flag & REPEAT != 0 {
+                let repeats = *g.get(at)?;
+                at += 1;
+                flags.extend(std::iter::repeat_n(flag, repeats as usize));
+            }
+        }
+        flags.truncate(point_count);
+
+        let mut read_coordinates = |short: u8, same_or_positive: u8| -> Option<Vec<f32>> {
+            let mut value = 0i32;
+            flags
+                .iter()
+                .map(|flag| {
+                    if flag & short != 0 {
+                        let delta = *g.get(at)? as i32;
+                        at += 1;
+                        value += if flag & same_or_positive != 0 {
+                            delta
+                        } else {
+                            -delta
+                        };
+                    } else if flag & same_or_positive == 0 {
+                        value += i16_at(g, at)? as i32;
+                        at += 2;
+                    }
+                    Some(value as f32)
+                })
+                .collect()
+        };
+        let xs = read_coordinates(X_SHORT, X_SAME_OR_POSITIVE)?;
+        let ys = read_coordinates(Y_SHORT, Y_SAME_OR_POSITIVE)?;
+
+        let mut start = 0;
+        Some(
+            ends.iter()
+                .map(|end| {
+                    let contour = (start..=*end)
+                        .filter_map(|i| {
+                            Some(OutlinePoint {
+                                x: *xs.get(i)?,
+                                y: *ys.get(i)?,
+                                on_curve: flags.get(i)? & ON_CURVE != 0,
+                            })
+                        })
+                        .collect();
+                    start = end + 1;
+                    contour
+                })
+                .collect(),
+        )
+    }
+
+    fn append_composite(
+        &self,
+        g: &[u8],
+        transform: [f32; 6],
+        depth: u32,
+        outline: &mut GlyphOutline,
+    ) {
+        const ARG_1_AND_2_ARE_WORDS: u16 = 1;
+        const ARGS_ARE_XY_VALUES: u16 = 2;
+        const WE_HAVE_A_SCALE: u16 = 8;
+        const MORE_COMPONENTS: u16 = 0x20;
+        const WE_HAVE_AN_X_AND_Y_SCALE: u16 = 0x40;
+        const WE_HAVE_A_TWO_BY_TWO: u16 = 0x80;
+
+        let f2dot14 = |at: usize| i16_at(g, at).map_or(0.0, |v| v as f32 / 16384.0);
+        let mut at = 10;
+        loop {
+            let (Some(flags), Some(component)) = (u16_at(g, at), u16_at(g, at + 2)) else {
+                return;
+            };
+            at += 4;
+            let (arg1, arg2) = if flags & ARG_1_AND_2_ARE_WORDS != 0 {
+                at += 4;
+                (
+                    i16_at(g, at - 4).unwrap_or(0) as f32,
+                    i16_at(g, at - 2).unwrap_or(0) as f32,
+                )
+            } else {
+                at += 2;
+                (
+                    g.get(at - 2).map_or(0, |v| *v as i8) as f32,
+                    g.get(at - 1).map_or(0, |v| *v as i8) as f32,
+                )
+            };
+            // anchoring by matching points is rare and not supported; such components are not moved
+            let (dx, dy) = if flags & ARGS_ARE_XY_VALUES != 0 {
+                (arg1, arg2)
+            } else {
+                (0.0, 0.0)
+            };
+            let [xx, xy, yx, yy] = if flags & WE_HAVE_A_SCALE != 0 {
+                at += 2;
+                let scale = f2dot14(at - 2);
+                [scale, 0.0, 0.0, scale]
+            } else if flags & WE_HAVE_AN_X_AND_Y_SCALE != 0 {
+                at += 4;
+                [f2dot14(at - 4), 0.0, 0.0, f2dot14(at - 2)]
+            } else if flags & WE_HAVE_A_TWO_BY_TWO != 0 {
+                at += 8;
+                [
+                    f2dot14(at - 8),
+                    f2dot14(at - 6),
+                    f2dot14(at - 4),
+                    f2dot14(at - 2),
+                ]
+            } else {
+                [1.0, 0.0, 0.0, 1.0]
+            };
+
+            // the component's transform, then the parent's
+            let [pxx, pxy, pyx, pyy, pdx, pdy] = transform;
+            let combined = [
+                pxx * xx + pyx * xy,
+                pxy * xx + pyy * xy,
+                pxx * yx + pyx * yy,
+                pxy * yx + pyy * yy,
+                pxx * dx + pyx * dy + pdx,
+                pxy * dx + pyy * dy + pdy,
+            ];
+            self.append_outline(component, combined, depth + 1, outline);
+
+            if flags & MORE_COMPONENTS == 0 {
+                return;
+            }
+        }
+    }
+
+    /// Rasterize `glyph` with `pixels_per_em` pixels to the em. `None` for glyphs without an outline.
+    pub fn rasterize(&self, glyph: u16, pixels_per_em: f32) -> Option<GlyphBitmap> {
+        let outline = self.outline(glyph);
+        if outline.contours.is_empty() {
+            return None;
+        }
+        let scale = pixels_per_em / self.units_per_em as f32;
+        let [x_min, y_min, x_max, y_max] = outline.bounds.map(|v| v as f32 * scale);
+        // a pixel of slack on every side, for rounding and antialiasing
+        let left = x_min.floor() as i32 - 1;
+        let top = y_max.ceil() as i32 + 1;
+        let width = (x_max.ceil() as i32 + 1 - left).max(1) as u32;
+        let height = (top - (y_min.floor() as i32 - 1)).max(1) as u32;
+
+        let mut rasterizer = Rasterizer::new(width, height);
+        for contour in &outline.contours {
+            let polyline = flatten_contour(contour, |x, y| {
+                [x * scale - left as f32, top as f32 - y * scale]
+            });
+            for segment in polyline.windows(2) {
+                rasterizer.line(segment[0], segment[1]);
+            }
+        }
+        Some(GlyphBitmap {
+            width,
+            height,
+            left,
+            top,
+            coverage: rasterizer.coverage(),
+        })
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct OutlinePoint {
+    pub x: f32,
+    pub y: f32,
+    /// off-curve points are quadratic control points
+    pub on_curve: bool,
+}
+
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct GlyphOutline {
+    pub contours: Vec<Vec<OutlinePoint>>,
+    /// x min, y min, x max, y max in font units
+    pub bounds: [i16; 4],
+}
+
+/// 8-bit coverage of one glyph, top row first.
+#[derive(Debug, Clone, PartialEq)]
+pub struct GlyphBitmap {
+    pub width: u32,
+    pub height: u32,
+    /// pixels from the pen position to the bitmap's left edge
+    pub left: i32,
+    /// pixels from the baseline up to the bitmap's top edge
+    pub top: i32,
+    pub coverage: Vec<u8>,
+}
+
+/// Turn a closed TrueType contour into a closed polyline, mapping each point through `to_pixels`.
+/// Consecutive off-curve points imply an on-curve point halfway between them.
+fn flatten_contour(
+    points: &[OutlinePoint],
+    to_pixels: impl Fn(f32, f32) -> [f32; 2],
+) -> Vec<[f32; 2]> {
+    let Some(last) = points.last() else {
+        return vec![];
+    };
+    let mid = |a: [f32; 2], b: [f32; 2]| [(a[0] + b[0]) / 2.0, (a[1] + b[1]) / 2.0];
+    let pixel = |p: &OutlinePoint| to_pixels(p.x, p.y);
+
+    let first_on = points.iter().position(|p| p.on_curve);
+    let start = match first_on {
+        Some(i) => pixel(&points[i]),
+        None => mid(pixel(last), pixel(&points[0])),
+    };
+    let offset = first_on.map_or(0, |i| i + 1);
+
+    let mut polyline = vec![start];
+    let quad = |polyline: &mut Vec<[f32; 2]>, from: [f32; 2], control: [f32; 2], to: [f32; 2]| {
+        let length = (control[0] - from[0]).hypot(control[1] - from[1])
+            + (to[0] - control[0]).hypot(to[1] - control[1]);
+        let steps = (length / 2.0).ceil().clamp(1.0, 16.0) as usize;
+        polyline.extend((1..=steps).map(|i| {
+            let t = i as f32 / steps as f32;
+            let s = 1.0 - t;
+            [0, 1].map(|k| s * s * from[k] + 2.0 * s * t * control[k] + t * t * to[k])
+        }));
+    };
+
+    let mut current = start;
+    let mut control: Option<[f32; 2]> = None;
+    for k in 0..points.len() {
+        let point = &points[(offset + k) % points.len()];
+        let p = pixel(point);
+        if point.on_curve {
+            match control.take() {
+                Some(c) => quad(&mut polyline, current, c, p),
+                None => polyline.push(p),
+            }
+            current = p;
+        } else {
+            if let Some(c) = control {
+                let m = mid(c, p);
+                quad(&mut polyline, current, c, m);
+                current = m;
+            }
+            control = Some(p);
+        }
+    }
+    match control {
+        Some(c) => quad(&mut polyline, current, c, start),
+        None if current != start => polyline.push(start),
+        None => {}
+    }
+    polyline
+}
+
+/// Exact area coverage: each line adds its signed area to the cells it crosses,
+/// and a running sum along each row turns that into coverage.
+struct Rasterizer {
+    width: usize,
+    height: usize,
+    /// one spare cell, because a line in the last column also touches the cell to its right
+    accumulation: Vec<f32>,
+}
+
+impl Rasterizer {
+    fn new(width: u32, height: u32) -> Self {
+        let (width, height) = (width as usize, height as usize);
+        Self {
+            width,
+            height,
+            accumulation: vec![0.0; width * height + 2],
+        }
+    }
+
+    fn line(&mut self, from: [f32; 2], to: [f32; 2]) {
+        if from[1] == to[1] {
+            return;
+        }
+        let (direction, top, bottom) = if from[1] < to[1] {
+            (1.0, from, to)
+        } else {
+            (-1.0, to, from)
+        };
+        let dxdy = (bottom[0] - top[0]) / (bottom[1] - top[1]);
+        let mut x = top[0];
+        if top[1] < 0.0 {
+            x -= top[1] * dxdy;
+        }
+        let max_x = (self.width - 1) as f32;
+        for y in (top[1].max(0.0) as usize)..self.height.min(bottom[1].ceil() as usize) {
+            let row = y * self.width;
+            let dy = bottom[1].min((y + 1) as f32) - top[1].max(y as f32);
+            let x_next = x + dxdy * dy;
+            let d = dy * direction;
+            let (x0, x1) = if x < x_next { (x, x_next) } else { (x_next, x) };
+            let (x0, x1) = (x0.clamp(0.0, max_x), x1.clamp(0.0, max_x));
+            let x0_floor = x0.floor();
+            let x0i = x0_floor as usize;
+            let x1_ceil = x1.ceil();
+            let x1i = x1_ceil as usize;
+            let cell = &mut self.accumulation[row..];
+            if x1i <= x0i + 1 {
+                // within a single cell
+                let x_mid = 0.5 * (x0 + x1) - x0_floor;
+                cell[x0i] += d - d * x_mid;
+                cell[x0i + 1] += d * x_mid;
+            } else {
+                let s = (x1 - x0).recip();
+                let x0f = x0 - x0_floor;
+                let a0 = 0.5 * s * (1.0 - x0f) * (1.0 - x0f);
+                let x1f = x1 - x1_ceil + 1.0;
+                let am = 0.5 * s * x1f * x1f;
+                cell[x0i] += d * a0;
+                if x1i == x0i + 2 {
+                    cell[x0i + 1] += d * (1.0 - a0 - am);
+                } else {
+                    let a1 = s * (1.5 - x0f);
+                    cell[x0i + 1] += d * (a1 - a0);
+                    for c in &mut cell[x0i + 2..x1i - 1] {
+                        *c += d * s;
+                    }
+                    let a2 = a1 + (x1i - x0i - 3) as f32 * s;
+                    cell[x1i - 1] += d * (1.0 - a2 - am);
+                }
+                cell[x1i] += d * am;
+            }
+            x = x_next;
+        }
+    }
+
+    fn coverage(&self) -> Vec<u8> {
+        let mut coverage = Vec::with_capacity(self.width * self.height);
+        for row in self.accumulation.chunks_exact(self.width).take(self.height) {
+            let mut sum = 0.0f32;
+            coverage.extend(row.iter().map(|a| {
+                sum += a;
+                (sum.abs().min(1.0) * 255.0).round() as u8
+            }));
+        }
+        coverage
+    }
+}
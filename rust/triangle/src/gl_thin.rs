@@ -1,16 +1,28 @@
-use js_sys::{Float32Array, Object, Uint8Array};
+use crate::backend::{ActiveInfo, GlBackend, MULTIVIEW_EXTENSION};
+use crate::vertex_layout::VertexLayout;
+use half::f16;
+use std::cell::RefCell;
+use std::fmt::{Display, Formatter};
 use std::marker::PhantomData;
-use web_sys::{WebGl2RenderingContext, WebGlBuffer};
+use std::panic::Location;
+use wasm_bindgen::JsValue;
+use web_sys::{HtmlVideoElement, WebGl2RenderingContext};
 
 /// A wrapper to make working with homogeneous GL data buffers easier.
 /// If you mix types in your GL buffer, this class will not be useful.
-pub struct HomogeneousGlBuffer<T> {
-    pub buffer: WebGlBuffer,
+///
+/// The buffer remembers how many elements it holds (`len`) and how many its GL store has room for (`capacity`).
+/// [`Self::upload`] only reallocates the store when the new payload does not fit.
+pub struct HomogeneousGlBuffer<T, G: GlBackend = WebGl2RenderingContext> {
+    pub buffer: G::Buffer,
     pub target: u32,
+    usage: u32,
+    len: usize,
+    capacity: usize,
     phantom: PhantomData<T>,
 }
 
-impl<T> HomogeneousGlBuffer<T>
+impl<T, G: GlBackend> HomogeneousGlBuffer<T, G>
 where
     [T]: ToBufferPayload,
 {
@@ -18,38 +30,111 @@ where
     /// # params
     /// * `target` -  probably [`WebGl2RenderingContext::ARRAY_BUFFER`] or  [`WebGl2RenderingContext::ELEMENT_ARRAY_BUFFER`]
     /// * `usage` - probably [`WebGl2RenderingContext::STATIC_DRAW`]
-    pub fn new_bound(
-        gl: &WebGl2RenderingContext,
-        payload: &[T],
+    #[track_caller]
+    pub fn new_bound(gl: &G, payload: &[T], target: u32, usage: u32) -> Result<Self, &'static str> {
+        let buffer = gl.create_buffer().ok_or("failed to create buffer")?;
+        gl.bind_buffer(target, Some(&buffer));
+        gl.buffer_data_with_u8_array(target, &payload.to_buffer_payload(), usage);
+
+        Ok(Self {
+            buffer,
+            target,
+            usage,
+            len: payload.len(),
+            capacity: payload.len(),
+            phantom: PhantomData,
+        })
+    }
+
+    /// Allocate room for `capacity` elements without filling any of them; `len` starts at 0.
+    /// The new buffer object remains bound to `target`.
+    /// # params
+    /// * `usage` - probably [`WebGl2RenderingContext::DYNAMIC_DRAW`] or [`WebGl2RenderingContext::STREAM_DRAW`]
+    #[track_caller]
+    pub fn with_capacity(
+        gl: &G,
+        capacity: usize,
         target: u32,
         usage: u32,
     ) -> Result<Self, &'static str> {
         let buffer = gl.create_buffer().ok_or("failed to create buffer")?;
         gl.bind_buffer(target, Some(&buffer));
-        gl.buffer_data_with_array_buffer_view(
-            target,
-            &ToBufferPayload::to_buffer_payload(payload),
-            usage,
-        );
+        gl.buffer_data_with_i32(target, byte_size::<T>(capacity)?, usage);
 
         Ok(Self {
             buffer,
             target,
+            usage,
+            len: 0,
+            capacity,
             phantom: PhantomData,
         })
     }
 
-    pub fn replace(&self, gl: &WebGl2RenderingContext, payload: &[T], usage: u32) {
+    /// Re-specify the whole buffer store with exactly `payload`.
+    pub fn replace(&mut self, gl: &G, payload: &[T], usage: u32) {
         gl.bind_buffer(self.target, Some(&self.buffer));
-        gl.buffer_data_with_array_buffer_view(
+        gl.buffer_data_with_u8_array(self.target, &payload.to_buffer_payload(), usage);
+        self.usage = usage;
+        self.len = payload.len();
+        self.capacity = payload.len();
+    }
+
+    /// Overwrite elements starting at `offset` (counted in elements, not bytes) with `buffer_sub_data`.
+    /// `len` grows if the write extends past it, but the write must fit within `capacity`.
+    pub fn update(&mut self, gl: &G, offset: usize, payload: &[T]) -> Result<(), &'static str> {
+        let end = offset + payload.len();
+        if end > self.capacity {
+            return Err("update extends past the end of the buffer");
+        }
+        gl.bind_buffer(self.target, Some(&self.buffer));
+        gl.buffer_sub_data_with_i32_and_u8_array(
             self.target,
-            &ToBufferPayload::to_buffer_payload(payload),
-            usage,
+            byte_size::<T>(offset)?,
+            &payload.to_buffer_payload(),
         );
+        self.len = self.len.max(end);
+        Ok(())
+    }
+
+    /// Make `payload` the entire contents of the buffer.
+    /// Reuses the existing store via `buffer_sub_data` when it fits,
+    /// otherwise reallocates with room to grow (the next power of two).
+    pub fn upload(&mut self, gl: &G, payload: &[T]) -> Result<(), &'static str> {
+        if payload.len() > self.capacity {
+            let capacity = payload.len().next_power_of_two();
+            gl.bind_buffer(self.target, Some(&self.buffer));
+            gl.buffer_data_with_i32(self.target, byte_size::<T>(capacity)?, self.usage);
+            self.capacity = capacity;
+        }
+        self.len = 0;
+        self.update(gl, 0, payload)
     }
 }
 
-impl<T> HomogeneousGlBuffer<T>
+impl<T, G: GlBackend> HomogeneousGlBuffer<T, G> {
+    /// the number of elements (not bytes, not vertices) currently in the buffer
+    pub fn len(&self) -> usize {
+        self.len
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    /// the number of elements the GL store has room for
+    pub fn capacity(&self) -> usize {
+        self.capacity
+    }
+}
+
+fn byte_size<T>(count: usize) -> Result<i32, &'static str> {
+    (count * size_of::<T>())
+        .try_into()
+        .map_err(|_| "buffer too large")
+}
+
+impl<T, G: GlBackend> HomogeneousGlBuffer<T, G>
 where
     T: GlType,
 {
@@ -66,7 +151,7 @@ where
     /// [MDN docs with example](https://developer.mozilla.org/en-US/docs/Web/API/WebGLRenderingContext/vertexAttribPointer)
     pub fn vertex_attrib_pointer(
         &self,
-        gl: &WebGl2RenderingContext,
+        gl: &G,
         shader_attribute_location: u32,
         element_size: i32,
         normalized: bool,
@@ -87,38 +172,1498 @@ where
     }
 }
 
-impl<T> HomogeneousGlBuffer<T> {
-    pub fn release(self, gl: &WebGl2RenderingContext) {
+impl<T, G: GlBackend> HomogeneousGlBuffer<T, G> {
+    pub fn release(self, gl: &G) {
         gl.delete_buffer(Some(&self.buffer));
     }
 }
 
 //
 
-pub trait ToBufferPayload {
-    fn to_buffer_payload(&self) -> Object;
+/// Several [`HomogeneousGlBuffer`]s used round-robin for data that changes every frame
+/// (controller rays, particles, strokes), so writing this frame's data never has to wait
+/// for the GPU to finish reading last frame's.
+pub struct RingGlBuffer<T, G: GlBackend = WebGl2RenderingContext> {
+    buffers: Vec<HomogeneousGlBuffer<T, G>>,
+    current: usize,
+}
+
+impl<T, G: GlBackend> RingGlBuffer<T, G>
+where
+    [T]: ToBufferPayload,
+{
+    /// A ring of 3 buffers, enough that the one being written is never one the GPU may still be reading.
+    #[track_caller]
+    pub fn new_triple(
+        gl: &G,
+        capacity: usize,
+        target: u32,
+        usage: u32,
+    ) -> Result<Self, &'static str> {
+        Self::new(gl, 3, capacity, target, usage)
+    }
+
+    /// # params
+    /// * `count` - how many buffers to rotate through
+    /// * `capacity` - initial room in each buffer, in elements; they grow as needed
+    /// * `usage` - probably [`WebGl2RenderingContext::STREAM_DRAW`]
+    #[track_caller]
+    pub fn new(
+        gl: &G,
+        count: usize,
+        capacity: usize,
+        target: u32,
+        usage: u32,
+    ) -> Result<Self, &'static str> {
+        // a loop rather than an iterator so #[track_caller] sees through to our caller
+        let mut buffers = Vec::with_capacity(count.max(1));
+        for _ in 0..count.max(1) {
+            match HomogeneousGlBuffer::with_capacity(gl, capacity, target, usage) {
+                Ok(buffer) => buffers.push(buffer),
+                Err(e) => {
+                    for buffer in buffers {
+                        buffer.release(gl);
+                    }
+                    return Err(e);
+                }
+            }
+        }
+        Ok(Self {
+            buffers,
+            current: 0,
+        })
+    }
+
+    /// Advance to the next buffer in the ring and upload `payload` into it.
+    /// Returns that buffer, which remains bound to its target;
+    /// point the attributes at it (with [`HomogeneousGlBuffer::vertex_attrib_pointer`]) before drawing.
+    pub fn next_frame(
+        &mut self,
+        gl: &G,
+        payload: &[T],
+    ) -> Result<&HomogeneousGlBuffer<T, G>, &'static str> {
+        self.current = (self.current + 1) % self.buffers.len();
+        let buffer = &mut self.buffers[self.current];
+        buffer.upload(gl, payload)?;
+        Ok(buffer)
+    }
+}
+
+impl<T, G: GlBackend> RingGlBuffer<T, G> {
+    /// the buffer most recently written by [`Self::next_frame`]
+    pub fn current(&self) -> &HomogeneousGlBuffer<T, G> {
+        &self.buffers[self.current]
+    }
+
+    pub fn release(self, gl: &G) {
+        for buffer in self.buffers {
+            buffer.release(gl);
+        }
+    }
+}
+
+//
+
+/// A GL buffer of interleaved vertices, where each row mixes types as described by [`VertexLayout`].
+pub struct InterleavedGlBuffer<V, G: GlBackend = WebGl2RenderingContext> {
+    pub buffer: G::Buffer,
+    pub target: u32,
+    len: usize,
+    phantom: PhantomData<V>,
 }
 
-impl ToBufferPayload for [f32] {
-    fn to_buffer_payload(&self) -> Object {
-        Float32Array::from(self).into()
+impl<V: VertexLayout, G: GlBackend> InterleavedGlBuffer<V, G> {
+    /// The new buffer object remains bound to `target` which is useful when configuring VAOs.
+    /// # params
+    /// * `target` -  probably [`WebGl2RenderingContext::ARRAY_BUFFER`]
+    /// * `usage` - probably [`WebGl2RenderingContext::STATIC_DRAW`]
+    #[track_caller]
+    pub fn new_bound(gl: &G, payload: &[V], target: u32, usage: u32) -> Result<Self, &'static str> {
+        let buffer = gl.create_buffer().ok_or("failed to create buffer")?;
+        gl.bind_buffer(target, Some(&buffer));
+        gl.buffer_data_with_u8_array(target, &V::to_bytes(payload), usage);
+
+        Ok(Self {
+            buffer,
+            target,
+            len: payload.len(),
+            phantom: PhantomData,
+        })
+    }
+
+    pub fn replace(&mut self, gl: &G, payload: &[V], usage: u32) {
+        gl.bind_buffer(self.target, Some(&self.buffer));
+        gl.buffer_data_with_u8_array(self.target, &V::to_bytes(payload), usage);
+        self.len = payload.len();
+    }
+
+    /// the number of vertices in the buffer
+    pub fn len(&self) -> usize {
+        self.len
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    /// Configure the current VAO to pull every field of `V` from this buffer.
+    /// # params
+    /// * `locations` - the shader attribute location for each field of `V`, in declaration order
+    pub fn vertex_attrib_pointers(&self, gl: &G, locations: &[u32]) {
+        gl.bind_buffer(WebGl2RenderingContext::ARRAY_BUFFER, Some(&self.buffer));
+        V::format().vertex_attrib_pointers(gl, locations);
+    }
+
+    /// Like [`Self::vertex_attrib_pointers`] but each element of this buffer is one instance
+    /// for `drawArraysInstanced`/`drawElementsInstanced` instead of one vertex.
+    pub fn instance_attrib_pointers(&self, gl: &G, locations: &[u32]) {
+        gl.bind_buffer(WebGl2RenderingContext::ARRAY_BUFFER, Some(&self.buffer));
+        V::format().vertex_attrib_pointers_with_divisor(gl, locations, 1);
     }
+
+    pub fn release(self, gl: &G) {
+        gl.delete_buffer(Some(&self.buffer));
+    }
+}
+
+//
+
+/// A GL element array buffer which remembers the type and number of its indices,
+/// so draw calls can pass the right `type_` to `drawElements` without the caller keeping track.
+pub struct IndexBuffer<G: GlBackend = WebGl2RenderingContext> {
+    pub buffer: G::Buffer,
+    /// [`WebGl2RenderingContext::UNSIGNED_BYTE`], [`WebGl2RenderingContext::UNSIGNED_SHORT`] or [`WebGl2RenderingContext::UNSIGNED_INT`]
+    pub index_type: u32,
+    pub index_count: i32,
 }
 
+impl<G: GlBackend> IndexBuffer<G> {
+    /// The new buffer object remains bound to `ELEMENT_ARRAY_BUFFER` which is useful when configuring VAOs.
+    /// # params
+    /// * `usage` - probably [`WebGl2RenderingContext::STATIC_DRAW`]
+    #[track_caller]
+    pub fn new_bound<T: IndexType>(gl: &G, indices: &[T], usage: u32) -> Result<Self, &'static str>
+    where
+        [T]: ToBufferPayload,
+    {
+        let index_count = indices.len().try_into().map_err(|_| "too many indices")?;
+        let buffer = gl.create_buffer().ok_or("failed to create buffer")?;
+        gl.bind_buffer(WebGl2RenderingContext::ELEMENT_ARRAY_BUFFER, Some(&buffer));
+        gl.buffer_data_with_u8_array(
+            WebGl2RenderingContext::ELEMENT_ARRAY_BUFFER,
+            &indices.to_buffer_payload(),
+            usage,
+        );
+
+        Ok(Self {
+            buffer,
+            index_type: T::my_type(),
+            index_count,
+        })
+    }
+
+    /// Like [`Self::new_bound`] but stores the indices as the narrowest type that can hold the largest one.
+    #[track_caller]
+    pub fn new_bound_narrowest(gl: &G, indices: &[u32], usage: u32) -> Result<Self, &'static str> {
+        let max = indices.iter().copied().max().unwrap_or(0);
+        if u8::try_from(max).is_ok() {
+            let narrow: Vec<u8> = indices.iter().map(|&i| i as u8).collect();
+            Self::new_bound(gl, &narrow, usage)
+        } else if u16::try_from(max).is_ok() {
+            let narrow: Vec<u16> = indices.iter().map(|&i| i as u16).collect();
+            Self::new_bound(gl, &narrow, usage)
+        } else {
+            Self::new_bound(gl, indices, usage)
+        }
+    }
+
+    /// Bind this buffer to `ELEMENT_ARRAY_BUFFER` (usually while a VAO is bound, so the VAO remembers it).
+    pub fn bind(&self, gl: &G) {
+        gl.bind_buffer(
+            WebGl2RenderingContext::ELEMENT_ARRAY_BUFFER,
+            Some(&self.buffer),
+        );
+    }
+
+    /// `drawElements` over every index, using the element array buffer of the currently bound VAO.
+    /// # params
+    /// * `mode` - probably [`WebGl2RenderingContext::TRIANGLES`]
+    pub fn draw_elements(&self, gl: &G, mode: u32) {
+        gl.draw_elements_with_i32(mode, self.index_count, self.index_type, 0);
+    }
+
+    /// `drawElementsInstanced` over every index, `instance_count` times.
+    pub fn draw_elements_instanced(&self, gl: &G, mode: u32, instance_count: i32) {
+        gl.draw_elements_instanced_with_i32(
+            mode,
+            self.index_count,
+            self.index_type,
+            0,
+            instance_count,
+        );
+    }
+
+    pub fn release(self, gl: &G) {
+        gl.delete_buffer(Some(&self.buffer));
+    }
+}
+
+/// The unsigned integer types `drawElements` accepts.
+pub trait IndexType: GlType + Copy {}
+
+impl IndexType for u8 {}
+impl IndexType for u16 {}
+impl IndexType for u32 {}
+
+//
+
+/// Convert a slice into the bytes GL will copy into a buffer.
+/// GL (and wasm) are little-endian, so the elements are laid out in little-endian order.
+pub trait ToBufferPayload {
+    fn to_buffer_payload(&self) -> Vec<u8>;
+}
+
+macro_rules! to_buffer_payload {
+    ($($t:ty),*) => {
+        $(
+            impl ToBufferPayload for [$t] {
+                fn to_buffer_payload(&self) -> Vec<u8> {
+                    self.iter().flat_map(|x| x.to_le_bytes()).collect()
+                }
+            }
+        )*
+    };
+}
+
+to_buffer_payload!(f32, f16, u16, u32, i8, i16, i32);
+
 impl ToBufferPayload for [u8] {
-    fn to_buffer_payload(&self) -> Object {
-        Uint8Array::from(self).into()
+    fn to_buffer_payload(&self) -> Vec<u8> {
+        self.to_vec()
     }
 }
 
 //
 
+/// The GL type enum (for `vertexAttribPointer`, `drawElements`, etc.) corresponding to a rust scalar type.
 pub trait GlType {
     fn my_type() -> u32;
 }
 
-impl GlType for f32 {
-    fn my_type() -> u32 {
-        WebGl2RenderingContext::FLOAT
+macro_rules! gl_type {
+    ($($t:ty => $gl:ident),*) => {
+        $(
+            impl GlType for $t {
+                fn my_type() -> u32 {
+                    WebGl2RenderingContext::$gl
+                }
+            }
+        )*
+    };
+}
+
+gl_type!(
+    f32 => FLOAT,
+    f16 => HALF_FLOAT,
+    u8 => UNSIGNED_BYTE,
+    u16 => UNSIGNED_SHORT,
+    u32 => UNSIGNED_INT,
+    i8 => BYTE,
+    i16 => SHORT,
+    i32 => INT
+);
+
+//
+
+/// Where a [`RenderTarget`] attachment keeps its pixels.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum AttachmentStorage {
+    /// can be sampled afterwards, e.g. by a mirror or a post-processing pass
+    Texture,
+    /// can not be sampled, but can be multisampled
+    Renderbuffer,
+}
+
+/// One image attached to a [`RenderTarget`]'s framebuffer.
+pub enum Attachment<G: GlBackend = WebGl2RenderingContext> {
+    Texture(G::Texture),
+    Renderbuffer(G::Renderbuffer),
+}
+
+impl<G: GlBackend> Attachment<G> {
+    fn release(self, gl: &G) {
+        match self {
+            Attachment::Texture(texture) => gl.delete_texture(Some(&texture)),
+            Attachment::Renderbuffer(renderbuffer) => gl.delete_renderbuffer(Some(&renderbuffer)),
+        }
+    }
+}
+
+/// What [`RenderTarget::new`] should allocate.
+/// The color attachment is `RGBA8` and the depth attachment is `DEPTH_COMPONENT24`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct RenderTargetDescription {
+    pub width: i32,
+    pub height: i32,
+    pub color: AttachmentStorage,
+    /// `None` for no depth buffer at all
+    pub depth: Option<AttachmentStorage>,
+    /// 0 for a single-sampled target.
+    /// Anything else requires renderbuffer storage for every attachment,
+    /// and the result has to be [resolved](RenderTarget::resolve_into) into a single-sampled target before it can be sampled.
+    pub samples: i32,
+}
+
+impl RenderTargetDescription {
+    /// A color texture and a depth renderbuffer: something to draw a scene into and then sample.
+    pub fn sampled(width: i32, height: i32) -> Self {
+        Self {
+            width,
+            height,
+            color: AttachmentStorage::Texture,
+            depth: Some(AttachmentStorage::Renderbuffer),
+            samples: 0,
+        }
+    }
+
+    /// Color and depth renderbuffers with `samples` samples per pixel.
+    pub fn multisampled(width: i32, height: i32, samples: i32) -> Self {
+        Self {
+            width,
+            height,
+            color: AttachmentStorage::Renderbuffer,
+            depth: Some(AttachmentStorage::Renderbuffer),
+            samples,
+        }
+    }
+}
+
+/// An offscreen framebuffer together with the color (and optionally depth) images it draws into.
+pub struct RenderTarget<G: GlBackend = WebGl2RenderingContext> {
+    pub framebuffer: G::Framebuffer,
+    pub color: Attachment<G>,
+    pub depth: Option<Attachment<G>>,
+    pub description: RenderTargetDescription,
+}
+
+impl<G: GlBackend> RenderTarget<G> {
+    /// Allocate the attachments and check the framebuffer is complete.
+    /// The draw framebuffer, `TEXTURE_2D` and `RENDERBUFFER` bindings are left as they were,
+    /// and nothing is left allocated if it fails.
+    #[track_caller]
+    pub fn new(gl: &G, description: &RenderTargetDescription) -> Result<Self, &'static str> {
+        if description.samples > 0
+            && (description.color == AttachmentStorage::Texture
+                || description.depth == Some(AttachmentStorage::Texture))
+        {
+            return Err("multisampled attachments must be renderbuffers");
+        }
+
+        let previous = gl.get_draw_framebuffer_binding();
+        let previous_texture = gl.get_texture_binding_2d();
+        let previous_renderbuffer = gl.get_renderbuffer_binding();
+        let framebuffer = gl
+            .create_framebuffer()
+            .ok_or("failed to create framebuffer")?;
+        gl.bind_framebuffer(WebGl2RenderingContext::FRAMEBUFFER, Some(&framebuffer));
+
+        let mut color = None;
+        let mut depth = None;
+        // a block rather than a closure so #[track_caller] sees through to our caller
+        let attached = 'attach: {
+            match Self::new_attachment(
+                gl,
+                description,
+                description.color,
+                WebGl2RenderingContext::COLOR_ATTACHMENT0,
+            ) {
+                Ok(attachment) => color = Some(attachment),
+                Err(e) => break 'attach Err(e),
+            }
+            if let Some(storage) = description.depth {
+                match Self::new_attachment(
+                    gl,
+                    description,
+                    storage,
+                    WebGl2RenderingContext::DEPTH_ATTACHMENT,
+                ) {
+                    Ok(attachment) => depth = Some(attachment),
+                    Err(e) => break 'attach Err(e),
+                }
+            }
+            match gl.check_framebuffer_status(WebGl2RenderingContext::FRAMEBUFFER) {
+                WebGl2RenderingContext::FRAMEBUFFER_COMPLETE => Ok(()),
+                _ => Err("framebuffer incomplete"),
+            }
+        };
+        gl.bind_framebuffer(WebGl2RenderingContext::FRAMEBUFFER, previous.as_ref());
+        gl.bind_texture(
+            WebGl2RenderingContext::TEXTURE_2D,
+            previous_texture.as_ref(),
+        );
+        gl.bind_renderbuffer(
+            WebGl2RenderingContext::RENDERBUFFER,
+            previous_renderbuffer.as_ref(),
+        );
+
+        match attached {
+            Ok(()) => Ok(Self {
+                framebuffer,
+                color: color.expect("attached above"),
+                depth,
+                description: *description,
+            }),
+            Err(e) => {
+                for attachment in color.into_iter().chain(depth) {
+                    attachment.release(gl);
+                }
+                gl.delete_framebuffer(Some(&framebuffer));
+                Err(e)
+            }
+        }
+    }
+
+    /// create one image and attach it to the framebuffer bound to `FRAMEBUFFER`
+    #[track_caller]
+    fn new_attachment(
+        gl: &G,
+        description: &RenderTargetDescription,
+        storage: AttachmentStorage,
+        attachment: u32,
+    ) -> Result<Attachment<G>, &'static str> {
+        let is_depth = attachment == WebGl2RenderingContext::DEPTH_ATTACHMENT;
+        let internalformat = if is_depth {
+            WebGl2RenderingContext::DEPTH_COMPONENT24
+        } else {
+            WebGl2RenderingContext::RGBA8
+        };
+        match storage {
+            AttachmentStorage::Texture => {
+                let target = WebGl2RenderingContext::TEXTURE_2D;
+                let texture = gl.create_texture().ok_or("failed to create texture")?;
+                gl.bind_texture(target, Some(&texture));
+                let (format, type_) = if is_depth {
+                    (
+                        WebGl2RenderingContext::DEPTH_COMPONENT,
+                        WebGl2RenderingContext::UNSIGNED_INT,
+                    )
+                } else {
+                    (
+                        WebGl2RenderingContext::RGBA,
+                        WebGl2RenderingContext::UNSIGNED_BYTE,
+                    )
+                };
+                if gl
+                    .tex_image_2d_with_u8_array(
+                        target,
+                        0,
+                        internalformat.try_into().unwrap(),
+                        description.width,
+                        description.height,
+                        0,
+                        format,
+                        type_,
+                        None,
+                    )
+                    .is_err()
+                {
+                    gl.delete_texture(Some(&texture));
+                    return Err("failed to allocate texture");
+                }
+                // there are no mipmaps, and depth textures can not be filtered
+                let filter = if is_depth {
+                    WebGl2RenderingContext::NEAREST
+                } else {
+                    WebGl2RenderingContext::LINEAR
+                };
+                for (pname, param) in [
+                    (WebGl2RenderingContext::TEXTURE_MIN_FILTER, filter),
+                    (WebGl2RenderingContext::TEXTURE_MAG_FILTER, filter),
+                    (
+                        WebGl2RenderingContext::TEXTURE_WRAP_S,
+                        WebGl2RenderingContext::CLAMP_TO_EDGE,
+                    ),
+                    (
+                        WebGl2RenderingContext::TEXTURE_WRAP_T,
+                        WebGl2RenderingContext::CLAMP_TO_EDGE,
+                    ),
+                ] {
+                    gl.tex_parameteri(target, pname, param.try_into().unwrap());
+                }
+                gl.framebuffer_texture_2d(
+                    WebGl2RenderingContext::FRAMEBUFFER,
+                    attachment,
+                    target,
+                    Some(&texture),
+                    0,
+                );
+                Ok(Attachment::Texture(texture))
+            }
+            AttachmentStorage::Renderbuffer => {
+                let target = WebGl2RenderingContext::RENDERBUFFER;
+                let renderbuffer = gl
+                    .create_renderbuffer()
+                    .ok_or("failed to create renderbuffer")?;
+                gl.bind_renderbuffer(target, Some(&renderbuffer));
+                if description.samples > 0 {
+                    gl.renderbuffer_storage_multisample(
+                        target,
+                        description.samples,
+                        internalformat,
+                        description.width,
+                        description.height,
+                    );
+                } else {
+                    gl.renderbuffer_storage(
+                        target,
+                        internalformat,
+                        description.width,
+                        description.height,
+                    );
+                }
+                gl.framebuffer_renderbuffer(
+                    WebGl2RenderingContext::FRAMEBUFFER,
+                    attachment,
+                    target,
+                    Some(&renderbuffer),
+                );
+                Ok(Attachment::Renderbuffer(renderbuffer))
+            }
+        }
+    }
+
+    /// the color image, if it is a texture that can be sampled
+    pub fn color_texture(&self) -> Option<&G::Texture> {
+        match &self.color {
+            Attachment::Texture(texture) => Some(texture),
+            Attachment::Renderbuffer(_) => None,
+        }
+    }
+
+    /// the depth image, if it is a texture that can be sampled
+    pub fn depth_texture(&self) -> Option<&G::Texture> {
+        match &self.depth {
+            Some(Attachment::Texture(texture)) => Some(texture),
+            _ => None,
+        }
+    }
+
+    /// Bind for drawing and set the viewport to the whole target.
+    pub fn bind(&self, gl: &G) {
+        gl.bind_framebuffer(WebGl2RenderingContext::FRAMEBUFFER, Some(&self.framebuffer));
+        gl.viewport(0, 0, self.description.width, self.description.height);
+    }
+
+    /// [Bind](Self::bind) this target, call `draw`, then put back
+    /// whichever framebuffer and viewport were current before (e.g. the XR layer's).
+    pub fn draw_with<R>(&self, gl: &G, draw: impl FnOnce(&G) -> R) -> R {
+        let previous = gl.get_draw_framebuffer_binding();
+        let [x, y, width, height] = gl.get_viewport();
+        self.bind(gl);
+        let rval = draw(gl);
+        gl.bind_framebuffer(WebGl2RenderingContext::FRAMEBUFFER, previous.as_ref());
+        gl.viewport(x, y, width, height);
+        rval
+    }
+
+    /// Copy this target's color into the single-sampled `dst`, resolving the samples if this target is multisampled.
+    /// A multisampled resolve can not scale, so the targets must be the same size.
+    pub fn resolve_into(&self, gl: &G, dst: &RenderTarget<G>) -> Result<(), &'static str> {
+        if dst.description.samples > 0 {
+            return Err("can not resolve into a multisampled target");
+        }
+        let size = [dst.description.width, dst.description.height];
+        if self.description.samples > 0 && size != [self.description.width, self.description.height]
+        {
+            return Err("a multisampled resolve needs targets of the same size");
+        }
+        self.blit_to(
+            gl,
+            Some(&dst.framebuffer),
+            [0, 0, size[0], size[1]],
+            WebGl2RenderingContext::COLOR_BUFFER_BIT,
+            WebGl2RenderingContext::LINEAR,
+        );
+        Ok(())
+    }
+
+    /// `blitFramebuffer` the whole of this target into `dst_rect` (`[x0, y0, x1, y1]`) of `dst`,
+    /// where `None` is the default framebuffer.
+    /// The draw framebuffer binding is left as it was.
+    /// # params
+    /// * `mask` - [`WebGl2RenderingContext::COLOR_BUFFER_BIT`] and/or [`WebGl2RenderingContext::DEPTH_BUFFER_BIT`]
+    /// * `filter` - [`WebGl2RenderingContext::NEAREST`] or [`WebGl2RenderingContext::LINEAR`] (color only)
+    pub fn blit_to(
+        &self,
+        gl: &G,
+        dst: Option<&G::Framebuffer>,
+        dst_rect: [i32; 4],
+        mask: u32,
+        filter: u32,
+    ) {
+        let previous = gl.get_draw_framebuffer_binding();
+        gl.bind_framebuffer(
+            WebGl2RenderingContext::READ_FRAMEBUFFER,
+            Some(&self.framebuffer),
+        );
+        gl.bind_framebuffer(WebGl2RenderingContext::DRAW_FRAMEBUFFER, dst);
+        let [x0, y0, x1, y1] = dst_rect;
+        gl.blit_framebuffer(
+            0,
+            0,
+            self.description.width,
+            self.description.height,
+            x0,
+            y0,
+            x1,
+            y1,
+            mask,
+            filter,
+        );
+        gl.bind_framebuffer(WebGl2RenderingContext::FRAMEBUFFER, previous.as_ref());
+    }
+
+    pub fn release(self, gl: &G) {
+        self.color.release(gl);
+        if let Some(depth) = self.depth {
+            depth.release(gl);
+        }
+        gl.delete_framebuffer(Some(&self.framebuffer));
+    }
+}
+
+/// A framebuffer whose color and depth are `TEXTURE_2D_ARRAY`s with one layer per view,
+/// attached with `OVR_multiview2` so a single draw lands in every layer (see [`ViewMode::Multiview`](crate::camera::ViewMode::Multiview)).
+/// The color array is `RGBA8` and can be sampled afterwards; the depth array is `DEPTH_COMPONENT24`.
+pub struct MultiviewTarget<G: GlBackend = WebGl2RenderingContext> {
+    pub framebuffer: G::Framebuffer,
+    pub color: G::Texture,
+    pub depth: G::Texture,
+    pub width: i32,
+    pub height: i32,
+    pub views: i32,
+}
+
+impl<G: GlBackend> MultiviewTarget<G> {
+    /// Allocate the arrays and check the framebuffer is complete.
+    /// The draw framebuffer binding is left as it was, and nothing is left allocated if it fails.
+    #[track_caller]
+    pub fn new(gl: &G, width: i32, height: i32, views: i32) -> Result<Self, &'static str> {
+        if !gl.has_extension(MULTIVIEW_EXTENSION) {
+            return Err("OVR_multiview2 is not supported");
+        }
+        let previous = gl.get_draw_framebuffer_binding();
+        let framebuffer = gl
+            .create_framebuffer()
+            .ok_or("failed to create framebuffer")?;
+        let Some(color) = gl.create_texture() else {
+            gl.delete_framebuffer(Some(&framebuffer));
+            return Err("failed to create texture");
+        };
+        let Some(depth) = gl.create_texture() else {
+            gl.delete_texture(Some(&color));
+            gl.delete_framebuffer(Some(&framebuffer));
+            return Err("failed to create texture");
+        };
+        let rval = Self {
+            framebuffer,
+            color,
+            depth,
+            width,
+            height,
+            views,
+        };
+        gl.bind_framebuffer(WebGl2RenderingContext::FRAMEBUFFER, Some(&rval.framebuffer));
+        for (texture, internalformat, attachment, filter) in [
+            (
+                &rval.color,
+                WebGl2RenderingContext::RGBA8,
+                WebGl2RenderingContext::COLOR_ATTACHMENT0,
+                WebGl2RenderingContext::LINEAR,
+            ),
+            (
+                &rval.depth,
+                WebGl2RenderingContext::DEPTH_COMPONENT24,
+                WebGl2RenderingContext::DEPTH_ATTACHMENT,
+                WebGl2RenderingContext::NEAREST,
+            ),
+        ] {
+            let target = WebGl2RenderingContext::TEXTURE_2D_ARRAY;
+            gl.bind_texture(target, Some(texture));
+            gl.tex_storage_3d(target, 1, internalformat, width, height, views);
+            for (pname, param) in [
+                (WebGl2RenderingContext::TEXTURE_MIN_FILTER, filter),
+                (WebGl2RenderingContext::TEXTURE_MAG_FILTER, filter),
+                (
+                    WebGl2RenderingContext::TEXTURE_WRAP_S,
+                    WebGl2RenderingContext::CLAMP_TO_EDGE,
+                ),
+                (
+                    WebGl2RenderingContext::TEXTURE_WRAP_T,
+                    WebGl2RenderingContext::CLAMP_TO_EDGE,
+                ),
+            ] {
+                gl.tex_parameteri(target, pname, param.try_into().unwrap());
+            }
+            gl.framebuffer_texture_multiview_ovr(
+                WebGl2RenderingContext::FRAMEBUFFER,
+                attachment,
+                Some(texture),
+                0,
+                0,
+                views,
+            );
+        }
+        let status = gl.check_framebuffer_status(WebGl2RenderingContext::FRAMEBUFFER);
+        gl.bind_framebuffer(WebGl2RenderingContext::FRAMEBUFFER, previous.as_ref());
+
+        if status == WebGl2RenderingContext::FRAMEBUFFER_COMPLETE {
+            Ok(rval)
+        } else {
+            rval.release(gl);
+            Err("multiview framebuffer incomplete")
+        }
+    }
+
+    /// Bind for drawing and set the viewport to the whole of every layer.
+    pub fn bind(&self, gl: &G) {
+        gl.bind_framebuffer(WebGl2RenderingContext::FRAMEBUFFER, Some(&self.framebuffer));
+        gl.viewport(0, 0, self.width, self.height);
+    }
+
+    pub fn release(self, gl: &G) {
+        gl.delete_texture(Some(&self.color));
+        gl.delete_texture(Some(&self.depth));
+        gl.delete_framebuffer(Some(&self.framebuffer));
+    }
+}
+
+//
+
+/// The kinds of GL object a [`ResourceRegistry`] keeps track of.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum ResourceKind {
+    Buffer,
+    Texture,
+    VertexArray,
+    Program,
+    Framebuffer,
+    Renderbuffer,
+    Query,
+}
+
+enum TrackedHandle<G: GlBackend> {
+    Buffer(G::Buffer),
+    Texture(G::Texture),
+    VertexArray(G::VertexArray),
+    Program(G::Program),
+    Framebuffer(G::Framebuffer),
+    Renderbuffer(G::Renderbuffer),
+    Query(G::Query),
+}
+
+impl<G: GlBackend> TrackedHandle<G> {
+    fn kind(&self) -> ResourceKind {
+        match self {
+            TrackedHandle::Buffer(_) => ResourceKind::Buffer,
+            TrackedHandle::Texture(_) => ResourceKind::Texture,
+            TrackedHandle::VertexArray(_) => ResourceKind::VertexArray,
+            TrackedHandle::Program(_) => ResourceKind::Program,
+            TrackedHandle::Framebuffer(_) => ResourceKind::Framebuffer,
+            TrackedHandle::Renderbuffer(_) => ResourceKind::Renderbuffer,
+            TrackedHandle::Query(_) => ResourceKind::Query,
+        }
+    }
+
+    fn delete(&self, gl: &G) {
+        match self {
+            TrackedHandle::Buffer(buffer) => gl.delete_buffer(Some(buffer)),
+            TrackedHandle::Texture(texture) => gl.delete_texture(Some(texture)),
+            TrackedHandle::VertexArray(vao) => gl.delete_vertex_array(Some(vao)),
+            TrackedHandle::Program(program) => gl.delete_program(Some(program)),
+            TrackedHandle::Framebuffer(framebuffer) => gl.delete_framebuffer(Some(framebuffer)),
+            TrackedHandle::Renderbuffer(renderbuffer) => {
+                gl.delete_renderbuffer(Some(renderbuffer));
+            }
+            TrackedHandle::Query(query) => gl.delete_query(Some(query)),
+        }
+    }
+}
+
+struct LiveResource<G: GlBackend> {
+    handle: TrackedHandle<G>,
+    /// only recorded in debug builds
+    site: Option<&'static Location<'static>>,
+}
+
+/// A [`GlBackend`] that forwards to another one,
+/// keeping a list of every buffer, texture, VAO, program, framebuffer, renderbuffer and query
+/// created through it until it is deleted.
+///
+/// Whatever is still alive when the owner tears down can be listed with [`Self::leak_report`]
+/// and freed in bulk with [`Self::release_all`].
+pub struct ResourceRegistry<G: GlBackend = WebGl2RenderingContext> {
+    gl: G,
+    live: RefCell<Vec<LiveResource<G>>>,
+}
+
+/// One GL object that was still alive when [`ResourceRegistry::leak_report`] was called.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Leak {
+    pub kind: ResourceKind,
+    /// where the object was created; `None` in release builds
+    pub site: Option<&'static Location<'static>>,
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct LeakReport {
+    pub leaks: Vec<Leak>,
+}
+
+impl LeakReport {
+    pub fn is_empty(&self) -> bool {
+        self.leaks.is_empty()
+    }
+}
+
+impl Display for LeakReport {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        for leak in &self.leaks {
+            match leak.site {
+                Some(site) => writeln!(f, "leaked {:?} created at {site}", leak.kind)?,
+                None => writeln!(f, "leaked {:?}", leak.kind)?,
+            }
+        }
+        Ok(())
+    }
+}
+
+impl<G: GlBackend> ResourceRegistry<G> {
+    pub fn new(gl: G) -> Self {
+        Self {
+            gl,
+            live: RefCell::new(Vec::new()),
+        }
+    }
+
+    /// the context this registry forwards to, for the calls that are not part of [`GlBackend`]
+    pub fn inner(&self) -> &G {
+        &self.gl
+    }
+
+    pub fn live_count(&self, kind: ResourceKind) -> usize {
+        self.live
+            .borrow()
+            .iter()
+            .filter(|live| live.handle.kind() == kind)
+            .count()
+    }
+
+    /// everything created through this registry that has not been deleted yet
+    pub fn leak_report(&self) -> LeakReport {
+        LeakReport {
+            leaks: self
+                .live
+                .borrow()
+                .iter()
+                .map(|live| Leak {
+                    kind: live.handle.kind(),
+                    site: live.site,
+                })
+                .collect(),
+        }
+    }
+
+    /// Stop tracking everything without deleting it.
+    /// For a lost context, whose objects are already gone.
+    pub fn forget_all(&self) {
+        self.live.borrow_mut().clear();
+    }
+
+    /// delete every object that is still alive
+    pub fn release_all(&self) {
+        for live in self.live.take() {
+            live.handle.delete(&self.gl);
+        }
+    }
+
+    #[track_caller]
+    fn track(&self, handle: TrackedHandle<G>) {
+        let site = if cfg!(debug_assertions) {
+            Some(Location::caller())
+        } else {
+            None
+        };
+        self.live.borrow_mut().push(LiveResource { handle, site });
+    }
+
+    fn forget(&self, matches: impl Fn(&TrackedHandle<G>) -> bool) {
+        let mut live = self.live.borrow_mut();
+        if let Some(idx) = live.iter().position(|live| matches(&live.handle)) {
+            live.swap_remove(idx);
+        }
+    }
+}
+
+impl<G: GlBackend> GlBackend for ResourceRegistry<G> {
+    type Buffer = G::Buffer;
+    type Texture = G::Texture;
+    type VertexArray = G::VertexArray;
+    type Shader = G::Shader;
+    type Program = G::Program;
+    type UniformLocation = G::UniformLocation;
+    type Framebuffer = G::Framebuffer;
+    type Renderbuffer = G::Renderbuffer;
+    type Query = G::Query;
+
+    #[track_caller]
+    fn create_buffer(&self) -> Option<G::Buffer> {
+        let buffer = self.gl.create_buffer()?;
+        self.track(TrackedHandle::Buffer(buffer.clone()));
+        Some(buffer)
+    }
+
+    fn bind_buffer(&self, target: u32, buffer: Option<&G::Buffer>) {
+        self.gl.bind_buffer(target, buffer);
+    }
+
+    fn buffer_data_with_u8_array(&self, target: u32, src_data: &[u8], usage: u32) {
+        self.gl.buffer_data_with_u8_array(target, src_data, usage);
+    }
+
+    fn buffer_data_with_i32(&self, target: u32, size: i32, usage: u32) {
+        self.gl.buffer_data_with_i32(target, size, usage);
+    }
+
+    fn buffer_sub_data_with_i32_and_u8_array(&self, target: u32, offset: i32, src_data: &[u8]) {
+        self.gl
+            .buffer_sub_data_with_i32_and_u8_array(target, offset, src_data);
+    }
+
+    fn bind_buffer_base(&self, target: u32, index: u32, buffer: Option<&G::Buffer>) {
+        self.gl.bind_buffer_base(target, index, buffer);
+    }
+
+    fn delete_buffer(&self, buffer: Option<&G::Buffer>) {
+        if let Some(buffer) = buffer {
+            self.forget(|h| matches!(h, TrackedHandle::Buffer(b) if b == buffer));
+        }
+        self.gl.delete_buffer(buffer);
+    }
+
+    #[track_caller]
+    fn create_vertex_array(&self) -> Option<G::VertexArray> {
+        let vao = self.gl.create_vertex_array()?;
+        self.track(TrackedHandle::VertexArray(vao.clone()));
+        Some(vao)
+    }
+
+    fn bind_vertex_array(&self, array: Option<&G::VertexArray>) {
+        self.gl.bind_vertex_array(array);
+    }
+
+    fn delete_vertex_array(&self, array: Option<&G::VertexArray>) {
+        if let Some(array) = array {
+            self.forget(|h| matches!(h, TrackedHandle::VertexArray(a) if a == array));
+        }
+        self.gl.delete_vertex_array(array);
+    }
+
+    fn vertex_attrib_pointer_with_i32(
+        &self,
+        index: u32,
+        size: i32,
+        type_: u32,
+        normalized: bool,
+        stride: i32,
+        offset: i32,
+    ) {
+        self.gl
+            .vertex_attrib_pointer_with_i32(index, size, type_, normalized, stride, offset);
+    }
+
+    fn enable_vertex_attrib_array(&self, index: u32) {
+        self.gl.enable_vertex_attrib_array(index);
+    }
+
+    fn vertex_attrib_divisor(&self, index: u32, divisor: u32) {
+        self.gl.vertex_attrib_divisor(index, divisor);
+    }
+
+    fn create_shader(&self, type_: u32) -> Option<G::Shader> {
+        self.gl.create_shader(type_)
+    }
+
+    fn shader_source(&self, shader: &G::Shader, source: &str) {
+        self.gl.shader_source(shader, source);
+    }
+
+    fn compile_shader(&self, shader: &G::Shader) {
+        self.gl.compile_shader(shader);
+    }
+
+    fn get_shader_compile_status(&self, shader: &G::Shader) -> bool {
+        self.gl.get_shader_compile_status(shader)
+    }
+
+    fn get_shader_info_log(&self, shader: &G::Shader) -> Option<String> {
+        self.gl.get_shader_info_log(shader)
+    }
+
+    fn delete_shader(&self, shader: Option<&G::Shader>) {
+        self.gl.delete_shader(shader);
+    }
+
+    #[track_caller]
+    fn create_program(&self) -> Option<G::Program> {
+        let program = self.gl.create_program()?;
+        self.track(TrackedHandle::Program(program.clone()));
+        Some(program)
+    }
+
+    fn attach_shader(&self, program: &G::Program, shader: &G::Shader) {
+        self.gl.attach_shader(program, shader);
+    }
+
+    fn bind_attrib_location(&self, program: &G::Program, index: u32, name: &str) {
+        self.gl.bind_attrib_location(program, index, name);
+    }
+
+    fn link_program(&self, program: &G::Program) {
+        self.gl.link_program(program);
+    }
+
+    fn get_program_link_status(&self, program: &G::Program) -> bool {
+        self.gl.get_program_link_status(program)
+    }
+
+    fn get_program_info_log(&self, program: &G::Program) -> Option<String> {
+        self.gl.get_program_info_log(program)
+    }
+
+    fn use_program(&self, program: Option<&G::Program>) {
+        self.gl.use_program(program);
+    }
+
+    fn delete_program(&self, program: Option<&G::Program>) {
+        if let Some(program) = program {
+            self.forget(|h| matches!(h, TrackedHandle::Program(p) if p == program));
+        }
+        self.gl.delete_program(program);
+    }
+
+    fn get_attrib_location(&self, program: &G::Program, name: &str) -> i32 {
+        self.gl.get_attrib_location(program, name)
+    }
+
+    fn get_active_attrib_count(&self, program: &G::Program) -> u32 {
+        self.gl.get_active_attrib_count(program)
+    }
+
+    fn get_active_attrib(&self, program: &G::Program, index: u32) -> Option<ActiveInfo> {
+        self.gl.get_active_attrib(program, index)
+    }
+
+    fn get_active_uniform_count(&self, program: &G::Program) -> u32 {
+        self.gl.get_active_uniform_count(program)
+    }
+
+    fn get_active_uniform(&self, program: &G::Program, index: u32) -> Option<ActiveInfo> {
+        self.gl.get_active_uniform(program, index)
+    }
+
+    fn get_uniform_location(&self, program: &G::Program, name: &str) -> Option<G::UniformLocation> {
+        self.gl.get_uniform_location(program, name)
+    }
+
+    fn get_uniform_block_index(&self, program: &G::Program, uniform_block_name: &str) -> u32 {
+        self.gl.get_uniform_block_index(program, uniform_block_name)
+    }
+
+    fn uniform_block_binding(
+        &self,
+        program: &G::Program,
+        uniform_block_index: u32,
+        uniform_block_binding: u32,
+    ) {
+        self.gl
+            .uniform_block_binding(program, uniform_block_index, uniform_block_binding);
+    }
+
+    fn uniform1i(&self, location: Option<&G::UniformLocation>, x: i32) {
+        self.gl.uniform1i(location, x);
+    }
+
+    fn uniform1f(&self, location: Option<&G::UniformLocation>, x: f32) {
+        self.gl.uniform1f(location, x);
+    }
+
+    fn uniform3fv_with_f32_array(&self, location: Option<&G::UniformLocation>, data: &[f32]) {
+        self.gl.uniform3fv_with_f32_array(location, data);
+    }
+
+    fn uniform4fv_with_f32_array(&self, location: Option<&G::UniformLocation>, data: &[f32]) {
+        self.gl.uniform4fv_with_f32_array(location, data);
+    }
+
+    fn uniform_matrix3fv_with_f32_array(
+        &self,
+        location: Option<&G::UniformLocation>,
+        transpose: bool,
+        data: &[f32],
+    ) {
+        self.gl
+            .uniform_matrix3fv_with_f32_array(location, transpose, data);
+    }
+
+    fn uniform_matrix4fv_with_f32_array(
+        &self,
+        location: Option<&G::UniformLocation>,
+        transpose: bool,
+        data: &[f32],
+    ) {
+        self.gl
+            .uniform_matrix4fv_with_f32_array(location, transpose, data);
+    }
+
+    #[track_caller]
+    fn create_texture(&self) -> Option<G::Texture> {
+        let texture = self.gl.create_texture()?;
+        self.track(TrackedHandle::Texture(texture.clone()));
+        Some(texture)
+    }
+
+    fn active_texture(&self, texture: u32) {
+        self.gl.active_texture(texture);
+    }
+
+    fn bind_texture(&self, target: u32, texture: Option<&G::Texture>) {
+        self.gl.bind_texture(target, texture);
+    }
+
+    fn get_texture_binding_2d(&self) -> Option<G::Texture> {
+        self.gl.get_texture_binding_2d()
+    }
+
+    fn tex_image_2d_with_u8_array(
+        &self,
+        target: u32,
+        level: i32,
+        internalformat: i32,
+        width: i32,
+        height: i32,
+        border: i32,
+        format: u32,
+        type_: u32,
+        pixels: Option<&[u8]>,
+    ) -> Result<(), JsValue> {
+        self.gl.tex_image_2d_with_u8_array(
+            target,
+            level,
+            internalformat,
+            width,
+            height,
+            border,
+            format,
+            type_,
+            pixels,
+        )
+    }
+
+    fn compressed_tex_image_2d_with_u8_array(
+        &self,
+        target: u32,
+        level: i32,
+        internalformat: u32,
+        width: i32,
+        height: i32,
+        border: i32,
+        data: &[u8],
+    ) {
+        self.gl.compressed_tex_image_2d_with_u8_array(
+            target,
+            level,
+            internalformat,
+            width,
+            height,
+            border,
+            data,
+        );
+    }
+
+    fn tex_image_2d_with_u32_and_u32_and_html_video_element(
+        &self,
+        target: u32,
+        level: i32,
+        internalformat: i32,
+        format: u32,
+        type_: u32,
+        source: &HtmlVideoElement,
+    ) -> Result<(), JsValue> {
+        self.gl
+            .tex_image_2d_with_u32_and_u32_and_html_video_element(
+                target,
+                level,
+                internalformat,
+                format,
+                type_,
+                source,
+            )
+    }
+
+    fn tex_storage_3d(
+        &self,
+        target: u32,
+        levels: i32,
+        internalformat: u32,
+        width: i32,
+        height: i32,
+        depth: i32,
+    ) {
+        self.gl
+            .tex_storage_3d(target, levels, internalformat, width, height, depth);
+    }
+
+    fn tex_parameteri(&self, target: u32, pname: u32, param: i32) {
+        self.gl.tex_parameteri(target, pname, param);
+    }
+
+    fn tex_parameterf(&self, target: u32, pname: u32, param: f32) {
+        self.gl.tex_parameterf(target, pname, param);
+    }
+
+    fn generate_mipmap(&self, target: u32) {
+        self.gl.generate_mipmap(target);
+    }
+
+    fn delete_texture(&self, texture: Option<&G::Texture>) {
+        if let Some(texture) = texture {
+            self.forget(|h| matches!(h, TrackedHandle::Texture(t) if t == texture));
+        }
+        self.gl.delete_texture(texture);
+    }
+
+    #[track_caller]
+    fn create_framebuffer(&self) -> Option<G::Framebuffer> {
+        let framebuffer = self.gl.create_framebuffer()?;
+        self.track(TrackedHandle::Framebuffer(framebuffer.clone()));
+        Some(framebuffer)
+    }
+
+    fn bind_framebuffer(&self, target: u32, framebuffer: Option<&G::Framebuffer>) {
+        self.gl.bind_framebuffer(target, framebuffer);
+    }
+
+    fn get_draw_framebuffer_binding(&self) -> Option<G::Framebuffer> {
+        self.gl.get_draw_framebuffer_binding()
+    }
+
+    fn framebuffer_texture_2d(
+        &self,
+        target: u32,
+        attachment: u32,
+        textarget: u32,
+        texture: Option<&G::Texture>,
+        level: i32,
+    ) {
+        self.gl
+            .framebuffer_texture_2d(target, attachment, textarget, texture, level);
+    }
+
+    fn framebuffer_renderbuffer(
+        &self,
+        target: u32,
+        attachment: u32,
+        renderbuffertarget: u32,
+        renderbuffer: Option<&G::Renderbuffer>,
+    ) {
+        self.gl
+            .framebuffer_renderbuffer(target, attachment, renderbuffertarget, renderbuffer);
+    }
+
+    fn framebuffer_texture_multiview_ovr(
+        &self,
+        target: u32,
+        attachment: u32,
+        texture: Option<&G::Texture>,
+        level: i32,
+        base_view_index: i32,
+        num_views: i32,
+    ) {
+        self.gl.framebuffer_texture_multiview_ovr(
+            target,
+            attachment,
+            texture,
+            level,
+            base_view_index,
+            num_views,
+        );
+    }
+
+    fn check_framebuffer_status(&self, target: u32) -> u32 {
+        self.gl.check_framebuffer_status(target)
+    }
+
+    fn blit_framebuffer(
+        &self,
+        src_x0: i32,
+        src_y0: i32,
+        src_x1: i32,
+        src_y1: i32,
+        dst_x0: i32,
+        dst_y0: i32,
+        dst_x1: i32,
+        dst_y1: i32,
+        mask: u32,
+        filter: u32,
+    ) {
+        self.gl.blit_framebuffer(
+            src_x0, src_y0, src_x1, src_y1, dst_x0, dst_y0, dst_x1, dst_y1, mask, filter,
+        );
+    }
+
+    fn delete_framebuffer(&self, framebuffer: Option<&G::Framebuffer>) {
+        if let Some(framebuffer) = framebuffer {
+            self.forget(|h| matches!(h, TrackedHandle::Framebuffer(f) if f == framebuffer));
+        }
+        self.gl.delete_framebuffer(framebuffer);
+    }
+
+    #[track_caller]
+    fn create_renderbuffer(&self) -> Option<G::Renderbuffer> {
+        let renderbuffer = self.gl.create_renderbuffer()?;
+        self.track(TrackedHandle::Renderbuffer(renderbuffer.clone()));
+        Some(renderbuffer)
+    }
+
+    fn bind_renderbuffer(&self, target: u32, renderbuffer: Option<&G::Renderbuffer>) {
+        self.gl.bind_renderbuffer(target, renderbuffer);
+    }
+
+    fn get_renderbuffer_binding(&self) -> Option<G::Renderbuffer> {
+        self.gl.get_renderbuffer_binding()
+    }
+
+    fn renderbuffer_storage(&self, target: u32, internalformat: u32, width: i32, height: i32) {
+        self.gl
+            .renderbuffer_storage(target, internalformat, width, height);
+    }
+
+    fn renderbuffer_storage_multisample(
+        &self,
+        target: u32,
+        samples: i32,
+        internalformat: u32,
+        width: i32,
+        height: i32,
+    ) {
+        self.gl
+            .renderbuffer_storage_multisample(target, samples, internalformat, width, height);
+    }
+
+    fn delete_renderbuffer(&self, renderbuffer: Option<&G::Renderbuffer>) {
+        if let Some(renderbuffer) = renderbuffer {
+            self.forget(|h| matches!(h, TrackedHandle::Renderbuffer(r) if r == renderbuffer));
+        }
+        self.gl.delete_renderbuffer(renderbuffer);
+    }
+
+    fn clear_color(&self, red: f32, green: f32, blue: f32, alpha: f32) {
+        self.gl.clear_color(red, green, blue, alpha);
+    }
+
+    fn clear(&self, mask: u32) {
+        self.gl.clear(mask);
+    }
+
+    fn viewport(&self, x: i32, y: i32, width: i32, height: i32) {
+        self.gl.viewport(x, y, width, height);
+    }
+
+    fn get_viewport(&self) -> [i32; 4] {
+        self.gl.get_viewport()
+    }
+
+    fn enable(&self, cap: u32) {
+        self.gl.enable(cap);
+    }
+
+    fn disable(&self, cap: u32) {
+        self.gl.disable(cap);
+    }
+
+    fn blend_func(&self, sfactor: u32, dfactor: u32) {
+        self.gl.blend_func(sfactor, dfactor);
+    }
+
+    fn depth_func(&self, func: u32) {
+        self.gl.depth_func(func);
+    }
+
+    fn depth_mask(&self, flag: bool) {
+        self.gl.depth_mask(flag);
+    }
+
+    fn has_extension(&self, name: &str) -> bool {
+        self.gl.has_extension(name)
+    }
+
+    #[track_caller]
+    fn create_query(&self) -> Option<G::Query> {
+        let query = self.gl.create_query()?;
+        self.track(TrackedHandle::Query(query.clone()));
+        Some(query)
+    }
+
+    fn begin_query(&self, target: u32, query: &G::Query) {
+        self.gl.begin_query(target, query);
+    }
+
+    fn end_query(&self, target: u32) {
+        self.gl.end_query(target);
+    }
+
+    fn get_query_result_available(&self, query: &G::Query) -> bool {
+        self.gl.get_query_result_available(query)
+    }
+
+    fn get_query_result(&self, query: &G::Query) -> u64 {
+        self.gl.get_query_result(query)
+    }
+
+    fn delete_query(&self, query: Option<&G::Query>) {
+        if let Some(query) = query {
+            self.forget(|h| matches!(h, TrackedHandle::Query(q) if q == query));
+        }
+        self.gl.delete_query(query);
+    }
+
+    fn get_gpu_disjoint(&self) -> bool {
+        self.gl.get_gpu_disjoint()
+    }
+
+    fn draw_arrays(&self, mode: u32, first: i32, count: i32) {
+        self.gl.draw_arrays(mode, first, count);
+    }
+
+    fn draw_elements_with_i32(&self, mode: u32, count: i32, type_: u32, offset: i32) {
+        self.gl.draw_elements_with_i32(mode, count, type_, offset);
+    }
+
+    fn draw_arrays_instanced(&self, mode: u32, first: i32, count: i32, instance_count: i32) {
+        self.gl
+            .draw_arrays_instanced(mode, first, count, instance_count);
+    }
+
+    fn draw_elements_instanced_with_i32(
+        &self,
+        mode: u32,
+        count: i32,
+        type_: u32,
+        offset: i32,
+        instance_count: i32,
+    ) {
+        self.gl
+            .draw_elements_instanced_with_i32(mode, count, type_, offset, instance_count);
     }
 }
@@ -0,0 +1,464 @@
+use crate::backend::GlBackend;
+use js_sys::Uint8Array;
+use wasm_bindgen::prelude::*;
+use wasm_bindgen::JsCast;
+
+/// the first 12 bytes of every KTX2 file
+pub const KTX2_IDENTIFIER: [u8; 12] = [
+    0xAB, b'K', b'T', b'X', b' ', b'2', b'0', 0xBB, b'\r', b'\n', 0x1A, b'\n',
+];
+
+pub const ASTC_EXTENSION: &str = "WEBGL_compressed_texture_astc";
+pub const ETC_EXTENSION: &str = "WEBGL_compressed_texture_etc";
+pub const S3TC_EXTENSION: &str = "WEBGL_compressed_texture_s3tc";
+pub const S3TC_SRGB_EXTENSION: &str = "WEBGL_compressed_texture_s3tc_srgb";
+
+// internal formats from the extensions above
+const COMPRESSED_RGBA_ASTC_4X4_KHR: u32 = 0x93B0;
+const COMPRESSED_SRGB8_ALPHA8_ASTC_4X4_KHR: u32 = 0x93D0;
+const COMPRESSED_RGB8_ETC2: u32 = 0x9274;
+const COMPRESSED_SRGB8_ETC2: u32 = 0x9275;
+const COMPRESSED_RGBA8_ETC2_EAC: u32 = 0x9278;
+const COMPRESSED_SRGB8_ALPHA8_ETC2_EAC: u32 = 0x9279;
+const COMPRESSED_RGB_S3TC_DXT1_EXT: u32 = 0x83F0;
+const COMPRESSED_RGBA_S3TC_DXT5_EXT: u32 = 0x83F3;
+const COMPRESSED_SRGB_S3TC_DXT1_EXT: u32 = 0x8C4C;
+const COMPRESSED_SRGB_ALPHA_S3TC_DXT5_EXT: u32 = 0x8C4F;
+
+// Khronos data format descriptor values
+const KHR_DF_MODEL_ETC1S: u8 = 163;
+const KHR_DF_MODEL_UASTC: u8 = 166;
+const KHR_DF_TRANSFER_SRGB: u8 = 2;
+const KHR_DF_CHANNEL_ETC1S_AAA: u8 = 15;
+const KHR_DF_CHANNEL_UASTC_RGBA: u8 = 3;
+const KHR_DF_CHANNEL_UASTC_RRRG: u8 = 5;
+
+const SUPERCOMPRESSION_BASIS_LZ: u32 = 1;
+
+/// The block compressed formats a KTX2 texture can end up in on the GPU. All of them use 4x4 blocks.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CompressedFormat {
+    Astc4x4,
+    Etc2Rgb,
+    Etc2Rgba,
+    Bc1,
+    Bc3,
+}
+
+impl CompressedFormat {
+    pub fn internal_format(self, srgb: bool) -> u32 {
+        match (self, srgb) {
+            (CompressedFormat::Astc4x4, false) => COMPRESSED_RGBA_ASTC_4X4_KHR,
+            (CompressedFormat::Astc4x4, true) => COMPRESSED_SRGB8_ALPHA8_ASTC_4X4_KHR,
+            (CompressedFormat::Etc2Rgb, false) => COMPRESSED_RGB8_ETC2,
+            (CompressedFormat::Etc2Rgb, true) => COMPRESSED_SRGB8_ETC2,
+            (CompressedFormat::Etc2Rgba, false) => COMPRESSED_RGBA8_ETC2_EAC,
+            (CompressedFormat::Etc2Rgba, true) => COMPRESSED_SRGB8_ALPHA8_ETC2_EAC,
+            (CompressedFormat::Bc1, false) => COMPRESSED_RGB_S3TC_DXT1_EXT,
+            (CompressedFormat::Bc1, true) => COMPRESSED_SRGB_S3TC_DXT1_EXT,
+            (CompressedFormat::Bc3, false) => COMPRESSED_RGBA_S3TC_DXT5_EXT,
+            (CompressedFormat::Bc3, true) => COMPRESSED_SRGB_ALPHA_S3TC_DXT5_EXT,
+        }
+    }
+
+    pub fn has_alpha(self) -> bool {
+        !matches!(self, CompressedFormat::Etc2Rgb | CompressedFormat::Bc1)
+    }
+
+    /// the size of one mip level, which `compressedTexImage2D` insists on exactly
+    pub fn level_byte_len(self, width: u32, height: u32) -> usize {
+        let block_bytes = if self.has_alpha() { 16 } else { 8 };
+        width.div_ceil(4) as usize * height.div_ceil(4) as usize * block_bytes
+    }
+
+    /// The Basis Universal `transcoder_texture_format` for this format,
+    /// or for uncompressed RGBA8 when `target` is `None`.
+    pub fn basis_format(target: Option<Self>) -> u32 {
+        match target {
+            Some(CompressedFormat::Etc2Rgb) => 0, // cTFETC1_RGB, which ETC2 decoders read
+            Some(CompressedFormat::Etc2Rgba) => 1,
+            Some(CompressedFormat::Bc1) => 2,
+            Some(CompressedFormat::Bc3) => 3,
+            Some(CompressedFormat::Astc4x4) => 10,
+            None => 13,
+        }
+    }
+
+    /// Interpret a Vulkan format from a KTX2 header: the GPU format (`None` for RGBA8) and whether it is sRGB.
+    fn from_vk_format(vk_format: u32) -> Option<(Option<Self>, bool)> {
+        Some(match vk_format {
+            37 => (None, false), // VK_FORMAT_R8G8B8A8_UNORM
+            43 => (None, true),
+            131 => (Some(CompressedFormat::Bc1), false), // VK_FORMAT_BC1_RGB_UNORM_BLOCK
+            132 => (Some(CompressedFormat::Bc1), true),
+            137 => (Some(CompressedFormat::Bc3), false), // VK_FORMAT_BC3_UNORM_BLOCK
+            138 => (Some(CompressedFormat::Bc3), true),
+            147 => (Some(CompressedFormat::Etc2Rgb), false), // VK_FORMAT_ETC2_R8G8B8_UNORM_BLOCK
+            148 => (Some(CompressedFormat::Etc2Rgb), true),
+            151 => (Some(CompressedFormat::Etc2Rgba), false), // VK_FORMAT_ETC2_R8G8B8A8_UNORM_BLOCK
+            152 => (Some(CompressedFormat::Etc2Rgba), true),
+            157 => (Some(CompressedFormat::Astc4x4), false), // VK_FORMAT_ASTC_4x4_UNORM_BLOCK
+            158 => (Some(CompressedFormat::Astc4x4), true),
+            _ => return None,
+        })
+    }
+}
+
+/// Which compressed texture extensions a context has.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct CompressionSupport {
+    pub astc: bool,
+    pub etc: bool,
+    pub s3tc: bool,
+    pub s3tc_srgb: bool,
+}
+
+impl CompressionSupport {
+    /// Ask the context, which also enables whichever extensions it has.
+    pub fn query<G: GlBackend>(gl: &G) -> Self {
+        Self {
+            astc: gl.has_extension(ASTC_EXTENSION),
+            etc: gl.has_extension(ETC_EXTENSION),
+            s3tc: gl.has_extension(S3TC_EXTENSION),
+            s3tc_srgb: gl.has_extension(S3TC_SRGB_EXTENSION),
+        }
+    }
+
+    pub fn supports(&self, format: CompressedFormat, srgb: bool) -> bool {
+        match format {
+            CompressedFormat::Astc4x4 => self.astc,
+            CompressedFormat::Etc2Rgb | CompressedFormat::Etc2Rgba => self.etc,
+            CompressedFormat::Bc1 | CompressedFormat::Bc3 if srgb => self.s3tc && self.s3tc_srgb,
+            CompressedFormat::Bc1 | CompressedFormat::Bc3 => self.s3tc,
+        }
+    }
+
+    /// The best format to transcode a Basis Universal texture to: ASTC, then ETC2, then S3TC,
+    /// and `None` (uncompressed RGBA8) when the context has none of them.
+    pub fn choose(&self, has_alpha: bool, srgb: bool) -> Option<CompressedFormat> {
+        let candidates = if has_alpha {
+            [
+                CompressedFormat::Astc4x4,
+                CompressedFormat::Etc2Rgba,
+                CompressedFormat::Bc3,
+            ]
+        } else {
+            [
+                CompressedFormat::Astc4x4,
+                CompressedFormat::Etc2Rgb,
+                CompressedFormat::Bc1,
+            ]
+        };
+        candidates
+            .into_iter()
+            .find(|format| self.supports(*format, srgb))
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Ktx2Header {
+    pub vk_format: u32,
+    pub type_size: u32,
+    pub pixel_width: u32,
+    pub pixel_height: u32,
+    pub pixel_depth: u32,
+    pub layer_count: u32,
+    pub face_count: u32,
+    /// 0 asks the loader to generate mipmaps from the single stored level
+    pub level_count: u32,
+    pub supercompression_scheme: u32,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Ktx2Level {
+    pub byte_offset: u64,
+    pub byte_length: u64,
+    pub uncompressed_byte_length: u64,
+}
+
+/// The parts of the basic data format descriptor needed to pick a transcode target.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct DataFormatDescriptor {
+    pub color_model: u8,
+    pub transfer_function: u8,
+    /// the channel id of each sample, without the qualifier bits
+    pub channel_ids: Vec<u8>,
+}
+
+/// How the image data in a KTX2 file is stored.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Ktx2Encoding {
+    /// Basis Universal ETC1S, supercompressed with BasisLZ
+    Etc1s,
+    /// Basis Universal UASTC, possibly zstd supercompressed
+    Uastc,
+    /// already in a GPU format (`None` for RGBA8) and uploaded as is
+    Raw(Option<CompressedFormat>),
+}
+
+/// A parsed KTX2 container. Only single 2D images (with mip levels) are supported:
+/// no arrays, cube maps or 3D textures.
+#[derive(Debug, Clone)]
+pub struct Ktx2<'a> {
+    bytes: &'a [u8],
+    pub header: Ktx2Header,
+    /// level 0 (the largest) first
+    pub levels: Vec<Ktx2Level>,
+    pub dfd: DataFormatDescriptor,
+}
+
+impl<'a> Ktx2<'a> {
+    pub fn parse(bytes: &'a [u8]) -> Result<Self, &'static str> {
+        if bytes.len() < 80 || bytes[..12] != KTX2_IDENTIFIER {
+            return Err("not a KTX2 file");
+        }
+        let u32_at =
+            |offset: usize| u32::from_le_bytes(bytes[offset..offset + 4].try_into().unwrap());
+        let u64_at =
+            |offset: usize| u64::from_le_bytes(bytes[offset..offset + 8].try_into().unwrap());
+
+        let header = Ktx2Header {
+            vk_format: u32_at(12),
+            type_size: u32_at(16),
+            pixel_width: u32_at(20),
+            pixel_height: u32_at(24),
+            pixel_depth: u32_at(28),
+            layer_count: u32_at(32),
+            face_count: u32_at(36),
+            level_count: u32_at(40),
+            supercompression_scheme: u32_at(44),
+        };
+        if header.pixel_width == 0 || header.pixel_height == 0 {
+            return Err("KTX2 texture has no size");
+        }
+        if header.pixel_depth > 0 || header.layer_count > 1 || header.face_count != 1 {
+            return Err("only single 2D KTX2 textures are supported");
+        }
+
+        let dfd_offset = u32_at(48) as usize;
+        let dfd_length = u32_at(52) as usize;
+
+        // levelCount comes from the file, so the size of its index can overflow a 32-bit usize
+        let level_count = header.level_count.max(1) as usize;
+        let level_index_end = 24_usize
+            .checked_mul(level_count)
+            .and_then(|length| length.checked_add(80));
+        if level_index_end.is_none_or(|end| bytes.len() < end) {
+            return Err("KTX2 level index is truncated");
+        }
+        let levels: Vec<_> = (0..level_count)
+            .map(|i| Ktx2Level {
+                byte_offset: u64_at(80 + 24 * i),
+                byte_length: u64_at(88 + 24 * i),
+                uncompressed_byte_length: u64_at(96 + 24 * i),
+            })
+            .collect();
+        if levels
+            .iter()
+            .any(|level| level.byte_offset.saturating_add(level.byte_length) > bytes.len() as u64)
+        {
+            return Err("KTX2 level data is truncated");
+        }
+
+        let dfd = match bytes.get(dfd_offset..dfd_offset.saturating_add(dfd_length)) {
+            Some(dfd) => Self::parse_dfd(dfd)?,
+            None => return Err("KTX2 data format descriptor is truncated"),
+        };
+
+        Ok(Self {
+            bytes,
+            header,
+            levels,
+            dfd,
+        })
+    }
+
+    /// `dfd` starts with the total size, followed by the basic descriptor block
+    fn parse_dfd(dfd: &[u8]) -> Result<DataFormatDescriptor, &'static str> {
+        let Some(block) = dfd.get(4..) else {
+            return Ok(DataFormatDescriptor::default());
+        };
+        if block.len() < 24 {
+            return Err("KTX2 data format descriptor is too short");
+        }
+        let block_size = (u16::from_le_bytes([block[6], block[7]]) as usize).min(block.len());
+        let channel_ids = block[24..block_size.max(24)]
+            .chunks_exact(16)
+            .map(|sample| sample[3] & 0xF)
+            .collect();
+        Ok(DataFormatDescriptor {
+            color_model: block[8],
+            transfer_function: block[10],
+            channel_ids,
+        })
+    }
+
+    pub fn width(&self) -> u32 {
+        self.header.pixel_width
+    }
+
+    pub fn height(&self) -> u32 {
+        self.header.pixel_height
+    }
+
+    pub fn encoding(&self) -> Result<Ktx2Encoding, &'static str> {
+        if self.header.supercompression_scheme == SUPERCOMPRESSION_BASIS_LZ {
+            return Ok(Ktx2Encoding::Etc1s);
+        }
+        if self.header.vk_format == 0 && self.dfd.color_model == KHR_DF_MODEL_UASTC {
+            return Ok(Ktx2Encoding::Uastc);
+        }
+        if self.header.supercompression_scheme != 0 {
+            return Err("supercompressed KTX2 textures must be Basis Universal");
+        }
+        match CompressedFormat::from_vk_format(self.header.vk_format) {
+            Some((format, _)) => Ok(Ktx2Encoding::Raw(format)),
+            None => Err("unsupported KTX2 vkFormat"),
+        }
+    }
+
+    pub fn is_srgb(&self) -> bool {
+        match CompressedFormat::from_vk_format(self.header.vk_format) {
+            Some((_, srgb)) => srgb,
+            None => self.dfd.transfer_function == KHR_DF_TRANSFER_SRGB,
+        }
+    }
+
+    pub fn has_alpha(&self) -> bool {
+        match self.dfd.color_model {
+            KHR_DF_MODEL_ETC1S => self.dfd.channel_ids.contains(&KHR_DF_CHANNEL_ETC1S_AAA),
+            KHR_DF_MODEL_UASTC => matches!(
+                self.dfd.channel_ids.first(),
+                Some(&KHR_DF_CHANNEL_UASTC_RGBA | &KHR_DF_CHANNEL_UASTC_RRRG)
+            ),
+            _ => match CompressedFormat::from_vk_format(self.header.vk_format) {
+                Some((Some(format), _)) => format.has_alpha(),
+                _ => true,
+            },
+        }
+    }
+
+    /// the stored bytes of mip level `level`
+    pub fn level_data(&self, level: usize) -> &'a [u8] {
+        let level = &self.levels[level];
+        &self.bytes[level.byte_offset as usize..(level.byte_offset + level.byte_length) as usize]
+    }
+}
+
+/// Turns Basis Universal payloads into GPU formats. [`BasisTranscoder`] is the real one;
+/// tests substitute their own.
+pub trait Ktx2Transcoder {
+    /// Transcode every mip level of the KTX2 file `ktx2` to `target` (`None` for RGBA8), level 0 first.
+    fn transcode(
+        &self,
+        ktx2: &[u8],
+        target: Option<CompressedFormat>,
+    ) -> Result<Vec<Vec<u8>>, JsValue>;
+}
+
+#[wasm_bindgen]
+extern "C" {
+    /// The module object that Binomial's `basis_transcoder.js` resolves to.
+    pub type BasisModule;
+
+    #[wasm_bindgen(method, js_name = initializeBasis)]
+    fn initialize_basis(this: &BasisModule);
+
+    #[wasm_bindgen(method, getter, js_name = KTX2File)]
+    fn ktx2_file_class(this: &BasisModule) -> js_sys::Function;
+
+    type Ktx2File;
+
+    #[wasm_bindgen(method, js_name = isValid)]
+    fn is_valid(this: &Ktx2File) -> bool;
+
+    #[wasm_bindgen(method, js_name = getLevels)]
+    fn get_levels(this: &Ktx2File) -> u32;
+
+    #[wasm_bindgen(method, js_name = startTranscoding)]
+    fn start_transcoding(this: &Ktx2File) -> bool;
+
+    #[wasm_bindgen(method, js_name = getImageTranscodedSizeInBytes)]
+    fn get_image_transcoded_size_in_bytes(
+        this: &Ktx2File,
+        level: u32,
+        layer: u32,
+        face: u32,
+        format: u32,
+    ) -> u32;
+
+    #[wasm_bindgen(method, js_name = transcodeImage)]
+    fn transcode_image(
+        this: &Ktx2File,
+        dst: &Uint8Array,
+        level: u32,
+        layer: u32,
+        face: u32,
+        format: u32,
+        get_alpha_for_opaque_formats: u32,
+        channel0: i32,
+        channel1: i32,
+    ) -> u32;
+
+    #[wasm_bindgen(method)]
+    fn close(this: &Ktx2File);
+
+    #[wasm_bindgen(method)]
+    fn delete(this: &Ktx2File);
+}
+
+/// [`Ktx2Transcoder`] backed by the Basis Universal WebAssembly transcoder, which the page loads and hands over.
+pub struct BasisTranscoder {
+    module: BasisModule,
+}
+
+impl BasisTranscoder {
+    pub fn new(module: JsValue) -> Self {
+        let module: BasisModule = module.unchecked_into();
+        module.initialize_basis();
+        Self { module }
+    }
+
+    fn transcode_file(
+        file: &Ktx2File,
+        target: Option<CompressedFormat>,
+    ) -> Result<Vec<Vec<u8>>, JsValue> {
+        if !file.is_valid() {
+            return Err("the Basis transcoder rejected the KTX2 file".into());
+        }
+        if !file.start_transcoding() {
+            return Err("unable to start Basis transcoding".into());
+        }
+        let format = CompressedFormat::basis_format(target);
+        (0..file.get_levels().max(1))
+            .map(|level| {
+                let len = file.get_image_transcoded_size_in_bytes(level, 0, 0, format);
+                let dst = Uint8Array::new_with_length(len);
+                if file.transcode_image(&dst, level, 0, 0, format, 0, -1, -1) == 0 {
+                    return Err(JsValue::from(format!(
+                        "failed to transcode KTX2 level {level}"
+                    )));
+                }
+                Ok(dst.to_vec())
+            })
+            .collect()
+    }
+}
+
+impl Ktx2Transcoder for BasisTranscoder {
+    fn transcode(
+        &self,
+        ktx2: &[u8],
+        target: Option<CompressedFormat>,
+    ) -> Result<Vec<Vec<u8>>, JsValue> {
+        let file: Ktx2File = js_sys::Reflect::construct(
+            &self.module.ktx2_file_class(),
+            &js_sys::Array::of1(&Uint8Array::from(ktx2)),
+        )?
+        .unchecked_into();
+        let rval = Self::transcode_file(&file, target);
+        file.close();
+        file.delete();
+        rval
+    }
+}
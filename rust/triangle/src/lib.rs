@@ -1,12 +1,31 @@
 #[macro_use]
 mod utils;
+pub mod assets;
+pub mod backend;
+pub mod camera;
+pub mod font;
 pub mod gl_thin;
+pub mod ktx2;
+pub mod lighting;
+pub mod material;
+pub mod model;
+pub mod multiview;
 pub mod objects;
+pub mod profiler;
+#[cfg(test)]
+pub mod recording;
+pub mod reflection;
+pub mod scene;
 pub mod shaders;
+pub mod state_cache;
 #[cfg(test)]
 mod test;
+pub mod text;
+pub mod texture;
+pub mod vertex_layout;
+pub mod video;
 
-use js_sys::{Date, Object, Promise, Reflect};
+use js_sys::{Object, Promise, Reflect};
 use std::cell::RefCell;
 use std::rc::Rc;
 use utils::set_panic_hook;
@@ -31,8 +50,22 @@ macro_rules! log {
         web_sys::console::log_1(&format!( $( $t )* ).into());
     }
 }
-use crate::objects::{GradientTriangle, SohmahPoster};
-pub(crate) use log;
+use crate::assets::{AssetKind, AssetLoader, AssetState};
+use crate::backend::GlBackend;
+use crate::camera::{CameraBlock, CameraUniforms, ViewMode, MULTIVIEW_VIEWS};
+use crate::font::Font;
+use crate::gl_thin::ResourceRegistry;
+use crate::lighting::{LightUniforms, Lights};
+use crate::material::{LightingModel, Material};
+use crate::model::{Model, ModelData, ModelLoad};
+use crate::multiview::MultiviewPass;
+use crate::objects::{pyramid, GradientTriangle, LitMesh, SohmahPoster, VideoPoster, POSTER_URL};
+use crate::profiler::{Profiler, ProfilerOverlay};
+use crate::scene::{NodeId, Scene, Transform};
+use crate::shaders::registry::ProgramRegistry;
+use crate::state_cache::{StateCache, StateStats};
+use crate::text::{TextLabel, TextRenderer, TextStyle, FONT_URL};
+use crate::video::VideoTexture;
 
 mod helper {
     use wasm_bindgen::JsValue;
@@ -56,6 +89,18 @@ mod helper {
             .append_child(&document.create_text_node(message));
         Ok(())
     }
+
+    /// Append `text` in a `<pre>`, for multi-line reports whose columns have to line up.
+    pub fn append_preformatted(text: &str) -> Result<(), JsValue> {
+        let document = document()?;
+        let pre = document.create_element("pre")?;
+        pre.set_text_content(Some(text));
+        document
+            .body()
+            .ok_or_else(|| JsValue::from("document has no body"))?
+            .append_child(&pre)?;
+        Ok(())
+    }
 }
 
 fn request_animation_frame_xr(session: &XrSession, f: &Closure<dyn FnMut(f64, XrFrame)>) -> u32 {
@@ -99,49 +144,623 @@ extern "C" {
     fn debug_new_layer(session: &XrSession, ctx: &WebGl2RenderingContext) -> XrWebGlLayer;
 }
 
-pub struct DrawLogic {
-    gradient_triangle: GradientTriangle,
-    sohma_poster: SohmahPoster,
+/// The GL context the app draws through: WebGL2 behind a [`StateCache`],
+/// with every GL object tracked so it can be released at teardown.
+pub type AppGl = ResourceRegistry<StateCache<WebGl2RenderingContext>>;
+
+/// how wide the message may get before it wraps, in ems
+const MESSAGE_WIDTH: f32 = 25.0;
+
+/// how often the profiler's report is logged while profiling, in frames (a few seconds' worth)
+const PROFILER_REPORT_PERIOD: u64 = 360;
+
+/// What a node of [`DrawLogic::scene`] draws: one of the objects the draw logic owns.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SceneObject {
+    GradientTriangle,
+    Poster,
+    Pyramid,
+    /// nothing until [`DrawLogic::set_video`]
+    Video,
+    /// nothing while the profiler is off
+    ProfilerOverlay,
+    /// nothing until there is a message and a font to write it with
+    Message,
+    /// a mesh of one of the models added with [`DrawLogic::add_model`]
+    Model {
+        model: usize,
+        mesh: usize,
+    },
 }
 
-impl DrawLogic {
-    pub fn new(gl: &WebGl2RenderingContext) -> Result<Self, JsValue> {
+impl SceneObject {
+    /// Drawn after everything else, over it rather than depth tested against it.
+    fn is_overlay(self) -> bool {
+        matches!(self, Self::ProfilerOverlay | Self::Message)
+    }
+}
+
+pub struct DrawLogic<G: GlBackend = AppGl> {
+    /// every object's programs, shared where they are the same, with how long each took to link
+    pub programs: ProgramRegistry<G>,
+    camera: CameraUniforms<G>,
+    lights: LightUniforms<G>,
+    /// uploaded by [`Self::upload_lights`]
+    pub scene_lights: Lights,
+    gradient_triangle: GradientTriangle<G>,
+    sohma_poster: SohmahPoster<G>,
+    pyramid: LitMesh<G>,
+    /// where everything is drawn, the same for the page and the headset
+    pub scene: Scene<SceneObject>,
+    /// turned by [`Self::animate`]
+    pyramid_node: NodeId,
+    /// disabled until [`Self::set_profiling`]
+    pub profiler: Profiler<G>,
+    /// created the first time the profiler is enabled
+    profiler_overlay: Option<ProfilerOverlay<G>>,
+    /// whether [`POSTER_URL`] has finished loading (or failing to), so [`Self::use_assets`] can stop looking
+    poster_settled: bool,
+    /// set with [`Self::set_video`]
+    video_poster: Option<VideoPoster<G>>,
+    /// in the order they were added; [`SceneObject::Model`] refers to them by index
+    models: Vec<Model<G>>,
+    /// started by [`Self::load_model`], each with where to put the model once it has arrived
+    model_loads: Vec<(ModelLoad, Transform)>,
+    /// created once [`FONT_URL`] has loaded; until then nothing is written in the scene
+    text: Option<TextRenderer<G>>,
+    /// whether [`FONT_URL`] has finished loading (or failing to)
+    font_settled: bool,
+    /// set with [`Self::set_message`], and kept so the label can be made once the font arrives
+    message: String,
+    message_label: Option<TextLabel<G>>,
+    /// the profiler's report next to its overlay
+    profiler_label: Option<TextLabel<G>>,
+    /// created by the first [`Self::draw_multiview`]
+    multiview: Option<MultiviewPass<G>>,
+    /// set when `OVR_multiview2` is unsupported or a `MULTIVIEW` variant does not link,
+    /// after which XR frames draw each eye separately (until [`Self::recreate`])
+    multiview_failed: bool,
+}
+
+impl<G: GlBackend> DrawLogic<G> {
+    pub fn new(gl: &G) -> Result<Self, JsValue> {
+        let programs = ProgramRegistry::new();
+        let (scene, pyramid_node) = Self::default_scene();
         Ok(Self {
-            gradient_triangle: GradientTriangle::new(gl)?,
-            sohma_poster: SohmahPoster::new(gl)?,
+            camera: CameraUniforms::new(gl)?,
+            lights: LightUniforms::new(gl)?,
+            scene_lights: Lights::default(),
+            gradient_triangle: GradientTriangle::new(gl, &programs)?,
+            sohma_poster: SohmahPoster::new(gl, &programs)?,
+            pyramid: Self::new_pyramid(gl, &programs)?,
+            scene,
+            pyramid_node,
+            profiler: Profiler::new(gl),
+            profiler_overlay: None,
+            poster_settled: false,
+            video_poster: None,
+            models: Vec::new(),
+            model_loads: Vec::new(),
+            text: None,
+            font_settled: false,
+            message: String::new(),
+            message_label: None,
+            profiler_label: None,
+            multiview: None,
+            multiview_failed: false,
+            programs,
         })
     }
 
-    fn blue() -> f32 {
-        const PERIOD: f64 = 10000.0;
-        let now = Date::now();
-        ((now % PERIOD) / PERIOD) as f32
+    /// A stage a meter in front of where the viewer starts, with everything on it.
+    /// Returns the scene and the pyramid's node.
+    fn default_scene() -> (Scene<SceneObject>, NodeId) {
+        use glam::{vec3, Quat};
+
+        let mut scene = Scene::new();
+        let stage = Transform::from_translation(vec3(0.0, 0.0, -1.0));
+        let stage = scene.add(None, "stage", stage, None);
+        let mut place =
+            |name, local: Transform, object| scene.add(Some(stage), name, local, Some(object));
+        place(
+            "gradient triangle",
+            Transform::from_translation(vec3(-0.3, 0.0, 0.0)).with_scale(glam::Vec3::splat(0.2)),
+            SceneObject::GradientTriangle,
+        );
+        place(
+            "poster",
+            Transform::from_translation(vec3(0.3, 0.0, 0.0)).with_scale(glam::Vec3::splat(0.2)),
+            SceneObject::Poster,
+        );
+        // between and below the triangle and poster
+        let pyramid = place(
+            "pyramid",
+            Transform::from_translation(vec3(0.0, -0.15, 0.0))
+                .with_rotation(Quat::from_rotation_y(0.6))
+                .with_scale(glam::Vec3::splat(0.15)),
+            SceneObject::Pyramid,
+        );
+        // above the triangle and poster, facing the viewer
+        place(
+            "video",
+            Transform::from_translation(vec3(0.0, 0.35, -0.2)).with_scale(vec3(0.25, 0.25, 1.0)),
+            SceneObject::Video,
+        );
+        // below and to the left of the stage, tilted up towards the viewer
+        place(
+            "profiler overlay",
+            Transform::from_translation(vec3(-0.4, -0.2, 0.1))
+                .with_rotation(Quat::from_rotation_x(0.4))
+                .with_scale(vec3(0.3, 0.3, 1.0)),
+            SceneObject::ProfilerOverlay,
+        );
+        // above the video
+        place(
+            "message",
+            Transform::from_translation(vec3(-0.45, 0.75, -0.2))
+                .with_scale(vec3(0.035, 0.035, 1.0)),
+            SceneObject::Message,
+        );
+        (scene, pyramid)
+    }
+
+    /// Move whatever moves by itself to where it is at `timestamp` (milliseconds).
+    pub fn animate(&mut self, timestamp: f64) {
+        let local = self
+            .scene
+            .node(self.pyramid_node)
+            .local()
+            .with_rotation(glam::Quat::from_rotation_y((timestamp / 2000.0) as f32));
+        self.scene.set_local(self.pyramid_node, local);
+    }
+
+    /// A brass pyramid, to show off the lighting.
+    fn new_pyramid(gl: &G, programs: &ProgramRegistry<G>) -> Result<LitMesh<G>, JsValue> {
+        let (vertices, indices) = pyramid();
+        let mut material = Material::new(glam::vec4(0.9, 0.6, 0.2, 1.0))
+            .with_lighting(LightingModel::MetallicRoughness);
+        material.metallic = 1.0;
+        material.roughness = 0.35;
+        LitMesh::new(gl, programs, &vertices, &indices, material)
+    }
+
+    /// Swap in whatever has arrived since the last frame. Until then the placeholders are drawn.
+    pub fn use_assets(&mut self, gl: &G, assets: &AssetLoader) {
+        if !self.poster_settled {
+            self.use_poster(gl, assets);
+        }
+        if !self.font_settled {
+            self.use_font(gl, assets);
+        }
+        if !self.model_loads.is_empty() {
+            self.use_models(gl, assets);
+        }
+    }
+
+    fn use_poster(&mut self, gl: &G, assets: &AssetLoader) {
+        match assets.state(POSTER_URL) {
+            Some(AssetState::Loaded(asset)) => {
+                self.poster_settled = true;
+                let Some(image) = asset.as_image() else {
+                    log!("{POSTER_URL} is not an image");
+                    return;
+                };
+                if let Err(e) = self.sohma_poster.set_image(gl, image.clone()) {
+                    console::log_2(&format!("unable to upload {POSTER_URL}").into(), &e);
+                }
+            }
+            Some(AssetState::Failed(e)) => {
+                self.poster_settled = true;
+                log!("unable to load {POSTER_URL}: {e}");
+            }
+            Some(AssetState::Pending) | None => {}
+        }
+    }
+
+    fn use_font(&mut self, gl: &G, assets: &AssetLoader) {
+        match assets.state(FONT_URL) {
+            Some(AssetState::Loaded(asset)) => {
+                self.font_settled = true;
+                let font = asset
+                    .as_binary()
+                    .ok_or("not a binary asset")
+                    .and_then(|bytes| Font::parse(bytes.clone()));
+                let font = match font {
+                    Ok(font) => font,
+                    Err(e) => {
+                        log!("unable to read {FONT_URL}: {e}");
+                        return;
+                    }
+                };
+                match TextRenderer::new(gl, &self.programs, font) {
+                    Ok(text) => self.text = Some(text),
+                    Err(e) => console::log_2(&"unable to create the text renderer".into(), &e),
+                }
+                let message = std::mem::take(&mut self.message);
+                self.set_message(gl, &message);
+            }
+            Some(AssetState::Failed(e)) => {
+                self.font_settled = true;
+                log!("unable to load {FONT_URL}: {e}");
+            }
+            Some(AssetState::Pending) | None => {}
+        }
+    }
+
+    fn use_models(&mut self, gl: &G, assets: &AssetLoader) {
+        for (mut load, local) in std::mem::take(&mut self.model_loads) {
+            match load.poll(assets) {
+                None => self.model_loads.push((load, local)),
+                Some(Ok(data)) => {
+                    if let Err(e) = self.add_model(gl, data, &load.url, local) {
+                        console::log_2(&format!("unable to upload {}", load.url).into(), &e);
+                    }
+                }
+                Some(Err(e)) => {
+                    log!("unable to load {}: {e}", load.url);
+                }
+            }
+        }
     }
 
-    pub fn draw(&self, gl: &WebGl2RenderingContext) {
-        use glam::{vec3, Mat4};
-        const SCALE: f32 = 0.4;
+    /// Fetch the glTF model at `url` (and the files it refers to) through the assets given to
+    /// [`Self::use_assets`], then put it on the stage at `local`.
+    pub fn load_model(&mut self, url: &str, local: Transform) {
+        self.model_loads.push((ModelLoad::new(url), local));
+    }
+
+    /// Upload `data` and put it on the stage at `local`, under a node called `name`.
+    pub fn add_model(
+        &mut self,
+        gl: &G,
+        data: ModelData,
+        name: &str,
+        local: Transform,
+    ) -> Result<NodeId, JsValue> {
+        let model = Model::new(gl, &self.programs, Rc::new(data))?;
+        let index = self.models.len();
+        let stage = self.scene.find("stage");
+        let node = model
+            .data
+            .add_to_scene(&mut self.scene, stage, name, local, |mesh| {
+                SceneObject::Model { model: index, mesh }
+            });
+        self.models.push(model);
+        Ok(node)
+    }
+
+    /// Show `message` in the scene (once the font has loaded), replacing the previous one.
+    /// An empty message hides it.
+    pub fn set_message(&mut self, gl: &G, message: &str) {
+        self.message = message.to_string();
+        let Some(text) = &mut self.text else {
+            return;
+        };
+        let result = match &mut self.message_label {
+            Some(label) => text.set_text(gl, label, message),
+            None => text
+                .label(gl, message, &TextStyle::wrapped(MESSAGE_WIDTH))
+                .map(|label| self.message_label = Some(label)),
+        };
+        if let Err(e) = result {
+            console::log_2(&"unable to set the message".into(), &e);
+        }
+    }
+
+    pub fn message(&self) -> &str {
+        &self.message
+    }
+
+    /// Show `video` on a quad above the scene, replacing (and releasing) any previous one.
+    pub fn set_video(&mut self, gl: &G, video: Option<VideoTexture<G>>) -> Result<(), JsValue> {
+        if let Some(old) = self.video_poster.take() {
+            old.release(gl);
+        }
+        if let Some(video) = video {
+            self.video_poster = Some(VideoPoster::new(gl, &self.programs, video)?);
+        }
+        Ok(())
+    }
 
-        gl.clear_color(0.0, 1.0, Self::blue(), 1.0);
+    pub fn video(&self) -> Option<&VideoTexture<G>> {
+        self.video_poster.as_ref().map(|poster| &poster.video)
+    }
+
+    /// Upload the video's latest frame. Once per animation frame, before drawing any view.
+    pub fn update_video(&mut self, gl: &G) {
+        if let Some(video_poster) = &mut self.video_poster {
+            if let Err(e) = video_poster.update(gl) {
+                console::log_2(&"unable to upload video frame".into(), &e);
+            }
+        }
+    }
+
+    /// Upload [`Self::scene_lights`]. Once per animation frame, before drawing any view;
+    /// every eye and every lit object shares the upload.
+    pub fn upload_lights(&mut self, gl: &G) {
+        if let Err(e) = self.lights.upload_and_bind(gl, &self.scene_lights) {
+            log!("unable to upload the lights: {e}");
+        }
+    }
+
+    /// # params
+    /// * `timestamp` - milliseconds, as passed to the animation frame callback
+    fn blue(timestamp: f64) -> f32 {
+        const PERIOD: f64 = 10000.0;
+        ((timestamp % PERIOD) / PERIOD) as f32
+    }
+
+    /// The scene on the page, seen from where the viewer starts in XR.
+    pub fn draw(&mut self, gl: &G, timestamp: f64) {
+        gl.clear_color(0.0, 1.0, Self::blue(timestamp), 1.0);
         gl.clear(
             WebGl2RenderingContext::COLOR_BUFFER_BIT | WebGl2RenderingContext::DEPTH_BUFFER_BIT,
         );
 
-        let scale = Mat4::from_scale(vec3(SCALE, SCALE, SCALE));
-        {
-            let model = (Mat4::from_translation(vec3(-0.5, 0.0, 0.0)) * scale).to_cols_array();
-            self.gradient_triangle.draw(gl, &model);
+        self.animate(timestamp);
+        if let Err(e) = self.draw_view(gl, &Self::page_camera()) {
+            console::log_2(&"unable to draw the page:".into(), &e);
+        }
+    }
+
+    /// Looking down -Z from the origin onto the page's square canvas.
+    fn page_camera() -> CameraBlock {
+        let projection = glam::Mat4::perspective_rh_gl(75f32.to_radians(), 1.0, 0.05, 100.0);
+        CameraBlock::new(&projection, &glam::Mat4::IDENTITY, glam::Vec3::ZERO, 0)
+    }
+
+    pub fn draw_xr_single(
+        &mut self,
+        gl: &G,
+        xr_view: &XrView,
+        eye_index: i32,
+    ) -> Result<(), JsValue> {
+        self.draw_view(gl, &CameraBlock::from_xr_view(xr_view, eye_index))
+    }
+
+    /// Upload `camera` once, then draw the scene for that eye.
+    /// Nothing is drawn if the upload fails (e.g. while the context is being lost).
+    pub fn draw_view(&mut self, gl: &G, camera: &CameraBlock) -> Result<(), JsValue> {
+        self.camera.upload_and_bind(gl, camera)?;
+        self.draw_scene(gl, ViewMode::Single);
+        Ok(())
+    }
+
+    /// Draw both eyes in one pass with `OVR_multiview2`, then copy each eye into its viewport of `dst`,
+    /// which is left bound. Fails if the extension is missing or a `MULTIVIEW` variant does not link,
+    /// which also turns the multiview path off; other failures (e.g. while the context is being lost)
+    /// only fail this frame.
+    /// # params
+    /// * `viewports` - `[x, y, width, height]` per eye; both eyes are drawn at the size of the first
+    pub fn draw_multiview(
+        &mut self,
+        gl: &G,
+        cameras: &[CameraBlock; MULTIVIEW_VIEWS],
+        dst: Option<&G::Framebuffer>,
+        viewports: &[[i32; 4]; MULTIVIEW_VIEWS],
+    ) -> Result<(), JsValue> {
+        // a vao that can not be created means the context is going, and recreate turns this back on
+        let supported = (|| {
+            if self.multiview.is_none() {
+                self.multiview = Some(MultiviewPass::new(gl, &self.programs)?);
+            }
+            self.enable_multiview(gl)
+        })();
+        if supported.is_err() {
+            self.multiview_failed = true;
+        }
+        supported?;
+        let [_, _, width, height] = viewports[0];
+        if let Some(multiview) = &mut self.multiview {
+            multiview.begin(gl, width, height)?;
+        }
+        self.camera.upload_and_bind_views(gl, cameras)?;
+        self.profiler.begin_scope(gl, "both eyes");
+        self.draw_scene(gl, ViewMode::Multiview);
+        self.profiler.end_scope(gl);
+
+        gl.bind_framebuffer(WebGl2RenderingContext::FRAMEBUFFER, dst);
+        if let Some(multiview) = &self.multiview {
+            self.profiler.scope(gl, "copy views", || {
+                for (view, &[x, y, width, height]) in viewports.iter().enumerate() {
+                    gl.viewport(x, y, width, height);
+                    multiview.copy_view(gl, view.try_into().unwrap());
+                }
+            });
+        }
+        Ok(())
+    }
+
+    /// Link the `MULTIVIEW` variants of everything in the scene that has none yet,
+    /// including whatever was created or recreated since the last call.
+    fn enable_multiview(&mut self, gl: &G) -> Result<(), JsValue> {
+        self.gradient_triangle
+            .enable_multiview(gl, &self.programs)?;
+        self.sohma_poster.enable_multiview(gl, &self.programs)?;
+        self.pyramid.enable_multiview(gl, &self.programs)?;
+        if let Some(video_poster) = &mut self.video_poster {
+            video_poster.enable_multiview(gl, &self.programs)?;
+        }
+        for model in &mut self.models {
+            model.enable_multiview(gl, &self.programs)?;
+        }
+        if let Some(overlay) = &mut self.profiler_overlay {
+            overlay.enable_multiview(gl, &self.programs)?;
+        }
+        if let Some(text) = &mut self.text {
+            text.enable_multiview(gl, &self.programs)?;
+        }
+        Ok(())
+    }
+
+    /// Everything in the scene, with whichever camera is bound: the opaque objects depth tested,
+    /// then the overlays on top. The depth test is off again afterwards.
+    fn draw_scene(&mut self, gl: &G, mode: ViewMode) {
+        self.scene.update_world_matrices();
+        // copied out, since drawing the profiler overlay needs all of `self`
+        let (overlays, opaque): (Vec<_>, Vec<_>) = self
+            .scene
+            .drawables()
+            .map(|(object, world)| (*object, world.to_cols_array()))
+            .partition(|(object, _)| object.is_overlay());
+        gl.enable(WebGl2RenderingContext::DEPTH_TEST);
+        gl.depth_func(WebGl2RenderingContext::LESS);
+        for (object, model) in &opaque {
+            self.draw_object(gl, *object, model, mode);
+        }
+        gl.disable(WebGl2RenderingContext::DEPTH_TEST);
+        for (object, model) in &overlays {
+            self.draw_object(gl, *object, model, mode);
+        }
+    }
+
+    fn draw_object(&mut self, gl: &G, object: SceneObject, model: &[f32; 16], mode: ViewMode) {
+        match object {
+            SceneObject::GradientTriangle => self.profiler.scope(gl, "gradient triangle", || {
+                self.gradient_triangle.draw(gl, model, mode)
+            }),
+            SceneObject::Poster => self
+                .profiler
+                .scope(gl, "poster", || self.sohma_poster.draw(gl, model, mode)),
+            SceneObject::Pyramid => self
+                .profiler
+                .scope(gl, "pyramid", || self.pyramid.draw(gl, model, mode)),
+            SceneObject::Video => {
+                if let Some(video_poster) = &self.video_poster {
+                    self.profiler
+                        .scope(gl, "video", || video_poster.draw(gl, model, mode));
+                }
+            }
+            SceneObject::ProfilerOverlay => self.draw_profiler_overlay(gl, model, mode),
+            SceneObject::Message => self.draw_message(gl, model, mode),
+            SceneObject::Model { model: index, mesh } => self.profiler.scope(gl, "model", || {
+                self.models[index].draw_mesh(gl, mesh, model, mode)
+            }),
+        }
+    }
+
+    /// Build fresh GPU objects on a restored context from the sources compiled into the app,
+    /// keeping CPU-side state like the profiler's history.
+    pub fn recreate(&mut self, gl: &G) -> Result<(), JsValue> {
+        // whatever the objects still hold died with the old context
+        self.programs.forget_all();
+        self.camera.recreate(gl)?;
+        self.lights.recreate(gl)?;
+        // made again by the next multiview frame, which also links the variants again
+        self.multiview = None;
+        self.multiview_failed = false;
+        self.gradient_triangle.recreate(gl, &self.programs)?;
+        self.sohma_poster.recreate(gl, &self.programs)?;
+        self.pyramid.recreate(gl, &self.programs)?;
+        self.profiler.recreate(gl);
+        if let Some(overlay) = &mut self.profiler_overlay {
+            overlay.recreate(gl, &self.programs)?;
+        }
+        if let Some(video_poster) = &mut self.video_poster {
+            video_poster.recreate(gl, &self.programs)?;
+        }
+        for model in &mut self.models {
+            model.recreate(gl, &self.programs)?;
+        }
+        if let Some(text) = &mut self.text {
+            text.recreate(gl, &self.programs)?;
+            for label in [&mut self.message_label, &mut self.profiler_label]
+                .into_iter()
+                .flatten()
+            {
+                text.recreate_label(gl, label)?;
+            }
+        }
+        Ok(())
+    }
+
+    /// Turn the profiler and its overlay on or off. Call this between frames.
+    pub fn set_profiling(&mut self, gl: &G, enabled: bool) -> Result<(), JsValue> {
+        if enabled && self.profiler_overlay.is_none() {
+            self.profiler_overlay = Some(ProfilerOverlay::new(gl, &self.programs)?);
+        }
+        self.profiler.set_enabled(enabled);
+        Ok(())
+    }
+
+    fn draw_profiler_overlay(&mut self, gl: &G, model: &[f32; 16], mode: ViewMode) {
+        if !self.profiler.is_enabled() {
+            return;
+        }
+        if let Some(overlay) = &mut self.profiler_overlay {
+            overlay.draw(gl, &self.profiler, model, mode);
+        }
+        let Some(text) = &mut self.text else {
+            return;
+        };
+        // refreshed a few times a second; every frame would be unreadable and rebuild the mesh each time
+        let refresh = self.profiler.frame_count().is_multiple_of(30);
+        let result = match &mut self.profiler_label {
+            Some(label) if refresh => text.set_text(gl, label, &self.profiler.report().to_string()),
+            Some(_) => Ok(()),
+            None => {
+                let style = TextStyle {
+                    size: 0.06,
+                    ..TextStyle::default()
+                };
+                text.label(gl, &self.profiler.report().to_string(), &style)
+                    .map(|label| self.profiler_label = Some(label))
+            }
+        };
+        if let Err(e) = result {
+            console::log_2(&"unable to update the profiler text".into(), &e);
         }
+        if let Some(label) = &self.profiler_label {
+            // to the right of the bars, which end at twice the budget
+            let model = to_mat4(model) * glam::Mat4::from_translation(glam::vec3(2.1, 0.0, 0.0));
+            text.draw(gl, label, &model.to_cols_array(), mode);
+        }
+    }
+
+    fn draw_message(&self, gl: &G, model: &[f32; 16], mode: ViewMode) {
+        if let (Some(text), Some(label)) = (&self.text, &self.message_label) {
+            if !label.text().is_empty() {
+                text.draw(gl, label, model, mode);
+            }
+        }
+    }
+
+    pub fn release(self, gl: &G) {
+        self.camera.release(gl);
+        self.lights.release(gl);
+        self.gradient_triangle.release(gl);
+        self.sohma_poster.release(gl);
+        self.pyramid.release(gl);
+        self.profiler.release(gl);
+        if let Some(overlay) = self.profiler_overlay {
+            overlay.release(gl);
+        }
+        if let Some(video_poster) = self.video_poster {
+            video_poster.release(gl);
+        }
+        for model in self.models {
+            model.release(gl);
+        }
+        for label in [self.message_label, self.profiler_label]
+            .into_iter()
+            .flatten()
         {
-            let model = (Mat4::from_translation(vec3(0.5, 0.0, 0.0)) * scale).to_cols_array();
-            self.sohma_poster.draw(gl, &model);
+            label.release(gl);
+        }
+        if let Some(text) = self.text {
+            text.release(gl);
+        }
+        if let Some(multiview) = self.multiview {
+            multiview.release(gl);
         }
     }
+}
 
+impl DrawLogic {
     pub fn draw_xr(
-        &self,
-        gl: &WebGl2RenderingContext,
-        _timestamp: f64,
+        &mut self,
+        gl: &AppGl,
+        timestamp: f64,
         frame: &XrFrame,
         viewer_ref_space: &XrReferenceSpace,
         session: &XrSession,
@@ -152,6 +771,7 @@ impl DrawLogic {
             return;
         };
         let gl_layer = session.render_state().base_layer().unwrap();
+        self.animate(timestamp);
 
         gl.bind_framebuffer(
             WebGl2RenderingContext::FRAMEBUFFER,
@@ -162,9 +782,37 @@ impl DrawLogic {
             WebGl2RenderingContext::COLOR_BUFFER_BIT | WebGl2RenderingContext::DEPTH_BUFFER_BIT,
         );
 
-        for view in viewer_pose.views() {
+        let views: Vec<XrView> = viewer_pose.views().into_iter().map(XrView::from).collect();
+        if let ([left, right], false) = (views.as_slice(), self.multiview_failed) {
+            let cameras = [
+                CameraBlock::from_xr_view(left, 0),
+                CameraBlock::from_xr_view(right, 1),
+            ];
+            let viewport = |view| {
+                let viewport = gl_layer.get_viewport(view).unwrap();
+                [
+                    viewport.x(),
+                    viewport.y(),
+                    viewport.width(),
+                    viewport.height(),
+                ]
+            };
+            let viewports = [viewport(left), viewport(right)];
+            match self.draw_multiview(gl, &cameras, gl_layer.framebuffer().as_ref(), &viewports) {
+                Ok(()) => return,
+                Err(e) => {
+                    console::log_2(&"drawing each eye separately:".into(), &e);
+                    gl.bind_framebuffer(
+                        WebGl2RenderingContext::FRAMEBUFFER,
+                        gl_layer.framebuffer().as_ref(),
+                    );
+                }
+            }
+        }
+
+        // the fallback: the whole scene once per eye
+        for (eye_index, view) in views.iter().enumerate() {
             // console::log_2(&"view ".into(), &view);
-            let view = &XrView::from(view);
             let viewport = gl_layer.get_viewport(view).unwrap();
             // console::log_2(&"viewport ".into(), &viewport);
             gl.viewport(
@@ -173,51 +821,15 @@ impl DrawLogic {
                 viewport.width(),
                 viewport.height(),
             );
-            self.draw_xr_single(gl, view);
-        }
-    }
-
-    pub fn draw_xr_single(&self, gl: &WebGl2RenderingContext, xr_view: &XrView) {
-        use glam::vec3;
-        let pv = projection_view_for(xr_view);
-
-        {
-            const SCALE: f32 = 0.2;
-            let scale = glam::Mat4::from_scale(vec3(SCALE, SCALE, SCALE));
-            let offset = glam::Mat4::from_translation(vec3(-0.3, 0.0, -1.0));
-            let model = offset * scale;
-
-            let mvp = pv * model;
-            let mvp_flat: &[f32; 16] = mvp.as_ref();
-
-            self.gradient_triangle.draw(gl, mvp_flat);
-        }
-        {
-            const SCALE: f32 = 0.2;
-            let scale = glam::Mat4::from_scale(vec3(SCALE, SCALE, SCALE));
-            let offset = glam::Mat4::from_translation(vec3(0.3, 0.0, -1.0));
-            let model = offset * scale;
-
-            let mvp = pv * model;
-            let mvp_flat: &[f32; 16] = mvp.as_ref();
-
-            self.sohma_poster.draw(gl, mvp_flat);
+            const EYES: [&str; 2] = ["left eye", "right eye"];
+            let name = EYES.get(eye_index).copied().unwrap_or("extra view");
+            self.profiler.begin_scope(gl, name);
+            if let Err(e) = self.draw_xr_single(gl, view, eye_index.try_into().unwrap()) {
+                console::log_2(&format!("unable to draw the {name}:").into(), &e);
+            }
+            self.profiler.end_scope(gl);
         }
     }
-
-    pub fn release(self, gl: &WebGl2RenderingContext) {
-        self.sohma_poster.release(gl);
-    }
-}
-
-fn projection_view_for(xr_view: &XrView) -> glam::Mat4 {
-    let p = xr_view.projection_matrix();
-    // console::log_2(&"proj= ".into(), &Float32Array::from(p.as_slice()));
-    let view = xr_view.transform();
-    // console::log_2(&"view= ".into(), &view);
-    let vm = to_mat4(&view.matrix()).inverse();
-    let pm = to_mat4(&p);
-    pm * vm
 }
 
 #[must_use]
@@ -232,12 +844,107 @@ pub fn to_mat4(src: &[f32]) -> glam::Mat4 {
 
 pub struct AppInner {
     session: Option<XrSession>,
-    gl: WebGl2RenderingContext,
+    gl: AppGl,
     viewer_ref_space: Option<XrReferenceSpace>,
-    draw_logic: DrawLogic,
+    /// `None` once the app has been torn down
+    draw_logic: Option<DrawLogic>,
+    /// what the [`StateCache`] saved during the most recent frame
+    last_frame_stats: StateStats,
+    /// between `webglcontextlost` and `webglcontextrestored`, when every GL handle is dead
+    context_lost: bool,
+    /// Bumped whenever a new animation loop starts; an older loop sees the change and ends.
+    loop_generation: u32,
+    assets: AssetLoader,
 }
 
 impl AppInner {
+    /// Tell the user something, both on the page and (once the font has loaded) in the scene,
+    /// where it can be read in a headset.
+    fn show_message(&mut self, message: &str) {
+        let _ = helper::append_to_document(&format!(" {message} "));
+        if let Some(draw_logic) = &mut self.draw_logic {
+            draw_logic.set_message(&self.gl, message);
+        }
+    }
+
+    /// Release everything [`DrawLogic`] owns, report (in debug builds) any GL objects that outlived it,
+    /// then delete those too.
+    fn teardown(&mut self) {
+        if let Some(draw_logic) = self.draw_logic.take() {
+            draw_logic.release(&self.gl);
+        }
+        let leaks = self.gl.leak_report();
+        if !leaks.is_empty() {
+            log!("{leaks}");
+        }
+        self.gl.release_all();
+    }
+
+    /// Start a fresh animation loop (on the session if there is one, otherwise on the window)
+    /// and let any previous loop die out.
+    fn start_animation_loop(app: &Rc<RefCell<AppInner>>) {
+        app.borrow_mut().loop_generation += 1;
+        request_animation_frame(
+            animation_callback(app.clone()).borrow().as_ref().unwrap(),
+            &app.borrow(),
+        );
+    }
+
+    /// The browser took the GL context away. Every handle is already dead,
+    /// so they are forgotten rather than deleted, and the animation loop stops at its next frame.
+    fn context_lost(&mut self) {
+        self.context_lost = true;
+        self.gl.forget_all();
+        self.gl.inner().invalidate();
+    }
+
+    /// Rebuild the GPU side of [`DrawLogic`] on the restored context, give the XR session (if any)
+    /// a layer for the new context, then resume drawing.
+    fn context_restored(app: Rc<RefCell<AppInner>>) -> Result<(), JsValue> {
+        {
+            let inner = &mut *app.borrow_mut();
+            inner.gl.inner().invalidate();
+            if let Some(draw_logic) = &mut inner.draw_logic {
+                draw_logic.recreate(&inner.gl)?;
+            }
+            inner.context_lost = false;
+        }
+        let session = app.borrow().session.clone();
+        match session {
+            None => Self::start_animation_loop(&app),
+            Some(session) => wasm_bindgen_futures::spawn_local(async move {
+                if let Err(e) = Self::attach_gl_layer(&app, &session).await {
+                    // the session did not survive; the old one is useless, so ask for a new one
+                    log!("unable to reattach XR session to the restored context {e:?}");
+                    app.borrow_mut().session = None;
+                    Self::start_animation_loop(&app);
+                    let navigator = helper::window().map(|window| window.navigator());
+                    if let Err(e) = navigator.and_then(|n| Self::request_xr_session(n.xr(), app)) {
+                        log!("unable to request a new XR session {e:?}");
+                    }
+                    return;
+                }
+                Self::start_animation_loop(&app);
+            }),
+        }
+        Ok(())
+    }
+
+    /// Make `session` render into a new layer for the app's GL context.
+    async fn attach_gl_layer(
+        app: &Rc<RefCell<AppInner>>,
+        session: &XrSession,
+    ) -> Result<(), JsValue> {
+        let gl = app.borrow().gl.inner().inner().clone();
+        JsFuture::from(gl.make_xr_compatible()).await?;
+        let xr_gl_layer = XrWebGlLayer::new_with_web_gl2_rendering_context(session, &gl)?;
+
+        let render_state_init = XrRenderStateInit::new();
+        render_state_init.set_base_layer(Some(&xr_gl_layer));
+        session.update_render_state_with_state(&render_state_init);
+        Ok(())
+    }
+
     fn request_xr_session(xr: XrSystem, app: Rc<RefCell<AppInner>>) -> Result<Promise, JsValue> {
         if app.borrow().session.is_some() {
             Ok(Promise::resolve(&JsValue::from("Session already exists")))
@@ -260,6 +967,7 @@ impl AppInner {
                 let supports_session = supports_session.unwrap();
                 if supports_session == false {
                     log!("XR session not supported");
+                    app.borrow_mut().show_message("XR session not supported");
                     return Ok(JsValue::from("XR session not supported"));
                 }
 
@@ -267,18 +975,7 @@ impl AppInner {
                 let xr_session = wasm_bindgen_futures::JsFuture::from(xr_session_promise).await;
                 let xr_session: XrSession = xr_session?.into();
 
-                {
-                    let gl = app.borrow().gl.clone();
-                    JsFuture::from(gl.make_xr_compatible()).await?;
-                }
-                let xr_gl_layer = XrWebGlLayer::new_with_web_gl2_rendering_context(
-                    &xr_session,
-                    &app.borrow().gl,
-                )?;
-
-                let render_state_init = XrRenderStateInit::new();
-                render_state_init.set_base_layer(Some(&xr_gl_layer));
-                xr_session.update_render_state_with_state(&render_state_init);
+                Self::attach_gl_layer(&app, &xr_session).await?;
 
                 let world_ref_space =
                     xr_session.request_reference_space(XrReferenceSpaceType::Local);
@@ -291,10 +988,8 @@ impl AppInner {
                 app1.viewer_ref_space = Some(world_ref_space);
                 drop(app1);
 
-                request_animation_frame(
-                    animation_callback(app.clone()).borrow().as_ref().unwrap(),
-                    &app.borrow(),
-                );
+                // replaces the window's loop
+                Self::start_animation_loop(&app);
 
                 Ok(JsValue::from("Session set"))
             };
@@ -322,18 +1017,40 @@ impl XrApp {
         if let Err(val) = &tmp {
             console::log_2(&"malfunction creating webgl_context".into(), val);
         }
-        let gl = tmp.unwrap();
+        let gl = ResourceRegistry::new(StateCache::new(tmp.unwrap()));
 
-        let draw_logic = DrawLogic::new(&gl).unwrap();
+        let draw_logic = match DrawLogic::new(&gl) {
+            Ok(draw_logic) => {
+                log!("{}", draw_logic.programs);
+                Some(draw_logic)
+            }
+            Err(e) => {
+                // most likely a shader the driver would not compile; say which line, rather than panic
+                console::error_1(&e);
+                let report = e.as_string().unwrap_or_else(|| format!("{e:?}"));
+                let _ = helper::append_preformatted(&report);
+                None
+            }
+        };
+        let assets = AssetLoader::new();
+        assets.load(POSTER_URL, AssetKind::Image);
+        assets.load(FONT_URL, AssetKind::Binary);
         let rval = XrApp {
             inner: Rc::new(RefCell::new(AppInner {
                 session: None,
                 gl,
                 viewer_ref_space: None,
                 draw_logic,
+                last_frame_stats: StateStats::default(),
+                context_lost: false,
+                loop_generation: 0,
+                assets,
             })),
         };
         let _ = rval.attach_button();
+        if let Err(e) = rval.attach_context_listeners() {
+            console::log_2(&"unable to watch for context loss".into(), &e);
+        }
         rval
     }
 
@@ -351,10 +1068,48 @@ impl XrApp {
         Ok("ok".into())
     }
 
+    /// Stop drawing on `webglcontextlost` and rebuild on `webglcontextrestored`.
+    fn attach_context_listeners(&self) -> Result<(), JsValue> {
+        let canvas: HtmlCanvasElement = self
+            .inner
+            .borrow()
+            .gl
+            .inner()
+            .inner()
+            .canvas()
+            .ok_or_else(|| JsValue::from("context has no canvas"))?
+            .dyn_into()?;
+
+        let inner = self.inner.clone();
+        let lost: Closure<dyn FnMut(Event)> = Closure::new(move |event: Event| {
+            // without this the browser never restores the context
+            event.prevent_default();
+            log!("WebGL context lost");
+            inner.borrow_mut().context_lost();
+        });
+        canvas
+            .add_event_listener_with_callback("webglcontextlost", lost.as_ref().unchecked_ref())?;
+        lost.forget();
+
+        let inner = self.inner.clone();
+        let restored: Closure<dyn FnMut(Event)> = Closure::new(move |_: Event| {
+            log!("WebGL context restored");
+            if let Err(e) = AppInner::context_restored(inner.clone()) {
+                console::log_2(&"failed to rebuild after context restore".into(), &e);
+            }
+        });
+        canvas.add_event_listener_with_callback(
+            "webglcontextrestored",
+            restored.as_ref().unchecked_ref(),
+        )?;
+        restored.forget();
+        Ok(())
+    }
+
     fn js_request_xr(app: Rc<RefCell<AppInner>>) -> Result<Promise, JsValue> {
-        match Self::js_request_xr_(app) {
+        match Self::js_request_xr_(app.clone()) {
             Err(e) => {
-                helper::append_to_document(&format!("boom {e:?}"))?;
+                app.borrow_mut().show_message(&format!("boom {e:?}"));
                 Err(e)
             }
             Ok(x) => Ok(x),
@@ -362,23 +1117,26 @@ impl XrApp {
     }
 
     fn js_request_xr_(app: Rc<RefCell<AppInner>>) -> Result<Promise, JsValue> {
-        helper::append_to_document(" requesting xr ")?;
+        app.borrow_mut().show_message("requesting xr");
         let navigator = helper::window()?.navigator();
         AppInner::request_xr_session(navigator.xr(), app)
     }
 
     pub fn start(&self) {
-        request_animation_frame(
-            animation_callback(self.inner.clone())
-                .borrow()
-                .as_ref()
-                .unwrap(),
-            &self.inner.borrow(),
-        );
+        AppInner::start_animation_loop(&self.inner);
     }
 
-    fn draw(timestamp: f64, xr_frame: &XrFrame, inner_app: &AppInner) {
-        let draw_logic = &inner_app.draw_logic;
+    fn draw(timestamp: f64, xr_frame: &XrFrame, inner_app: &mut AppInner) {
+        if inner_app.context_lost {
+            return;
+        }
+        let Some(draw_logic) = &mut inner_app.draw_logic else {
+            return;
+        };
+        draw_logic.use_assets(&inner_app.gl, &inner_app.assets);
+        draw_logic.update_video(&inner_app.gl);
+        draw_logic.upload_lights(&inner_app.gl);
+        draw_logic.profiler.begin_frame(&inner_app.gl, timestamp);
         //let inner_app = inner.borrow();
         match inner_app.session.as_ref() {
             Some(session) => {
@@ -391,9 +1149,112 @@ impl XrApp {
                 );
             }
             None => {
-                draw_logic.draw(&inner_app.gl);
+                draw_logic.draw(&inner_app.gl, timestamp);
             }
         }
+        inner_app.last_frame_stats = inner_app.gl.inner().take_frame_stats();
+
+        let profiler = &mut draw_logic.profiler;
+        profiler.end_frame(&inner_app.gl);
+        if profiler.is_enabled()
+            && profiler
+                .frame_count()
+                .is_multiple_of(PROFILER_REPORT_PERIOD)
+        {
+            log!("{}", profiler.report());
+        }
+    }
+
+    /// Time each eye and object on the CPU (and the GPU, where `EXT_disjoint_timer_query_webgl2` is available),
+    /// log the rolling means every few seconds, and show them as bars in the scene.
+    pub fn set_profiling(&self, enabled: bool) -> Result<(), JsValue> {
+        let inner = &mut *self.inner.borrow_mut();
+        match &mut inner.draw_logic {
+            Some(draw_logic) => draw_logic.set_profiling(&inner.gl, enabled),
+            None => Ok(()),
+        }
+    }
+
+    /// Start playing the video at `url` on a quad above the scene, replacing any previous video.
+    /// The promise resolves once playback starts.
+    pub fn show_video(&self, url: &str) -> Result<Promise, JsValue> {
+        let inner = &mut *self.inner.borrow_mut();
+        let Some(draw_logic) = &mut inner.draw_logic else {
+            return Err("torn down".into());
+        };
+        draw_logic.set_video(&inner.gl, Some(VideoTexture::from_url(&inner.gl, url)?))?;
+        draw_logic.video().unwrap().play()
+    }
+
+    pub fn hide_video(&self) -> Result<(), JsValue> {
+        let inner = &mut *self.inner.borrow_mut();
+        match &mut inner.draw_logic {
+            Some(draw_logic) => draw_logic.set_video(&inner.gl, None),
+            None => Ok(()),
+        }
+    }
+
+    /// Resolves once playback resumes.
+    pub fn play_video(&self) -> Result<Promise, JsValue> {
+        self.with_video(|video| video.play())
+    }
+
+    pub fn pause_video(&self) -> Result<(), JsValue> {
+        self.with_video(|video| video.pause())
+    }
+
+    /// Jump to `seconds` from the start of the video.
+    pub fn seek_video(&self, seconds: f64) -> Result<(), JsValue> {
+        self.with_video(|video| {
+            video.seek(seconds);
+            Ok(())
+        })
+    }
+
+    /// Load the glTF 2.0 model (`.gltf` or `.glb`) at `url` onto the middle of the stage,
+    /// scaled by `scale` (glTF is in meters). It appears once it and everything it refers to have arrived.
+    pub fn load_model(&self, url: &str, scale: f32) -> Result<(), JsValue> {
+        let inner = &mut *self.inner.borrow_mut();
+        let Some(draw_logic) = &mut inner.draw_logic else {
+            return Err("torn down".into());
+        };
+        draw_logic.load_model(
+            url,
+            Transform::IDENTITY.with_scale(glam::Vec3::splat(scale)),
+        );
+        Ok(())
+    }
+
+    /// Show `message` on the page and in the scene, replacing the previous one in the scene.
+    pub fn show_message(&self, message: &str) {
+        self.inner.borrow_mut().show_message(message);
+    }
+
+    /// How many program/VAO/texture/framebuffer/viewport/blend/depth calls the last frame issued
+    /// and how many the state cache dropped as redundant, e.g. `issued 12, elided 7`.
+    #[must_use]
+    pub fn last_frame_state_stats(&self) -> String {
+        self.inner.borrow().last_frame_stats.to_string()
+    }
+}
+
+impl XrApp {
+    fn with_video<R>(
+        &self,
+        f: impl FnOnce(&VideoTexture<AppGl>) -> Result<R, JsValue>,
+    ) -> Result<R, JsValue> {
+        let inner = self.inner.borrow();
+        match inner.draw_logic.as_ref().and_then(DrawLogic::video) {
+            Some(video) => f(video),
+            None => Err("no video; call show_video first".into()),
+        }
+    }
+}
+
+impl Drop for XrApp {
+    /// `xrApp.free()` from javascript ends up here
+    fn drop(&mut self) {
+        self.inner.borrow_mut().teardown();
     }
 }
 
@@ -404,10 +1265,18 @@ pub fn animation_callback(
 ) -> Rc<RefCell<Option<Closure<dyn FnMut(f64, XrFrame)>>>> {
     let cell = Rc::new(RefCell::new(None));
     let f = cell.clone();
+    let generation = app.borrow().loop_generation;
     *cell.borrow_mut() = Some(Closure::new(move |timestamp: f64, xr_frame: XrFrame| {
         //log!("debug");
         //draw_logic.draw(gl.as_ref());
-        XrApp::draw(timestamp, &xr_frame, &app.borrow());
+        XrApp::draw(timestamp, &xr_frame, &mut app.borrow_mut());
+        {
+            let app = app.borrow();
+            if app.draw_logic.is_none() || app.context_lost || app.loop_generation != generation {
+                // torn down, context lost, or replaced by a newer loop; let this one end
+                return;
+            }
+        }
         request_animation_frame(f.borrow().as_ref().unwrap(), &app.borrow());
     }));
     cell
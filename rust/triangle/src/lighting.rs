@@ -0,0 +1,260 @@
+//! The lights every lit draw shares, uploaded once per frame into a uniform buffer
+//! the way [`CameraUniforms`](crate::camera::CameraUniforms) uploads the camera.
+
+use crate::backend::GlBackend;
+use crate::camera::write_f32s;
+use crate::gl_thin::HomogeneousGlBuffer;
+use glam::Vec3;
+use std::mem::offset_of;
+use wasm_bindgen::JsValue;
+use web_sys::WebGl2RenderingContext;
+
+/// The uniform buffer binding point every lit program's `Lights` block is attached to.
+pub const LIGHTS_BINDING: u32 = 1;
+
+/// The name of the uniform block in `lights.glsl`
+pub const LIGHTS_BLOCK_NAME: &str = "Lights";
+
+/// The size of the block's arrays; lights past these are ignored.
+pub const MAX_DIRECTIONAL_LIGHTS: usize = 2;
+pub const MAX_POINT_LIGHTS: usize = 4;
+
+/// Sunlight: the same direction and brightness everywhere.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct DirectionalLight {
+    /// the way the light travels, in world space; need not be normalized
+    pub direction: Vec3,
+    /// linear RGB
+    pub color: Vec3,
+    pub intensity: f32,
+}
+
+/// A bulb: inverse square falloff, windowed to reach zero at `range`.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct PointLight {
+    /// world space
+    pub position: Vec3,
+    /// linear RGB
+    pub color: Vec3,
+    pub intensity: f32,
+    /// meters
+    pub range: f32,
+}
+
+/// Everything that lights the scene.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Lights {
+    /// linear RGB, added to every lit surface regardless of its normal
+    pub ambient: Vec3,
+    /// at most [`MAX_DIRECTIONAL_LIGHTS`]
+    pub directional: Vec<DirectionalLight>,
+    /// at most [`MAX_POINT_LIGHTS`]
+    pub point: Vec<PointLight>,
+}
+
+impl Default for Lights {
+    /// A dim sky, a sun from above and behind the viewer and a warm bulb to the right.
+    fn default() -> Self {
+        Self {
+            ambient: Vec3::splat(0.08),
+            directional: vec![DirectionalLight {
+                direction: Vec3::new(-0.3, -1.0, -0.5),
+                color: Vec3::ONE,
+                intensity: 2.5,
+            }],
+            point: vec![PointLight {
+                position: Vec3::new(0.6, 0.3, -0.6),
+                color: Vec3::new(1.0, 0.8, 0.6),
+                intensity: 0.6,
+                range: 3.0,
+            }],
+        }
+    }
+}
+
+impl Lights {
+    /// The block to upload, with directions normalized and colors scaled by their intensities.
+    pub fn to_block(&self) -> LightsBlock {
+        let mut rval = LightsBlock {
+            directional: [DirectionalLightBlock::default(); MAX_DIRECTIONAL_LIGHTS],
+            point: [PointLightBlock::default(); MAX_POINT_LIGHTS],
+            ambient: self.ambient.to_array(),
+            directional_count: 0,
+            point_count: 0,
+            _padding: [0; 3],
+        };
+        for (dst, light) in rval.directional.iter_mut().zip(&self.directional) {
+            *dst = DirectionalLightBlock {
+                direction: light.direction.normalize_or_zero().to_array(),
+                _padding0: 0.0,
+                color: (light.color * light.intensity).to_array(),
+                _padding1: 0.0,
+            };
+            rval.directional_count += 1;
+        }
+        for (dst, light) in rval.point.iter_mut().zip(&self.point) {
+            *dst = PointLightBlock {
+                position: light.position.to_array(),
+                range: light.range,
+                color: (light.color * light.intensity).to_array(),
+                _padding: 0.0,
+            };
+            rval.point_count += 1;
+        }
+        rval
+    }
+}
+
+/// One element of the block's `directional` array.
+#[repr(C)]
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct DirectionalLightBlock {
+    pub direction: [f32; 3],
+    _padding0: f32,
+    pub color: [f32; 3],
+    _padding1: f32,
+}
+
+/// One element of the block's `point` array.
+#[repr(C)]
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct PointLightBlock {
+    pub position: [f32; 3],
+    pub range: f32,
+    pub color: [f32; 3],
+    _padding: f32,
+}
+
+/// Mirrors the std140 uniform block in `lights.glsl`:
+/// ```glsl
+/// layout(std140) uniform Lights {
+///     DirectionalLight directional[MAX_DIRECTIONAL_LIGHTS];
+///     PointLight point[MAX_POINT_LIGHTS];
+///     vec3 ambient;
+///     int directional_count;
+///     int point_count;
+/// } lights;
+/// ```
+#[repr(C)]
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct LightsBlock {
+    pub directional: [DirectionalLightBlock; MAX_DIRECTIONAL_LIGHTS],
+    pub point: [PointLightBlock; MAX_POINT_LIGHTS],
+    pub ambient: [f32; 3],
+    pub directional_count: i32,
+    pub point_count: i32,
+    _padding: [i32; 3],
+}
+
+// std140: a struct is aligned (and its array stride rounded up) to 16 bytes,
+// each vec3 starts a new 16 bytes and a scalar may pack into the 4 bytes after one.
+const _: () = assert!(size_of::<DirectionalLightBlock>() == 32);
+const _: () = assert!(offset_of!(DirectionalLightBlock, color) == 16);
+const _: () = assert!(size_of::<PointLightBlock>() == 32);
+const _: () = assert!(offset_of!(PointLightBlock, range) == 12);
+const _: () = assert!(offset_of!(PointLightBlock, color) == 16);
+const _: () = assert!(offset_of!(LightsBlock, directional) == 0);
+const _: () = assert!(offset_of!(LightsBlock, point) == 64);
+const _: () = assert!(offset_of!(LightsBlock, ambient) == 192);
+const _: () = assert!(offset_of!(LightsBlock, directional_count) == 204);
+const _: () = assert!(offset_of!(LightsBlock, point_count) == 208);
+const _: () = assert!(size_of::<LightsBlock>() == 224);
+
+impl LightsBlock {
+    /// the bytes for `bufferSubData`, in std140 layout
+    pub fn to_std140_bytes(&self) -> Vec<u8> {
+        let mut rval = vec![0; size_of::<Self>()];
+        for (i, light) in self.directional.iter().enumerate() {
+            let at = offset_of!(Self, directional) + i * size_of::<DirectionalLightBlock>();
+            write_f32s(
+                &mut rval,
+                at + offset_of!(DirectionalLightBlock, direction),
+                &light.direction,
+            );
+            write_f32s(
+                &mut rval,
+                at + offset_of!(DirectionalLightBlock, color),
+                &light.color,
+            );
+        }
+        for (i, light) in self.point.iter().enumerate() {
+            let at = offset_of!(Self, point) + i * size_of::<PointLightBlock>();
+            write_f32s(
+                &mut rval,
+                at + offset_of!(PointLightBlock, position),
+                &light.position,
+            );
+            write_f32s(
+                &mut rval,
+                at + offset_of!(PointLightBlock, range),
+                &[light.range],
+            );
+            write_f32s(
+                &mut rval,
+                at + offset_of!(PointLightBlock, color),
+                &light.color,
+            );
+        }
+        write_f32s(&mut rval, offset_of!(Self, ambient), &self.ambient);
+        for (offset, count) in [
+            (offset_of!(Self, directional_count), self.directional_count),
+            (offset_of!(Self, point_count), self.point_count),
+        ] {
+            rval[offset..offset + 4].copy_from_slice(&count.to_le_bytes());
+        }
+        rval
+    }
+}
+
+/// The buffer behind [`LIGHTS_BINDING`]. Unlike the camera there is one per frame, not one per view.
+pub struct LightUniforms<G: GlBackend = WebGl2RenderingContext> {
+    buffer: HomogeneousGlBuffer<u8, G>,
+}
+
+impl<G: GlBackend> LightUniforms<G> {
+    #[track_caller]
+    pub fn new(gl: &G) -> Result<Self, &'static str> {
+        Ok(Self {
+            buffer: HomogeneousGlBuffer::with_capacity(
+                gl,
+                size_of::<LightsBlock>(),
+                WebGl2RenderingContext::UNIFORM_BUFFER,
+                WebGl2RenderingContext::DYNAMIC_DRAW,
+            )?,
+        })
+    }
+
+    /// Upload `lights` and attach the buffer to [`LIGHTS_BINDING`], where it stays for every draw after.
+    pub fn upload_and_bind(&mut self, gl: &G, lights: &Lights) -> Result<(), &'static str> {
+        self.buffer
+            .upload(gl, &lights.to_block().to_std140_bytes())?;
+        gl.bind_buffer_base(
+            WebGl2RenderingContext::UNIFORM_BUFFER,
+            LIGHTS_BINDING,
+            Some(&self.buffer.buffer),
+        );
+        Ok(())
+    }
+
+    /// A new buffer on a restored context; the old one died with the lost context.
+    pub fn recreate(&mut self, gl: &G) -> Result<(), &'static str> {
+        *self = Self::new(gl)?;
+        Ok(())
+    }
+
+    pub fn release(self, gl: &G) {
+        self.buffer.release(gl);
+    }
+}
+
+/// Attach `program`'s `Lights` block to [`LIGHTS_BINDING`].
+pub fn bind_lights_block<G: GlBackend>(gl: &G, program: &G::Program) -> Result<(), JsValue> {
+    let index = gl.get_uniform_block_index(program, LIGHTS_BLOCK_NAME);
+    if index == WebGl2RenderingContext::INVALID_INDEX {
+        return Err(JsValue::from(format!(
+            "missing uniform block {LIGHTS_BLOCK_NAME}"
+        )));
+    }
+    gl.uniform_block_binding(program, index, LIGHTS_BINDING);
+    Ok(())
+}
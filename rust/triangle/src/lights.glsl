@@ -0,0 +1,29 @@
+#include "common.glsl"
+
+#define MAX_DIRECTIONAL_LIGHTS 2
+#define MAX_POINT_LIGHTS 4
+
+struct DirectionalLight {
+    // the way the light travels, world space, normalized
+    vec3 direction;
+    // linear RGB, times the intensity
+    vec3 color;
+};
+
+struct PointLight {
+    // world space
+    vec3 position;
+    // the distance at which the light has faded to nothing
+    float range;
+    // linear RGB, times the intensity
+    vec3 color;
+};
+
+// uploaded once per frame, shared by every lit draw
+layout(std140) uniform Lights {
+    DirectionalLight directional[MAX_DIRECTIONAL_LIGHTS];
+    PointLight point[MAX_POINT_LIGHTS];
+    vec3 ambient;
+    int directional_count;
+    int point_count;
+} lights;
@@ -0,0 +1,81 @@
+#include "lights.glsl"
+
+in vec3 world_position;
+in vec3 world_normal;
+in vec3 to_eye;
+in vec2 uv2;
+// linear RGBA, multiplied with the texture
+uniform vec4 base_color;
+uniform sampler2D base_color_texture;
+uniform float roughness;
+uniform float metallic;
+// linear RGB, added after lighting
+uniform vec3 emissive;
+out vec4 color;
+
+const float PI = 3.14159265;
+
+// inverse square falloff, windowed so it reaches zero at range
+float attenuation(float distance, float range)
+{
+    float window = clamp(1.0 - pow(distance/range, 4.0), 0.0, 1.0);
+    return window*window/max(distance*distance, 0.0001);
+}
+
+#ifdef PBR
+// glTF's metallic-roughness: GGX distribution, Schlick-Smith visibility and Schlick Fresnel
+vec3 shade(vec3 albedo, vec3 n, vec3 v, vec3 l, vec3 radiance)
+{
+    vec3 h = normalize(v + l);
+    float n_dot_l = max(dot(n, l), 0.0);
+    float n_dot_v = max(dot(n, v), 0.0001);
+    float n_dot_h = max(dot(n, h), 0.0);
+    float alpha = max(roughness*roughness, 0.002);
+    float alpha2 = alpha*alpha;
+    float d = n_dot_h*n_dot_h*(alpha2 - 1.0) + 1.0;
+    float distribution = alpha2/(PI*d*d);
+    float k = alpha*0.5;
+    float visibility = 0.25/((n_dot_l*(1.0 - k) + k)*(n_dot_v*(1.0 - k) + k));
+    vec3 f0 = mix(vec3(0.04), albedo, metallic);
+    vec3 fresnel = f0 + (1.0 - f0)*pow(1.0 - max(dot(h, v), 0.0), 5.0);
+    vec3 diffuse = (1.0 - fresnel)*(1.0 - metallic)*albedo/PI;
+    return (diffuse + fresnel*distribution*visibility)*radiance*n_dot_l;
+}
+#else
+// Blinn-Phong, with the shininess and specular color taken from the same material parameters
+// and normalized so the same lights look about as bright as with PBR
+vec3 shade(vec3 albedo, vec3 n, vec3 v, vec3 l, vec3 radiance)
+{
+    vec3 h = normalize(v + l);
+    float n_dot_l = max(dot(n, l), 0.0);
+    float shininess = 2.0/max(pow(roughness, 4.0), 0.001) - 2.0;
+    vec3 specular_color = mix(vec3(0.04), albedo, metallic);
+    vec3 diffuse = (1.0 - metallic)*albedo/PI;
+    vec3 specular = specular_color*(shininess + 8.0)/(8.0*PI)*pow(max(dot(n, h), 0.0), shininess);
+    return (diffuse + specular)*radiance*n_dot_l;
+}
+#endif
+
+void main() {
+    vec4 albedo = base_color*texture(base_color_texture, uv2);
+    vec3 n = normalize(world_normal);
+    vec3 v = normalize(to_eye);
+    vec3 rgb = lights.ambient*albedo.rgb + emissive;
+    for (int i = 0; i < MAX_DIRECTIONAL_LIGHTS; i++) {
+        if (i >= lights.directional_count) {
+            break;
+        }
+        rgb += shade(albedo.rgb, n, v, -lights.directional[i].direction, lights.directional[i].color);
+    }
+    for (int i = 0; i < MAX_POINT_LIGHTS; i++) {
+        if (i >= lights.point_count) {
+            break;
+        }
+        vec3 to_light = lights.point[i].position - world_position;
+        float distance = length(to_light);
+        vec3 radiance = lights.point[i].color*attenuation(distance, lights.point[i].range);
+        rgb += shade(albedo.rgb, n, v, to_light/max(distance, 0.0001), radiance);
+    }
+    // the framebuffer is not sRGB, so encode here
+    color = vec4(pow(rgb, vec3(1.0/2.2)), albedo.a);
+}
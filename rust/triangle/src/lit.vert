@@ -0,0 +1,24 @@
+#include "camera.glsl"
+
+in vec3 position;
+in vec3 normal;
+in vec2 uv;
+uniform mat4 model;
+// the inverse transpose of the upper 3x3 of model, so normals stay perpendicular under non-uniform scale
+uniform mat3 normal_matrix;
+// e.g. glTF's KHR_texture_transform; the identity for most materials
+uniform mat3 uv_transform;
+out vec3 world_position;
+out vec3 world_normal;
+out vec3 to_eye;
+out vec2 uv2;
+
+void main()
+{
+    vec4 world = model*vec4(position, 1.0);
+    world_position = world.xyz;
+    world_normal = normal_matrix*normal;
+    to_eye = camera.eye_position - world.xyz;
+    uv2 = (uv_transform*vec3(uv, 1.0)).xy;
+    gl_Position = camera.view_projection*world;
+}
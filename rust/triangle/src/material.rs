@@ -0,0 +1,83 @@
+use crate::backend::GlBackend;
+use crate::texture::Texture;
+use glam::{Mat3, Vec3, Vec4};
+use std::rc::Rc;
+use web_sys::WebGl2RenderingContext;
+
+/// Which of the lit shader's lighting models a material is drawn with.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum LightingModel {
+    /// cheap, and good enough for most props; specular is derived from roughness and metallic
+    #[default]
+    BlinnPhong,
+    /// glTF's metallic-roughness model
+    MetallicRoughness,
+    /// glTF's `KHR_materials_unlit`: the base color as it is, ignoring the lights and everything else
+    Unlit,
+}
+
+/// How a lit surface responds to light, in the terms of glTF's metallic-roughness model.
+/// [`LightingModel::BlinnPhong`] maps the same parameters onto a shininess and specular color,
+/// so a material looks roughly alike with either.
+pub struct Material<G: GlBackend = WebGl2RenderingContext> {
+    /// linear RGBA, multiplied with the texture
+    pub base_color: Vec4,
+    /// sRGB encoded; a 1x1 white texture is bound in its place when `None`
+    pub base_color_texture: Option<Rc<Texture<G>>>,
+    /// applied to the texture coordinates before sampling, e.g. to pick a region of an atlas
+    pub uv_transform: Mat3,
+    /// 0 is a mirror, 1 is chalk
+    pub roughness: f32,
+    /// 0 for dielectrics, 1 for bare metal
+    pub metallic: f32,
+    /// linear RGB, added after lighting
+    pub emissive: Vec3,
+    pub lighting: LightingModel,
+}
+
+impl<G: GlBackend> Material<G> {
+    /// A rough, non-metallic surface of `base_color`.
+    pub fn new(base_color: Vec4) -> Self {
+        Self {
+            base_color,
+            base_color_texture: None,
+            uv_transform: Mat3::IDENTITY,
+            roughness: 0.5,
+            metallic: 0.0,
+            emissive: Vec3::ZERO,
+            lighting: LightingModel::BlinnPhong,
+        }
+    }
+
+    pub fn with_lighting(mut self, lighting: LightingModel) -> Self {
+        self.lighting = lighting;
+        self
+    }
+
+    pub fn with_texture(mut self, texture: Rc<Texture<G>>) -> Self {
+        self.base_color_texture = Some(texture);
+        self
+    }
+
+    /// Releases the texture too, unless another material still shares it.
+    pub fn release(self, gl: &G) {
+        if let Some(texture) = self.base_color_texture.and_then(Rc::into_inner) {
+            texture.release(gl);
+        }
+    }
+}
+
+// not derived, which would require `G: Clone`
+impl<G: GlBackend> Clone for Material<G> {
+    fn clone(&self) -> Self {
+        Self {
+            base_color: self.base_color,
+            base_color_texture: self.base_color_texture.clone(),
+            uv_transform: self.uv_transform,
+            roughness: self.roughness,
+            metallic: self.metallic,
+            emissive: self.emissive,
+            lighting: self.lighting,
+        }
+    }
+}
@@ -0,0 +1,671 @@
+//! glTF 2.0 models, e.g. exported from Blender. Everything but the upload runs (and is tested) without a GPU:
+//! [`GltfFile::parse`] reads a `.gltf` or `.glb` file and lists the files it refers to,
+//! [`GltfFile::import`] turns it and those files into [`ModelData`], and [`Model::new`] uploads that
+//! as a [`LitMesh`] per primitive. [`ModelLoad`] does all three steps through an [`AssetLoader`].
+//!
+//! Read: triangle lists, strips and fans, indexed or not, with positions, normals (flat ones are made
+//! where a primitive has none) and the texture coordinates its base color texture uses;
+//! the node hierarchy of the default scene; metallic-roughness materials' base color, base color texture,
+//! metallic, roughness and emissive factors, `KHR_texture_transform` on the base color texture and
+//! `KHR_materials_unlit`; buffers and images in a GLB's binary chunk, in data URIs or in files next to the model.
+//! Points and lines are skipped; the other textures, alpha modes, skins, morph targets, animations
+//! and cameras are ignored.
+
+use crate::assets::{AssetKind, AssetLoader, AssetState};
+use crate::backend::GlBackend;
+use crate::camera::ViewMode;
+use crate::material::{LightingModel, Material};
+use crate::objects::{LitMesh, PositionNormalUv};
+use crate::scene::{NodeId, Scene, Transform};
+use crate::shaders::registry::ProgramRegistry;
+use crate::texture::{ColorSpace, SamplerDescription, Texture};
+use base64::Engine;
+use glam::{vec3, Mat3, Quat, Vec2, Vec3, Vec4};
+use gltf::mesh::Mode;
+use image::DynamicImage;
+use std::borrow::Cow;
+use std::rc::Rc;
+use wasm_bindgen::JsValue;
+use web_sys::WebGl2RenderingContext;
+
+/// A parsed `.gltf` or `.glb` file, not yet joined with the files it refers to.
+pub struct GltfFile {
+    gltf: gltf::Gltf,
+}
+
+impl GltfFile {
+    /// JSON and binary glTF alike; the first bytes tell them apart.
+    pub fn parse(bytes: &[u8]) -> Result<Self, String> {
+        let gltf = gltf::Gltf::from_slice(bytes).map_err(|e| e.to_string())?;
+        Ok(Self { gltf })
+    }
+
+    /// The buffers and images kept in other files, by their URIs relative to this one.
+    /// Data URIs and a GLB's own binary chunk are not listed.
+    pub fn external_uris(&self) -> Vec<&str> {
+        let buffers = self
+            .gltf
+            .buffers()
+            .filter_map(|buffer| match buffer.source() {
+                gltf::buffer::Source::Uri(uri) => Some(uri),
+                gltf::buffer::Source::Bin => None,
+            });
+        let images = self.gltf.images().filter_map(|image| match image.source() {
+            gltf::image::Source::Uri { uri, .. } => Some(uri),
+            gltf::image::Source::View { .. } => None,
+        });
+        let mut rval: Vec<&str> = Vec::new();
+        for uri in buffers.chain(images) {
+            if !uri.starts_with("data:") && !rval.contains(&uri) {
+                rval.push(uri);
+            }
+        }
+        rval
+    }
+
+    /// Decode everything the model needs. `external` supplies the bytes of each of [`Self::external_uris`].
+    pub fn import(
+        &self,
+        external: impl Fn(&str) -> Result<Rc<[u8]>, String>,
+    ) -> Result<ModelData, String> {
+        let load = |uri: &str| -> Result<Rc<[u8]>, String> {
+            match uri.strip_prefix("data:") {
+                Some(data) => decode_data_uri(data).map(Rc::from),
+                None => external(uri),
+            }
+        };
+
+        let mut buffers: Vec<Rc<[u8]>> = Vec::new();
+        for buffer in self.gltf.buffers() {
+            let data = match buffer.source() {
+                gltf::buffer::Source::Bin => self
+                    .gltf
+                    .blob
+                    .as_deref()
+                    .map(Rc::from)
+                    .ok_or("the GLB has no binary chunk")?,
+                gltf::buffer::Source::Uri(uri) => load(uri)?,
+            };
+            if data.len() < buffer.length() {
+                return Err(format!(
+                    "buffer {} has {} bytes rather than {}",
+                    buffer.index(),
+                    data.len(),
+                    buffer.length()
+                ));
+            }
+            buffers.push(data);
+        }
+
+        let mut images = Vec::new();
+        for image in self.gltf.images() {
+            let bytes: Cow<[u8]> = match image.source() {
+                gltf::image::Source::View { view, .. } => {
+                    let buffer = &buffers[view.buffer().index()];
+                    Cow::Borrowed(
+                        buffer
+                            .get(view.offset()..view.offset() + view.length())
+                            .ok_or_else(|| format!("image {} is past its buffer", image.index()))?,
+                    )
+                }
+                gltf::image::Source::Uri { uri, .. } => Cow::Owned(load(uri)?.to_vec()),
+            };
+            let decoded = image::load_from_memory(&bytes)
+                .map_err(|e| format!("image {}: {e}", image.index()))?;
+            images.push(Rc::new(decoded));
+        }
+
+        let textures = self
+            .gltf
+            .textures()
+            .map(|texture| TextureData {
+                image: texture.source().index(),
+                sampler: sampler_description(&texture.sampler()),
+            })
+            .collect();
+
+        let materials: Vec<MaterialData> = self.gltf.materials().map(material_data).collect();
+
+        let mut meshes = Vec::new();
+        for mesh in self.gltf.meshes() {
+            let mut primitives = Vec::new();
+            for primitive in mesh.primitives() {
+                let which = || format!("mesh {} primitive {}", mesh.index(), primitive.index());
+                if let Some(primitive) = primitive_data(&primitive, &buffers, &materials)
+                    .map_err(|e| format!("{}: {e}", which()))?
+                {
+                    primitives.push(primitive);
+                }
+            }
+            meshes.push(MeshData {
+                name: mesh
+                    .name()
+                    .map_or_else(|| format!("mesh {}", mesh.index()), str::to_string),
+                primitives,
+            });
+        }
+
+        let nodes = self
+            .gltf
+            .nodes()
+            .map(|node| {
+                let (translation, rotation, scale) = node.transform().decomposed();
+                NodeData {
+                    name: node
+                        .name()
+                        .map_or_else(|| format!("node {}", node.index()), str::to_string),
+                    local: Transform {
+                        translation: translation.into(),
+                        rotation: Quat::from_array(rotation),
+                        scale: scale.into(),
+                    },
+                    mesh: node.mesh().map(|mesh| mesh.index()),
+                    children: node.children().map(|child| child.index()).collect(),
+                }
+            })
+            .collect::<Vec<_>>();
+
+        let roots = match self
+            .gltf
+            .default_scene()
+            .or_else(|| self.gltf.scenes().next())
+        {
+            Some(scene) => scene.nodes().map(|node| node.index()).collect(),
+            // no scene, so every node nobody claims as a child
+            None => (0..nodes.len())
+                .filter(|&index| !nodes.iter().any(|node| node.children.contains(&index)))
+                .collect(),
+        };
+
+        Ok(ModelData {
+            meshes,
+            materials,
+            textures,
+            images,
+            nodes,
+            roots,
+        })
+    }
+}
+
+/// Everything a [`Model`] is made from, decoded and on the CPU.
+/// The indices in here are the glTF file's own.
+pub struct ModelData {
+    pub meshes: Vec<MeshData>,
+    pub materials: Vec<MaterialData>,
+    pub textures: Vec<TextureData>,
+    pub images: Vec<Rc<DynamicImage>>,
+    pub nodes: Vec<NodeData>,
+    /// the nodes of the default scene (or the first), whose descendants make up the model
+    pub roots: Vec<usize>,
+}
+
+pub struct MeshData {
+    pub name: String,
+    /// without those [`GltfFile::import`] skipped
+    pub primitives: Vec<PrimitiveData>,
+}
+
+/// Triangles with one material, ready for [`LitMesh::new`].
+pub struct PrimitiveData {
+    pub vertices: Vec<PositionNormalUv>,
+    /// a triangle list, counter-clockwise on the front
+    pub indices: Vec<u32>,
+    /// `None` for glTF's default material
+    pub material: Option<usize>,
+}
+
+/// glTF's metallic-roughness material, as far as [`Material`] can draw it.
+#[derive(Debug, Clone, PartialEq)]
+pub struct MaterialData {
+    pub name: String,
+    /// linear RGBA
+    pub base_color: Vec4,
+    /// an index into [`ModelData::textures`]
+    pub base_color_texture: Option<usize>,
+    /// from `KHR_texture_transform` on the base color texture
+    pub uv_transform: Mat3,
+    /// which `TEXCOORD_n` the base color texture is read with
+    pub tex_coord: u32,
+    pub roughness: f32,
+    pub metallic: f32,
+    /// linear RGB
+    pub emissive: Vec3,
+    /// `KHR_materials_unlit`
+    pub unlit: bool,
+}
+
+impl Default for MaterialData {
+    /// glTF's default material: white, fully rough and fully metallic.
+    fn default() -> Self {
+        Self {
+            name: String::new(),
+            base_color: Vec4::ONE,
+            base_color_texture: None,
+            uv_transform: Mat3::IDENTITY,
+            tex_coord: 0,
+            roughness: 1.0,
+            metallic: 1.0,
+            emissive: Vec3::ZERO,
+            unlit: false,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct TextureData {
+    /// an index into [`ModelData::images`]
+    pub image: usize,
+    pub sampler: SamplerDescription,
+}
+
+pub struct NodeData {
+    pub name: String,
+    pub local: Transform,
+    pub mesh: Option<usize>,
+    pub children: Vec<usize>,
+}
+
+impl ModelData {
+    /// The material `index` refers to, glTF's default one for `None`.
+    pub fn material(&self, index: Option<usize>) -> Cow<'_, MaterialData> {
+        match index.and_then(|index| self.materials.get(index)) {
+            Some(material) => Cow::Borrowed(material),
+            None => Cow::Owned(MaterialData::default()),
+        }
+    }
+
+    /// Add a node called `name` below `parent`, and the model's nodes below that.
+    /// Nodes with a mesh get `drawable(mesh index)`. Returns the new node.
+    pub fn add_to_scene<D>(
+        &self,
+        scene: &mut Scene<D>,
+        parent: Option<NodeId>,
+        name: &str,
+        local: Transform,
+        mut drawable: impl FnMut(usize) -> D,
+    ) -> NodeId {
+        let root = scene.add(parent, name, local, None);
+        // glTF forbids cycles, but a broken file should not hang the page
+        let mut added = vec![false; self.nodes.len()];
+        let mut stack: Vec<(usize, NodeId)> =
+            self.roots.iter().rev().map(|&node| (node, root)).collect();
+        while let Some((index, parent)) = stack.pop() {
+            let Some(node) = self.nodes.get(index) else {
+                continue;
+            };
+            if std::mem::replace(&mut added[index], true) {
+                continue;
+            }
+            let id = scene.add(
+                Some(parent),
+                &node.name,
+                node.local,
+                node.mesh.map(&mut drawable),
+            );
+            stack.extend(node.children.iter().rev().map(|&child| (child, id)));
+        }
+        root
+    }
+}
+
+/// `KHR_texture_transform`: scale, then rotate (counter-clockwise in UV space), then offset.
+pub fn texture_transform(offset: Vec2, rotation: f32, scale: Vec2) -> Mat3 {
+    let (sin, cos) = rotation.sin_cos();
+    let rotation = Mat3::from_cols(vec3(cos, -sin, 0.0), vec3(sin, cos, 0.0), Vec3::Z);
+    Mat3::from_translation(offset) * rotation * Mat3::from_scale(scale)
+}
+
+/// `base_url` with its last path segment replaced by `uri`, the way a browser resolves a relative link.
+pub fn resolve_uri(base_url: &str, uri: &str) -> String {
+    match base_url.rfind('/') {
+        Some(slash) => format!("{}{uri}", &base_url[..=slash]),
+        None => uri.to_string(),
+    }
+}
+
+/// the part of a `data:` URI after the scheme
+fn decode_data_uri(data: &str) -> Result<Vec<u8>, String> {
+    let (header, payload) = data.split_once(',').ok_or("malformed data URI")?;
+    if !header.ends_with(";base64") {
+        return Err(format!("data URI {header:?} is not base64"));
+    }
+    base64::engine::general_purpose::STANDARD
+        .decode(payload)
+        .map_err(|e| format!("data URI: {e}"))
+}
+
+fn sampler_description(sampler: &gltf::texture::Sampler) -> SamplerDescription {
+    // what glTF leaves to the implementation when a filter is missing
+    let default = SamplerDescription::default();
+    SamplerDescription {
+        min_filter: sampler
+            .min_filter()
+            .map_or(default.min_filter, |filter| filter.as_gl_enum()),
+        mag_filter: sampler
+            .mag_filter()
+            .map_or(default.mag_filter, |filter| filter.as_gl_enum()),
+        wrap_s: sampler.wrap_s().as_gl_enum(),
+        wrap_t: sampler.wrap_t().as_gl_enum(),
+        max_anisotropy: None,
+    }
+}
+
+fn material_data(material: gltf::Material) -> MaterialData {
+    let pbr = material.pbr_metallic_roughness();
+    let base_color_texture = pbr.base_color_texture();
+    let transform = base_color_texture
+        .as_ref()
+        .and_then(|info| info.texture_transform());
+    MaterialData {
+        name: material.name().map_or_else(
+            || format!("material {:?}", material.index()),
+            str::to_string,
+        ),
+        base_color: pbr.base_color_factor().into(),
+        base_color_texture: base_color_texture
+            .as_ref()
+            .map(|info| info.texture().index()),
+        uv_transform: transform.as_ref().map_or(Mat3::IDENTITY, |transform| {
+            texture_transform(
+                transform.offset().into(),
+                transform.rotation(),
+                transform.scale().into(),
+            )
+        }),
+        tex_coord: transform
+            .as_ref()
+            .and_then(|transform| transform.tex_coord())
+            .or(base_color_texture.as_ref().map(|info| info.tex_coord()))
+            .unwrap_or(0),
+        roughness: pbr.roughness_factor(),
+        metallic: pbr.metallic_factor(),
+        emissive: material.emissive_factor().into(),
+        unlit: material.unlit(),
+    }
+}
+
+/// `Ok(None)` for points and lines.
+fn primitive_data(
+    primitive: &gltf::Primitive,
+    buffers: &[Rc<[u8]>],
+    materials: &[MaterialData],
+) -> Result<Option<PrimitiveData>, String> {
+    let reader = primitive.reader(|buffer| buffers.get(buffer.index()).map(|data| &**data));
+    let positions: Vec<Vec3> = reader
+        .read_positions()
+        .ok_or("no positions")?
+        .map(Vec3::from)
+        .collect();
+    let material = primitive.material().index();
+    let tex_coord = material.map_or(0, |index| materials[index].tex_coord);
+    let uvs: Vec<Vec2> = match reader.read_tex_coords(tex_coord) {
+        Some(uvs) => uvs.into_f32().map(Vec2::from).collect(),
+        None => vec![Vec2::ZERO; positions.len()],
+    };
+    let normals: Option<Vec<Vec3>> = reader
+        .read_normals()
+        .map(|normals| normals.map(Vec3::from).collect());
+    if uvs.len() != positions.len() || normals.as_ref().is_some_and(|n| n.len() != positions.len())
+    {
+        return Err("attributes of different lengths".into());
+    }
+
+    let indices: Vec<u32> = match reader.read_indices() {
+        Some(indices) => indices.into_u32().collect(),
+        None => (0..positions.len() as u32).collect(),
+    };
+    if let Some(index) = indices.iter().find(|&&i| i as usize >= positions.len()) {
+        return Err(format!("index {index} is past the last vertex"));
+    }
+    let indices = match primitive.mode() {
+        Mode::Triangles => indices[..indices.len() / 3 * 3].to_vec(),
+        Mode::TriangleStrip => (2..indices.len())
+            .flat_map(|i| match i % 2 {
+                // every other triangle is wound the other way round
+                0 => [indices[i - 2], indices[i - 1], indices[i]],
+                _ => [indices[i - 1], indices[i - 2], indices[i]],
+            })
+            .collect(),
+        Mode::TriangleFan => (2..indices.len())
+            .flat_map(|i| [indices[0], indices[i - 1], indices[i]])
+            .collect(),
+        Mode::Points | Mode::Lines | Mode::LineLoop | Mode::LineStrip => return Ok(None),
+    };
+
+    let (vertices, indices) = match normals {
+        Some(normals) => {
+            let vertices = positions
+                .iter()
+                .zip(&normals)
+                .zip(&uvs)
+                .map(|((&position, &normal), &uv)| PositionNormalUv {
+                    position,
+                    normal,
+                    uv,
+                })
+                .collect();
+            (vertices, indices)
+        }
+        None => flat_shaded(&positions, &uvs, &indices),
+    };
+    Ok(Some(PrimitiveData {
+        vertices,
+        indices,
+        material,
+    }))
+}
+
+/// Three vertices of their own per triangle, each with the triangle's normal,
+/// which is what glTF asks for when a primitive has no normals.
+fn flat_shaded(
+    positions: &[Vec3],
+    uvs: &[Vec2],
+    indices: &[u32],
+) -> (Vec<PositionNormalUv>, Vec<u32>) {
+    let vertices: Vec<PositionNormalUv> = indices
+        .chunks_exact(3)
+        .flat_map(|triangle| {
+            let [a, b, c] = [0, 1, 2].map(|i| positions[triangle[i] as usize]);
+            let normal = (b - a).cross(c - a).normalize_or_zero();
+            triangle.iter().map(move |&i| PositionNormalUv {
+                position: positions[i as usize],
+                normal,
+                uv: uvs[i as usize],
+            })
+        })
+        .collect();
+    let indices = (0..vertices.len() as u32).collect();
+    (vertices, indices)
+}
+
+/// A glTF model on the GPU. Where its meshes are drawn is up to the [`Scene`] they were added to.
+pub struct Model<G: GlBackend = WebGl2RenderingContext> {
+    /// kept to upload again on a restored context
+    pub data: Rc<ModelData>,
+    /// per mesh of [`ModelData::meshes`], one per primitive
+    pub meshes: Vec<Vec<LitMesh<G>>>,
+}
+
+impl<G: GlBackend> Model<G> {
+    /// Upload every mesh, and every texture a material uses (once, however many materials share it).
+    pub fn new(
+        gl: &G,
+        programs: &ProgramRegistry<G>,
+        data: Rc<ModelData>,
+    ) -> Result<Self, JsValue> {
+        let mut textures: Vec<Option<Rc<Texture<G>>>> = vec![None; data.textures.len()];
+        let mut meshes: Vec<Vec<LitMesh<G>>> = Vec::with_capacity(data.meshes.len());
+        let uploaded = (|| {
+            for mesh in &data.meshes {
+                meshes.push(Vec::with_capacity(mesh.primitives.len()));
+                for primitive in &mesh.primitives {
+                    let material = Self::material(gl, &data, primitive.material, &mut textures)?;
+                    let primitive = LitMesh::new(
+                        gl,
+                        programs,
+                        &primitive.vertices,
+                        &primitive.indices,
+                        material,
+                    )?;
+                    meshes.last_mut().unwrap().push(primitive);
+                }
+            }
+            Ok(())
+        })();
+        if let Err(e) = uploaded {
+            for primitive in meshes.into_iter().flatten() {
+                primitive.release(gl);
+            }
+            // what is left of each texture once the meshes have let go of theirs
+            for texture in textures.into_iter().flatten().filter_map(Rc::into_inner) {
+                texture.release(gl);
+            }
+            return Err(e);
+        }
+        Ok(Self { data, meshes })
+    }
+
+    fn material(
+        gl: &G,
+        data: &ModelData,
+        index: Option<usize>,
+        textures: &mut [Option<Rc<Texture<G>>>],
+    ) -> Result<Material<G>, JsValue> {
+        let source = data.material(index);
+        let lighting = match source.unlit {
+            true => LightingModel::Unlit,
+            false => LightingModel::MetallicRoughness,
+        };
+        let mut material = Material::new(source.base_color).with_lighting(lighting);
+        material.uv_transform = source.uv_transform;
+        material.roughness = source.roughness;
+        material.metallic = source.metallic;
+        material.emissive = source.emissive;
+        if let Some(index) = source.base_color_texture {
+            let texture = match &textures[index] {
+                Some(texture) => texture.clone(),
+                None => {
+                    let TextureData { image, sampler } = data.textures[index];
+                    let texture = Rc::new(Texture::from_image(
+                        gl,
+                        &data.images[image],
+                        ColorSpace::Srgb,
+                        &sampler,
+                    )?);
+                    textures[index] = Some(texture.clone());
+                    texture
+                }
+            };
+            material = material.with_texture(texture);
+        }
+        Ok(material)
+    }
+
+    /// Every primitive of mesh `mesh`.
+    pub fn draw_mesh(&self, gl: &G, mesh: usize, model: &[f32; 16], mode: ViewMode) {
+        for primitive in &self.meshes[mesh] {
+            primitive.draw(gl, model, mode);
+        }
+    }
+
+    /// Make [`ViewMode::Multiview`] available to [`Self::draw_mesh`].
+    pub fn enable_multiview(
+        &mut self,
+        gl: &G,
+        programs: &ProgramRegistry<G>,
+    ) -> Result<(), JsValue> {
+        for primitive in self.meshes.iter_mut().flatten() {
+            primitive.enable_multiview(gl, programs)?;
+        }
+        Ok(())
+    }
+
+    /// Upload everything again on a restored context, textures included.
+    pub fn recreate(&mut self, gl: &G, programs: &ProgramRegistry<G>) -> Result<(), JsValue> {
+        *self = Self::new(gl, programs, self.data.clone())?;
+        Ok(())
+    }
+
+    /// Releases the textures too.
+    pub fn release(self, gl: &G) {
+        for primitive in self.meshes.into_iter().flatten() {
+            primitive.release(gl);
+        }
+    }
+}
+
+/// A model being fetched: first the file, then whatever it refers to.
+pub struct ModelLoad {
+    pub url: String,
+    /// once the file itself has arrived
+    file: Option<GltfFile>,
+}
+
+impl ModelLoad {
+    pub fn new(url: &str) -> Self {
+        Self {
+            url: url.to_string(),
+            file: None,
+        }
+    }
+
+    /// Start fetching whatever is missing. `None` while anything is still on its way;
+    /// then the model, or why it could not be loaded.
+    /// Either way the files are then dropped from `assets`, so loading the same URL again fetches them again.
+    pub fn poll(&mut self, assets: &AssetLoader) -> Option<Result<ModelData, String>> {
+        let rval = self.poll_files(assets)?;
+        assets.forget(&self.url);
+        if let Some(file) = &self.file {
+            for uri in file.external_uris() {
+                assets.forget(&resolve_uri(&self.url, uri));
+            }
+        }
+        Some(rval)
+    }
+
+    fn poll_files(&mut self, assets: &AssetLoader) -> Option<Result<ModelData, String>> {
+        if self.file.is_none() {
+            assets.load(&self.url, AssetKind::Binary);
+            let file = match assets.state(&self.url)? {
+                AssetState::Pending => return None,
+                AssetState::Failed(e) => Err(e),
+                AssetState::Loaded(asset) => asset
+                    .as_binary()
+                    .ok_or_else(|| "not a binary asset".to_string())
+                    .and_then(|bytes| GltfFile::parse(bytes)),
+            };
+            match file {
+                Ok(file) => self.file = Some(file),
+                Err(e) => return Some(Err(e)),
+            }
+        }
+        let file = self.file.as_ref()?;
+
+        let urls: Vec<String> = file
+            .external_uris()
+            .into_iter()
+            .map(|uri| resolve_uri(&self.url, uri))
+            .collect();
+        let mut waiting = false;
+        for url in &urls {
+            assets.load(url, AssetKind::Binary);
+            match assets.state(url) {
+                Some(AssetState::Loaded(_)) => {}
+                Some(AssetState::Failed(e)) => return Some(Err(e)),
+                Some(AssetState::Pending) | None => waiting = true,
+            }
+        }
+        if waiting {
+            return None;
+        }
+
+        Some(file.import(|uri| {
+            let url = resolve_uri(&self.url, uri);
+            assets
+                .get(&url)
+                .and_then(|asset| asset.as_binary().cloned())
+                .ok_or_else(|| format!("{url} is not a binary asset"))
+        }))
+    }
+}
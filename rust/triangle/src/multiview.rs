@@ -0,0 +1,86 @@
+//! Single-pass stereo: the scene is drawn once into a two-layer [`MultiviewTarget`]
+//! with the shaders' `MULTIVIEW` variants, then each layer is copied into its eye's
+//! viewport of the `XRWebGLLayer`.
+
+use crate::backend::{GlBackend, MULTIVIEW_EXTENSION};
+use crate::camera::MULTIVIEW_VIEWS;
+use crate::gl_thin::MultiviewTarget;
+use crate::reflection::TextureUnit;
+use crate::shaders::registry::ProgramRegistry;
+use crate::shaders::LayerCopyShader;
+use wasm_bindgen::JsValue;
+use web_sys::WebGl2RenderingContext;
+
+/// The render target and the copy that takes its layers out again.
+pub struct MultiviewPass<G: GlBackend = WebGl2RenderingContext> {
+    /// allocated by [`Self::begin`], at the size of an eye's viewport
+    target: Option<MultiviewTarget<G>>,
+    copy: LayerCopyShader<G>,
+    /// with no attributes; the copy's triangle comes from `gl_VertexID`
+    vao: G::VertexArray,
+}
+
+impl<G: GlBackend> MultiviewPass<G> {
+    /// Fails if `OVR_multiview2` is not supported.
+    pub fn new(gl: &G, programs: &ProgramRegistry<G>) -> Result<Self, JsValue> {
+        if !gl.has_extension(MULTIVIEW_EXTENSION) {
+            return Err(JsValue::from_str("OVR_multiview2 is not supported"));
+        }
+        let copy = LayerCopyShader::new(gl, programs)?;
+        let Some(vao) = gl.create_vertex_array() else {
+            copy.release(gl);
+            return Err(JsValue::from_str("failed to create vao"));
+        };
+        Ok(Self {
+            target: None,
+            copy,
+            vao,
+        })
+    }
+
+    /// Bind a `width`x`height` target, replacing the old one if the size changed,
+    /// and clear every layer to transparent black (for camera pass-through).
+    pub fn begin(&mut self, gl: &G, width: i32, height: i32) -> Result<(), JsValue> {
+        let resized = self
+            .target
+            .as_ref()
+            .is_none_or(|target| [target.width, target.height] != [width, height]);
+        if resized {
+            if let Some(old) = self.target.take() {
+                old.release(gl);
+            }
+            let views = MULTIVIEW_VIEWS.try_into().unwrap();
+            self.target = Some(MultiviewTarget::new(gl, width, height, views)?);
+        }
+        let target = self.target.as_ref().unwrap();
+        target.bind(gl);
+        gl.clear_color(0.0, 0.0, 0.0, 0.0);
+        gl.clear(
+            WebGl2RenderingContext::COLOR_BUFFER_BIT | WebGl2RenderingContext::DEPTH_BUFFER_BIT,
+        );
+        Ok(())
+    }
+
+    /// Copy layer `view` of what was drawn since [`Self::begin`] over the current viewport
+    /// of the current draw framebuffer.
+    pub fn copy_view(&self, gl: &G, view: i32) {
+        let Some(target) = &self.target else {
+            return;
+        };
+        let tex_index = 0;
+        gl.active_texture(WebGl2RenderingContext::TEXTURE0 + tex_index);
+        gl.bind_texture(
+            WebGl2RenderingContext::TEXTURE_2D_ARRAY,
+            Some(&target.color),
+        );
+        self.copy.draw(gl, &self.vao, TextureUnit(tex_index), view);
+    }
+
+    pub fn release(self, gl: &G) {
+        if let Some(target) = self.target {
+            target.release(gl);
+        }
+        self.copy.release(gl);
+        gl.delete_vertex_array(Some(&self.vao));
+    }
+}
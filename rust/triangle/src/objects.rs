@@ -1,47 +1,57 @@
-use crate::gl_thin::HomogeneousGlBuffer;
-use crate::shaders::{GradientShader, TextureShader};
-use crate::{gl_thin, log};
-use image::{DynamicImage, ImageError};
-use std::io::Cursor;
+use crate::backend::GlBackend;
+use crate::camera::ViewMode;
+use crate::gl_thin;
+use crate::gl_thin::{HomogeneousGlBuffer, IndexBuffer, InterleavedGlBuffer};
+use crate::material::Material;
+use crate::reflection::TextureUnit;
+use crate::shaders::registry::ProgramRegistry;
+use crate::shaders::{
+    GradientShader, InstancedGradientShader, InstancedTextureShader, LitShader, TextureShader,
+};
+use crate::texture::{ColorSpace, SamplerDescription, Texture, TextureFormat, TexturePixels};
+use crate::vertex_layout::{vertex_layout, Normalized};
+use crate::video::VideoTexture;
+use glam::{vec2, vec3, Mat4, Vec2, Vec3, Vec4};
+use image::DynamicImage;
+use std::rc::Rc;
 use wasm_bindgen::JsValue;
-use web_sys::{WebGl2RenderingContext, WebGlBuffer, WebGlTexture, WebGlVertexArrayObject};
+use web_sys::WebGl2RenderingContext;
 
-pub struct GradientTriangle {
-    pub shader: GradientShader,
-    pub triangle_vertices: WebGlBuffer,
-    pub vao: WebGlVertexArrayObject,
+/// where the page serves the poster image from, relative to the page
+pub const POSTER_URL: &str = "assets/sohma_g_dawling_poster.png";
+
+pub struct GradientTriangle<G: GlBackend = WebGl2RenderingContext> {
+    pub shader: GradientShader<G>,
+    pub triangle_vertices: InterleavedGlBuffer<XYRGB, G>,
+    pub vao: G::VertexArray,
 }
 
-impl GradientTriangle {
-    pub fn new(gl: &WebGl2RenderingContext) -> Result<Self, JsValue> {
-        let shader = GradientShader::new(gl)?;
+impl<G: GlBackend> GradientTriangle<G> {
+    pub fn new(gl: &G, programs: &ProgramRegistry<G>) -> Result<Self, JsValue> {
+        let shader = GradientShader::new(gl, programs)?;
 
-        let vao = gl
-            .create_vertex_array()
-            .ok_or_else(|| JsValue::from_str("failed to create vao"))
-            .unwrap();
+        let Some(vao) = gl.create_vertex_array() else {
+            shader.release(gl);
+            return Err(JsValue::from_str("failed to create vao"));
+        };
         gl.bind_vertex_array(Some(&vao));
 
-        let diam: f32 = 1.0;
-        let xys: [XYRGB; 3] = [
-            (0.0f32, diam, 0xff, 0, 0).into(), //
-            (-diam, -diam, 0, 0xff, 0).into(), //
-            (diam, -diam, 0, 0, 0xff).into(),
-        ];
-
-        let triangle_vertices = {
-            let buffer = gl.create_buffer().ok_or("failed to create buffer")?;
-            gl.bind_buffer(WebGl2RenderingContext::ARRAY_BUFFER, Some(&buffer));
-            XYRGB::load_buffer(
-                gl,
-                WebGl2RenderingContext::ARRAY_BUFFER,
-                &xys,
-                WebGl2RenderingContext::STATIC_DRAW,
-            );
-            buffer
+        let triangle_vertices = match InterleavedGlBuffer::new_bound(
+            gl,
+            &triangle_xyrgb(),
+            WebGl2RenderingContext::ARRAY_BUFFER,
+            WebGl2RenderingContext::STATIC_DRAW,
+        ) {
+            Ok(buffer) => buffer,
+            Err(e) => {
+                gl.bind_vertex_array(None);
+                gl.delete_vertex_array(Some(&vao));
+                shader.release(gl);
+                return Err(e.into());
+            }
         };
 
-        XYRGB::gl_attr(gl, shader.sal_xy, shader.sal_rgb);
+        triangle_vertices.vertex_attrib_pointers(gl, &[shader.sal_xy, shader.sal_rgb]);
 
         gl.bind_vertex_array(None);
 
@@ -52,184 +62,681 @@ impl GradientTriangle {
         })
     }
 
-    pub fn draw(&self, gl: &WebGl2RenderingContext, mvp: &[f32; 16]) {
-        self.shader.draw(gl, 0, 3, &self.vao, mvp);
+    pub fn draw(&self, gl: &G, model: &[f32; 16], mode: ViewMode) {
+        let vertex_count = self.triangle_vertices.len().try_into().unwrap();
+        self.shader
+            .for_view(mode)
+            .draw(gl, 0, vertex_count, &self.vao, model);
+    }
+
+    /// Make [`ViewMode::Multiview`] available to [`Self::draw`].
+    pub fn enable_multiview(
+        &mut self,
+        gl: &G,
+        programs: &ProgramRegistry<G>,
+    ) -> Result<(), JsValue> {
+        self.shader.enable_multiview(gl, programs)
+    }
+
+    /// Build everything again on a restored context.
+    /// The old handles died with the lost context, so they are dropped instead of released.
+    pub fn recreate(&mut self, gl: &G, programs: &ProgramRegistry<G>) -> Result<(), JsValue> {
+        *self = Self::new(gl, programs)?;
+        Ok(())
+    }
+
+    pub fn release(self, gl: &G) {
+        self.triangle_vertices.release(gl);
+        self.shader.release(gl);
+        gl.delete_vertex_array(Some(&self.vao));
     }
 }
 
 //
 
-pub struct SohmahPoster {
-    pub shader: TextureShader,
-    square_vertices: HomogeneousGlBuffer<f32>,
-    indices: HomogeneousGlBuffer<u8>,
-    index_count: i32,
-    tex_id: WebGlTexture,
-    vao: WebGlVertexArrayObject,
+pub struct SohmahPoster<G: GlBackend = WebGl2RenderingContext> {
+    pub shader: TextureShader<G>,
+    square_vertices: HomogeneousGlBuffer<f32, G>,
+    indices: IndexBuffer<G>,
+    /// a placeholder until [`Self::set_image`]
+    texture: Texture<G>,
+    vao: G::VertexArray,
+    /// what `texture` was made from, for [`Self::recreate`]
+    image: Option<Rc<DynamicImage>>,
 }
 
-impl SohmahPoster {
-    pub fn new(gl: &WebGl2RenderingContext) -> Result<Self, JsValue> {
-        let shader = TextureShader::new(gl)?;
+impl<G: GlBackend> SohmahPoster<G> {
+    pub fn new(gl: &G, programs: &ProgramRegistry<G>) -> Result<Self, JsValue> {
+        let shader = TextureShader::new(gl, programs)?;
+        let (square_vertices, indices, vao) = poster_quad(gl, &shader)?;
+        let texture = placeholder_texture(gl)?;
+
+        Ok(Self {
+            shader,
+            square_vertices,
+            indices,
+            texture,
+            vao,
+            image: None,
+        })
+    }
+
+    /// Show `image` instead of the placeholder (or the previous image).
+    pub fn set_image(&mut self, gl: &G, image: Rc<DynamicImage>) -> Result<(), JsValue> {
+        let texture = poster_texture(gl, &image)?;
+        std::mem::replace(&mut self.texture, texture).release(gl);
+        self.image = Some(image);
+        Ok(())
+    }
+
+    pub fn draw(&self, gl: &G, model: &[f32; 16], mode: ViewMode) {
+        let tex_index = 0;
+        self.texture.bind(gl, tex_index);
+
+        self.shader.for_view(mode).draw(
+            gl,
+            &self.indices,
+            &self.vao,
+            model,
+            TextureUnit(tex_index),
+        );
+    }
+
+    /// Make [`ViewMode::Multiview`] available to [`Self::draw`].
+    pub fn enable_multiview(
+        &mut self,
+        gl: &G,
+        programs: &ProgramRegistry<G>,
+    ) -> Result<(), JsValue> {
+        self.shader.enable_multiview(gl, programs)
+    }
+
+    /// Rebuild everything on a restored context, uploading the image again if there is one.
+    /// See [`GradientTriangle::recreate`].
+    pub fn recreate(&mut self, gl: &G, programs: &ProgramRegistry<G>) -> Result<(), JsValue> {
+        let image = self.image.take();
+        *self = Self::new(gl, programs)?;
+        match image {
+            Some(image) => self.set_image(gl, image),
+            None => Ok(()),
+        }
+    }
+
+    pub fn release(self, gl: &G) {
+        self.square_vertices.release(gl);
+        self.indices.release(gl);
+        self.shader.release(gl);
+        gl.delete_vertex_array(Some(&self.vao));
+        self.texture.release(gl);
+    }
+}
+
+/// the triangle vertices, which fill clip space when the model matrix is the identity
+fn triangle_xyrgb() -> [XYRGB; 3] {
+    let diam: f32 = 1.0;
+    [
+        (0.0f32, diam, 0xff, 0, 0).into(), //
+        (-diam, -diam, 0, 0xff, 0).into(), //
+        (diam, -diam, 0, 0, 0xff).into(),
+    ]
+}
+
+/// Many copies of the [`GradientTriangle`] in a single draw call.
+/// Each copy gets its own model matrix and tint from an [`InstanceData`].
+pub struct GradientTriangles<G: GlBackend = WebGl2RenderingContext> {
+    pub shader: InstancedGradientShader<G>,
+    pub triangle_vertices: InterleavedGlBuffer<XYRGB, G>,
+    pub instances: InterleavedGlBuffer<InstanceData, G>,
+    pub vao: G::VertexArray,
+    /// a CPU-side copy of `instances`, for [`Self::recreate`]
+    instance_data: Vec<InstanceData>,
+}
+
+impl<G: GlBackend> GradientTriangles<G> {
+    pub fn new(
+        gl: &G,
+        programs: &ProgramRegistry<G>,
+        instances: &[InstanceData],
+    ) -> Result<Self, JsValue> {
+        let instance_data = instances.to_vec();
+        let shader = InstancedGradientShader::new(gl, programs)?;
 
         let vao = gl
             .create_vertex_array()
-            .ok_or_else(|| JsValue::from_str("failed to create vao"))
-            .unwrap();
+            .ok_or_else(|| JsValue::from_str("failed to create vao"))?;
         gl.bind_vertex_array(Some(&vao));
 
-        let diam = 1.0;
-        let xys = [-diam, -diam, diam, -diam, -diam, diam, diam, diam];
-        let square_vertices = gl_thin::HomogeneousGlBuffer::new_bound(
+        let triangle_vertices = InterleavedGlBuffer::new_bound(
             gl,
-            &xys,
+            &triangle_xyrgb(),
             WebGl2RenderingContext::ARRAY_BUFFER,
             WebGl2RenderingContext::STATIC_DRAW,
         )?;
+        triangle_vertices.vertex_attrib_pointers(gl, &[shader.sal_xy, shader.sal_rgb]);
 
-        let indices_u8 = [0, 1, 2, 2, 1, 3];
-        let indices = HomogeneousGlBuffer::new_bound(
+        let instances = InterleavedGlBuffer::new_bound(
             gl,
-            &indices_u8,
-            WebGl2RenderingContext::ELEMENT_ARRAY_BUFFER,
-            WebGl2RenderingContext::STATIC_DRAW,
+            instances,
+            WebGl2RenderingContext::ARRAY_BUFFER,
+            WebGl2RenderingContext::DYNAMIC_DRAW,
         )?;
+        instances.instance_attrib_pointers(gl, &[shader.sal_model, shader.sal_tint]);
+
+        gl.bind_vertex_array(None);
+
+        Ok(Self {
+            shader,
+            triangle_vertices,
+            instances,
+            vao,
+            instance_data,
+        })
+    }
+
+    /// Replace every instance. The VAO keeps pointing at the same buffer so it needs no reconfiguring.
+    pub fn set_instances(&mut self, gl: &G, instances: &[InstanceData]) {
+        self.instances
+            .replace(gl, instances, WebGl2RenderingContext::DYNAMIC_DRAW);
+        self.instance_data = instances.to_vec();
+    }
+
+    /// Rebuild everything on a restored context, with the most recent instances.
+    /// See [`GradientTriangle::recreate`].
+    pub fn recreate(&mut self, gl: &G, programs: &ProgramRegistry<G>) -> Result<(), JsValue> {
+        let instance_data = std::mem::take(&mut self.instance_data);
+        *self = Self::new(gl, programs, &instance_data)?;
+        Ok(())
+    }
+
+    pub fn draw(&self, gl: &G) {
+        if self.instances.is_empty() {
+            return;
+        }
+        let vertex_count = self.triangle_vertices.len().try_into().unwrap();
+        let instance_count = self.instances.len().try_into().unwrap();
+        self.shader
+            .draw_instanced(gl, 0, vertex_count, instance_count, &self.vao);
+    }
+
+    pub fn release(self, gl: &G) {
+        self.triangle_vertices.release(gl);
+        self.instances.release(gl);
+        self.shader.release(gl);
+        gl.delete_vertex_array(Some(&self.vao));
+    }
+}
+
+//
+
+/// Many copies of the [`SohmahPoster`] in a single draw call, sharing one texture.
+pub struct SohmahPosters<G: GlBackend = WebGl2RenderingContext> {
+    pub shader: InstancedTextureShader<G>,
+    square_vertices: HomogeneousGlBuffer<f32, G>,
+    indices: IndexBuffer<G>,
+    instances: InterleavedGlBuffer<InstanceData, G>,
+    /// a placeholder until [`Self::set_image`]
+    texture: Texture<G>,
+    vao: G::VertexArray,
+    /// a CPU-side copy of `instances`, for [`Self::recreate`]
+    instance_data: Vec<InstanceData>,
+    image: Option<Rc<DynamicImage>>,
+}
 
+impl<G: GlBackend> SohmahPosters<G> {
+    pub fn new(
+        gl: &G,
+        programs: &ProgramRegistry<G>,
+        instances: &[InstanceData],
+    ) -> Result<Self, JsValue> {
+        let instance_data = instances.to_vec();
+        let shader = InstancedTextureShader::new(gl, programs)?;
+
+        let vao = gl
+            .create_vertex_array()
+            .ok_or_else(|| JsValue::from_str("failed to create vao"))?;
+        gl.bind_vertex_array(Some(&vao));
+
+        let (square_vertices, indices) = square_buffers(gl)?;
         square_vertices.vertex_attrib_pointer(gl, shader.sal_xy, 2, false, 0, 0);
 
-        gl.bind_vertex_array(None);
+        let instances = InterleavedGlBuffer::new_bound(
+            gl,
+            instances,
+            WebGl2RenderingContext::ARRAY_BUFFER,
+            WebGl2RenderingContext::DYNAMIC_DRAW,
+        )?;
+        instances.instance_attrib_pointers(gl, &[shader.sal_model, shader.sal_tint]);
 
-        let image = sohma_poster().map_err(|e| JsValue::from(format!("{e}")))?;
+        gl.bind_vertex_array(None);
 
-        let tex_id = texture_from_image(gl, &image)?;
+        let texture = placeholder_texture(gl)?;
 
         Ok(Self {
             shader,
             square_vertices,
             indices,
-            index_count: indices_u8.len().try_into().unwrap(),
-            tex_id,
+            instances,
+            texture,
             vao,
+            instance_data,
+            image: None,
         })
     }
 
-    pub fn draw(&self, gl: &WebGl2RenderingContext, mvp: &[f32; 16]) {
+    /// Show `image` on every instance. See [`SohmahPoster::set_image`].
+    pub fn set_image(&mut self, gl: &G, image: Rc<DynamicImage>) -> Result<(), JsValue> {
+        let texture = poster_texture(gl, &image)?;
+        std::mem::replace(&mut self.texture, texture).release(gl);
+        self.image = Some(image);
+        Ok(())
+    }
+
+    /// Replace every instance. The VAO keeps pointing at the same buffer so it needs no reconfiguring.
+    pub fn set_instances(&mut self, gl: &G, instances: &[InstanceData]) {
+        self.instances
+            .replace(gl, instances, WebGl2RenderingContext::DYNAMIC_DRAW);
+        self.instance_data = instances.to_vec();
+    }
+
+    /// Rebuild everything on a restored context, with the most recent instances.
+    /// See [`GradientTriangle::recreate`].
+    pub fn recreate(&mut self, gl: &G, programs: &ProgramRegistry<G>) -> Result<(), JsValue> {
+        let instance_data = std::mem::take(&mut self.instance_data);
+        let image = self.image.take();
+        *self = Self::new(gl, programs, &instance_data)?;
+        match image {
+            Some(image) => self.set_image(gl, image),
+            None => Ok(()),
+        }
+    }
+
+    pub fn draw(&self, gl: &G) {
+        if self.instances.is_empty() {
+            return;
+        }
         let tex_index = 0;
-        gl.active_texture(WebGl2RenderingContext::TEXTURE0 + tex_index);
-        gl.bind_texture(WebGl2RenderingContext::TEXTURE_2D, Some(&self.tex_id));
+        self.texture.bind(gl, tex_index);
 
-        self.shader.draw(
+        self.shader.draw_instanced(
             gl,
-            self.index_count,
+            &self.indices,
+            self.instances.len().try_into().unwrap(),
             &self.vao,
-            mvp,
-            tex_index.try_into().unwrap(),
+            TextureUnit(tex_index),
         );
     }
 
-    pub fn release(self, gl: &WebGl2RenderingContext) {
+    pub fn release(self, gl: &G) {
         self.square_vertices.release(gl);
         self.indices.release(gl);
+        self.instances.release(gl);
         self.shader.release(gl);
         gl.delete_vertex_array(Some(&self.vao));
+        self.texture.release(gl);
     }
 }
 
-//
+/// A [`VideoTexture`] on the [`SohmahPoster`] quad, stretched horizontally to the video's aspect ratio.
+pub struct VideoPoster<G: GlBackend = WebGl2RenderingContext> {
+    pub shader: TextureShader<G>,
+    square_vertices: HomogeneousGlBuffer<f32, G>,
+    indices: IndexBuffer<G>,
+    vao: G::VertexArray,
+    pub video: VideoTexture<G>,
+}
 
-/// We use this for a heterogenous interleaved GL buffer of vertex data.
-/// The X and Y can be used raw, but we should ask GL to "normalize" the r,g,b values.
-/// 12 bytes:
-///
-/// `xxxxyyyyrgb_`
-#[repr(C)]
-pub struct XYRGB {
-    x: f32,
-    y: f32,
-    r: u8,
-    g: u8,
-    b: u8,
-}
-
-impl XYRGB {
-    pub fn load_buffer(gl: &WebGl2RenderingContext, target: u32, xys: &[XYRGB], usage: u32) {
-        let raw_slice = {
-            let ptr = xys.as_ptr();
-            let size = size_of_val(xys);
-            unsafe { std::slice::from_raw_parts(ptr.cast::<u8>(), size) }
-        };
-        gl.buffer_data_with_array_buffer_view(
-            target,
-            &js_sys::Uint8Array::from(raw_slice).into(),
-            usage,
-        );
+impl<G: GlBackend> VideoPoster<G> {
+    pub fn new(
+        gl: &G,
+        programs: &ProgramRegistry<G>,
+        video: VideoTexture<G>,
+    ) -> Result<Self, JsValue> {
+        let shader = TextureShader::new(gl, programs)?;
+        let (square_vertices, indices, vao) = poster_quad(gl, &shader)?;
+        Ok(Self {
+            shader,
+            square_vertices,
+            indices,
+            vao,
+            video,
+        })
+    }
+
+    /// Upload the video's current frame if it changed. Once per frame, not once per view.
+    pub fn update(&mut self, gl: &G) -> Result<bool, JsValue> {
+        self.video.update(gl)
     }
 
-    /// configure the current VAO to pull `vec2 xy;` and `vec3: rgb` from the active buffer
-    pub fn gl_attr(gl: &WebGl2RenderingContext, sal_xy: u32, sal_rgb: u32) {
-        // heterogeneous buffer layout
-        gl.vertex_attrib_pointer_with_i32(
-            sal_rgb,
-            3,
-            WebGl2RenderingContext::UNSIGNED_BYTE,
-            true, // convert from u8 to f32: a/255
-            size_of::<XYRGB>().try_into().unwrap(),
-            (2 * size_of::<f32>()).try_into().unwrap(),
+    pub fn draw(&self, gl: &G, model: &[f32; 16], mode: ViewMode) {
+        let tex_index = 0;
+        self.video.bind(gl, tex_index);
+
+        let model = Mat4::from_cols_array(model)
+            * Mat4::from_scale(glam::vec3(self.video.aspect(), 1.0, 1.0));
+        self.shader.for_view(mode).draw(
+            gl,
+            &self.indices,
+            &self.vao,
+            &model.to_cols_array(),
+            TextureUnit(tex_index),
         );
-        gl.enable_vertex_attrib_array(sal_rgb);
-
-        gl.vertex_attrib_pointer_with_i32(
-            sal_xy,
-            2,
-            WebGl2RenderingContext::FLOAT,
-            false,
-            size_of::<XYRGB>().try_into().unwrap(),
-            0,
+    }
+
+    /// Make [`ViewMode::Multiview`] available to [`Self::draw`].
+    pub fn enable_multiview(
+        &mut self,
+        gl: &G,
+        programs: &ProgramRegistry<G>,
+    ) -> Result<(), JsValue> {
+        self.shader.enable_multiview(gl, programs)
+    }
+
+    /// Rebuild the GL side on a restored context; the video keeps playing. See [`GradientTriangle::recreate`].
+    pub fn recreate(&mut self, gl: &G, programs: &ProgramRegistry<G>) -> Result<(), JsValue> {
+        self.shader = TextureShader::new(gl, programs)?;
+        (self.square_vertices, self.indices, self.vao) = poster_quad(gl, &self.shader)?;
+        self.video.recreate(gl)
+    }
+
+    pub fn release(self, gl: &G) {
+        self.square_vertices.release(gl);
+        self.indices.release(gl);
+        self.shader.release(gl);
+        gl.delete_vertex_array(Some(&self.vao));
+        self.video.release(gl);
+    }
+}
+
+/// An indexed mesh with normals, lit by whatever [`Lights`](crate::lighting::Lights) are bound,
+/// drawn with the [`LitShader`] for its material's [`LightingModel`](crate::material::LightingModel).
+pub struct LitMesh<G: GlBackend = WebGl2RenderingContext> {
+    pub shader: LitShader<G>,
+    vertices: InterleavedGlBuffer<PositionNormalUv, G>,
+    indices: IndexBuffer<G>,
+    vao: G::VertexArray,
+    pub material: Material<G>,
+    /// bound in place of a missing base color texture
+    white: Texture<G>,
+    /// CPU-side copies of the buffers, for [`Self::recreate`]
+    vertex_data: Vec<PositionNormalUv>,
+    index_data: Vec<u32>,
+}
+
+impl<G: GlBackend> LitMesh<G> {
+    /// Counter-clockwise triangles are the front faces.
+    pub fn new(
+        gl: &G,
+        programs: &ProgramRegistry<G>,
+        vertices: &[PositionNormalUv],
+        indices: &[u32],
+        material: Material<G>,
+    ) -> Result<Self, JsValue> {
+        let shader = LitShader::new(gl, programs, material.lighting)?;
+
+        let vao = gl
+            .create_vertex_array()
+            .ok_or_else(|| JsValue::from_str("failed to create vao"))?;
+        gl.bind_vertex_array(Some(&vao));
+
+        let vertex_buffer = InterleavedGlBuffer::new_bound(
+            gl,
+            vertices,
+            WebGl2RenderingContext::ARRAY_BUFFER,
+            WebGl2RenderingContext::STATIC_DRAW,
+        )?;
+        // an unlit program has no normal, so point the normals at a location it does not read
+        let normal = shader.sal_normal.unwrap_or(shader.sal_uv + 1);
+        vertex_buffer.vertex_attrib_pointers(gl, &[shader.sal_position, normal, shader.sal_uv]);
+        let index_buffer =
+            IndexBuffer::new_bound_narrowest(gl, indices, WebGl2RenderingContext::STATIC_DRAW)?;
+
+        gl.bind_vertex_array(None);
+
+        Ok(Self {
+            shader,
+            vertices: vertex_buffer,
+            indices: index_buffer,
+            vao,
+            material,
+            white: white_texture(gl)?,
+            vertex_data: vertices.to_vec(),
+            index_data: indices.to_vec(),
+        })
+    }
+
+    /// With back faces culled.
+    pub fn draw(&self, gl: &G, model: &[f32; 16], mode: ViewMode) {
+        let tex_index = 0;
+        match &self.material.base_color_texture {
+            Some(texture) => texture.bind(gl, tex_index),
+            None => self.white.bind(gl, tex_index),
+        }
+
+        gl.enable(WebGl2RenderingContext::CULL_FACE);
+        self.shader.for_view(mode).draw(
+            gl,
+            &self.indices,
+            &self.vao,
+            model,
+            &self.material,
+            TextureUnit(tex_index),
         );
-        gl.enable_vertex_attrib_array(sal_xy);
+        gl.disable(WebGl2RenderingContext::CULL_FACE);
+    }
+
+    /// Make [`ViewMode::Multiview`] available to [`Self::draw`].
+    pub fn enable_multiview(
+        &mut self,
+        gl: &G,
+        programs: &ProgramRegistry<G>,
+    ) -> Result<(), JsValue> {
+        self.shader.enable_multiview(gl, programs)
+    }
+
+    /// Rebuild the mesh on a restored context. See [`GradientTriangle::recreate`].
+    /// The material is kept as it is; a texture it refers to has to be recreated by whoever made it.
+    pub fn recreate(&mut self, gl: &G, programs: &ProgramRegistry<G>) -> Result<(), JsValue> {
+        let vertex_data = std::mem::take(&mut self.vertex_data);
+        let index_data = std::mem::take(&mut self.index_data);
+        *self = Self::new(
+            gl,
+            programs,
+            &vertex_data,
+            &index_data,
+            self.material.clone(),
+        )?;
+        Ok(())
+    }
+
+    /// Releases the material too (see [`Material::release`]).
+    pub fn release(self, gl: &G) {
+        self.vertices.release(gl);
+        self.indices.release(gl);
+        self.shader.release(gl);
+        gl.delete_vertex_array(Some(&self.vao));
+        self.white.release(gl);
+        self.material.release(gl);
     }
 }
 
-impl From<(f32, f32, u8, u8, u8)> for XYRGB {
-    #[allow(clippy::many_single_char_names)]
-    fn from((x, y, r, g, b): (f32, f32, u8, u8, u8)) -> Self {
-        Self { x, y, r, g, b }
+/// A square pyramid one unit across and one unit tall, centered on the origin,
+/// with a normal per face so the edges stay sharp.
+pub fn pyramid() -> (Vec<PositionNormalUv>, Vec<u32>) {
+    let apex = vec3(0.0, 0.5, 0.0);
+    let corners = [
+        vec3(-0.5, -0.5, 0.5),
+        vec3(0.5, -0.5, 0.5),
+        vec3(0.5, -0.5, -0.5),
+        vec3(-0.5, -0.5, -0.5),
+    ];
+    let mut faces: Vec<Vec<(Vec3, Vec2)>> = (0..4)
+        .map(|i| {
+            vec![
+                (corners[i], vec2(0.0, 1.0)),
+                (corners[(i + 1) % 4], vec2(1.0, 1.0)),
+                (apex, vec2(0.5, 0.0)),
+            ]
+        })
+        .collect();
+    // the base, seen from below
+    faces.push(vec![
+        (corners[3], vec2(0.0, 0.0)),
+        (corners[2], vec2(1.0, 0.0)),
+        (corners[1], vec2(1.0, 1.0)),
+        (corners[0], vec2(0.0, 1.0)),
+    ]);
+
+    let mut vertices = Vec::new();
+    let mut indices = Vec::new();
+    for face in faces {
+        let [a, b, c] = [face[0].0, face[1].0, face[2].0];
+        let normal = (b - a).cross(c - a).normalize();
+        let first = vertices.len() as u32;
+        vertices.extend(face.iter().map(|&(position, uv)| PositionNormalUv {
+            position,
+            normal,
+            uv,
+        }));
+        // a fan, which covers both the triangles and the square
+        for i in 1..face.len() as u32 - 1 {
+            indices.extend([first, first + i, first + i + 1]);
+        }
     }
+    (vertices, indices)
 }
 
-//
+/// The square plus a VAO which feeds it to `shader`.
+#[track_caller]
+fn poster_quad<G: GlBackend>(
+    gl: &G,
+    shader: &TextureShader<G>,
+) -> Result<(HomogeneousGlBuffer<f32, G>, IndexBuffer<G>, G::VertexArray), JsValue> {
+    let vao = gl
+        .create_vertex_array()
+        .ok_or_else(|| JsValue::from_str("failed to create vao"))?;
+    gl.bind_vertex_array(Some(&vao));
+
+    let (square_vertices, indices) = square_buffers(gl)?;
+    square_vertices.vertex_attrib_pointer(gl, shader.sal_xy, 2, false, 0, 0);
+
+    gl.bind_vertex_array(None);
+    Ok((square_vertices, indices, vao))
+}
 
-fn texture_from_image(
-    gl: &WebGl2RenderingContext,
-    image: &DynamicImage,
-) -> Result<WebGlTexture, JsValue> {
-    let x = image.color();
-    let rgb = match &image {
-        DynamicImage::ImageRgb8(img) => img.as_flat_samples(),
-        _ => return Err(JsValue::from("unable to extract RGB samples from image")),
-    };
-    let width = image.width();
-    let height = image.width();
-
-    let tex_id = gl.create_texture().unwrap();
-    gl.bind_texture(WebGl2RenderingContext::TEXTURE_2D, Some(&tex_id));
-    gl.tex_image_2d_with_i32_and_i32_and_i32_and_format_and_type_and_opt_u8_array(
-        WebGl2RenderingContext::TEXTURE_2D,
-        0,
-        WebGl2RenderingContext::RGB.try_into().unwrap(),
-        width.try_into().unwrap(),
-        height.try_into().unwrap(),
-        0,
-        WebGl2RenderingContext::RGB,
-        WebGl2RenderingContext::UNSIGNED_BYTE,
-        Some(rgb.as_slice()),
+/// The poster's four XY corners plus the indices of its two triangles.
+/// Both buffers remain bound, ready for the current VAO.
+#[track_caller]
+fn square_buffers<G: GlBackend>(
+    gl: &G,
+) -> Result<(HomogeneousGlBuffer<f32, G>, IndexBuffer<G>), JsValue> {
+    let diam = 1.0;
+    let xys = [-diam, -diam, diam, -diam, -diam, diam, diam, diam];
+    let square_vertices = gl_thin::HomogeneousGlBuffer::new_bound(
+        gl,
+        &xys,
+        WebGl2RenderingContext::ARRAY_BUFFER,
+        WebGl2RenderingContext::STATIC_DRAW,
     )?;
-    gl.generate_mipmap(WebGl2RenderingContext::TEXTURE_2D);
-    Ok(tex_id)
+
+    let indices_u8: [u8; 6] = [0, 1, 2, 2, 1, 3];
+    let indices = IndexBuffer::new_bound(gl, &indices_u8, WebGl2RenderingContext::STATIC_DRAW)?;
+
+    Ok((square_vertices, indices))
+}
+
+#[track_caller]
+fn poster_texture<G: GlBackend>(gl: &G, image: &DynamicImage) -> Result<Texture<G>, JsValue> {
+    Texture::from_image(
+        gl,
+        image,
+        ColorSpace::Linear,
+        &SamplerDescription::default(),
+    )
+}
+
+/// What an untextured material samples, so its base color comes through unchanged.
+#[track_caller]
+fn white_texture<G: GlBackend>(gl: &G) -> Result<Texture<G>, JsValue> {
+    let pixels = TexturePixels {
+        width: 1,
+        height: 1,
+        format: TextureFormat::Rgba8,
+        bytes: vec![0xff; 4],
+    };
+    Texture::from_pixels(gl, &pixels, &SamplerDescription::nearest())
+}
+
+/// A grey 2x2 checkerboard to draw until the real image arrives.
+#[track_caller]
+fn placeholder_texture<G: GlBackend>(gl: &G) -> Result<Texture<G>, JsValue> {
+    const DARK: [u8; 4] = [0x60, 0x60, 0x60, 0xff];
+    const LIGHT: [u8; 4] = [0xa0, 0xa0, 0xa0, 0xff];
+    let pixels = TexturePixels {
+        width: 2,
+        height: 2,
+        format: TextureFormat::Rgba8,
+        bytes: [DARK, LIGHT, LIGHT, DARK].concat(),
+    };
+    Texture::from_pixels(gl, &pixels, &SamplerDescription::nearest())
 }
 
-pub fn sohma_poster() -> Result<DynamicImage, ImageError> {
-    image::ImageReader::new(Cursor::new(include_bytes!("sohma_g_dawling_poster.png")))
-        .with_guessed_format()?
-        .decode()
+//
+
+vertex_layout! {
+    /// Per-instance attributes for the instanced shaders. 80 bytes:
+    /// the model matrix (4 `vec4` columns) then an RGBA tint which multiplies the fragment color.
+    #[derive(Debug, Clone, Copy, PartialEq)]
+    pub struct InstanceData {
+        pub model: Mat4,
+        pub tint: Vec4,
+    }
+}
+
+impl InstanceData {
+    /// untinted
+    pub fn new(model: Mat4) -> Self {
+        Self::tinted(model, Vec4::ONE)
+    }
+
+    pub fn tinted(model: Mat4, tint: Vec4) -> Self {
+        Self { model, tint }
+    }
+}
+
+//
+
+vertex_layout! {
+    /// The vertices of a [`LitMesh`]. 32 bytes:
+    /// a model space position and normal, then texture coordinates with (0, 0) at the top left.
+    #[derive(Debug, Clone, Copy, PartialEq)]
+    pub struct PositionNormalUv {
+        pub position: Vec3,
+        pub normal: Vec3,
+        pub uv: Vec2,
+    }
+}
+
+//
+
+vertex_layout! {
+    /// We use this for a heterogenous interleaved GL buffer of vertex data.
+    /// The X and Y can be used raw, but we should ask GL to "normalize" the r,g,b values.
+    /// 12 bytes:
+    ///
+    /// `xxxxyyyyrgb_`
+    #[derive(Debug, Clone, Copy, PartialEq)]
+    pub struct XYRGB {
+        pub xy: [f32; 2],
+        pub rgb: Normalized<[u8; 3]>,
+    }
+}
+
+impl From<(f32, f32, u8, u8, u8)> for XYRGB {
+    #[allow(clippy::many_single_char_names)]
+    fn from((x, y, r, g, b): (f32, f32, u8, u8, u8)) -> Self {
+        Self {
+            xy: [x, y],
+            rgb: Normalized([r, g, b]),
+        }
+    }
 }
@@ -0,0 +1,577 @@
+use crate::backend::{GlBackend, TIME_ELAPSED_EXT};
+use crate::camera::ViewMode;
+use crate::gl_thin::{HomogeneousGlBuffer, InterleavedGlBuffer};
+use crate::shaders::registry::ProgramRegistry;
+use crate::shaders::BarShader;
+use crate::vertex_layout::vertex_layout;
+use glam::{vec4, Vec4};
+use std::collections::VecDeque;
+use std::fmt::{Display, Formatter};
+use wasm_bindgen::JsValue;
+use web_sys::WebGl2RenderingContext;
+
+/// the extension that provides [`TIME_ELAPSED_EXT`] queries
+pub const TIMER_QUERY_EXTENSION: &str = "EXT_disjoint_timer_query_webgl2";
+
+/// The last `capacity` samples of something measured once per frame, in milliseconds.
+#[derive(Debug, Clone)]
+pub struct RollingStats {
+    samples: VecDeque<f64>,
+    capacity: usize,
+}
+
+impl RollingStats {
+    pub fn new(capacity: usize) -> Self {
+        Self {
+            samples: VecDeque::with_capacity(capacity),
+            capacity,
+        }
+    }
+
+    pub fn push(&mut self, sample: f64) {
+        if self.samples.len() == self.capacity {
+            self.samples.pop_front();
+        }
+        self.samples.push_back(sample);
+    }
+
+    pub fn len(&self) -> usize {
+        self.samples.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.samples.is_empty()
+    }
+
+    pub fn last(&self) -> Option<f64> {
+        self.samples.back().copied()
+    }
+
+    /// 0 when there are no samples
+    pub fn mean(&self) -> f64 {
+        if self.samples.is_empty() {
+            0.0
+        } else {
+            self.samples.iter().sum::<f64>() / self.samples.len() as f64
+        }
+    }
+
+    /// 0 when there are no samples
+    pub fn max(&self) -> f64 {
+        self.samples.iter().copied().fold(0.0, f64::max)
+    }
+}
+
+/// The per-frame totals for one named scope.
+#[derive(Debug, Clone)]
+pub struct ScopeStats {
+    pub name: &'static str,
+    /// wall-clock time between entering and leaving the scope, including nested scopes
+    pub cpu_ms: RollingStats,
+    /// GPU time spent on commands issued in this scope but *not* in a nested scope,
+    /// because timer queries can not overlap. Empty without [`TIMER_QUERY_EXTENSION`].
+    pub gpu_ms: RollingStats,
+    cpu_frame_sum: f64,
+    gpu_frame_sum: f64,
+}
+
+impl ScopeStats {
+    fn new(name: &'static str, window: usize) -> Self {
+        Self {
+            name,
+            cpu_ms: RollingStats::new(window),
+            gpu_ms: RollingStats::new(window),
+            cpu_frame_sum: 0.0,
+            gpu_frame_sum: 0.0,
+        }
+    }
+}
+
+/// a timer query that has been ended but whose result has not been read yet
+struct PendingQuery<G: GlBackend> {
+    frame: u64,
+    scope: usize,
+    query: G::Query,
+}
+
+/// Times named scopes (an eye, an object) on the CPU and, when [`TIMER_QUERY_EXTENSION`] is available, on the GPU.
+///
+/// Call [`Self::begin_frame`] with the XR frame time at the top of the animation callback
+/// and [`Self::end_frame`] at the bottom; wrap the interesting parts in [`Self::scope`].
+/// GPU results arrive a frame or more late and are folded in by [`Self::begin_frame`].
+///
+/// A disabled profiler (the default) makes no GL calls and never reads the clock.
+pub struct Profiler<G: GlBackend = WebGl2RenderingContext> {
+    enabled: bool,
+    gpu_timer: bool,
+    /// milliseconds, any origin
+    clock: Box<dyn Fn() -> f64>,
+    window: usize,
+    scopes: Vec<ScopeStats>,
+    /// `(scope, cpu start)` for every scope that has been entered but not left
+    stack: Vec<(usize, f64)>,
+    /// the query timing the innermost open scope
+    active_query: Option<G::Query>,
+    pending: VecDeque<PendingQuery<G>>,
+    spare_queries: Vec<G::Query>,
+    frame: u64,
+    frame_start: f64,
+    last_frame_time: Option<f64>,
+    /// the frame whose GPU results are currently being summed
+    gpu_frame: Option<u64>,
+    /// time between XR frames, from the timestamps the browser passes to the animation callback
+    pub frame_interval_ms: RollingStats,
+    /// time from [`Self::begin_frame`] to [`Self::end_frame`]
+    pub cpu_frame_ms: RollingStats,
+}
+
+impl<G: GlBackend> Profiler<G> {
+    /// about two seconds on a 72Hz headset
+    pub const DEFAULT_WINDOW: usize = 144;
+
+    /// A disabled profiler that reads `performance.now()` once enabled.
+    pub fn new(gl: &G) -> Self {
+        Self::with_clock(gl, Box::new(performance_now))
+    }
+
+    /// A disabled profiler that reads `clock` (milliseconds) once enabled.
+    pub fn with_clock(gl: &G, clock: Box<dyn Fn() -> f64>) -> Self {
+        let window = Self::DEFAULT_WINDOW;
+        Self {
+            enabled: false,
+            gpu_timer: gl.has_extension(TIMER_QUERY_EXTENSION),
+            clock,
+            window,
+            scopes: Vec::new(),
+            stack: Vec::new(),
+            active_query: None,
+            pending: VecDeque::new(),
+            spare_queries: Vec::new(),
+            frame: 0,
+            frame_start: 0.0,
+            last_frame_time: None,
+            gpu_frame: None,
+            frame_interval_ms: RollingStats::new(window),
+            cpu_frame_ms: RollingStats::new(window),
+        }
+    }
+
+    pub fn is_enabled(&self) -> bool {
+        self.enabled
+    }
+
+    /// Only call this between frames.
+    pub fn set_enabled(&mut self, enabled: bool) {
+        self.enabled = enabled;
+        self.last_frame_time = None;
+    }
+
+    /// whether GPU times are being measured
+    pub fn has_gpu_timer(&self) -> bool {
+        self.gpu_timer
+    }
+
+    pub fn frame_count(&self) -> u64 {
+        self.frame
+    }
+
+    pub fn scopes(&self) -> &[ScopeStats] {
+        &self.scopes
+    }
+
+    pub fn scope_stats(&self, name: &str) -> Option<&ScopeStats> {
+        self.scopes.iter().find(|scope| scope.name == name)
+    }
+
+    /// # params
+    /// * `frame_time` - milliseconds, as passed to the animation frame callback
+    pub fn begin_frame(&mut self, gl: &G, frame_time: f64) {
+        if !self.enabled {
+            return;
+        }
+        self.collect_gpu_results(gl);
+        if let Some(last) = self.last_frame_time {
+            self.frame_interval_ms.push(frame_time - last);
+        }
+        self.last_frame_time = Some(frame_time);
+        self.frame += 1;
+        self.frame_start = (self.clock)();
+    }
+
+    pub fn end_frame(&mut self, gl: &G) {
+        if !self.enabled {
+            return;
+        }
+        debug_assert!(self.stack.is_empty(), "unbalanced profiler scopes");
+        while !self.stack.is_empty() {
+            self.end_scope(gl);
+        }
+        for scope in &mut self.scopes {
+            if scope.cpu_frame_sum > 0.0 {
+                scope.cpu_ms.push(scope.cpu_frame_sum);
+                scope.cpu_frame_sum = 0.0;
+            }
+        }
+        self.cpu_frame_ms.push((self.clock)() - self.frame_start);
+    }
+
+    /// Time `f` under `name`. Scopes may nest.
+    pub fn scope<R>(&mut self, gl: &G, name: &'static str, f: impl FnOnce() -> R) -> R {
+        self.begin_scope(gl, name);
+        let rval = f();
+        self.end_scope(gl);
+        rval
+    }
+
+    pub fn begin_scope(&mut self, gl: &G, name: &'static str) {
+        if !self.enabled {
+            return;
+        }
+        let scope = match self.scopes.iter().position(|scope| scope.name == name) {
+            Some(idx) => idx,
+            None => {
+                self.scopes.push(ScopeStats::new(name, self.window));
+                self.scopes.len() - 1
+            }
+        };
+        if let Some(&(outer, _)) = self.stack.last() {
+            self.end_query(gl, outer);
+        }
+        self.stack.push((scope, (self.clock)()));
+        self.begin_query(gl);
+    }
+
+    pub fn end_scope(&mut self, gl: &G) {
+        if !self.enabled {
+            return;
+        }
+        let Some((scope, start)) = self.stack.pop() else {
+            return;
+        };
+        self.scopes[scope].cpu_frame_sum += (self.clock)() - start;
+        self.end_query(gl, scope);
+        if !self.stack.is_empty() {
+            // resume timing the enclosing scope
+            self.begin_query(gl);
+        }
+    }
+
+    fn begin_query(&mut self, gl: &G) {
+        if !self.gpu_timer {
+            return;
+        }
+        let query = self.spare_queries.pop().or_else(|| gl.create_query());
+        if let Some(query) = &query {
+            gl.begin_query(TIME_ELAPSED_EXT, query);
+        }
+        self.active_query = query;
+    }
+
+    fn end_query(&mut self, gl: &G, scope: usize) {
+        if let Some(query) = self.active_query.take() {
+            gl.end_query(TIME_ELAPSED_EXT);
+            self.pending.push_back(PendingQuery {
+                frame: self.frame,
+                scope,
+                query,
+            });
+        }
+    }
+
+    /// Read every timer query that has finished, in order, and fold whole frames into the stats.
+    fn collect_gpu_results(&mut self, gl: &G) {
+        if self.pending.is_empty() {
+            return;
+        }
+        if gl.get_gpu_disjoint() {
+            // something (a power state change, another context) made every outstanding result meaningless
+            self.spare_queries
+                .extend(self.pending.drain(..).map(|pending| pending.query));
+            self.gpu_frame = None;
+            for scope in &mut self.scopes {
+                scope.gpu_frame_sum = 0.0;
+            }
+            return;
+        }
+        while let Some(pending) = self.pending.front() {
+            if !gl.get_query_result_available(&pending.query) {
+                break;
+            }
+            let pending = self.pending.pop_front().unwrap();
+            if self.gpu_frame != Some(pending.frame) {
+                self.flush_gpu_frame();
+                self.gpu_frame = Some(pending.frame);
+            }
+            let nanoseconds = gl.get_query_result(&pending.query);
+            self.scopes[pending.scope].gpu_frame_sum += nanoseconds as f64 / 1e6;
+            self.spare_queries.push(pending.query);
+        }
+        // every query from this frame has been read, so it is complete
+        if self
+            .pending
+            .front()
+            .is_none_or(|pending| Some(pending.frame) != self.gpu_frame)
+        {
+            self.flush_gpu_frame();
+            self.gpu_frame = None;
+        }
+    }
+
+    fn flush_gpu_frame(&mut self) {
+        if self.gpu_frame.is_none() {
+            return;
+        }
+        for scope in &mut self.scopes {
+            if scope.gpu_frame_sum > 0.0 {
+                scope.gpu_ms.push(scope.gpu_frame_sum);
+                scope.gpu_frame_sum = 0.0;
+            }
+        }
+    }
+
+    /// a snapshot of the rolling means, for the console
+    pub fn report(&self) -> ProfileReport {
+        ProfileReport {
+            frame_interval_ms: self.frame_interval_ms.mean(),
+            frame_interval_max_ms: self.frame_interval_ms.max(),
+            cpu_frame_ms: self.cpu_frame_ms.mean(),
+            scopes: self
+                .scopes
+                .iter()
+                .map(|scope| ScopeReport {
+                    name: scope.name,
+                    cpu_ms: scope.cpu_ms.mean(),
+                    gpu_ms: (!scope.gpu_ms.is_empty()).then(|| scope.gpu_ms.mean()),
+                })
+                .collect(),
+        }
+    }
+
+    /// Forget every query, which died with the lost context, and check for the timer extension again
+    /// (extensions have to be re-enabled on a restored context). The CPU-side history is kept.
+    pub fn recreate(&mut self, gl: &G) {
+        self.gpu_timer = gl.has_extension(TIMER_QUERY_EXTENSION);
+        self.active_query = None;
+        self.pending.clear();
+        self.spare_queries.clear();
+        self.gpu_frame = None;
+        self.stack.clear();
+        self.last_frame_time = None;
+        for scope in &mut self.scopes {
+            scope.cpu_frame_sum = 0.0;
+            scope.gpu_frame_sum = 0.0;
+        }
+    }
+
+    pub fn release(self, gl: &G) {
+        for query in self
+            .spare_queries
+            .into_iter()
+            .chain(self.pending.into_iter().map(|pending| pending.query))
+            .chain(self.active_query)
+        {
+            gl.delete_query(Some(&query));
+        }
+    }
+}
+
+/// `performance.now()`; off the web, where there is no `window`, milliseconds since the first call.
+pub(crate) fn performance_now() -> f64 {
+    #[cfg(target_arch = "wasm32")]
+    {
+        web_sys::window()
+            .and_then(|window| window.performance())
+            .map_or(0.0, |performance| performance.now())
+    }
+    #[cfg(not(target_arch = "wasm32"))]
+    {
+        static START: std::sync::OnceLock<std::time::Instant> = std::sync::OnceLock::new();
+        START
+            .get_or_init(std::time::Instant::now)
+            .elapsed()
+            .as_secs_f64()
+            * 1000.0
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct ScopeReport {
+    pub name: &'static str,
+    pub cpu_ms: f64,
+    /// `None` without GPU timing
+    pub gpu_ms: Option<f64>,
+}
+
+/// Rolling means from a [`Profiler`], one line per scope when displayed.
+#[derive(Debug, Clone, PartialEq)]
+pub struct ProfileReport {
+    pub frame_interval_ms: f64,
+    pub frame_interval_max_ms: f64,
+    pub cpu_frame_ms: f64,
+    pub scopes: Vec<ScopeReport>,
+}
+
+impl Display for ProfileReport {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        writeln!(
+            f,
+            "frame interval {:.2}ms (max {:.2}ms), cpu {:.2}ms",
+            self.frame_interval_ms, self.frame_interval_max_ms, self.cpu_frame_ms
+        )?;
+        for scope in &self.scopes {
+            write!(f, "  {}: cpu {:.2}ms", scope.name, scope.cpu_ms)?;
+            if let Some(gpu_ms) = scope.gpu_ms {
+                write!(f, ", gpu (self) {gpu_ms:.2}ms")?;
+            }
+            writeln!(f)?;
+        }
+        Ok(())
+    }
+}
+
+//
+
+vertex_layout! {
+    /// One bar of the [`ProfilerOverlay`], in panel units where 1.0 across is one frame budget.
+    #[derive(Debug, Clone, Copy, PartialEq)]
+    pub struct Bar {
+        /// x, y, width, height
+        pub rect: Vec4,
+        pub color: Vec4,
+    }
+}
+
+const ROW_HEIGHT: f32 = 0.08;
+const ROW_GAP: f32 = 0.02;
+const CPU_COLOR: Vec4 = vec4(0.3, 0.5, 1.0, 1.0);
+const GPU_COLOR: Vec4 = vec4(1.0, 0.6, 0.1, 1.0);
+const GOOD_COLOR: Vec4 = vec4(0.2, 0.9, 0.2, 1.0);
+const BAD_COLOR: Vec4 = vec4(1.0, 0.2, 0.2, 1.0);
+
+/// The bars for a [`ProfilerOverlay`], top to bottom:
+/// the frame interval (green within `budget_ms`, red over it), the CPU frame time,
+/// then a row per scope with CPU time in blue above GPU time in orange.
+/// A white line marks the budget; bars are clipped at twice the budget.
+pub fn overlay_bars(report: &ProfileReport, budget_ms: f64) -> Vec<Bar> {
+    let width = |ms: f64| ((ms / budget_ms) as f32).clamp(0.0, 2.0);
+    let mut rval = Vec::new();
+    let mut y = 0.0;
+    let mut row = |rval: &mut Vec<Bar>, values: &[(f64, Vec4)]| {
+        y -= ROW_HEIGHT + ROW_GAP;
+        let height = ROW_HEIGHT / values.len() as f32;
+        for (i, (ms, color)) in values.iter().enumerate() {
+            rval.push(Bar {
+                rect: vec4(0.0, y + height * i as f32, width(*ms), height),
+                color: *color,
+            });
+        }
+    };
+    let interval_color = if report.frame_interval_ms > budget_ms {
+        BAD_COLOR
+    } else {
+        GOOD_COLOR
+    };
+    row(&mut rval, &[(report.frame_interval_ms, interval_color)]);
+    row(&mut rval, &[(report.cpu_frame_ms, CPU_COLOR)]);
+    for scope in &report.scopes {
+        match scope.gpu_ms {
+            // the first value is drawn lowest
+            Some(gpu_ms) => row(&mut rval, &[(gpu_ms, GPU_COLOR), (scope.cpu_ms, CPU_COLOR)]),
+            None => row(&mut rval, &[(scope.cpu_ms, CPU_COLOR)]),
+        }
+    }
+    rval.push(Bar {
+        rect: vec4(1.0, y, 0.01, -y),
+        color: Vec4::ONE,
+    });
+    rval
+}
+
+/// Draws a [`Profiler`]'s rolling means as horizontal bars on a panel in the scene,
+/// so frame timing can be read on a headset with no devtools attached.
+pub struct ProfilerOverlay<G: GlBackend = WebGl2RenderingContext> {
+    pub shader: BarShader<G>,
+    corners: HomogeneousGlBuffer<f32, G>,
+    bars: InterleavedGlBuffer<Bar, G>,
+    vao: G::VertexArray,
+    /// the width of the panel, in milliseconds; 72Hz by default
+    pub budget_ms: f64,
+}
+
+impl<G: GlBackend> ProfilerOverlay<G> {
+    pub fn new(gl: &G, programs: &ProgramRegistry<G>) -> Result<Self, JsValue> {
+        let shader = BarShader::new(gl, programs)?;
+
+        let vao = gl
+            .create_vertex_array()
+            .ok_or_else(|| JsValue::from_str("failed to create vao"))?;
+        gl.bind_vertex_array(Some(&vao));
+
+        let corners = HomogeneousGlBuffer::new_bound(
+            gl,
+            &[0.0f32, 0.0, 1.0, 0.0, 0.0, 1.0, 1.0, 1.0],
+            WebGl2RenderingContext::ARRAY_BUFFER,
+            WebGl2RenderingContext::STATIC_DRAW,
+        )?;
+        corners.vertex_attrib_pointer(gl, shader.sal_corner, 2, false, 0, 0);
+
+        let bars = InterleavedGlBuffer::new_bound(
+            gl,
+            &[],
+            WebGl2RenderingContext::ARRAY_BUFFER,
+            WebGl2RenderingContext::DYNAMIC_DRAW,
+        )?;
+        bars.instance_attrib_pointers(gl, &[shader.sal_rect, shader.sal_color]);
+
+        gl.bind_vertex_array(None);
+
+        Ok(Self {
+            shader,
+            corners,
+            bars,
+            vao,
+            budget_ms: 1000.0 / 72.0,
+        })
+    }
+
+    /// # params
+    /// * `model` - places the panel, whose bars extend along +x and stack down -y from the origin
+    pub fn draw(&mut self, gl: &G, profiler: &Profiler<G>, model: &[f32; 16], mode: ViewMode) {
+        let bars = overlay_bars(&profiler.report(), self.budget_ms);
+        self.bars
+            .replace(gl, &bars, WebGl2RenderingContext::DYNAMIC_DRAW);
+        // an overlay, like the text next to it
+        gl.depth_mask(false);
+        self.shader.for_view(mode).draw_instanced(
+            gl,
+            &self.vao,
+            bars.len().try_into().unwrap(),
+            model,
+        );
+        gl.depth_mask(true);
+    }
+
+    /// Make [`ViewMode::Multiview`] available to [`Self::draw`].
+    pub fn enable_multiview(
+        &mut self,
+        gl: &G,
+        programs: &ProgramRegistry<G>,
+    ) -> Result<(), JsValue> {
+        self.shader.enable_multiview(gl, programs)
+    }
+
+    /// Build everything again on a restored context.
+    pub fn recreate(&mut self, gl: &G, programs: &ProgramRegistry<G>) -> Result<(), JsValue> {
+        let budget_ms = self.budget_ms;
+        *self = Self::new(gl, programs)?;
+        self.budget_ms = budget_ms;
+        Ok(())
+    }
+
+    pub fn release(self, gl: &G) {
+        self.corners.release(gl);
+        self.bars.release(gl);
+        self.shader.release(gl);
+        gl.delete_vertex_array(Some(&self.vao));
+    }
+}
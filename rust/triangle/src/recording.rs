@@ -0,0 +1,1180 @@
+use crate::backend::{ActiveInfo, GlBackend};
+use crate::reflection::GlslType;
+use std::cell::{Cell, RefCell};
+use std::collections::HashMap;
+use wasm_bindgen::JsValue;
+use web_sys::{HtmlVideoElement, WebGl2RenderingContext};
+
+/// Every GL object created through [`RecordingGl`] is identified by a small integer.
+/// Ids are unique across all object kinds, starting at 1.
+pub type Handle = u32;
+
+/// One call made through [`RecordingGl`].
+/// Creation calls record the handle they returned.
+#[derive(Debug, Clone, PartialEq)]
+pub enum GlCall {
+    CreateBuffer(Handle),
+    BindBuffer {
+        target: u32,
+        buffer: Option<Handle>,
+    },
+    BufferData {
+        target: u32,
+        data: Vec<u8>,
+        usage: u32,
+    },
+    BufferDataSize {
+        target: u32,
+        size: i32,
+        usage: u32,
+    },
+    BufferSubData {
+        target: u32,
+        offset: i32,
+        data: Vec<u8>,
+    },
+    BindBufferBase {
+        target: u32,
+        index: u32,
+        buffer: Option<Handle>,
+    },
+    DeleteBuffer(Option<Handle>),
+
+    CreateVertexArray(Handle),
+    BindVertexArray(Option<Handle>),
+    DeleteVertexArray(Option<Handle>),
+    VertexAttribPointer {
+        index: u32,
+        size: i32,
+        type_: u32,
+        normalized: bool,
+        stride: i32,
+        offset: i32,
+    },
+    EnableVertexAttribArray(u32),
+    VertexAttribDivisor {
+        index: u32,
+        divisor: u32,
+    },
+
+    CreateShader {
+        type_: u32,
+        shader: Handle,
+    },
+    ShaderSource(Handle),
+    CompileShader(Handle),
+    DeleteShader(Option<Handle>),
+
+    CreateProgram(Handle),
+    AttachShader {
+        program: Handle,
+        shader: Handle,
+    },
+    BindAttribLocation {
+        program: Handle,
+        index: u32,
+        name: String,
+    },
+    LinkProgram(Handle),
+    UseProgram(Option<Handle>),
+    DeleteProgram(Option<Handle>),
+
+    UniformBlockBinding {
+        program: Handle,
+        uniform_block_index: u32,
+        uniform_block_binding: u32,
+    },
+    Uniform1i {
+        location: Option<Handle>,
+        x: i32,
+    },
+    Uniform1f {
+        location: Option<Handle>,
+        x: f32,
+    },
+    Uniform3fv {
+        location: Option<Handle>,
+        data: Vec<f32>,
+    },
+    Uniform4fv {
+        location: Option<Handle>,
+        data: Vec<f32>,
+    },
+    UniformMatrix3fv {
+        location: Option<Handle>,
+        transpose: bool,
+        data: Vec<f32>,
+    },
+    UniformMatrix4fv {
+        location: Option<Handle>,
+        transpose: bool,
+        data: Vec<f32>,
+    },
+
+    CreateTexture(Handle),
+    ActiveTexture(u32),
+    BindTexture {
+        target: u32,
+        texture: Option<Handle>,
+    },
+    TexImage2d {
+        target: u32,
+        level: i32,
+        internalformat: i32,
+        width: i32,
+        height: i32,
+        format: u32,
+        type_: u32,
+        byte_len: usize,
+    },
+    /// the video element itself is not recorded
+    TexImage2dVideo {
+        target: u32,
+        level: i32,
+        internalformat: i32,
+        format: u32,
+        type_: u32,
+    },
+    CompressedTexImage2d {
+        target: u32,
+        level: i32,
+        internalformat: u32,
+        width: i32,
+        height: i32,
+        byte_len: usize,
+    },
+    TexStorage3d {
+        target: u32,
+        levels: i32,
+        internalformat: u32,
+        width: i32,
+        height: i32,
+        depth: i32,
+    },
+    TexParameteri {
+        target: u32,
+        pname: u32,
+        param: i32,
+    },
+    TexParameterf {
+        target: u32,
+        pname: u32,
+        param: f32,
+    },
+    GenerateMipmap(u32),
+    DeleteTexture(Option<Handle>),
+
+    CreateFramebuffer(Handle),
+
+    BindFramebuffer {
+        target: u32,
+        framebuffer: Option<Handle>,
+    },
+    FramebufferTexture2d {
+        target: u32,
+        attachment: u32,
+        textarget: u32,
+        texture: Option<Handle>,
+        level: i32,
+    },
+    FramebufferTextureMultiviewOvr {
+        target: u32,
+        attachment: u32,
+        texture: Option<Handle>,
+        level: i32,
+        base_view_index: i32,
+        num_views: i32,
+    },
+    FramebufferRenderbuffer {
+        target: u32,
+        attachment: u32,
+        renderbuffertarget: u32,
+        renderbuffer: Option<Handle>,
+    },
+    /// `[x0, y0, x1, y1]` rectangles
+    BlitFramebuffer {
+        src: [i32; 4],
+        dst: [i32; 4],
+        mask: u32,
+        filter: u32,
+    },
+    DeleteFramebuffer(Option<Handle>),
+
+    CreateRenderbuffer(Handle),
+    BindRenderbuffer {
+        target: u32,
+        renderbuffer: Option<Handle>,
+    },
+    RenderbufferStorage {
+        target: u32,
+        internalformat: u32,
+        width: i32,
+        height: i32,
+    },
+    RenderbufferStorageMultisample {
+        target: u32,
+        samples: i32,
+        internalformat: u32,
+        width: i32,
+        height: i32,
+    },
+    DeleteRenderbuffer(Option<Handle>),
+
+    CreateQuery(Handle),
+    BeginQuery {
+        target: u32,
+        query: Handle,
+    },
+    EndQuery(u32),
+    DeleteQuery(Option<Handle>),
+
+    ClearColor([f32; 4]),
+    Clear(u32),
+    Viewport {
+        x: i32,
+        y: i32,
+        width: i32,
+        height: i32,
+    },
+
+    Enable(u32),
+    Disable(u32),
+    BlendFunc {
+        sfactor: u32,
+        dfactor: u32,
+    },
+    DepthFunc(u32),
+    DepthMask(bool),
+
+    DrawArrays {
+        mode: u32,
+        first: i32,
+        count: i32,
+    },
+    DrawElements {
+        mode: u32,
+        count: i32,
+        type_: u32,
+        offset: i32,
+    },
+    DrawArraysInstanced {
+        mode: u32,
+        first: i32,
+        count: i32,
+        instance_count: i32,
+    },
+    DrawElementsInstanced {
+        mode: u32,
+        count: i32,
+        type_: u32,
+        offset: i32,
+        instance_count: i32,
+    },
+}
+
+/// A [`GlBackend`] that talks to no GPU at all and instead remembers every call made through it.
+/// Shaders compile unless [`Self::fail_compile`] says otherwise, and programs always link.
+/// Attribute locations are the ones bound before linking, or else handed out per program in the order they are first asked for.
+/// A linked program reports the `in` variables of its vertex shader and the uniforms outside blocks
+/// as active, as they are declared in the source; nothing is optimized away.
+/// Framebuffers are always complete.
+/// The draw framebuffer binding and the viewport are remembered so they can be queried back.
+/// No extensions are supported unless they are added with [`Self::with_extensions`].
+/// Queries are always available, with the result set by [`Self::set_query_result`].
+#[derive(Default)]
+pub struct RecordingGl {
+    calls: RefCell<Vec<GlCall>>,
+    next_handle: Cell<Handle>,
+    attrib_locations: RefCell<HashMap<(Handle, String), i32>>,
+    uniform_names: RefCell<HashMap<Handle, String>>,
+    /// each shader's type and source
+    shaders: RefCell<HashMap<Handle, (u32, String)>>,
+    attached_shaders: RefCell<HashMap<Handle, Vec<Handle>>>,
+    /// each linked program's active attributes and uniforms
+    active_variables: RefCell<HashMap<Handle, (Vec<ActiveInfo>, Vec<ActiveInfo>)>>,
+    draw_framebuffer: Cell<Option<Handle>>,
+    active_texture: Cell<u32>,
+    /// the `TEXTURE_2D` bound to each texture unit
+    textures_2d: RefCell<HashMap<u32, Handle>>,
+    renderbuffer: Cell<Option<Handle>>,
+    viewport: Cell<[i32; 4]>,
+    extensions: Vec<String>,
+    query_result: Cell<u64>,
+    /// source text that makes a shader fail to compile, and the info log it gets
+    compile_failures: RefCell<Vec<(String, String)>>,
+    /// how many more `create_*` calls succeed, if limited
+    creations_left: Cell<Option<usize>>,
+}
+
+impl RecordingGl {
+    #[must_use]
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// pretend to support the `names` extensions
+    #[must_use]
+    pub fn with_extensions(mut self, names: &[&str]) -> Self {
+        self.extensions
+            .extend(names.iter().map(|name| (*name).to_string()));
+        self
+    }
+
+    /// what every query will report from now on
+    pub fn set_query_result(&self, result: u64) {
+        self.query_result.set(result);
+    }
+
+    /// From now on, shaders whose source contains `needle` fail to compile with `log` as their info log.
+    pub fn fail_compile(&self, needle: &str, log: &str) {
+        self.compile_failures
+            .borrow_mut()
+            .push((needle.to_string(), log.to_string()));
+    }
+
+    /// From now on, only `count` more GL objects can be created;
+    /// every `create_*` after that returns `None`, as it would with a lost context.
+    pub fn fail_creations_after(&self, count: usize) {
+        self.creations_left.set(Some(count));
+    }
+
+    fn compile_failure(&self, shader: &Handle) -> Option<String> {
+        let shaders = self.shaders.borrow();
+        let (_, source) = shaders.get(shader)?;
+        self.compile_failures
+            .borrow()
+            .iter()
+            .find(|(needle, _)| source.contains(needle.as_str()))
+            .map(|(_, log)| log.clone())
+    }
+
+    /// a copy of the call log so far
+    pub fn calls(&self) -> Vec<GlCall> {
+        self.calls.borrow().clone()
+    }
+
+    /// return the call log so far and start a fresh one
+    pub fn take_calls(&self) -> Vec<GlCall> {
+        self.calls.take()
+    }
+
+    /// The name a uniform location was looked up by, for making sense of [`GlCall::Uniform1i`] and friends.
+    pub fn uniform_name(&self, location: Handle) -> Option<String> {
+        self.uniform_names.borrow().get(&location).cloned()
+    }
+
+    fn record(&self, call: GlCall) {
+        self.calls.borrow_mut().push(call);
+    }
+
+    fn new_handle(&self) -> Handle {
+        let handle = self.next_handle.get() + 1;
+        self.next_handle.set(handle);
+        handle
+    }
+
+    /// a handle for a `create_*` call, unless [`Self::fail_creations_after`] ran out
+    fn new_object(&self) -> Option<Handle> {
+        match self.creations_left.get() {
+            Some(0) => return None,
+            Some(left) => self.creations_left.set(Some(left - 1)),
+            None => (),
+        }
+        Some(self.new_handle())
+    }
+}
+
+impl GlBackend for RecordingGl {
+    type Buffer = Handle;
+    type Texture = Handle;
+    type VertexArray = Handle;
+    type Shader = Handle;
+    type Program = Handle;
+    type UniformLocation = Handle;
+    type Framebuffer = Handle;
+    type Renderbuffer = Handle;
+    type Query = Handle;
+
+    fn create_buffer(&self) -> Option<Handle> {
+        let handle = self.new_object()?;
+        self.record(GlCall::CreateBuffer(handle));
+        Some(handle)
+    }
+
+    fn bind_buffer(&self, target: u32, buffer: Option<&Handle>) {
+        self.record(GlCall::BindBuffer {
+            target,
+            buffer: buffer.copied(),
+        });
+    }
+
+    fn buffer_data_with_u8_array(&self, target: u32, src_data: &[u8], usage: u32) {
+        self.record(GlCall::BufferData {
+            target,
+            data: src_data.to_vec(),
+            usage,
+        });
+    }
+
+    fn buffer_data_with_i32(&self, target: u32, size: i32, usage: u32) {
+        self.record(GlCall::BufferDataSize {
+            target,
+            size,
+            usage,
+        });
+    }
+
+    fn buffer_sub_data_with_i32_and_u8_array(&self, target: u32, offset: i32, src_data: &[u8]) {
+        self.record(GlCall::BufferSubData {
+            target,
+            offset,
+            data: src_data.to_vec(),
+        });
+    }
+
+    fn bind_buffer_base(&self, target: u32, index: u32, buffer: Option<&Handle>) {
+        self.record(GlCall::BindBufferBase {
+            target,
+            index,
+            buffer: buffer.copied(),
+        });
+    }
+
+    fn delete_buffer(&self, buffer: Option<&Handle>) {
+        self.record(GlCall::DeleteBuffer(buffer.copied()));
+    }
+
+    fn create_vertex_array(&self) -> Option<Handle> {
+        let handle = self.new_object()?;
+        self.record(GlCall::CreateVertexArray(handle));
+        Some(handle)
+    }
+
+    fn bind_vertex_array(&self, array: Option<&Handle>) {
+        self.record(GlCall::BindVertexArray(array.copied()));
+    }
+
+    fn delete_vertex_array(&self, array: Option<&Handle>) {
+        self.record(GlCall::DeleteVertexArray(array.copied()));
+    }
+
+    fn vertex_attrib_pointer_with_i32(
+        &self,
+        index: u32,
+        size: i32,
+        type_: u32,
+        normalized: bool,
+        stride: i32,
+        offset: i32,
+    ) {
+        self.record(GlCall::VertexAttribPointer {
+            index,
+            size,
+            type_,
+            normalized,
+            stride,
+            offset,
+        });
+    }
+
+    fn enable_vertex_attrib_array(&self, index: u32) {
+        self.record(GlCall::EnableVertexAttribArray(index));
+    }
+
+    fn vertex_attrib_divisor(&self, index: u32, divisor: u32) {
+        self.record(GlCall::VertexAttribDivisor { index, divisor });
+    }
+
+    fn create_shader(&self, type_: u32) -> Option<Handle> {
+        let shader = self.new_object()?;
+        self.shaders
+            .borrow_mut()
+            .insert(shader, (type_, String::new()));
+        self.record(GlCall::CreateShader { type_, shader });
+        Some(shader)
+    }
+
+    fn shader_source(&self, shader: &Handle, source: &str) {
+        if let Some((_, old)) = self.shaders.borrow_mut().get_mut(shader) {
+            *old = source.to_string();
+        }
+        self.record(GlCall::ShaderSource(*shader));
+    }
+
+    fn compile_shader(&self, shader: &Handle) {
+        self.record(GlCall::CompileShader(*shader));
+    }
+
+    fn get_shader_compile_status(&self, shader: &Handle) -> bool {
+        self.compile_failure(shader).is_none()
+    }
+
+    fn get_shader_info_log(&self, shader: &Handle) -> Option<String> {
+        Some(self.compile_failure(shader).unwrap_or_default())
+    }
+
+    fn delete_shader(&self, shader: Option<&Handle>) {
+        self.record(GlCall::DeleteShader(shader.copied()));
+    }
+
+    fn create_program(&self) -> Option<Handle> {
+        let handle = self.new_object()?;
+        self.record(GlCall::CreateProgram(handle));
+        Some(handle)
+    }
+
+    fn attach_shader(&self, program: &Handle, shader: &Handle) {
+        self.attached_shaders
+            .borrow_mut()
+            .entry(*program)
+            .or_default()
+            .push(*shader);
+        self.record(GlCall::AttachShader {
+            program: *program,
+            shader: *shader,
+        });
+    }
+
+    fn link_program(&self, program: &Handle) {
+        let mut attributes = Vec::new();
+        let mut uniforms: Vec<ActiveInfo> = Vec::new();
+        let shaders = self.shaders.borrow();
+        for shader in self
+            .attached_shaders
+            .borrow()
+            .get(program)
+            .into_iter()
+            .flatten()
+        {
+            let Some((type_, source)) = shaders.get(shader) else {
+                continue;
+            };
+            let (ins, shader_uniforms) = declared_variables(source);
+            if *type_ == WebGl2RenderingContext::VERTEX_SHADER {
+                attributes = ins;
+            }
+            for uniform in shader_uniforms {
+                if !uniforms.iter().any(|u| u.name == uniform.name) {
+                    uniforms.push(uniform);
+                }
+            }
+        }
+        self.active_variables
+            .borrow_mut()
+            .insert(*program, (attributes, uniforms));
+        self.record(GlCall::LinkProgram(*program));
+    }
+
+    fn get_program_link_status(&self, _program: &Handle) -> bool {
+        true
+    }
+
+    fn get_program_info_log(&self, _program: &Handle) -> Option<String> {
+        Some(String::new())
+    }
+
+    fn use_program(&self, program: Option<&Handle>) {
+        self.record(GlCall::UseProgram(program.copied()));
+    }
+
+    fn delete_program(&self, program: Option<&Handle>) {
+        self.record(GlCall::DeleteProgram(program.copied()));
+    }
+
+    fn bind_attrib_location(&self, program: &Handle, index: u32, name: &str) {
+        self.attrib_locations
+            .borrow_mut()
+            .insert((*program, name.to_string()), index as i32);
+        self.record(GlCall::BindAttribLocation {
+            program: *program,
+            index,
+            name: name.to_string(),
+        });
+    }
+
+    fn get_attrib_location(&self, program: &Handle, name: &str) -> i32 {
+        let mut locations = self.attrib_locations.borrow_mut();
+        let next = locations.keys().filter(|(p, _)| p == program).count();
+        *locations
+            .entry((*program, name.to_string()))
+            .or_insert_with(|| next.try_into().unwrap())
+    }
+
+    fn get_active_attrib_count(&self, program: &Handle) -> u32 {
+        self.active_variables
+            .borrow()
+            .get(program)
+            .map_or(0, |(attributes, _)| attributes.len() as u32)
+    }
+
+    fn get_active_attrib(&self, program: &Handle, index: u32) -> Option<ActiveInfo> {
+        let active = self.active_variables.borrow();
+        active.get(program)?.0.get(index as usize).cloned()
+    }
+
+    fn get_active_uniform_count(&self, program: &Handle) -> u32 {
+        self.active_variables
+            .borrow()
+            .get(program)
+            .map_or(0, |(_, uniforms)| uniforms.len() as u32)
+    }
+
+    fn get_active_uniform(&self, program: &Handle, index: u32) -> Option<ActiveInfo> {
+        let active = self.active_variables.borrow();
+        active.get(program)?.1.get(index as usize).cloned()
+    }
+
+    fn get_uniform_location(&self, _program: &Handle, name: &str) -> Option<Handle> {
+        let handle = self.new_handle();
+        self.uniform_names
+            .borrow_mut()
+            .insert(handle, name.to_string());
+        Some(handle)
+    }
+
+    fn get_uniform_block_index(&self, _program: &Handle, _uniform_block_name: &str) -> u32 {
+        0
+    }
+
+    fn uniform_block_binding(
+        &self,
+        program: &Handle,
+        uniform_block_index: u32,
+        uniform_block_binding: u32,
+    ) {
+        self.record(GlCall::UniformBlockBinding {
+            program: *program,
+            uniform_block_index,
+            uniform_block_binding,
+        });
+    }
+
+    fn uniform1i(&self, location: Option<&Handle>, x: i32) {
+        self.record(GlCall::Uniform1i {
+            location: location.copied(),
+            x,
+        });
+    }
+
+    fn uniform1f(&self, location: Option<&Handle>, x: f32) {
+        self.record(GlCall::Uniform1f {
+            location: location.copied(),
+            x,
+        });
+    }
+
+    fn uniform3fv_with_f32_array(&self, location: Option<&Handle>, data: &[f32]) {
+        self.record(GlCall::Uniform3fv {
+            location: location.copied(),
+            data: data.to_vec(),
+        });
+    }
+
+    fn uniform4fv_with_f32_array(&self, location: Option<&Handle>, data: &[f32]) {
+        self.record(GlCall::Uniform4fv {
+            location: location.copied(),
+            data: data.to_vec(),
+        });
+    }
+
+    fn uniform_matrix3fv_with_f32_array(
+        &self,
+        location: Option<&Handle>,
+        transpose: bool,
+        data: &[f32],
+    ) {
+        self.record(GlCall::UniformMatrix3fv {
+            location: location.copied(),
+            transpose,
+            data: data.to_vec(),
+        });
+    }
+
+    fn uniform_matrix4fv_with_f32_array(
+        &self,
+        location: Option<&Handle>,
+        transpose: bool,
+        data: &[f32],
+    ) {
+        self.record(GlCall::UniformMatrix4fv {
+            location: location.copied(),
+            transpose,
+            data: data.to_vec(),
+        });
+    }
+
+    fn create_texture(&self) -> Option<Handle> {
+        let handle = self.new_object()?;
+        self.record(GlCall::CreateTexture(handle));
+        Some(handle)
+    }
+
+    fn active_texture(&self, texture: u32) {
+        self.active_texture.set(texture);
+        self.record(GlCall::ActiveTexture(texture));
+    }
+
+    fn bind_texture(&self, target: u32, texture: Option<&Handle>) {
+        if target == WebGl2RenderingContext::TEXTURE_2D {
+            let mut textures = self.textures_2d.borrow_mut();
+            match texture {
+                Some(texture) => textures.insert(self.active_texture.get(), *texture),
+                None => textures.remove(&self.active_texture.get()),
+            };
+        }
+        self.record(GlCall::BindTexture {
+            target,
+            texture: texture.copied(),
+        });
+    }
+
+    fn get_texture_binding_2d(&self) -> Option<Handle> {
+        self.textures_2d
+            .borrow()
+            .get(&self.active_texture.get())
+            .copied()
+    }
+
+    fn tex_image_2d_with_u8_array(
+        &self,
+        target: u32,
+        level: i32,
+        internalformat: i32,
+        width: i32,
+        height: i32,
+        _border: i32,
+        format: u32,
+        type_: u32,
+        pixels: Option<&[u8]>,
+    ) -> Result<(), JsValue> {
+        self.record(GlCall::TexImage2d {
+            target,
+            level,
+            internalformat,
+            width,
+            height,
+            format,
+            type_,
+            byte_len: pixels.map_or(0, <[u8]>::len),
+        });
+        Ok(())
+    }
+
+    fn compressed_tex_image_2d_with_u8_array(
+        &self,
+        target: u32,
+        level: i32,
+        internalformat: u32,
+        width: i32,
+        height: i32,
+        _border: i32,
+        data: &[u8],
+    ) {
+        self.record(GlCall::CompressedTexImage2d {
+            target,
+            level,
+            internalformat,
+            width,
+            height,
+            byte_len: data.len(),
+        });
+    }
+
+    fn tex_image_2d_with_u32_and_u32_and_html_video_element(
+        &self,
+        target: u32,
+        level: i32,
+        internalformat: i32,
+        format: u32,
+        type_: u32,
+        _source: &HtmlVideoElement,
+    ) -> Result<(), JsValue> {
+        self.record(GlCall::TexImage2dVideo {
+            target,
+            level,
+            internalformat,
+            format,
+            type_,
+        });
+        Ok(())
+    }
+
+    fn tex_storage_3d(
+        &self,
+        target: u32,
+        levels: i32,
+        internalformat: u32,
+        width: i32,
+        height: i32,
+        depth: i32,
+    ) {
+        self.record(GlCall::TexStorage3d {
+            target,
+            levels,
+            internalformat,
+            width,
+            height,
+            depth,
+        });
+    }
+
+    fn tex_parameteri(&self, target: u32, pname: u32, param: i32) {
+        self.record(GlCall::TexParameteri {
+            target,
+            pname,
+            param,
+        });
+    }
+
+    fn tex_parameterf(&self, target: u32, pname: u32, param: f32) {
+        self.record(GlCall::TexParameterf {
+            target,
+            pname,
+            param,
+        });
+    }
+
+    fn generate_mipmap(&self, target: u32) {
+        self.record(GlCall::GenerateMipmap(target));
+    }
+
+    fn delete_texture(&self, texture: Option<&Handle>) {
+        self.record(GlCall::DeleteTexture(texture.copied()));
+    }
+
+    fn clear_color(&self, red: f32, green: f32, blue: f32, alpha: f32) {
+        self.record(GlCall::ClearColor([red, green, blue, alpha]));
+    }
+
+    fn create_framebuffer(&self) -> Option<Handle> {
+        let handle = self.new_object()?;
+        self.record(GlCall::CreateFramebuffer(handle));
+        Some(handle)
+    }
+
+    fn bind_framebuffer(&self, target: u32, framebuffer: Option<&Handle>) {
+        if target != WebGl2RenderingContext::READ_FRAMEBUFFER {
+            self.draw_framebuffer.set(framebuffer.copied());
+        }
+        self.record(GlCall::BindFramebuffer {
+            target,
+            framebuffer: framebuffer.copied(),
+        });
+    }
+
+    fn get_draw_framebuffer_binding(&self) -> Option<Handle> {
+        self.draw_framebuffer.get()
+    }
+
+    fn framebuffer_texture_2d(
+        &self,
+        target: u32,
+        attachment: u32,
+        textarget: u32,
+        texture: Option<&Handle>,
+        level: i32,
+    ) {
+        self.record(GlCall::FramebufferTexture2d {
+            target,
+            attachment,
+            textarget,
+            texture: texture.copied(),
+            level,
+        });
+    }
+
+    fn framebuffer_renderbuffer(
+        &self,
+        target: u32,
+        attachment: u32,
+        renderbuffertarget: u32,
+        renderbuffer: Option<&Handle>,
+    ) {
+        self.record(GlCall::FramebufferRenderbuffer {
+            target,
+            attachment,
+            renderbuffertarget,
+            renderbuffer: renderbuffer.copied(),
+        });
+    }
+
+    fn get_renderbuffer_binding(&self) -> Option<Handle> {
+        self.renderbuffer.get()
+    }
+
+    fn framebuffer_texture_multiview_ovr(
+        &self,
+        target: u32,
+        attachment: u32,
+        texture: Option<&Handle>,
+        level: i32,
+        base_view_index: i32,
+        num_views: i32,
+    ) {
+        self.record(GlCall::FramebufferTextureMultiviewOvr {
+            target,
+            attachment,
+            texture: texture.copied(),
+            level,
+            base_view_index,
+            num_views,
+        });
+    }
+
+    fn check_framebuffer_status(&self, _target: u32) -> u32 {
+        WebGl2RenderingContext::FRAMEBUFFER_COMPLETE
+    }
+
+    fn blit_framebuffer(
+        &self,
+        src_x0: i32,
+        src_y0: i32,
+        src_x1: i32,
+        src_y1: i32,
+        dst_x0: i32,
+        dst_y0: i32,
+        dst_x1: i32,
+        dst_y1: i32,
+        mask: u32,
+        filter: u32,
+    ) {
+        self.record(GlCall::BlitFramebuffer {
+            src: [src_x0, src_y0, src_x1, src_y1],
+            dst: [dst_x0, dst_y0, dst_x1, dst_y1],
+            mask,
+            filter,
+        });
+    }
+
+    fn delete_framebuffer(&self, framebuffer: Option<&Handle>) {
+        if framebuffer.is_some() && self.draw_framebuffer.get() == framebuffer.copied() {
+            self.draw_framebuffer.set(None);
+        }
+        self.record(GlCall::DeleteFramebuffer(framebuffer.copied()));
+    }
+
+    fn create_renderbuffer(&self) -> Option<Handle> {
+        let handle = self.new_object()?;
+        self.record(GlCall::CreateRenderbuffer(handle));
+        Some(handle)
+    }
+
+    fn bind_renderbuffer(&self, target: u32, renderbuffer: Option<&Handle>) {
+        self.renderbuffer.set(renderbuffer.copied());
+        self.record(GlCall::BindRenderbuffer {
+            target,
+            renderbuffer: renderbuffer.copied(),
+        });
+    }
+
+    fn renderbuffer_storage(&self, target: u32, internalformat: u32, width: i32, height: i32) {
+        self.record(GlCall::RenderbufferStorage {
+            target,
+            internalformat,
+            width,
+            height,
+        });
+    }
+
+    fn renderbuffer_storage_multisample(
+        &self,
+        target: u32,
+        samples: i32,
+        internalformat: u32,
+        width: i32,
+        height: i32,
+    ) {
+        self.record(GlCall::RenderbufferStorageMultisample {
+            target,
+            samples,
+            internalformat,
+            width,
+            height,
+        });
+    }
+
+    fn delete_renderbuffer(&self, renderbuffer: Option<&Handle>) {
+        self.record(GlCall::DeleteRenderbuffer(renderbuffer.copied()));
+    }
+
+    fn clear(&self, mask: u32) {
+        self.record(GlCall::Clear(mask));
+    }
+
+    fn viewport(&self, x: i32, y: i32, width: i32, height: i32) {
+        self.viewport.set([x, y, width, height]);
+        self.record(GlCall::Viewport {
+            x,
+            y,
+            width,
+            height,
+        });
+    }
+
+    fn get_viewport(&self) -> [i32; 4] {
+        self.viewport.get()
+    }
+
+    fn enable(&self, cap: u32) {
+        self.record(GlCall::Enable(cap));
+    }
+
+    fn disable(&self, cap: u32) {
+        self.record(GlCall::Disable(cap));
+    }
+
+    fn blend_func(&self, sfactor: u32, dfactor: u32) {
+        self.record(GlCall::BlendFunc { sfactor, dfactor });
+    }
+
+    fn depth_func(&self, func: u32) {
+        self.record(GlCall::DepthFunc(func));
+    }
+
+    fn depth_mask(&self, flag: bool) {
+        self.record(GlCall::DepthMask(flag));
+    }
+
+    fn has_extension(&self, name: &str) -> bool {
+        self.extensions.iter().any(|extension| extension == name)
+    }
+
+    fn create_query(&self) -> Option<Handle> {
+        let handle = self.new_object()?;
+        self.record(GlCall::CreateQuery(handle));
+        Some(handle)
+    }
+
+    fn begin_query(&self, target: u32, query: &Handle) {
+        self.record(GlCall::BeginQuery {
+            target,
+            query: *query,
+        });
+    }
+
+    fn end_query(&self, target: u32) {
+        self.record(GlCall::EndQuery(target));
+    }
+
+    fn get_query_result_available(&self, _query: &Handle) -> bool {
+        true
+    }
+
+    fn get_query_result(&self, _query: &Handle) -> u64 {
+        self.query_result.get()
+    }
+
+    fn delete_query(&self, query: Option<&Handle>) {
+        self.record(GlCall::DeleteQuery(query.copied()));
+    }
+
+    fn get_gpu_disjoint(&self) -> bool {
+        false
+    }
+
+    fn draw_arrays(&self, mode: u32, first: i32, count: i32) {
+        self.record(GlCall::DrawArrays { mode, first, count });
+    }
+
+    fn draw_elements_with_i32(&self, mode: u32, count: i32, type_: u32, offset: i32) {
+        self.record(GlCall::DrawElements {
+            mode,
+            count,
+            type_,
+            offset,
+        });
+    }
+
+    fn draw_arrays_instanced(&self, mode: u32, first: i32, count: i32, instance_count: i32) {
+        self.record(GlCall::DrawArraysInstanced {
+            mode,
+            first,
+            count,
+            instance_count,
+        });
+    }
+
+    fn draw_elements_instanced_with_i32(
+        &self,
+        mode: u32,
+        count: i32,
+        type_: u32,
+        offset: i32,
+        instance_count: i32,
+    ) {
+        self.record(GlCall::DrawElementsInstanced {
+            mode,
+            count,
+            type_,
+            offset,
+            instance_count,
+        });
+    }
+}
+
+/// The `in` and `uniform` declarations of GLSL `source` outside any block, in order, the way
+/// `getActiveAttrib` and `getActiveUniform` describe them. Good enough for the shaders in this crate;
+/// it is no GLSL parser.
+fn declared_variables(source: &str) -> (Vec<ActiveInfo>, Vec<ActiveInfo>) {
+    const QUALIFIERS: [&str; 7] = [
+        "highp",
+        "mediump",
+        "lowp",
+        "flat",
+        "smooth",
+        "centroid",
+        "invariant",
+    ];
+    let mut ins = Vec::new();
+    let mut uniforms = Vec::new();
+    let mut depth = 0;
+    let mut statement = String::new();
+    let code = source
+        .lines()
+        .filter(|line| !line.trim_start().starts_with('#'))
+        .map(|line| line.split("//").next().unwrap_or(""));
+    for c in code.flat_map(|line| line.chars().chain(Some('\n'))) {
+        match c {
+            '{' => {
+                depth += 1;
+                statement.clear();
+            }
+            '}' => {
+                depth -= 1;
+                statement.clear();
+            }
+            ';' if depth == 0 => {
+                // drop any layout(...) qualifier
+                let declaration = match statement.rfind(')') {
+                    Some(end) => &statement[end + 1..],
+                    None => &statement[..],
+                };
+                let mut words = declaration
+                    .split_whitespace()
+                    .filter(|word| !QUALIFIERS.contains(word));
+                let list = match (words.next(), words.next()) {
+                    (Some("in"), Some(type_)) => Some((&mut ins, type_)),
+                    (Some("uniform"), Some(type_)) => Some((&mut uniforms, type_)),
+                    _ => None,
+                };
+                if let Some((list, type_)) = list {
+                    let type_ = GlslType::from_glsl(type_).map_or(0, GlslType::to_gl);
+                    let names: String = words.collect();
+                    for name in names.split(',') {
+                        let (name, size) = match name.split_once('[') {
+                            Some((name, size)) => (
+                                format!("{name}[0]"),
+                                size.trim_end_matches(']').parse().unwrap_or(1),
+                            ),
+                            None => (name.to_string(), 1),
+                        };
+                        list.push(ActiveInfo { name, size, type_ });
+                    }
+                }
+                statement.clear();
+            }
+            _ => statement.push(c),
+        }
+    }
+    (ins, uniforms)
+}
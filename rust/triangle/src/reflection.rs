@@ -0,0 +1,516 @@
+use crate::backend::GlBackend;
+use crate::shaders::preprocess::ShaderDefines;
+use crate::shaders::{simple_shader_program, variant_program};
+use glam::{Mat3, Mat4, Vec3, Vec4};
+use std::fmt::{Display, Formatter};
+use std::marker::PhantomData;
+use wasm_bindgen::JsValue;
+use web_sys::WebGl2RenderingContext;
+
+/// The types an attribute or uniform can have, as `getActiveAttrib` and `getActiveUniform` report them.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum GlslType {
+    Float,
+    Vec2,
+    Vec3,
+    Vec4,
+    Int,
+    IVec2,
+    IVec3,
+    IVec4,
+    UInt,
+    UVec2,
+    UVec3,
+    UVec4,
+    Bool,
+    Mat2,
+    Mat3,
+    Mat4,
+    Sampler2D,
+    Sampler3D,
+    SamplerCube,
+    Sampler2DArray,
+    Sampler2DShadow,
+    /// any other GL type enum
+    Other(u32),
+}
+
+/// each [`GlslType`] (but [`GlslType::Other`]) with its GL enum and GLSL spelling
+const GLSL_TYPES: [(GlslType, u32, &str); 21] = [
+    (GlslType::Float, WebGl2RenderingContext::FLOAT, "float"),
+    (GlslType::Vec2, WebGl2RenderingContext::FLOAT_VEC2, "vec2"),
+    (GlslType::Vec3, WebGl2RenderingContext::FLOAT_VEC3, "vec3"),
+    (GlslType::Vec4, WebGl2RenderingContext::FLOAT_VEC4, "vec4"),
+    (GlslType::Int, WebGl2RenderingContext::INT, "int"),
+    (GlslType::IVec2, WebGl2RenderingContext::INT_VEC2, "ivec2"),
+    (GlslType::IVec3, WebGl2RenderingContext::INT_VEC3, "ivec3"),
+    (GlslType::IVec4, WebGl2RenderingContext::INT_VEC4, "ivec4"),
+    (GlslType::UInt, WebGl2RenderingContext::UNSIGNED_INT, "uint"),
+    (
+        GlslType::UVec2,
+        WebGl2RenderingContext::UNSIGNED_INT_VEC2,
+        "uvec2",
+    ),
+    (
+        GlslType::UVec3,
+        WebGl2RenderingContext::UNSIGNED_INT_VEC3,
+        "uvec3",
+    ),
+    (
+        GlslType::UVec4,
+        WebGl2RenderingContext::UNSIGNED_INT_VEC4,
+        "uvec4",
+    ),
+    (GlslType::Bool, WebGl2RenderingContext::BOOL, "bool"),
+    (GlslType::Mat2, WebGl2RenderingContext::FLOAT_MAT2, "mat2"),
+    (GlslType::Mat3, WebGl2RenderingContext::FLOAT_MAT3, "mat3"),
+    (GlslType::Mat4, WebGl2RenderingContext::FLOAT_MAT4, "mat4"),
+    (
+        GlslType::Sampler2D,
+        WebGl2RenderingContext::SAMPLER_2D,
+        "sampler2D",
+    ),
+    (
+        GlslType::Sampler3D,
+        WebGl2RenderingContext::SAMPLER_3D,
+        "sampler3D",
+    ),
+    (
+        GlslType::SamplerCube,
+        WebGl2RenderingContext::SAMPLER_CUBE,
+        "samplerCube",
+    ),
+    (
+        GlslType::Sampler2DArray,
+        WebGl2RenderingContext::SAMPLER_2D_ARRAY,
+        "sampler2DArray",
+    ),
+    (
+        GlslType::Sampler2DShadow,
+        WebGl2RenderingContext::SAMPLER_2D_SHADOW,
+        "sampler2DShadow",
+    ),
+];
+
+impl GlslType {
+    pub fn from_gl(type_: u32) -> Self {
+        GLSL_TYPES
+            .iter()
+            .find(|(_, gl, _)| *gl == type_)
+            .map_or(GlslType::Other(type_), |(t, _, _)| *t)
+    }
+
+    pub fn to_gl(self) -> u32 {
+        match self {
+            GlslType::Other(type_) => type_,
+            _ => GLSL_TYPES.iter().find(|(t, _, _)| *t == self).unwrap().1,
+        }
+    }
+
+    /// `None` for names that are not one of the listed types
+    pub fn from_glsl(name: &str) -> Option<Self> {
+        GLSL_TYPES
+            .iter()
+            .find(|(_, _, glsl)| *glsl == name)
+            .map(|(t, _, _)| *t)
+    }
+
+    /// how many attribute locations an attribute of this type takes: one per matrix column
+    pub fn locations(self) -> u32 {
+        match self {
+            GlslType::Mat2 => 2,
+            GlslType::Mat3 => 3,
+            GlslType::Mat4 => 4,
+            _ => 1,
+        }
+    }
+
+    pub fn is_sampler(self) -> bool {
+        matches!(
+            self,
+            GlslType::Sampler2D
+                | GlslType::Sampler3D
+                | GlslType::SamplerCube
+                | GlslType::Sampler2DArray
+                | GlslType::Sampler2DShadow
+        )
+    }
+}
+
+impl Display for GlslType {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match GLSL_TYPES.iter().find(|(t, _, _)| t == self) {
+            Some((_, _, glsl)) => f.write_str(glsl),
+            None => write!(f, "type {:#06x}", self.to_gl()),
+        }
+    }
+}
+
+/// What the Rust side of a shader feeds its program: every attribute, and every uniform outside a uniform block
+/// (blocks are bound separately, see [`bind_camera_block`](crate::camera::bind_camera_block)).
+#[derive(Debug, Clone, Copy)]
+pub struct ShaderInterface {
+    pub attributes: &'static [(&'static str, GlslType)],
+    pub uniforms: &'static [(&'static str, GlslType)],
+}
+
+/// One way a linked program differs from its [`ShaderInterface`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum InterfaceMismatch {
+    /// Declared but not active: misspelled, or optimized away because the shader never uses it.
+    MissingAttribute {
+        name: String,
+        expected: GlslType,
+    },
+    MissingUniform {
+        name: String,
+        expected: GlslType,
+    },
+    AttributeType {
+        name: String,
+        expected: GlslType,
+        found: GlslType,
+    },
+    UniformType {
+        name: String,
+        expected: GlslType,
+        found: GlslType,
+    },
+    /// Active but not declared, so nothing would ever feed it.
+    UndeclaredAttribute {
+        name: String,
+        found: GlslType,
+    },
+    UndeclaredUniform {
+        name: String,
+        found: GlslType,
+    },
+}
+
+impl Display for InterfaceMismatch {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            InterfaceMismatch::MissingAttribute { name, expected } => write!(
+                f,
+                "attribute {expected} {name} is not active (misspelled, or unused and optimized away)"
+            ),
+            InterfaceMismatch::MissingUniform { name, expected } => write!(
+                f,
+                "uniform {expected} {name} is not active (misspelled, or unused and optimized away)"
+            ),
+            InterfaceMismatch::AttributeType {
+                name,
+                expected,
+                found,
+            } => write!(f, "attribute {name} is {found}, expected {expected}"),
+            InterfaceMismatch::UniformType {
+                name,
+                expected,
+                found,
+            } => write!(f, "uniform {name} is {found}, expected {expected}"),
+            InterfaceMismatch::UndeclaredAttribute { name, found } => {
+                write!(f, "attribute {found} {name} is not declared on the Rust side")
+            }
+            InterfaceMismatch::UndeclaredUniform { name, found } => {
+                write!(f, "uniform {found} {name} is not declared on the Rust side")
+            }
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ActiveAttribute {
+    pub name: String,
+    pub type_: GlslType,
+    /// the array length, 1 for anything else
+    pub size: i32,
+    pub location: u32,
+}
+
+#[derive(Debug, Clone)]
+pub struct ActiveUniform<G: GlBackend = WebGl2RenderingContext> {
+    /// arrays without the `[0]` suffix GL reports them with
+    pub name: String,
+    pub type_: GlslType,
+    pub size: i32,
+    pub location: G::UniformLocation,
+}
+
+/// A linked program and the attributes and uniforms GL reports as active in it.
+pub struct ReflectedProgram<G: GlBackend = WebGl2RenderingContext> {
+    pub program: G::Program,
+    /// without the `gl_` built-ins
+    pub attributes: Vec<ActiveAttribute>,
+    /// without the members of uniform blocks, which have no location
+    pub uniforms: Vec<ActiveUniform<G>>,
+}
+
+impl<G: GlBackend> ReflectedProgram<G> {
+    /// Compile and link, then check the program against `interface`.
+    /// If it does not match, the program is deleted and the error lists every mismatch.
+    #[track_caller]
+    pub fn link(
+        gl: &G,
+        vertex_shader_source: &str,
+        fragment_shader_source: &str,
+        interface: &ShaderInterface,
+    ) -> Result<Self, JsValue> {
+        let program = simple_shader_program(gl, vertex_shader_source, fragment_shader_source)?;
+        Self::checked(gl, program, interface)
+    }
+
+    /// [`link`](Self::link) a variant of embedded sources, see [`variant_program`].
+    /// The attributes get locations in the order `interface` lists them,
+    /// so every variant of the same sources can share one VAO.
+    #[track_caller]
+    pub fn link_variant(
+        gl: &G,
+        vertex_shader: &str,
+        fragment_shader: &str,
+        defines: &ShaderDefines,
+        interface: &ShaderInterface,
+    ) -> Result<Self, JsValue> {
+        let program = variant_program(
+            gl,
+            vertex_shader,
+            fragment_shader,
+            defines,
+            interface.attributes,
+        )?;
+        Self::checked(gl, program, interface)
+    }
+
+    fn checked(gl: &G, program: G::Program, interface: &ShaderInterface) -> Result<Self, JsValue> {
+        let reflected = Self::reflect(gl, program);
+        let mismatches = reflected.mismatches(interface);
+        if mismatches.is_empty() {
+            return Ok(reflected);
+        }
+        gl.delete_program(Some(&reflected.program));
+        let mismatches: Vec<_> = mismatches.iter().map(ToString::to_string).collect();
+        Err(format!("shader interface mismatch: {}", mismatches.join("; ")).into())
+    }
+
+    /// Enumerate the active attributes and uniforms of a linked `program`.
+    pub fn reflect(gl: &G, program: G::Program) -> Self {
+        let attributes = (0..gl.get_active_attrib_count(&program))
+            .filter_map(|index| gl.get_active_attrib(&program, index))
+            .filter(|info| !info.name.starts_with("gl_"))
+            .filter_map(|info| {
+                Some(ActiveAttribute {
+                    location: gl
+                        .get_attrib_location(&program, &info.name)
+                        .try_into()
+                        .ok()?,
+                    type_: GlslType::from_gl(info.type_),
+                    size: info.size,
+                    name: info.name,
+                })
+            })
+            .collect();
+        let uniforms = (0..gl.get_active_uniform_count(&program))
+            .filter_map(|index| gl.get_active_uniform(&program, index))
+            .filter_map(|info| {
+                Some(ActiveUniform {
+                    location: gl.get_uniform_location(&program, &info.name)?,
+                    type_: GlslType::from_gl(info.type_),
+                    size: info.size,
+                    name: info
+                        .name
+                        .strip_suffix("[0]")
+                        .unwrap_or(&info.name)
+                        .to_string(),
+                })
+            })
+            .collect();
+        Self {
+            program,
+            attributes,
+            uniforms,
+        }
+    }
+
+    /// Everything that differs between the program and `interface`, declared names first.
+    pub fn mismatches(&self, interface: &ShaderInterface) -> Vec<InterfaceMismatch> {
+        let mut rval = Vec::new();
+        for (name, expected) in interface.attributes {
+            let (name, expected) = (name.to_string(), *expected);
+            match self.attributes.iter().find(|a| a.name == name) {
+                None => rval.push(InterfaceMismatch::MissingAttribute { name, expected }),
+                Some(a) if a.type_ != expected => rval.push(InterfaceMismatch::AttributeType {
+                    name,
+                    expected,
+                    found: a.type_,
+                }),
+                Some(_) => {}
+            }
+        }
+        for (name, expected) in interface.uniforms {
+            let (name, expected) = (name.to_string(), *expected);
+            match self.uniforms.iter().find(|u| u.name == name) {
+                None => rval.push(InterfaceMismatch::MissingUniform { name, expected }),
+                Some(u) if u.type_ != expected => rval.push(InterfaceMismatch::UniformType {
+                    name,
+                    expected,
+                    found: u.type_,
+                }),
+                Some(_) => {}
+            }
+        }
+        for a in &self.attributes {
+            if !interface.attributes.iter().any(|(name, _)| *name == a.name) {
+                rval.push(InterfaceMismatch::UndeclaredAttribute {
+                    name: a.name.clone(),
+                    found: a.type_,
+                });
+            }
+        }
+        for u in &self.uniforms {
+            if !interface.uniforms.iter().any(|(name, _)| *name == u.name) {
+                rval.push(InterfaceMismatch::UndeclaredUniform {
+                    name: u.name.clone(),
+                    found: u.type_,
+                });
+            }
+        }
+        rval
+    }
+
+    /// The location of the active attribute `name`.
+    pub fn attribute(&self, name: &str) -> Result<u32, String> {
+        self.attributes
+            .iter()
+            .find(|a| a.name == name)
+            .map(|a| a.location)
+            .ok_or_else(|| format!("no active attribute {name}"))
+    }
+
+    /// A setter for the active uniform `name`, provided `T` can be uploaded to its type.
+    pub fn uniform<T: UniformType>(&self, name: &str) -> Result<Uniform<T, G>, String> {
+        let u = self
+            .uniforms
+            .iter()
+            .find(|u| u.name == name)
+            .ok_or_else(|| format!("no active uniform {name}"))?;
+        if !T::accepts(u.type_) {
+            return Err(format!(
+                "uniform {name} is {}, which {} cannot set",
+                u.type_,
+                std::any::type_name::<T>()
+            ));
+        }
+        Ok(Uniform {
+            location: u.location.clone(),
+            _type: PhantomData,
+        })
+    }
+}
+
+/// A Rust value that can be uploaded to a uniform of some GLSL type.
+pub trait UniformType {
+    fn accepts(type_: GlslType) -> bool;
+    fn upload<G: GlBackend>(&self, gl: &G, location: &G::UniformLocation);
+}
+
+impl UniformType for Mat3 {
+    fn accepts(type_: GlslType) -> bool {
+        type_ == GlslType::Mat3
+    }
+
+    fn upload<G: GlBackend>(&self, gl: &G, location: &G::UniformLocation) {
+        gl.uniform_matrix3fv_with_f32_array(Some(location), false, self.as_ref());
+    }
+}
+
+impl UniformType for Mat4 {
+    fn accepts(type_: GlslType) -> bool {
+        type_ == GlslType::Mat4
+    }
+
+    fn upload<G: GlBackend>(&self, gl: &G, location: &G::UniformLocation) {
+        gl.uniform_matrix4fv_with_f32_array(Some(location), false, self.as_ref());
+    }
+}
+
+/// a column major `mat4`, as from [`Mat4::to_cols_array`]
+impl UniformType for [f32; 16] {
+    fn accepts(type_: GlslType) -> bool {
+        type_ == GlslType::Mat4
+    }
+
+    fn upload<G: GlBackend>(&self, gl: &G, location: &G::UniformLocation) {
+        gl.uniform_matrix4fv_with_f32_array(Some(location), false, self);
+    }
+}
+
+impl UniformType for Vec3 {
+    fn accepts(type_: GlslType) -> bool {
+        type_ == GlslType::Vec3
+    }
+
+    fn upload<G: GlBackend>(&self, gl: &G, location: &G::UniformLocation) {
+        gl.uniform3fv_with_f32_array(Some(location), self.as_ref());
+    }
+}
+
+impl UniformType for Vec4 {
+    fn accepts(type_: GlslType) -> bool {
+        type_ == GlslType::Vec4
+    }
+
+    fn upload<G: GlBackend>(&self, gl: &G, location: &G::UniformLocation) {
+        gl.uniform4fv_with_f32_array(Some(location), self.as_ref());
+    }
+}
+
+impl UniformType for f32 {
+    fn accepts(type_: GlslType) -> bool {
+        type_ == GlslType::Float
+    }
+
+    fn upload<G: GlBackend>(&self, gl: &G, location: &G::UniformLocation) {
+        gl.uniform1f(Some(location), *self);
+    }
+}
+
+/// `int` and `bool` uniforms
+impl UniformType for i32 {
+    fn accepts(type_: GlslType) -> bool {
+        matches!(type_, GlslType::Int | GlslType::Bool)
+    }
+
+    fn upload<G: GlBackend>(&self, gl: &G, location: &G::UniformLocation) {
+        gl.uniform1i(Some(location), *self);
+    }
+}
+
+/// The texture unit a sampler reads from.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct TextureUnit(pub u32);
+
+impl UniformType for TextureUnit {
+    fn accepts(type_: GlslType) -> bool {
+        type_.is_sampler()
+    }
+
+    fn upload<G: GlBackend>(&self, gl: &G, location: &G::UniformLocation) {
+        gl.uniform1i(Some(location), self.0.try_into().unwrap());
+    }
+}
+
+/// A uniform location that only takes values of type `T`, checked against the GLSL type when it was looked up.
+pub struct Uniform<T, G: GlBackend = WebGl2RenderingContext> {
+    location: G::UniformLocation,
+    _type: PhantomData<fn(&T)>,
+}
+
+impl<T: UniformType, G: GlBackend> Uniform<T, G> {
+    /// Upload `value` into the program that is in use, which must be the one this was looked up in.
+    pub fn set(&self, gl: &G, value: &T) {
+        value.upload(gl, &self.location);
+    }
+
+    pub fn location(&self) -> &G::UniformLocation {
+        &self.location
+    }
+}
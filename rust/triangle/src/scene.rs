@@ -0,0 +1,243 @@
+//! A transform hierarchy. Each node has a local transform relative to its parent and optionally
+//! something to draw. World matrices are cached and only recomputed below a node whose
+//! local transform (or parent) changed.
+
+use glam::{Mat4, Quat, Vec3};
+
+/// Translation, rotation and scale, applied in the reverse order: scale first, translation last.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Transform {
+    pub translation: Vec3,
+    pub rotation: Quat,
+    pub scale: Vec3,
+}
+
+impl Default for Transform {
+    fn default() -> Self {
+        Self::IDENTITY
+    }
+}
+
+impl Transform {
+    pub const IDENTITY: Self = Self {
+        translation: Vec3::ZERO,
+        rotation: Quat::IDENTITY,
+        scale: Vec3::ONE,
+    };
+
+    pub fn from_translation(translation: Vec3) -> Self {
+        Self {
+            translation,
+            ..Self::IDENTITY
+        }
+    }
+
+    /// Decompose `matrix`, which must not have shear.
+    pub fn from_mat4(matrix: &Mat4) -> Self {
+        let (scale, rotation, translation) = matrix.to_scale_rotation_translation();
+        Self {
+            translation,
+            rotation,
+            scale,
+        }
+    }
+
+    pub fn with_rotation(self, rotation: Quat) -> Self {
+        Self { rotation, ..self }
+    }
+
+    pub fn with_scale(self, scale: Vec3) -> Self {
+        Self { scale, ..self }
+    }
+
+    pub fn to_mat4(&self) -> Mat4 {
+        Mat4::from_scale_rotation_translation(self.scale, self.rotation, self.translation)
+    }
+}
+
+/// A node's index in its [`Scene`]. Nodes are never removed, so an id stays valid.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct NodeId(usize);
+
+pub struct Node<D> {
+    pub name: String,
+    local: Transform,
+    parent: Option<NodeId>,
+    children: Vec<NodeId>,
+    pub drawable: Option<D>,
+    /// as of the last [`Scene::update_world_matrices`]
+    world: Mat4,
+    /// whether `local` or `parent` changed since `world` was computed
+    dirty: bool,
+}
+
+impl<D> Node<D> {
+    pub fn local(&self) -> &Transform {
+        &self.local
+    }
+
+    pub fn parent(&self) -> Option<NodeId> {
+        self.parent
+    }
+
+    /// in drawing order
+    pub fn children(&self) -> &[NodeId] {
+        &self.children
+    }
+
+    /// model to world, as of the last [`Scene::update_world_matrices`]
+    pub fn world(&self) -> &Mat4 {
+        &self.world
+    }
+}
+
+/// Nodes in a forest, each holding an optional `D` to draw.
+/// Drawables are visited depth first, parents before children, siblings in the order they were added.
+pub struct Scene<D> {
+    nodes: Vec<Node<D>>,
+    roots: Vec<NodeId>,
+}
+
+impl<D> Default for Scene<D> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<D> Scene<D> {
+    pub fn new() -> Self {
+        Self {
+            nodes: Vec::new(),
+            roots: Vec::new(),
+        }
+    }
+
+    /// Add a node as the last child of `parent` (or as the last root).
+    pub fn add(
+        &mut self,
+        parent: Option<NodeId>,
+        name: &str,
+        local: Transform,
+        drawable: Option<D>,
+    ) -> NodeId {
+        let id = NodeId(self.nodes.len());
+        self.nodes.push(Node {
+            name: name.to_string(),
+            local,
+            parent,
+            children: Vec::new(),
+            drawable,
+            world: Mat4::IDENTITY,
+            dirty: true,
+        });
+        match parent {
+            Some(parent) => self.nodes[parent.0].children.push(id),
+            None => self.roots.push(id),
+        }
+        id
+    }
+
+    pub fn len(&self) -> usize {
+        self.nodes.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.nodes.is_empty()
+    }
+
+    pub fn roots(&self) -> &[NodeId] {
+        &self.roots
+    }
+
+    pub fn node(&self, id: NodeId) -> &Node<D> {
+        &self.nodes[id.0]
+    }
+
+    pub fn drawable_mut(&mut self, id: NodeId) -> Option<&mut D> {
+        self.nodes[id.0].drawable.as_mut()
+    }
+
+    /// The first node called `name`, depth first.
+    pub fn find(&self, name: &str) -> Option<NodeId> {
+        self.walk().find(|&id| self.node(id).name == name)
+    }
+
+    pub fn set_local(&mut self, id: NodeId, local: Transform) {
+        let node = &mut self.nodes[id.0];
+        node.local = local;
+        node.dirty = true;
+    }
+
+    /// Move `id` (with its subtree) to the end of `parent`'s children, keeping its local transform.
+    /// Fails if `parent` is `id` or one of its descendants.
+    pub fn set_parent(&mut self, id: NodeId, parent: Option<NodeId>) -> Result<(), &'static str> {
+        let mut ancestor = parent;
+        while let Some(above) = ancestor {
+            if above == id {
+                return Err("a node cannot be its own ancestor");
+            }
+            ancestor = self.nodes[above.0].parent;
+        }
+        let siblings = match self.nodes[id.0].parent {
+            Some(old) => &mut self.nodes[old.0].children,
+            None => &mut self.roots,
+        };
+        siblings.retain(|&sibling| sibling != id);
+        match parent {
+            Some(parent) => self.nodes[parent.0].children.push(id),
+            None => self.roots.push(id),
+        }
+        let node = &mut self.nodes[id.0];
+        node.parent = parent;
+        node.dirty = true;
+        Ok(())
+    }
+
+    /// Recompute the world matrix of every dirty node and everything below it.
+    /// Returns how many were recomputed.
+    pub fn update_world_matrices(&mut self) -> usize {
+        let mut recomputed = 0;
+        let mut stack: Vec<(NodeId, Mat4, bool)> = self
+            .roots
+            .iter()
+            .rev()
+            .map(|&root| (root, Mat4::IDENTITY, false))
+            .collect();
+        while let Some((id, parent_world, parent_changed)) = stack.pop() {
+            let node = &mut self.nodes[id.0];
+            let changed = node.dirty || parent_changed;
+            if changed {
+                node.world = parent_world * node.local.to_mat4();
+                node.dirty = false;
+                recomputed += 1;
+            }
+            let world = node.world;
+            stack.extend(
+                node.children
+                    .iter()
+                    .rev()
+                    .map(|&child| (child, world, changed)),
+            );
+        }
+        recomputed
+    }
+
+    /// Every node, depth first.
+    pub fn walk(&self) -> impl Iterator<Item = NodeId> + '_ {
+        let mut stack: Vec<NodeId> = self.roots.iter().rev().copied().collect();
+        std::iter::from_fn(move || {
+            let id = stack.pop()?;
+            stack.extend(self.nodes[id.0].children.iter().rev());
+            Some(id)
+        })
+    }
+
+    /// Every drawable with its world matrix, in drawing order.
+    /// Call [`Self::update_world_matrices`] first.
+    pub fn drawables(&self) -> impl Iterator<Item = (&D, &Mat4)> + '_ {
+        self.walk().filter_map(|id| {
+            let node = self.node(id);
+            Some((node.drawable.as_ref()?, &node.world))
+        })
+    }
+}
@@ -1,168 +1,963 @@
+use crate::backend::GlBackend;
+use crate::camera::{bind_camera_block, ViewMode};
+use crate::gl_thin::IndexBuffer;
+use crate::lighting::bind_lights_block;
+use crate::material::{LightingModel, Material};
+use crate::reflection::{GlslType, ReflectedProgram, ShaderInterface, TextureUnit, Uniform};
+use diagnostics::{ShaderError, ShaderStage};
+use glam::{Mat3, Mat4, Vec3, Vec4};
+use preprocess::{embedded_variant, PreprocessedSource, ShaderDefines};
+use registry::{ProgramRegistry, SharedProgram};
 use wasm_bindgen::JsValue;
-use web_sys::{
-    console, WebGl2RenderingContext, WebGlProgram, WebGlShader, WebGlUniformLocation,
-    WebGlVertexArrayObject,
-};
+use web_sys::WebGl2RenderingContext;
+
+pub mod diagnostics;
+pub mod preprocess;
+pub mod registry;
+
+/// What a shader wrapper links: two [`preprocess::EMBEDDED_SOURCES`], the defines of its variant
+/// and the attributes and uniforms it looks up.
+pub struct ProgramDescription {
+    pub vertex: &'static str,
+    pub fragment: &'static str,
+    pub defines: &'static [(&'static str, &'static str)],
+    pub interface: ShaderInterface,
+}
+
+impl ProgramDescription {
+    pub fn defines(&self) -> ShaderDefines {
+        self.defines
+            .iter()
+            .fold(ShaderDefines::new(), |defines, (name, value)| {
+                defines.with_value(name, value)
+            })
+    }
+
+    #[track_caller]
+    pub fn link<G: GlBackend>(&self, gl: &G) -> Result<ReflectedProgram<G>, JsValue> {
+        self.link_variant(gl, &self.defines())
+    }
+
+    /// With `defines` in place of the description's own.
+    #[track_caller]
+    pub fn link_variant<G: GlBackend>(
+        &self,
+        gl: &G,
+        defines: &ShaderDefines,
+    ) -> Result<ReflectedProgram<G>, JsValue> {
+        ReflectedProgram::link_variant(gl, self.vertex, self.fragment, defines, &self.interface)
+    }
+}
+
+/// Every program the wrappers below link, so they can be checked without a GPU.
+pub const PROGRAMS: &[&ProgramDescription] = &[
+    &GRADIENT_PROGRAM,
+    &INSTANCED_GRADIENT_PROGRAM,
+    &TEXTURE_PROGRAM,
+    &INSTANCED_TEXTURE_PROGRAM,
+    &BAR_PROGRAM,
+    &TEXT_PROGRAM,
+    &LAYER_COPY_PROGRAM,
+    &LIT_PROGRAM,
+    &PBR_LIT_PROGRAM,
+    &UNLIT_PROGRAM,
+];
 
-pub struct GradientShader {
-    pub program: WebGlProgram,
+/// The define that turns `camera.glsl` into its `OVR_multiview2` form, for [`ViewMode::Multiview`].
+pub const MULTIVIEW_DEFINE: &str = "MULTIVIEW";
+
+pub struct GradientShader<G: GlBackend = WebGl2RenderingContext> {
+    pub program: SharedProgram<G>,
     pub sal_xy: u32,
     pub sal_rgb: u32,
-    pub sul_mvp: WebGlUniformLocation,
+    pub sul_model: Uniform<[f32; 16], G>,
+    /// the `MULTIVIEW` variant, once [`Self::enable_multiview`] has linked it
+    pub multiview: Option<Box<Self>>,
 }
 
-static FLAT_VS: &str = include_str!("flat.vert");
-static FLAT_FS: &str = include_str!("flat.frag");
+pub const GRADIENT_PROGRAM: ProgramDescription = ProgramDescription {
+    vertex: "flat.vert",
+    fragment: "flat.frag",
+    defines: &[],
+    interface: ShaderInterface {
+        attributes: &[("xy", GlslType::Vec2), ("rgb", GlslType::Vec3)],
+        uniforms: &[("model", GlslType::Mat4)],
+    },
+};
+
+impl<G: GlBackend> GradientShader<G> {
+    #[track_caller]
+    pub fn new(gl: &G, programs: &ProgramRegistry<G>) -> Result<Self, JsValue> {
+        Self::variant(gl, programs, &GRADIENT_PROGRAM.defines())
+    }
 
-impl GradientShader {
-    pub fn new(gl: &WebGl2RenderingContext) -> Result<GradientShader, JsValue> {
-        let program = simple_shader_program(gl, FLAT_VS, FLAT_FS)?;
-        let sal_xy = gl.get_attrib_location(&program, "xy").try_into().unwrap();
-        let sal_rgb = gl.get_attrib_location(&program, "rgb").try_into().unwrap();
-        let sul_mvp = gl
-            .get_uniform_location(&program, "mvp")
-            .ok_or_else(|| JsValue::from("missing uniform mvp"))?;
+    #[track_caller]
+    fn variant(
+        gl: &G,
+        programs: &ProgramRegistry<G>,
+        defines: &ShaderDefines,
+    ) -> Result<Self, JsValue> {
+        let program = programs.link(gl, &GRADIENT_PROGRAM, defines, |program| {
+            bind_camera_block(gl, program)
+        })?;
         Ok(Self {
+            sal_xy: program.attribute("xy")?,
+            sal_rgb: program.attribute("rgb")?,
+            sul_model: program.uniform("model")?,
             program,
-            sal_xy,
-            sal_rgb,
-            sul_mvp,
+            multiview: None,
         })
     }
 
+    /// Link the `MULTIVIEW` variant if it is not linked yet.
+    /// Its attributes are at the same locations, so it draws from the same VAOs.
+    pub fn enable_multiview(
+        &mut self,
+        gl: &G,
+        programs: &ProgramRegistry<G>,
+    ) -> Result<(), JsValue> {
+        if self.multiview.is_none() {
+            let defines = GRADIENT_PROGRAM.defines().with(MULTIVIEW_DEFINE);
+            self.multiview = Some(Box::new(Self::variant(gl, programs, &defines)?));
+        }
+        Ok(())
+    }
+
+    /// The program to draw `mode` with. [`ViewMode::Multiview`] needs [`Self::enable_multiview`] first.
+    pub fn for_view(&self, mode: ViewMode) -> &Self {
+        match (mode, &self.multiview) {
+            (ViewMode::Multiview, Some(multiview)) => multiview,
+            _ => self,
+        }
+    }
+
+    /// Leaves `vao` bound, so with a [`StateCache`](crate::state_cache::StateCache)
+    /// the next draw using the same VAO costs no bind at all.
     pub fn draw(
         &self,
-        gl: &WebGl2RenderingContext,
+        gl: &G,
         offset: i32,
         vertex_count: i32,
-        vao: &WebGlVertexArrayObject,
-        projection_matrix: &[f32],
+        vao: &G::VertexArray,
+        model: &[f32; 16],
     ) {
-        gl.use_program(Some(&self.program));
+        gl.use_program(Some(&*self.program));
 
         gl.bind_vertex_array(Some(vao));
 
-        gl.uniform_matrix4fv_with_f32_array(Some(&self.sul_mvp), false, projection_matrix);
+        self.sul_model.set(gl, model);
 
         gl.draw_arrays(WebGl2RenderingContext::TRIANGLES, offset, vertex_count);
+    }
+
+    pub fn release(self, gl: &G) {
+        self.program.release(gl);
+        if let Some(multiview) = self.multiview {
+            multiview.release(gl);
+        }
+    }
+}
+
+/// [`GradientShader`] with the model matrix and a tint color as per-instance attributes,
+/// so any number of copies cost one draw call.
+pub struct InstancedGradientShader<G: GlBackend = WebGl2RenderingContext> {
+    pub program: SharedProgram<G>,
+    pub sal_xy: u32,
+    pub sal_rgb: u32,
+    /// the first of 4 consecutive locations, one per column
+    pub sal_model: u32,
+    pub sal_tint: u32,
+}
+
+pub const INSTANCED_GRADIENT_PROGRAM: ProgramDescription = ProgramDescription {
+    vertex: "flat.vert",
+    fragment: "flat.frag",
+    defines: &[("INSTANCED", "")],
+    interface: ShaderInterface {
+        attributes: &[
+            ("xy", GlslType::Vec2),
+            ("rgb", GlslType::Vec3),
+            ("model", GlslType::Mat4),
+            ("tint", GlslType::Vec4),
+        ],
+        uniforms: &[],
+    },
+};
+
+impl<G: GlBackend> InstancedGradientShader<G> {
+    #[track_caller]
+    pub fn new(gl: &G, programs: &ProgramRegistry<G>) -> Result<Self, JsValue> {
+        let program = programs.link(
+            gl,
+            &INSTANCED_GRADIENT_PROGRAM,
+            &INSTANCED_GRADIENT_PROGRAM.defines(),
+            |program| bind_camera_block(gl, program),
+        )?;
+        Ok(Self {
+            sal_xy: program.attribute("xy")?,
+            sal_rgb: program.attribute("rgb")?,
+            sal_model: program.attribute("model")?,
+            sal_tint: program.attribute("tint")?,
+            program,
+        })
+    }
+
+    /// `vao` must pull `model` and `tint` from an instance buffer (see [`InterleavedGlBuffer::instance_attrib_pointers`](crate::gl_thin::InterleavedGlBuffer::instance_attrib_pointers)).
+    pub fn draw_instanced(
+        &self,
+        gl: &G,
+        offset: i32,
+        vertex_count: i32,
+        instance_count: i32,
+        vao: &G::VertexArray,
+    ) {
+        gl.use_program(Some(&*self.program));
+
+        gl.bind_vertex_array(Some(vao));
 
-        gl.bind_vertex_array(None);
+        gl.draw_arrays_instanced(
+            WebGl2RenderingContext::TRIANGLES,
+            offset,
+            vertex_count,
+            instance_count,
+        );
+    }
+
+    pub fn release(self, gl: &G) {
+        self.program.release(gl);
     }
 }
 
 //
 
-pub struct TextureShader {
-    pub program: WebGlProgram,
+pub struct TextureShader<G: GlBackend = WebGl2RenderingContext> {
+    pub program: SharedProgram<G>,
     pub sal_xy: u32,
-    pub sul_mvp: WebGlUniformLocation,
-    pub sul_tex: WebGlUniformLocation,
-}
-
-const TEXTURED_VS: &str = include_str!("texture.vert");
-const TEXTURED_FS: &str = include_str!("texture.frag");
-
-impl TextureShader {
-    pub fn new(gl: &WebGl2RenderingContext) -> Result<Self, JsValue> {
-        let program = simple_shader_program(gl, TEXTURED_VS, TEXTURED_FS)?;
-        let sal_xy = gl.get_attrib_location(&program, "xy").try_into().unwrap();
-        let sul_mvp = gl
-            .get_uniform_location(&program, "mvp")
-            .ok_or_else(|| JsValue::from("missing uniform mvp"))?;
-        let sul_tex = gl
-            .get_uniform_location(&program, "tex")
-            .ok_or_else(|| JsValue::from("missing uniform tex"))?;
+    pub sul_model: Uniform<[f32; 16], G>,
+    pub sul_tex: Uniform<TextureUnit, G>,
+    /// the `MULTIVIEW` variant, once [`Self::enable_multiview`] has linked it
+    pub multiview: Option<Box<Self>>,
+    /// what this was linked with, for the `MULTIVIEW` variant
+    defines: ShaderDefines,
+}
+
+pub const TEXTURE_PROGRAM: ProgramDescription = ProgramDescription {
+    vertex: "texture.vert",
+    fragment: "texture.frag",
+    defines: &[],
+    interface: ShaderInterface {
+        attributes: &[("xy", GlslType::Vec2)],
+        uniforms: &[("model", GlslType::Mat4), ("tex", GlslType::Sampler2D)],
+    },
+};
+
+impl<G: GlBackend> TextureShader<G> {
+    #[track_caller]
+    pub fn new(gl: &G, programs: &ProgramRegistry<G>) -> Result<Self, JsValue> {
+        Self::variant(gl, programs, &ShaderDefines::new())
+    }
+
+    /// With `ALPHA_CUTOFF` defined, fragments with less alpha are discarded,
+    /// so cut-out textures need neither blending nor sorting.
+    #[track_caller]
+    pub fn variant(
+        gl: &G,
+        programs: &ProgramRegistry<G>,
+        defines: &ShaderDefines,
+    ) -> Result<Self, JsValue> {
+        let program = programs.link(gl, &TEXTURE_PROGRAM, defines, |program| {
+            bind_camera_block(gl, program)
+        })?;
         Ok(Self {
+            sal_xy: program.attribute("xy")?,
+            sul_model: program.uniform("model")?,
+            sul_tex: program.uniform("tex")?,
             program,
-            sal_xy,
-            sul_mvp,
-            sul_tex,
+            multiview: None,
+            defines: defines.clone(),
         })
     }
 
+    /// See [`GradientShader::enable_multiview`]. The variant keeps this one's defines.
+    pub fn enable_multiview(
+        &mut self,
+        gl: &G,
+        programs: &ProgramRegistry<G>,
+    ) -> Result<(), JsValue> {
+        if self.multiview.is_none() {
+            let defines = self.defines.clone().with(MULTIVIEW_DEFINE);
+            self.multiview = Some(Box::new(Self::variant(gl, programs, &defines)?));
+        }
+        Ok(())
+    }
+
+    /// See [`GradientShader::for_view`].
+    pub fn for_view(&self, mode: ViewMode) -> &Self {
+        match (mode, &self.multiview) {
+            (ViewMode::Multiview, Some(multiview)) => multiview,
+            _ => self,
+        }
+    }
+
     pub fn draw(
         &self,
-        gl: &WebGl2RenderingContext,
-        index_count: i32,
-        vao: &WebGlVertexArrayObject,
-        projection_matrix: &[f32],
-        texture_id: i32,
+        gl: &G,
+        indices: &IndexBuffer<G>,
+        vao: &G::VertexArray,
+        model: &[f32; 16],
+        texture_unit: TextureUnit,
     ) {
-        gl.use_program(Some(&self.program));
+        gl.use_program(Some(&*self.program));
 
         gl.bind_vertex_array(Some(vao));
 
-        gl.uniform_matrix4fv_with_f32_array(Some(&self.sul_mvp), false, projection_matrix);
-        gl.uniform1i(Some(&self.sul_tex), texture_id);
+        self.sul_model.set(gl, model);
+        self.sul_tex.set(gl, &texture_unit);
 
-        gl.draw_elements_with_i32(
-            WebGl2RenderingContext::TRIANGLES,
-            index_count,
-            WebGl2RenderingContext::UNSIGNED_BYTE,
-            0,
-        );
+        indices.draw_elements(gl, WebGl2RenderingContext::TRIANGLES);
+    }
+
+    pub fn release(self, gl: &G) {
+        self.program.release(gl);
+        if let Some(multiview) = self.multiview {
+            multiview.release(gl);
+        }
+    }
+}
+
+//
+
+/// [`TextureShader`] with the model matrix and a tint color as per-instance attributes.
+pub struct InstancedTextureShader<G: GlBackend = WebGl2RenderingContext> {
+    pub program: SharedProgram<G>,
+    pub sal_xy: u32,
+    /// the first of 4 consecutive locations, one per column
+    pub sal_model: u32,
+    pub sal_tint: u32,
+    pub sul_tex: Uniform<TextureUnit, G>,
+}
+
+pub const INSTANCED_TEXTURE_PROGRAM: ProgramDescription = ProgramDescription {
+    vertex: "texture.vert",
+    fragment: "texture.frag",
+    defines: &[("INSTANCED", "")],
+    interface: ShaderInterface {
+        attributes: &[
+            ("xy", GlslType::Vec2),
+            ("model", GlslType::Mat4),
+            ("tint", GlslType::Vec4),
+        ],
+        uniforms: &[("tex", GlslType::Sampler2D)],
+    },
+};
+
+impl<G: GlBackend> InstancedTextureShader<G> {
+    #[track_caller]
+    pub fn new(gl: &G, programs: &ProgramRegistry<G>) -> Result<Self, JsValue> {
+        let program = programs.link(
+            gl,
+            &INSTANCED_TEXTURE_PROGRAM,
+            &INSTANCED_TEXTURE_PROGRAM.defines(),
+            |program| bind_camera_block(gl, program),
+        )?;
+        Ok(Self {
+            sal_xy: program.attribute("xy")?,
+            sal_model: program.attribute("model")?,
+            sal_tint: program.attribute("tint")?,
+            sul_tex: program.uniform("tex")?,
+            program,
+        })
+    }
+
+    pub fn draw_instanced(
+        &self,
+        gl: &G,
+        indices: &IndexBuffer<G>,
+        instance_count: i32,
+        vao: &G::VertexArray,
+        texture_unit: TextureUnit,
+    ) {
+        gl.use_program(Some(&*self.program));
+
+        gl.bind_vertex_array(Some(vao));
+
+        self.sul_tex.set(gl, &texture_unit);
+
+        indices.draw_elements_instanced(gl, WebGl2RenderingContext::TRIANGLES, instance_count);
+    }
+
+    pub fn release(self, gl: &G) {
+        self.program.release(gl);
+    }
+}
+
+//
+
+/// Solid rectangles, one per instance, for overlays like the
+/// [`ProfilerOverlay`](crate::profiler::ProfilerOverlay).
+pub struct BarShader<G: GlBackend = WebGl2RenderingContext> {
+    pub program: SharedProgram<G>,
+    /// a unit square's corners
+    pub sal_corner: u32,
+    /// per instance: x, y, width, height
+    pub sal_rect: u32,
+    /// per instance
+    pub sal_color: u32,
+    pub sul_model: Uniform<[f32; 16], G>,
+    /// the `MULTIVIEW` variant, once [`Self::enable_multiview`] has linked it
+    pub multiview: Option<Box<Self>>,
+}
+
+pub const BAR_PROGRAM: ProgramDescription = ProgramDescription {
+    vertex: "bar.vert",
+    fragment: "bar.frag",
+    defines: &[],
+    interface: ShaderInterface {
+        attributes: &[
+            ("corner", GlslType::Vec2),
+            ("rect", GlslType::Vec4),
+            ("color", GlslType::Vec4),
+        ],
+        uniforms: &[("model", GlslType::Mat4)],
+    },
+};
+
+impl<G: GlBackend> BarShader<G> {
+    #[track_caller]
+    pub fn new(gl: &G, programs: &ProgramRegistry<G>) -> Result<Self, JsValue> {
+        Self::variant(gl, programs, &BAR_PROGRAM.defines())
+    }
+
+    #[track_caller]
+    fn variant(
+        gl: &G,
+        programs: &ProgramRegistry<G>,
+        defines: &ShaderDefines,
+    ) -> Result<Self, JsValue> {
+        let program = programs.link(gl, &BAR_PROGRAM, defines, |program| {
+            bind_camera_block(gl, program)
+        })?;
+        Ok(Self {
+            sal_corner: program.attribute("corner")?,
+            sal_rect: program.attribute("rect")?,
+            sal_color: program.attribute("color")?,
+            sul_model: program.uniform("model")?,
+            program,
+            multiview: None,
+        })
+    }
+
+    /// See [`GradientShader::enable_multiview`].
+    pub fn enable_multiview(
+        &mut self,
+        gl: &G,
+        programs: &ProgramRegistry<G>,
+    ) -> Result<(), JsValue> {
+        if self.multiview.is_none() {
+            let defines = BAR_PROGRAM.defines().with(MULTIVIEW_DEFINE);
+            self.multiview = Some(Box::new(Self::variant(gl, programs, &defines)?));
+        }
+        Ok(())
+    }
+
+    /// See [`GradientShader::for_view`].
+    pub fn for_view(&self, mode: ViewMode) -> &Self {
+        match (mode, &self.multiview) {
+            (ViewMode::Multiview, Some(multiview)) => multiview,
+            _ => self,
+        }
+    }
 
-        gl.bind_vertex_array(None);
+    /// `vao` must pull `corner` from a 4-vertex triangle strip and `rect` and `color` from an instance buffer.
+    pub fn draw_instanced(
+        &self,
+        gl: &G,
+        vao: &G::VertexArray,
+        instance_count: i32,
+        model: &[f32; 16],
+    ) {
+        gl.use_program(Some(&*self.program));
+
+        gl.bind_vertex_array(Some(vao));
+
+        self.sul_model.set(gl, model);
+
+        gl.draw_arrays_instanced(WebGl2RenderingContext::TRIANGLE_STRIP, 0, 4, instance_count);
+    }
+
+    pub fn release(self, gl: &G) {
+        self.program.release(gl);
+        if let Some(multiview) = self.multiview {
+            multiview.release(gl);
+        }
+    }
+}
+
+//
+
+/// Glyph quads from a [`TextRenderer`](crate::text::TextRenderer), colored per vertex,
+/// with the atlas coverage as alpha.
+pub struct TextShader<G: GlBackend = WebGl2RenderingContext> {
+    pub program: SharedProgram<G>,
+    pub sal_xy: u32,
+    pub sal_uv: u32,
+    pub sal_rgba: u32,
+    pub sul_model: Uniform<[f32; 16], G>,
+    pub sul_atlas: Uniform<TextureUnit, G>,
+    /// the `MULTIVIEW` variant, once [`Self::enable_multiview`] has linked it
+    pub multiview: Option<Box<Self>>,
+}
+
+pub const TEXT_PROGRAM: ProgramDescription = ProgramDescription {
+    vertex: "text.vert",
+    fragment: "text.frag",
+    defines: &[],
+    interface: ShaderInterface {
+        attributes: &[
+            ("xy", GlslType::Vec2),
+            ("uv", GlslType::Vec2),
+            ("rgba", GlslType::Vec4),
+        ],
+        uniforms: &[("model", GlslType::Mat4), ("atlas", GlslType::Sampler2D)],
+    },
+};
+
+impl<G: GlBackend> TextShader<G> {
+    #[track_caller]
+    pub fn new(gl: &G, programs: &ProgramRegistry<G>) -> Result<Self, JsValue> {
+        Self::variant(gl, programs, &TEXT_PROGRAM.defines())
+    }
+
+    #[track_caller]
+    fn variant(
+        gl: &G,
+        programs: &ProgramRegistry<G>,
+        defines: &ShaderDefines,
+    ) -> Result<Self, JsValue> {
+        let program = programs.link(gl, &TEXT_PROGRAM, defines, |program| {
+            bind_camera_block(gl, program)
+        })?;
+        Ok(Self {
+            sal_xy: program.attribute("xy")?,
+            sal_uv: program.attribute("uv")?,
+            sal_rgba: program.attribute("rgba")?,
+            sul_model: program.uniform("model")?,
+            sul_atlas: program.uniform("atlas")?,
+            program,
+            multiview: None,
+        })
+    }
+
+    /// See [`GradientShader::enable_multiview`].
+    pub fn enable_multiview(
+        &mut self,
+        gl: &G,
+        programs: &ProgramRegistry<G>,
+    ) -> Result<(), JsValue> {
+        if self.multiview.is_none() {
+            let defines = TEXT_PROGRAM.defines().with(MULTIVIEW_DEFINE);
+            self.multiview = Some(Box::new(Self::variant(gl, programs, &defines)?));
+        }
+        Ok(())
+    }
+
+    /// See [`GradientShader::for_view`].
+    pub fn for_view(&self, mode: ViewMode) -> &Self {
+        match (mode, &self.multiview) {
+            (ViewMode::Multiview, Some(multiview)) => multiview,
+            _ => self,
+        }
     }
 
-    pub fn release(self, gl: &WebGl2RenderingContext) {
-        gl.delete_program(Some(&self.program));
+    pub fn draw(
+        &self,
+        gl: &G,
+        indices: &IndexBuffer<G>,
+        vao: &G::VertexArray,
+        model: &[f32; 16],
+        texture_unit: TextureUnit,
+    ) {
+        gl.use_program(Some(&*self.program));
+
+        gl.bind_vertex_array(Some(vao));
+
+        self.sul_model.set(gl, model);
+        self.sul_atlas.set(gl, &texture_unit);
+
+        indices.draw_elements(gl, WebGl2RenderingContext::TRIANGLES);
+    }
+
+    pub fn release(self, gl: &G) {
+        self.program.release(gl);
+        if let Some(multiview) = self.multiview {
+            multiview.release(gl);
+        }
     }
 }
 
 //
 
-pub fn simple_shader_program(
-    gl: &WebGl2RenderingContext,
+/// Meshes with normals, shaded by the [`Lights`](crate::lighting::Lights) bound at
+/// [`LIGHTS_BINDING`](crate::lighting::LIGHTS_BINDING) and a [`Material`]
+/// (or, for [`LightingModel::Unlit`], colored by the material alone).
+pub struct LitShader<G: GlBackend = WebGl2RenderingContext> {
+    pub program: SharedProgram<G>,
+    pub sal_position: u32,
+    /// `None` for [`LightingModel::Unlit`], like every uniform only lighting needs
+    pub sal_normal: Option<u32>,
+    pub sal_uv: u32,
+    pub sul_model: Uniform<[f32; 16], G>,
+    pub sul_normal_matrix: Option<Uniform<Mat3, G>>,
+    pub sul_uv_transform: Uniform<Mat3, G>,
+    pub sul_base_color: Uniform<Vec4, G>,
+    pub sul_base_color_texture: Uniform<TextureUnit, G>,
+    pub sul_roughness: Option<Uniform<f32, G>>,
+    pub sul_metallic: Option<Uniform<f32, G>>,
+    pub sul_emissive: Option<Uniform<Vec3, G>>,
+    pub lighting: LightingModel,
+    /// the `MULTIVIEW` variant, once [`Self::enable_multiview`] has linked it
+    pub multiview: Option<Box<Self>>,
+}
+
+const LIT_INTERFACE: ShaderInterface = ShaderInterface {
+    attributes: &[
+        ("position", GlslType::Vec3),
+        ("normal", GlslType::Vec3),
+        ("uv", GlslType::Vec2),
+    ],
+    uniforms: &[
+        ("model", GlslType::Mat4),
+        ("normal_matrix", GlslType::Mat3),
+        ("uv_transform", GlslType::Mat3),
+        ("base_color", GlslType::Vec4),
+        ("base_color_texture", GlslType::Sampler2D),
+        ("roughness", GlslType::Float),
+        ("metallic", GlslType::Float),
+        ("emissive", GlslType::Vec3),
+    ],
+};
+
+/// [`LightingModel::BlinnPhong`]
+pub const LIT_PROGRAM: ProgramDescription = ProgramDescription {
+    vertex: "lit.vert",
+    fragment: "lit.frag",
+    defines: &[],
+    interface: LIT_INTERFACE,
+};
+
+/// [`LightingModel::MetallicRoughness`]
+pub const PBR_LIT_PROGRAM: ProgramDescription = ProgramDescription {
+    vertex: "lit.vert",
+    fragment: "lit.frag",
+    defines: &[("PBR", "")],
+    interface: LIT_INTERFACE,
+};
+
+/// [`LightingModel::Unlit`]
+pub const UNLIT_PROGRAM: ProgramDescription = ProgramDescription {
+    vertex: "unlit.vert",
+    fragment: "unlit.frag",
+    defines: &[],
+    interface: ShaderInterface {
+        attributes: &[("position", GlslType::Vec3), ("uv", GlslType::Vec2)],
+        uniforms: &[
+            ("model", GlslType::Mat4),
+            ("uv_transform", GlslType::Mat3),
+            ("base_color", GlslType::Vec4),
+            ("base_color_texture", GlslType::Sampler2D),
+        ],
+    },
+};
+
+impl<G: GlBackend> LitShader<G> {
+    #[track_caller]
+    pub fn new(
+        gl: &G,
+        programs: &ProgramRegistry<G>,
+        lighting: LightingModel,
+    ) -> Result<Self, JsValue> {
+        Self::variant(
+            gl,
+            programs,
+            lighting,
+            &Self::description(lighting).defines(),
+        )
+    }
+
+    fn description(lighting: LightingModel) -> &'static ProgramDescription {
+        match lighting {
+            LightingModel::BlinnPhong => &LIT_PROGRAM,
+            LightingModel::MetallicRoughness => &PBR_LIT_PROGRAM,
+            LightingModel::Unlit => &UNLIT_PROGRAM,
+        }
+    }
+
+    #[track_caller]
+    fn variant(
+        gl: &G,
+        programs: &ProgramRegistry<G>,
+        lighting: LightingModel,
+        defines: &ShaderDefines,
+    ) -> Result<Self, JsValue> {
+        let description = Self::description(lighting);
+        let lit = lighting != LightingModel::Unlit;
+        let program = programs.link(gl, description, defines, |program| {
+            bind_camera_block(gl, program)?;
+            if lit {
+                bind_lights_block(gl, program)?;
+            }
+            Ok(())
+        })?;
+        Ok(Self {
+            sal_position: program.attribute("position")?,
+            sal_normal: lit.then(|| program.attribute("normal")).transpose()?,
+            sal_uv: program.attribute("uv")?,
+            sul_model: program.uniform("model")?,
+            sul_normal_matrix: lit.then(|| program.uniform("normal_matrix")).transpose()?,
+            sul_uv_transform: program.uniform("uv_transform")?,
+            sul_base_color: program.uniform("base_color")?,
+            sul_base_color_texture: program.uniform("base_color_texture")?,
+            sul_roughness: lit.then(|| program.uniform("roughness")).transpose()?,
+            sul_metallic: lit.then(|| program.uniform("metallic")).transpose()?,
+            sul_emissive: lit.then(|| program.uniform("emissive")).transpose()?,
+            program,
+            lighting,
+            multiview: None,
+        })
+    }
+
+    /// See [`GradientShader::enable_multiview`].
+    pub fn enable_multiview(
+        &mut self,
+        gl: &G,
+        programs: &ProgramRegistry<G>,
+    ) -> Result<(), JsValue> {
+        if self.multiview.is_none() {
+            let defines = Self::description(self.lighting)
+                .defines()
+                .with(MULTIVIEW_DEFINE);
+            self.multiview = Some(Box::new(Self::variant(
+                gl,
+                programs,
+                self.lighting,
+                &defines,
+            )?));
+        }
+        Ok(())
+    }
+
+    /// See [`GradientShader::for_view`].
+    pub fn for_view(&self, mode: ViewMode) -> &Self {
+        match (mode, &self.multiview) {
+            (ViewMode::Multiview, Some(multiview)) => multiview,
+            _ => self,
+        }
+    }
+
+    /// `texture_unit` must have the material's base color texture (or a white stand-in) bound.
+    pub fn draw(
+        &self,
+        gl: &G,
+        indices: &IndexBuffer<G>,
+        vao: &G::VertexArray,
+        model: &[f32; 16],
+        material: &Material<G>,
+        texture_unit: TextureUnit,
+    ) {
+        gl.use_program(Some(&*self.program));
+
+        gl.bind_vertex_array(Some(vao));
+
+        self.sul_model.set(gl, model);
+        if let Some(sul_normal_matrix) = &self.sul_normal_matrix {
+            let normal_matrix = Mat3::from_mat4(Mat4::from_cols_array(model))
+                .inverse()
+                .transpose();
+            sul_normal_matrix.set(gl, &normal_matrix);
+        }
+        self.sul_uv_transform.set(gl, &material.uv_transform);
+        self.sul_base_color.set(gl, &material.base_color);
+        self.sul_base_color_texture.set(gl, &texture_unit);
+        if let Some(sul_roughness) = &self.sul_roughness {
+            sul_roughness.set(gl, &material.roughness);
+        }
+        if let Some(sul_metallic) = &self.sul_metallic {
+            sul_metallic.set(gl, &material.metallic);
+        }
+        if let Some(sul_emissive) = &self.sul_emissive {
+            sul_emissive.set(gl, &material.emissive);
+        }
+
+        indices.draw_elements(gl, WebGl2RenderingContext::TRIANGLES);
+    }
+
+    pub fn release(self, gl: &G) {
+        self.program.release(gl);
+        if let Some(multiview) = self.multiview {
+            multiview.release(gl);
+        }
+    }
+}
+
+//
+
+/// Copies one layer of a `TEXTURE_2D_ARRAY` over the whole viewport, as a draw rather than a blit
+/// so the destination may be multisampled (like an antialiased `XRWebGLLayer`).
+pub struct LayerCopyShader<G: GlBackend = WebGl2RenderingContext> {
+    pub program: SharedProgram<G>,
+    pub sul_views: Uniform<TextureUnit, G>,
+    pub sul_layer: Uniform<i32, G>,
+}
+
+pub const LAYER_COPY_PROGRAM: ProgramDescription = ProgramDescription {
+    vertex: "copy_layer.vert",
+    fragment: "copy_layer.frag",
+    defines: &[],
+    interface: ShaderInterface {
+        attributes: &[],
+        uniforms: &[
+            ("views", GlslType::Sampler2DArray),
+            ("layer", GlslType::Int),
+        ],
+    },
+};
+
+impl<G: GlBackend> LayerCopyShader<G> {
+    #[track_caller]
+    pub fn new(gl: &G, programs: &ProgramRegistry<G>) -> Result<Self, JsValue> {
+        let program = programs.link(gl, &LAYER_COPY_PROGRAM, &ShaderDefines::new(), |_| Ok(()))?;
+        Ok(Self {
+            sul_views: program.uniform("views")?,
+            sul_layer: program.uniform("layer")?,
+            program,
+        })
+    }
+
+    /// `vao` needs no attributes; the vertices come from `gl_VertexID`.
+    pub fn draw(&self, gl: &G, vao: &G::VertexArray, texture_unit: TextureUnit, layer: i32) {
+        gl.use_program(Some(&*self.program));
+
+        gl.bind_vertex_array(Some(vao));
+
+        self.sul_views.set(gl, &texture_unit);
+        self.sul_layer.set(gl, &layer);
+
+        gl.draw_arrays(WebGl2RenderingContext::TRIANGLES, 0, 3);
+    }
+
+    pub fn release(self, gl: &G) {
+        self.program.release(gl);
+    }
+}
+
+//
+
+#[track_caller]
+pub fn simple_shader_program<G: GlBackend>(
+    gl: &G,
     vertex_shader_source: &str,
     fragment_shader_source: &str,
-) -> Result<WebGlProgram, JsValue> {
-    let vertex_shader = load_shader(
+) -> Result<G::Program, ShaderError> {
+    link_sources(
         gl,
-        WebGl2RenderingContext::VERTEX_SHADER,
-        vertex_shader_source,
-    )?;
-    let fragment_shader = load_shader(
+        &PreprocessedSource::verbatim("source", vertex_shader_source),
+        &PreprocessedSource::verbatim("source", fragment_shader_source),
+        &[],
+    )
+}
+
+/// Like [`simple_shader_program`], from the [`preprocess::EMBEDDED_SOURCES`] named `vertex_shader` and `fragment_shader`.
+/// Errors point at the line in the original file.
+///
+/// `attributes` are bound to consecutive locations from 0 before linking (a `mat4` takes four);
+/// GL picks the locations of any others.
+#[track_caller]
+pub fn variant_program<G: GlBackend>(
+    gl: &G,
+    vertex_shader: &str,
+    fragment_shader: &str,
+    defines: &ShaderDefines,
+    attributes: &[(&str, GlslType)],
+) -> Result<G::Program, ShaderError> {
+    let vertex_shader = embedded_variant(vertex_shader, defines)
+        .map_err(|e| ShaderError::preprocess(ShaderStage::Vertex, e))?;
+    let fragment_shader = embedded_variant(fragment_shader, defines)
+        .map_err(|e| ShaderError::preprocess(ShaderStage::Fragment, e))?;
+    link_sources(gl, &vertex_shader, &fragment_shader, attributes)
+}
+
+#[track_caller]
+fn link_sources<G: GlBackend>(
+    gl: &G,
+    vertex_shader: &PreprocessedSource,
+    fragment_shader: &PreprocessedSource,
+    attributes: &[(&str, GlslType)],
+) -> Result<G::Program, ShaderError> {
+    let vertex_shader =
+        load_preprocessed_shader(gl, WebGl2RenderingContext::VERTEX_SHADER, vertex_shader)?;
+    let fragment_shader = match load_preprocessed_shader(
         gl,
         WebGl2RenderingContext::FRAGMENT_SHADER,
-        fragment_shader_source,
-    )?;
-    let program = gl
-        .create_program()
-        .ok_or_else(|| JsValue::from("failed to create program"))?;
-    gl.attach_shader(&program, &vertex_shader);
-    gl.attach_shader(&program, &fragment_shader);
-    gl.link_program(&program);
-
-    let status = gl.get_program_parameter(&program, WebGl2RenderingContext::LINK_STATUS);
-    if !status.as_bool().unwrap_or(false) {
-        return Err(gl
-            .get_program_info_log(&program)
-            .unwrap_or("shader link failure".into())
-            .into());
+        fragment_shader,
+    ) {
+        Ok(shader) => shader,
+        Err(e) => {
+            gl.delete_shader(Some(&vertex_shader));
+            return Err(e);
+        }
+    };
+    let program = gl.create_program();
+    if let Some(program) = &program {
+        gl.attach_shader(program, &vertex_shader);
+        gl.attach_shader(program, &fragment_shader);
+        let mut location = 0;
+        for (name, type_) in attributes {
+            gl.bind_attrib_location(program, location, name);
+            location += type_.locations();
+        }
+        gl.link_program(program);
+    }
+    // the program keeps what it needs; the shader objects are flagged for deletion once detached
+    gl.delete_shader(Some(&vertex_shader));
+    gl.delete_shader(Some(&fragment_shader));
+    let program =
+        program.ok_or_else(|| ShaderError::new(ShaderStage::Link, "failed to create program"))?;
+
+    if !gl.get_program_link_status(&program) {
+        let log = gl.get_program_info_log(&program).unwrap_or_default();
+        gl.delete_program(Some(&program));
+        return Err(ShaderError::link(&log));
     }
     Ok(program)
 }
 
-pub fn load_shader(
-    gl: &WebGl2RenderingContext,
+pub fn load_shader<G: GlBackend>(
+    gl: &G,
     type_: u32,
     source: &str,
-) -> Result<WebGlShader, JsValue> {
+) -> Result<G::Shader, ShaderError> {
+    load_preprocessed_shader(gl, type_, &PreprocessedSource::verbatim("source", source))
+}
+
+#[track_caller]
+pub fn load_preprocessed_shader<G: GlBackend>(
+    gl: &G,
+    type_: u32,
+    source: &PreprocessedSource,
+) -> Result<G::Shader, ShaderError> {
+    let stage = ShaderStage::from_gl(type_);
     let shader = gl
         .create_shader(type_)
-        .ok_or_else(|| JsValue::from_str("failed to create shader object"))?;
-    gl.shader_source(&shader, source);
+        .ok_or_else(|| ShaderError::new(stage, "failed to create shader object"))?;
+    gl.shader_source(&shader, &source.text);
 
     gl.compile_shader(&shader);
 
-    let status = gl.get_shader_parameter(&shader, WebGl2RenderingContext::COMPILE_STATUS);
-    if status.as_bool().unwrap_or(false) {
+    if gl.get_shader_compile_status(&shader) {
         Ok(shader)
     } else {
-        let message = gl
-            .get_shader_info_log(&shader)
-            .unwrap_or("shader compile error".into());
-        Err(JsValue::from(&message))
+        let log = gl.get_shader_info_log(&shader).unwrap_or_default();
+        gl.delete_shader(Some(&shader));
+        Err(ShaderError::compile(stage, &log, source))
     }
 }
@@ -0,0 +1,328 @@
+//! Compile and link failures, parsed out of whatever info log the driver wrote
+//! and shown against the original source.
+
+use super::preprocess::{PreprocessError, PreprocessedSource};
+use std::fmt::{Display, Formatter};
+use wasm_bindgen::JsValue;
+use web_sys::WebGl2RenderingContext;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ShaderStage {
+    Vertex,
+    Fragment,
+    Link,
+}
+
+impl ShaderStage {
+    /// `VERTEX_SHADER` or `FRAGMENT_SHADER`
+    pub fn from_gl(type_: u32) -> Self {
+        if type_ == WebGl2RenderingContext::VERTEX_SHADER {
+            Self::Vertex
+        } else {
+            Self::Fragment
+        }
+    }
+}
+
+impl Display for ShaderStage {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        f.write_str(match self {
+            Self::Vertex => "vertex shader",
+            Self::Fragment => "fragment shader",
+            Self::Link => "program",
+        })
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Severity {
+    Error,
+    Warning,
+}
+
+/// One line of an info log, in the form ANGLE, Adreno, Mesa, NVIDIA or Mali write it.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct LogEntry {
+    pub severity: Severity,
+    /// 1-based, in the source as it was handed to GL
+    pub line: Option<u32>,
+    /// 1-based, where the driver reports one
+    pub column: Option<u32>,
+    pub message: String,
+}
+
+/// Every entry in `log`, without the "N compilation errors" summaries.
+pub fn parse_info_log(log: &str) -> Vec<LogEntry> {
+    log.lines().filter_map(parse_log_line).collect()
+}
+
+fn parse_log_line(line: &str) -> Option<LogEntry> {
+    let mut rest = line.trim_matches(|c: char| c.is_whitespace() || c == '\0');
+    if rest.is_empty() {
+        return None;
+    }
+    // ANGLE and Adreno: `ERROR: 0:12: 'xy' : undeclared identifier`
+    let mut severity = Severity::Error;
+    for (prefix, prefixed) in [("ERROR:", Severity::Error), ("WARNING:", Severity::Warning)] {
+        if let Some(after) = rest.strip_prefix(prefix) {
+            severity = prefixed;
+            rest = after.trim_start();
+        }
+    }
+    let (line, column) = match parse_location(rest) {
+        Some((line, column, after)) => {
+            rest = after.trim_start();
+            (Some(line), column)
+        }
+        None => (None, None),
+    };
+    // Mesa `error: …` and NVIDIA `error C1008: …`
+    if let Some((head, tail)) = rest.split_once(':') {
+        match head.split_whitespace().next() {
+            Some("error") => (severity, rest) = (Severity::Error, tail.trim_start()),
+            Some("warning") => (severity, rest) = (Severity::Warning, tail.trim_start()),
+            _ => {}
+        }
+    }
+    // older Adreno drivers number their messages: `error(#143) Undeclared identifier: xy`
+    if let Some(after) = rest.strip_prefix("error(#") {
+        rest = after
+            .split_once(')')
+            .map_or(after, |(_, tail)| tail.trim_start());
+    }
+    if rest.contains("compilation errors") && rest.contains("No code generated") {
+        return None;
+    }
+    Some(LogEntry {
+        severity,
+        line,
+        column,
+        message: rest.to_string(),
+    })
+}
+
+/// `0:12:` or `0:12(5):` or `0(12) :`, each starting with the source string number
+fn parse_location(s: &str) -> Option<(u32, Option<u32>, &str)> {
+    let (_, rest) = split_number(s)?;
+    if let Some(rest) = rest.strip_prefix(':') {
+        let (line, rest) = split_number(rest)?;
+        let (column, rest) = match rest.strip_prefix('(') {
+            Some(rest) => {
+                let (column, rest) = split_number(rest)?;
+                (Some(column), rest.strip_prefix(')')?)
+            }
+            None => (None, rest),
+        };
+        Some((line, column, rest.trim_start().strip_prefix(':')?))
+    } else {
+        let (line, rest) = split_number(rest.strip_prefix('(')?)?;
+        let rest = rest.strip_prefix(')')?.trim_start().strip_prefix(':')?;
+        Some((line, None, rest))
+    }
+}
+
+fn split_number(s: &str) -> Option<(u32, &str)> {
+    let digits = s.bytes().take_while(u8::is_ascii_digit).count();
+    Some((s[..digits].parse().ok()?, &s[digits..]))
+}
+
+/// A log entry traced back to the file it came from.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ShaderDiagnostic {
+    pub severity: Severity,
+    pub file: Option<String>,
+    /// 1-based, in `file`
+    pub line: Option<u32>,
+    pub column: Option<u32>,
+    pub message: String,
+    /// the text of `line`
+    pub source_line: Option<String>,
+}
+
+impl ShaderDiagnostic {
+    /// Where to put the caret: at the reported column, or else under the first quoted name
+    /// in the message that appears in the line, as `start..end` in chars.
+    fn highlight(&self) -> Option<(usize, usize)> {
+        let source_line = self.source_line.as_ref()?;
+        if let Some(column) = self.column {
+            let start = column.saturating_sub(1) as usize;
+            return Some((start, start + 1));
+        }
+        quoted_names(&self.message).find_map(|name| {
+            let at = find_identifier(source_line, name)?;
+            let start = source_line[..at].chars().count();
+            Some((start, start + name.chars().count()))
+        })
+    }
+}
+
+/// `'xy'`, `` `xy' `` and `"xy"`
+fn quoted_names(message: &str) -> impl Iterator<Item = &str> {
+    let mut rest = message;
+    std::iter::from_fn(move || loop {
+        let open = rest.find(['\'', '`', '"'])?;
+        let quote = rest[open..].chars().next()?;
+        let close_quote = if quote == '`' { '\'' } else { quote };
+        let after = &rest[open + 1..];
+        let close = after.find(close_quote)?;
+        rest = &after[close + 1..];
+        let name = after[..close].trim();
+        if !name.is_empty() {
+            return Some(name);
+        }
+    })
+}
+
+/// the byte offset of `name` in `line`, not as part of a longer identifier
+fn find_identifier(line: &str, name: &str) -> Option<usize> {
+    let is_word = |c: char| c.is_ascii_alphanumeric() || c == '_';
+    line.match_indices(name).map(|(at, _)| at).find(|&at| {
+        let before = line[..at].chars().next_back();
+        let after = line[at + name.len()..].chars().next();
+        !before.is_some_and(is_word) && !after.is_some_and(is_word)
+    })
+}
+
+/// A shader that failed to preprocess, compile or link.
+/// [`Display`] renders every diagnostic with the offending line and a caret under the problem.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ShaderError {
+    pub stage: ShaderStage,
+    /// the file the shader was built from, if it has a name
+    pub file: Option<String>,
+    pub diagnostics: Vec<ShaderDiagnostic>,
+}
+
+impl ShaderError {
+    /// A failure with no info log to go on.
+    pub fn new(stage: ShaderStage, message: &str) -> Self {
+        Self {
+            stage,
+            file: None,
+            diagnostics: vec![ShaderDiagnostic {
+                severity: Severity::Error,
+                file: None,
+                line: None,
+                column: None,
+                message: message.to_string(),
+                source_line: None,
+            }],
+        }
+    }
+
+    /// Parse the info log of a shader compiled from `source`, tracing lines back through any includes.
+    pub fn compile(stage: ShaderStage, log: &str, source: &PreprocessedSource) -> Self {
+        let diagnostics = parse_info_log(log)
+            .into_iter()
+            .map(|entry| {
+                let location = entry.line.and_then(|line| source.location(line));
+                ShaderDiagnostic {
+                    severity: entry.severity,
+                    file: location.map(|(file, _)| file.to_string()),
+                    line: location.map(|(_, line)| line).or(entry.line),
+                    column: entry.column,
+                    message: entry.message,
+                    source_line: entry
+                        .line
+                        .and_then(|line| source.line(line))
+                        .map(str::to_string),
+                }
+            })
+            .collect();
+        Self {
+            stage,
+            file: Some(source.name().to_string()),
+            diagnostics,
+        }
+    }
+
+    /// Parse the info log of a program that failed to link.
+    pub fn link(log: &str) -> Self {
+        let diagnostics = parse_info_log(log)
+            .into_iter()
+            .map(|entry| ShaderDiagnostic {
+                severity: entry.severity,
+                file: None,
+                line: entry.line,
+                column: entry.column,
+                message: entry.message,
+                source_line: None,
+            })
+            .collect();
+        Self {
+            stage: ShaderStage::Link,
+            file: None,
+            diagnostics,
+        }
+    }
+
+    pub fn preprocess(stage: ShaderStage, error: PreprocessError) -> Self {
+        Self {
+            stage,
+            file: Some(error.file.clone()),
+            diagnostics: vec![ShaderDiagnostic {
+                severity: Severity::Error,
+                file: Some(error.file),
+                line: Some(error.line),
+                column: None,
+                message: error.message,
+                source_line: None,
+            }],
+        }
+    }
+
+    /// The first error, which is usually the one to fix.
+    pub fn first_error(&self) -> Option<&ShaderDiagnostic> {
+        self.diagnostics
+            .iter()
+            .find(|diagnostic| diagnostic.severity == Severity::Error)
+    }
+}
+
+impl Display for ShaderError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match (&self.file, self.stage) {
+            (_, ShaderStage::Link) => writeln!(f, "program failed to link")?,
+            (Some(file), stage) => writeln!(f, "{stage} {file} failed to compile")?,
+            (None, stage) => writeln!(f, "{stage} failed to compile")?,
+        }
+        if self.diagnostics.is_empty() {
+            writeln!(f, "(the info log is empty)")?;
+        }
+        for diagnostic in &self.diagnostics {
+            let severity = match diagnostic.severity {
+                Severity::Error => "error",
+                Severity::Warning => "warning",
+            };
+            match (&diagnostic.file, diagnostic.line) {
+                (Some(file), Some(line)) => write!(f, "{file}:{line}: ")?,
+                (None, Some(line)) => write!(f, "line {line}: ")?,
+                _ => {}
+            }
+            writeln!(f, "{severity}: {}", diagnostic.message)?;
+            let (Some(source_line), Some(line)) = (&diagnostic.source_line, diagnostic.line) else {
+                continue;
+            };
+            let gutter = line.to_string();
+            writeln!(f, "{gutter} | {source_line}")?;
+            if let Some((start, end)) = diagnostic.highlight() {
+                // keep tabs so the caret lines up however wide they are drawn
+                let indent: String = source_line
+                    .chars()
+                    .chain(std::iter::repeat(' '))
+                    .take(start)
+                    .map(|c| if c == '\t' { '\t' } else { ' ' })
+                    .collect();
+                let blank = " ".repeat(gutter.len());
+                writeln!(f, "{blank} | {indent}{}", "^".repeat(end - start))?;
+            }
+        }
+        Ok(())
+    }
+}
+
+impl From<ShaderError> for JsValue {
+    fn from(error: ShaderError) -> Self {
+        JsValue::from(error.to_string())
+    }
+}
@@ -0,0 +1,334 @@
+//! `#include` and per-variant `#define`s for the GLSL embedded in the crate.
+//!
+//! Every preprocessed shader starts with [`GLSL_VERSION`] followed by the variant's defines,
+//! so the sources themselves leave out `#version`.
+//! `#ifdef`/`#ifndef`/`#else`/`#endif` are resolved here, which lets an include or a declaration
+//! depend on a variant; `#if` and `#ifdef GL_…` are left for the GLSL compiler.
+//! Each file is included at most once.
+
+use std::borrow::Cow;
+use std::cell::RefCell;
+use std::collections::{BTreeMap, HashMap, HashSet};
+use std::fmt::{Display, Formatter};
+use std::rc::Rc;
+
+pub const GLSL_VERSION: &str = "#version 300 es";
+
+/// Every shader source in the crate, by the name `#include` and [`embedded_variant`] use.
+pub static EMBEDDED_SOURCES: &[(&str, &str)] = &[
+    ("common.glsl", include_str!("../common.glsl")),
+    ("camera.glsl", include_str!("../camera.glsl")),
+    ("flat.vert", include_str!("../flat.vert")),
+    ("flat.frag", include_str!("../flat.frag")),
+    ("texture.vert", include_str!("../texture.vert")),
+    ("texture.frag", include_str!("../texture.frag")),
+    ("bar.vert", include_str!("../bar.vert")),
+    ("bar.frag", include_str!("../bar.frag")),
+    ("text.vert", include_str!("../text.vert")),
+    ("text.frag", include_str!("../text.frag")),
+    ("copy_layer.vert", include_str!("../copy_layer.vert")),
+    ("copy_layer.frag", include_str!("../copy_layer.frag")),
+    ("lights.glsl", include_str!("../lights.glsl")),
+    ("lit.vert", include_str!("../lit.vert")),
+    ("lit.frag", include_str!("../lit.frag")),
+    ("unlit.vert", include_str!("../unlit.vert")),
+    ("unlit.frag", include_str!("../unlit.frag")),
+];
+
+/// The `#define`s that make up a shader variant, kept sorted so equal sets compare and hash equal.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Hash)]
+pub struct ShaderDefines(BTreeMap<String, String>);
+
+impl ShaderDefines {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// `#define name`
+    pub fn with(self, name: &str) -> Self {
+        self.with_value(name, "")
+    }
+
+    /// `#define name value`
+    pub fn with_value(mut self, name: &str, value: impl ToString) -> Self {
+        self.0.insert(name.to_string(), value.to_string());
+        self
+    }
+
+    pub fn is_defined(&self, name: &str) -> bool {
+        self.0.contains_key(name)
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = (&str, &str)> {
+        self.0
+            .iter()
+            .map(|(name, value)| (name.as_str(), value.as_str()))
+    }
+}
+
+/// A shader ready for `shaderSource`, and where each of its lines came from.
+#[derive(Debug)]
+pub struct PreprocessedSource {
+    pub text: String,
+    files: Vec<String>,
+    /// per line of `text`: an index into `files` and the 1-based line there, `None` for generated lines
+    lines: Vec<Option<(usize, u32)>>,
+}
+
+impl PreprocessedSource {
+    /// `text` as it is, as the file `name`.
+    pub fn verbatim(name: &str, text: &str) -> Self {
+        Self {
+            text: text.to_string(),
+            files: vec![name.to_string()],
+            lines: (1..=text.lines().count() as u32)
+                .map(|line| Some((0, line)))
+                .collect(),
+        }
+    }
+
+    /// The file the source was built from.
+    pub fn name(&self) -> &str {
+        self.files.first().map_or("", String::as_str)
+    }
+
+    /// 1-based `line` of [`text`](Self::text).
+    pub fn line(&self, line: u32) -> Option<&str> {
+        self.text.lines().nth(line.checked_sub(1)? as usize)
+    }
+
+    /// The file and line that 1-based `line` of [`text`](Self::text) came from.
+    pub fn location(&self, line: u32) -> Option<(&str, u32)> {
+        let (file, line) = (*self.lines.get(line.checked_sub(1)? as usize)?)?;
+        Some((&self.files[file], line))
+    }
+
+    fn push(&mut self, text: &str, location: Option<(usize, u32)>) {
+        self.text.push_str(text);
+        self.text.push('\n');
+        self.lines.push(location);
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PreprocessError {
+    pub file: String,
+    pub line: u32,
+    pub message: String,
+}
+
+impl Display for PreprocessError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}:{}: {}", self.file, self.line, self.message)
+    }
+}
+
+/// Named GLSL sources, and the variants preprocessed from them so far.
+#[derive(Default)]
+pub struct ShaderLibrary {
+    files: HashMap<String, Cow<'static, str>>,
+    variants: RefCell<HashMap<(String, ShaderDefines), Rc<PreprocessedSource>>>,
+}
+
+impl ShaderLibrary {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// A library of [`EMBEDDED_SOURCES`].
+    pub fn embedded() -> Self {
+        let mut rval = Self::new();
+        for (name, source) in EMBEDDED_SOURCES {
+            rval.insert(name, *source);
+        }
+        rval
+    }
+
+    /// Add or replace a file, forgetting every variant preprocessed so far.
+    pub fn insert(&mut self, name: &str, source: impl Into<Cow<'static, str>>) {
+        self.files.insert(name.to_string(), source.into());
+        self.variants.get_mut().clear();
+    }
+
+    pub fn source(&self, name: &str) -> Option<&str> {
+        self.files.get(name).map(|source| source.as_ref())
+    }
+
+    /// [`preprocess`](Self::preprocess), only once per file and define set.
+    pub fn variant(
+        &self,
+        name: &str,
+        defines: &ShaderDefines,
+    ) -> Result<Rc<PreprocessedSource>, PreprocessError> {
+        let key = (name.to_string(), defines.clone());
+        if let Some(cached) = self.variants.borrow().get(&key) {
+            return Ok(cached.clone());
+        }
+        let preprocessed = Rc::new(self.preprocess(name, defines)?);
+        self.variants.borrow_mut().insert(key, preprocessed.clone());
+        Ok(preprocessed)
+    }
+
+    pub fn cached_variants(&self) -> usize {
+        self.variants.borrow().len()
+    }
+
+    pub fn preprocess(
+        &self,
+        name: &str,
+        defines: &ShaderDefines,
+    ) -> Result<PreprocessedSource, PreprocessError> {
+        let mut expander = Expander {
+            library: self,
+            defined: defines.iter().map(|(name, _)| name.to_string()).collect(),
+            included: HashSet::new(),
+            output: PreprocessedSource {
+                text: String::new(),
+                files: Vec::new(),
+                lines: Vec::new(),
+            },
+        };
+        expander.output.push(GLSL_VERSION, None);
+        for (name, value) in defines.iter() {
+            let line = format!("#define {name} {value}");
+            expander.output.push(line.trim_end(), None);
+        }
+        expander.expand(name, None)?;
+        Ok(expander.output)
+    }
+}
+
+thread_local! {
+    static EMBEDDED_LIBRARY: ShaderLibrary = ShaderLibrary::embedded();
+}
+
+/// [`ShaderLibrary::variant`] of the [`EMBEDDED_SOURCES`], shared by every shader in the crate.
+pub fn embedded_variant(
+    name: &str,
+    defines: &ShaderDefines,
+) -> Result<Rc<PreprocessedSource>, PreprocessError> {
+    EMBEDDED_LIBRARY.with(|library| library.variant(name, defines))
+}
+
+/// One level of `#ifdef` nesting.
+struct Branch {
+    /// whether the lines around the conditional are kept
+    enclosing: bool,
+    active: bool,
+    /// `false` for conditionals passed through to the compiler, whose branches are all kept
+    evaluated: bool,
+}
+
+struct Expander<'a> {
+    library: &'a ShaderLibrary,
+    defined: HashSet<String>,
+    included: HashSet<String>,
+    output: PreprocessedSource,
+}
+
+impl Expander<'_> {
+    fn expand(&mut self, name: &str, from: Option<(&str, u32)>) -> Result<(), PreprocessError> {
+        let error = |(file, line): (&str, u32), message: String| PreprocessError {
+            file: file.to_string(),
+            line,
+            message,
+        };
+        let Some(source) = self.library.source(name) else {
+            let message = format!("no shader source named {name:?}");
+            return Err(error(from.unwrap_or((name, 0)), message));
+        };
+        if !self.included.insert(name.to_string()) {
+            return Ok(());
+        }
+        let file = self.output.files.len();
+        self.output.files.push(name.to_string());
+
+        let mut branches: Vec<Branch> = Vec::new();
+        for (index, text) in source.lines().enumerate() {
+            let line = index as u32 + 1;
+            let here = (name, line);
+            let active = branches.last().is_none_or(|branch| branch.active);
+            let Some(directive) = text.trim_start().strip_prefix('#') else {
+                if active {
+                    self.output.push(text, Some((file, line)));
+                }
+                continue;
+            };
+            let directive = directive.split("//").next().unwrap_or("").trim();
+            let (keyword, argument) = directive
+                .split_once(char::is_whitespace)
+                .map_or((directive, ""), |(keyword, argument)| {
+                    (keyword, argument.trim())
+                });
+            let mut keep = active;
+            match keyword {
+                "version" => keep = false,
+                "include" if active => {
+                    let included = argument
+                        .strip_prefix('"')
+                        .and_then(|argument| argument.strip_suffix('"'))
+                        .ok_or_else(|| {
+                            error(
+                                here,
+                                format!("expected #include \"file\", not {argument:?}"),
+                            )
+                        })?;
+                    self.expand(included, Some(here))?;
+                    keep = false;
+                }
+                "ifdef" | "ifndef" if !argument.starts_with("GL_") => {
+                    let defined = self.defined.contains(argument);
+                    branches.push(Branch {
+                        enclosing: active,
+                        active: active && (defined == (keyword == "ifdef")),
+                        evaluated: true,
+                    });
+                    keep = false;
+                }
+                "ifdef" | "ifndef" | "if" => branches.push(Branch {
+                    enclosing: active,
+                    active,
+                    evaluated: false,
+                }),
+                "elif" | "else" | "endif" => {
+                    let Some(branch) = branches.last_mut() else {
+                        return Err(error(here, format!("#{keyword} without #if")));
+                    };
+                    keep = branch.enclosing && !branch.evaluated;
+                    match keyword {
+                        "elif" if branch.evaluated => {
+                            return Err(error(here, "#elif after #ifdef is not supported".into()));
+                        }
+                        "else" if branch.evaluated => {
+                            branch.active = branch.enclosing && !branch.active
+                        }
+                        "endif" => {
+                            branches.pop();
+                        }
+                        _ => {}
+                    }
+                }
+                "define" if active => {
+                    if let Some(defined) = argument
+                        .split(|c: char| c.is_whitespace() || c == '(')
+                        .next()
+                    {
+                        self.defined.insert(defined.to_string());
+                    }
+                }
+                "undef" if active => {
+                    self.defined.remove(argument);
+                }
+                _ => {}
+            }
+            if keep {
+                self.output.push(text, Some((file, line)));
+            }
+        }
+        if !branches.is_empty() {
+            let line = source.lines().count() as u32;
+            return Err(error((name, line), "missing #endif".into()));
+        }
+        Ok(())
+    }
+}
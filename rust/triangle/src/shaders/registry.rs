@@ -0,0 +1,220 @@
+//! Linked programs shared by every shader wrapper that asks for the same sources and variant,
+//! so ten posters cost one program rather than ten.
+
+use super::preprocess::ShaderDefines;
+use super::ProgramDescription;
+use crate::backend::GlBackend;
+use crate::profiler::performance_now;
+use crate::reflection::{ReflectedProgram, Uniform, UniformType};
+use std::cell::RefCell;
+use std::collections::HashMap;
+use std::fmt::{Display, Formatter};
+use std::ops::Deref;
+use std::rc::{Rc, Weak};
+use wasm_bindgen::JsValue;
+use web_sys::WebGl2RenderingContext;
+
+/// What makes two programs the same: their embedded sources and the variant's defines.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct ProgramKey {
+    pub vertex: &'static str,
+    pub fragment: &'static str,
+    pub defines: ShaderDefines,
+}
+
+impl Display for ProgramKey {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{} + {}", self.vertex, self.fragment)?;
+        let defines: Vec<String> = self
+            .defines
+            .iter()
+            .map(|(name, value)| match value {
+                "" => name.to_string(),
+                value => format!("{name}={value}"),
+            })
+            .collect();
+        if !defines.is_empty() {
+            write!(f, " [{}]", defines.join(", "))?;
+        }
+        Ok(())
+    }
+}
+
+/// One program, linked once and checked against its description's interface.
+pub struct LinkedProgram<G: GlBackend = WebGl2RenderingContext> {
+    pub key: ProgramKey,
+    pub reflected: ReflectedProgram<G>,
+    /// how long compiling and linking took, including reflection
+    pub compile_ms: f64,
+}
+
+/// A counted reference to a [`LinkedProgram`], which dereferences to the GL program.
+/// The program is deleted when the last handle is [released](Self::release).
+/// Dropping the last handle instead leaks the program, which a
+/// [`ResourceRegistry`](crate::gl_thin::ResourceRegistry) reports like any other leak.
+pub struct SharedProgram<G: GlBackend = WebGl2RenderingContext>(Rc<LinkedProgram<G>>);
+
+impl<G: GlBackend> SharedProgram<G> {
+    /// The location `interface` put attribute `name` at.
+    pub fn attribute(&self, name: &str) -> Result<u32, String> {
+        self.0.reflected.attribute(name)
+    }
+
+    pub fn uniform<T: UniformType>(&self, name: &str) -> Result<Uniform<T, G>, String> {
+        self.0.reflected.uniform(name)
+    }
+
+    pub fn key(&self) -> &ProgramKey {
+        &self.0.key
+    }
+
+    pub fn compile_ms(&self) -> f64 {
+        self.0.compile_ms
+    }
+
+    /// how many handles share the program, this one included
+    pub fn users(&self) -> usize {
+        Rc::strong_count(&self.0)
+    }
+
+    /// Delete the program if this is the last handle to it.
+    pub fn release(self, gl: &G) {
+        if let Some(linked) = Rc::into_inner(self.0) {
+            gl.delete_program(Some(&linked.reflected.program));
+        }
+    }
+}
+
+impl<G: GlBackend> Deref for SharedProgram<G> {
+    type Target = G::Program;
+
+    fn deref(&self) -> &G::Program {
+        &self.0.reflected.program
+    }
+}
+
+// not derived, which would require `G: Clone`
+impl<G: GlBackend> Clone for SharedProgram<G> {
+    fn clone(&self) -> Self {
+        Self(self.0.clone())
+    }
+}
+
+/// One compile and link, kept after the program itself is gone.
+#[derive(Debug, Clone, PartialEq)]
+pub struct CompileRecord {
+    pub key: ProgramKey,
+    pub milliseconds: f64,
+}
+
+/// The programs in use on one context, by [`ProgramKey`].
+/// Holds no references of its own, so a program lives exactly as long as its [`SharedProgram`]s.
+pub struct ProgramRegistry<G: GlBackend = WebGl2RenderingContext> {
+    programs: RefCell<HashMap<ProgramKey, Weak<LinkedProgram<G>>>>,
+    compiles: RefCell<Vec<CompileRecord>>,
+    /// milliseconds, any origin
+    clock: Box<dyn Fn() -> f64>,
+}
+
+impl<G: GlBackend> Default for ProgramRegistry<G> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<G: GlBackend> ProgramRegistry<G> {
+    /// Timing compiles with `performance.now()`.
+    pub fn new() -> Self {
+        Self::with_clock(Box::new(performance_now))
+    }
+
+    /// Timing compiles with `clock` (milliseconds).
+    pub fn with_clock(clock: Box<dyn Fn() -> f64>) -> Self {
+        Self {
+            programs: RefCell::new(HashMap::new()),
+            compiles: RefCell::new(Vec::new()),
+            clock,
+        }
+    }
+
+    /// Another handle to the program `description` links with `defines`, linking it first if nobody holds one.
+    /// `setup` runs once, right after linking, for state that belongs to the program (e.g. uniform block bindings).
+    #[track_caller]
+    pub fn link(
+        &self,
+        gl: &G,
+        description: &ProgramDescription,
+        defines: &ShaderDefines,
+        setup: impl FnOnce(&G::Program) -> Result<(), JsValue>,
+    ) -> Result<SharedProgram<G>, JsValue> {
+        let key = ProgramKey {
+            vertex: description.vertex,
+            fragment: description.fragment,
+            defines: defines.clone(),
+        };
+        if let Some(linked) = self.programs.borrow().get(&key).and_then(Weak::upgrade) {
+            return Ok(SharedProgram(linked));
+        }
+
+        let start = (self.clock)();
+        let reflected = description.link_variant(gl, defines)?;
+        if let Err(e) = setup(&reflected.program) {
+            gl.delete_program(Some(&reflected.program));
+            return Err(e);
+        }
+        let compile_ms = (self.clock)() - start;
+
+        self.compiles.borrow_mut().push(CompileRecord {
+            key: key.clone(),
+            milliseconds: compile_ms,
+        });
+        let linked = Rc::new(LinkedProgram {
+            key: key.clone(),
+            reflected,
+            compile_ms,
+        });
+        let mut programs = self.programs.borrow_mut();
+        programs.retain(|_, program| program.strong_count() > 0);
+        programs.insert(key, Rc::downgrade(&linked));
+        Ok(SharedProgram(linked))
+    }
+
+    /// How many programs have at least one handle.
+    pub fn live_programs(&self) -> usize {
+        self.programs
+            .borrow()
+            .values()
+            .filter(|program| program.strong_count() > 0)
+            .count()
+    }
+
+    /// Every compile so far, in order, including programs since released and those lost with a context.
+    pub fn compiles(&self) -> Vec<CompileRecord> {
+        self.compiles.borrow().clone()
+    }
+
+    /// On a restored context: the programs died with the old one, so link afresh even for
+    /// handles that have not been dropped yet.
+    pub fn forget_all(&self) {
+        self.programs.borrow_mut().clear();
+    }
+}
+
+impl<G: GlBackend> Display for ProgramRegistry<G> {
+    /// The total, then each compile from slowest to fastest.
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        let mut compiles = self.compiles();
+        compiles.sort_by(|a, b| b.milliseconds.total_cmp(&a.milliseconds));
+        let total: f64 = compiles.iter().map(|compile| compile.milliseconds).sum();
+        writeln!(
+            f,
+            "{} programs linked in {total:.1} ms ({} in use)",
+            compiles.len(),
+            self.live_programs()
+        )?;
+        for compile in compiles {
+            writeln!(f, "{:>7.1} ms  {}", compile.milliseconds, compile.key)?;
+        }
+        Ok(())
+    }
+}
@@ -0,0 +1,781 @@
+use crate::backend::{ActiveInfo, GlBackend};
+use std::cell::{Cell, RefCell};
+use std::fmt::{Display, Formatter};
+use wasm_bindgen::JsValue;
+use web_sys::{HtmlVideoElement, WebGl2RenderingContext};
+
+/// How many state-setting calls went through to the context and how many were dropped
+/// because they would not have changed anything.
+/// Only the calls [`StateCache`] tracks are counted; draws, uploads and uniforms are not.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct StateStats {
+    pub issued: u32,
+    pub elided: u32,
+}
+
+impl Display for StateStats {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(f, "issued {}, elided {}", self.issued, self.elided)
+    }
+}
+
+/// The state [`StateCache`] believes the context is in.
+/// `None` means unknown, so the next call is always forwarded.
+struct Shadow<G: GlBackend> {
+    program: Option<Option<G::Program>>,
+    vertex_array: Option<Option<G::VertexArray>>,
+    active_texture: Option<u32>,
+    /// `(unit, target, texture)`; a unit/target pair that is not listed is unknown
+    textures: Vec<(u32, u32, Option<G::Texture>)>,
+    draw_framebuffer: Option<Option<G::Framebuffer>>,
+    read_framebuffer: Option<Option<G::Framebuffer>>,
+    viewport: Option<[i32; 4]>,
+    /// `(cap, enabled)`
+    capabilities: Vec<(u32, bool)>,
+    blend_func: Option<(u32, u32)>,
+    depth_func: Option<u32>,
+    depth_mask: Option<bool>,
+}
+
+impl<G: GlBackend> Default for Shadow<G> {
+    fn default() -> Self {
+        Self {
+            program: None,
+            vertex_array: None,
+            active_texture: None,
+            textures: Vec::new(),
+            draw_framebuffer: None,
+            read_framebuffer: None,
+            viewport: None,
+            capabilities: Vec::new(),
+            blend_func: None,
+            depth_func: None,
+            depth_mask: None,
+        }
+    }
+}
+
+/// A [`GlBackend`] that forwards to another one, remembering the current program, VAO,
+/// texture bindings, framebuffer, viewport, blend and depth state,
+/// and dropping calls that would set them to what they already are.
+///
+/// The cache only knows about calls made through it.
+/// Anything that changes state behind its back (raw `web_sys` calls, a lost context)
+/// must be followed by [`Self::invalidate`].
+pub struct StateCache<G: GlBackend = WebGl2RenderingContext> {
+    gl: G,
+    shadow: RefCell<Shadow<G>>,
+    stats: Cell<StateStats>,
+}
+
+impl<G: GlBackend> StateCache<G> {
+    pub fn new(gl: G) -> Self {
+        Self {
+            gl,
+            shadow: RefCell::new(Shadow::default()),
+            stats: Cell::new(StateStats::default()),
+        }
+    }
+
+    /// the context this cache forwards to, for the calls that are not part of [`GlBackend`]
+    pub fn inner(&self) -> &G {
+        &self.gl
+    }
+
+    /// Forget everything, so the next call of each kind goes through to the context.
+    pub fn invalidate(&self) {
+        *self.shadow.borrow_mut() = Shadow::default();
+    }
+
+    /// the counts since the last call to this method; call it once per frame
+    pub fn take_frame_stats(&self) -> StateStats {
+        self.stats.take()
+    }
+
+    fn issued(&self) {
+        let mut stats = self.stats.get();
+        stats.issued += 1;
+        self.stats.set(stats);
+    }
+
+    fn elided(&self) {
+        let mut stats = self.stats.get();
+        stats.elided += 1;
+        self.stats.set(stats);
+    }
+
+    fn set_capability(&self, cap: u32, enabled: bool) {
+        let mut shadow = self.shadow.borrow_mut();
+        match shadow.capabilities.iter_mut().find(|(c, _)| *c == cap) {
+            Some((_, known)) if *known == enabled => self.elided(),
+            Some((_, known)) => {
+                self.forward_capability(cap, enabled);
+                *known = enabled;
+            }
+            None => {
+                self.forward_capability(cap, enabled);
+                shadow.capabilities.push((cap, enabled));
+            }
+        }
+    }
+
+    fn forward_capability(&self, cap: u32, enabled: bool) {
+        if enabled {
+            self.gl.enable(cap);
+        } else {
+            self.gl.disable(cap);
+        }
+        self.issued();
+    }
+
+    /// Forward `call` unless `slot` already holds `value`, and remember `value` either way.
+    fn set<T: PartialEq>(&self, slot: &mut Option<T>, value: T, call: impl FnOnce(&G)) {
+        if slot.as_ref() == Some(&value) {
+            self.elided();
+        } else {
+            call(&self.gl);
+            *slot = Some(value);
+            self.issued();
+        }
+    }
+}
+
+impl<G: GlBackend> GlBackend for StateCache<G> {
+    type Buffer = G::Buffer;
+    type Texture = G::Texture;
+    type VertexArray = G::VertexArray;
+    type Shader = G::Shader;
+    type Program = G::Program;
+    type UniformLocation = G::UniformLocation;
+    type Framebuffer = G::Framebuffer;
+    type Renderbuffer = G::Renderbuffer;
+    type Query = G::Query;
+
+    #[track_caller]
+    fn create_buffer(&self) -> Option<G::Buffer> {
+        self.gl.create_buffer()
+    }
+
+    fn bind_buffer(&self, target: u32, buffer: Option<&G::Buffer>) {
+        self.gl.bind_buffer(target, buffer);
+    }
+
+    fn buffer_data_with_u8_array(&self, target: u32, src_data: &[u8], usage: u32) {
+        self.gl.buffer_data_with_u8_array(target, src_data, usage);
+    }
+
+    fn buffer_data_with_i32(&self, target: u32, size: i32, usage: u32) {
+        self.gl.buffer_data_with_i32(target, size, usage);
+    }
+
+    fn buffer_sub_data_with_i32_and_u8_array(&self, target: u32, offset: i32, src_data: &[u8]) {
+        self.gl
+            .buffer_sub_data_with_i32_and_u8_array(target, offset, src_data);
+    }
+
+    fn bind_buffer_base(&self, target: u32, index: u32, buffer: Option<&G::Buffer>) {
+        self.gl.bind_buffer_base(target, index, buffer);
+    }
+
+    fn delete_buffer(&self, buffer: Option<&G::Buffer>) {
+        self.gl.delete_buffer(buffer);
+    }
+
+    #[track_caller]
+    fn create_vertex_array(&self) -> Option<G::VertexArray> {
+        self.gl.create_vertex_array()
+    }
+
+    fn bind_vertex_array(&self, array: Option<&G::VertexArray>) {
+        let mut shadow = self.shadow.borrow_mut();
+        self.set(&mut shadow.vertex_array, array.cloned(), |gl| {
+            gl.bind_vertex_array(array);
+        });
+    }
+
+    fn delete_vertex_array(&self, array: Option<&G::VertexArray>) {
+        self.gl.delete_vertex_array(array);
+        // deleting the bound VAO reverts to the default one
+        let mut shadow = self.shadow.borrow_mut();
+        if array.is_some() && shadow.vertex_array == Some(array.cloned()) {
+            shadow.vertex_array = Some(None);
+        }
+    }
+
+    fn vertex_attrib_pointer_with_i32(
+        &self,
+        index: u32,
+        size: i32,
+        type_: u32,
+        normalized: bool,
+        stride: i32,
+        offset: i32,
+    ) {
+        self.gl
+            .vertex_attrib_pointer_with_i32(index, size, type_, normalized, stride, offset);
+    }
+
+    fn enable_vertex_attrib_array(&self, index: u32) {
+        self.gl.enable_vertex_attrib_array(index);
+    }
+
+    fn vertex_attrib_divisor(&self, index: u32, divisor: u32) {
+        self.gl.vertex_attrib_divisor(index, divisor);
+    }
+
+    fn create_shader(&self, type_: u32) -> Option<G::Shader> {
+        self.gl.create_shader(type_)
+    }
+
+    fn shader_source(&self, shader: &G::Shader, source: &str) {
+        self.gl.shader_source(shader, source);
+    }
+
+    fn compile_shader(&self, shader: &G::Shader) {
+        self.gl.compile_shader(shader);
+    }
+
+    fn get_shader_compile_status(&self, shader: &G::Shader) -> bool {
+        self.gl.get_shader_compile_status(shader)
+    }
+
+    fn get_shader_info_log(&self, shader: &G::Shader) -> Option<String> {
+        self.gl.get_shader_info_log(shader)
+    }
+
+    fn delete_shader(&self, shader: Option<&G::Shader>) {
+        self.gl.delete_shader(shader);
+    }
+
+    #[track_caller]
+    fn create_program(&self) -> Option<G::Program> {
+        self.gl.create_program()
+    }
+
+    fn attach_shader(&self, program: &G::Program, shader: &G::Shader) {
+        self.gl.attach_shader(program, shader);
+    }
+
+    fn bind_attrib_location(&self, program: &G::Program, index: u32, name: &str) {
+        self.gl.bind_attrib_location(program, index, name);
+    }
+
+    fn link_program(&self, program: &G::Program) {
+        self.gl.link_program(program);
+    }
+
+    fn get_program_link_status(&self, program: &G::Program) -> bool {
+        self.gl.get_program_link_status(program)
+    }
+
+    fn get_program_info_log(&self, program: &G::Program) -> Option<String> {
+        self.gl.get_program_info_log(program)
+    }
+
+    fn use_program(&self, program: Option<&G::Program>) {
+        let mut shadow = self.shadow.borrow_mut();
+        self.set(&mut shadow.program, program.cloned(), |gl| {
+            gl.use_program(program);
+        });
+    }
+
+    fn delete_program(&self, program: Option<&G::Program>) {
+        self.gl.delete_program(program);
+        // a deleted program may come back as a new one with the same handle, so forget it
+        if let Some(program) = program {
+            let shadow = &mut *self.shadow.borrow_mut();
+            if shadow.program.as_ref() == Some(&Some(program.clone())) {
+                shadow.program = None;
+            }
+        }
+    }
+
+    fn get_attrib_location(&self, program: &G::Program, name: &str) -> i32 {
+        self.gl.get_attrib_location(program, name)
+    }
+
+    fn get_active_attrib_count(&self, program: &G::Program) -> u32 {
+        self.gl.get_active_attrib_count(program)
+    }
+
+    fn get_active_attrib(&self, program: &G::Program, index: u32) -> Option<ActiveInfo> {
+        self.gl.get_active_attrib(program, index)
+    }
+
+    fn get_active_uniform_count(&self, program: &G::Program) -> u32 {
+        self.gl.get_active_uniform_count(program)
+    }
+
+    fn get_active_uniform(&self, program: &G::Program, index: u32) -> Option<ActiveInfo> {
+        self.gl.get_active_uniform(program, index)
+    }
+
+    fn get_uniform_location(&self, program: &G::Program, name: &str) -> Option<G::UniformLocation> {
+        self.gl.get_uniform_location(program, name)
+    }
+
+    fn get_uniform_block_index(&self, program: &G::Program, uniform_block_name: &str) -> u32 {
+        self.gl.get_uniform_block_index(program, uniform_block_name)
+    }
+
+    fn uniform_block_binding(
+        &self,
+        program: &G::Program,
+        uniform_block_index: u32,
+        uniform_block_binding: u32,
+    ) {
+        self.gl
+            .uniform_block_binding(program, uniform_block_index, uniform_block_binding);
+    }
+
+    fn uniform1i(&self, location: Option<&G::UniformLocation>, x: i32) {
+        self.gl.uniform1i(location, x);
+    }
+
+    fn uniform1f(&self, location: Option<&G::UniformLocation>, x: f32) {
+        self.gl.uniform1f(location, x);
+    }
+
+    fn uniform3fv_with_f32_array(&self, location: Option<&G::UniformLocation>, data: &[f32]) {
+        self.gl.uniform3fv_with_f32_array(location, data);
+    }
+
+    fn uniform4fv_with_f32_array(&self, location: Option<&G::UniformLocation>, data: &[f32]) {
+        self.gl.uniform4fv_with_f32_array(location, data);
+    }
+
+    fn uniform_matrix3fv_with_f32_array(
+        &self,
+        location: Option<&G::UniformLocation>,
+        transpose: bool,
+        data: &[f32],
+    ) {
+        self.gl
+            .uniform_matrix3fv_with_f32_array(location, transpose, data);
+    }
+
+    fn uniform_matrix4fv_with_f32_array(
+        &self,
+        location: Option<&G::UniformLocation>,
+        transpose: bool,
+        data: &[f32],
+    ) {
+        self.gl
+            .uniform_matrix4fv_with_f32_array(location, transpose, data);
+    }
+
+    #[track_caller]
+    fn create_texture(&self) -> Option<G::Texture> {
+        self.gl.create_texture()
+    }
+
+    fn active_texture(&self, texture: u32) {
+        let mut shadow = self.shadow.borrow_mut();
+        self.set(&mut shadow.active_texture, texture, |gl| {
+            gl.active_texture(texture);
+        });
+    }
+
+    fn bind_texture(&self, target: u32, texture: Option<&G::Texture>) {
+        let mut shadow = self.shadow.borrow_mut();
+        let Some(unit) = shadow.active_texture else {
+            // without knowing the unit there is nothing to compare against
+            self.gl.bind_texture(target, texture);
+            self.issued();
+            return;
+        };
+        match shadow
+            .textures
+            .iter_mut()
+            .find(|(u, t, _)| *u == unit && *t == target)
+        {
+            Some((_, _, bound)) if bound.as_ref() == texture => self.elided(),
+            Some((_, _, bound)) => {
+                self.gl.bind_texture(target, texture);
+                *bound = texture.cloned();
+                self.issued();
+            }
+            None => {
+                self.gl.bind_texture(target, texture);
+                shadow.textures.push((unit, target, texture.cloned()));
+                self.issued();
+            }
+        }
+    }
+
+    fn get_texture_binding_2d(&self) -> Option<G::Texture> {
+        let shadow = self.shadow.borrow();
+        let known = shadow.active_texture.and_then(|unit| {
+            shadow
+                .textures
+                .iter()
+                .find(|(u, t, _)| *u == unit && *t == WebGl2RenderingContext::TEXTURE_2D)
+        });
+        match known {
+            Some((_, _, texture)) => texture.clone(),
+            None => self.gl.get_texture_binding_2d(),
+        }
+    }
+
+    fn tex_image_2d_with_u8_array(
+        &self,
+        target: u32,
+        level: i32,
+        internalformat: i32,
+        width: i32,
+        height: i32,
+        border: i32,
+        format: u32,
+        type_: u32,
+        pixels: Option<&[u8]>,
+    ) -> Result<(), JsValue> {
+        self.gl.tex_image_2d_with_u8_array(
+            target,
+            level,
+            internalformat,
+            width,
+            height,
+            border,
+            format,
+            type_,
+            pixels,
+        )
+    }
+
+    fn compressed_tex_image_2d_with_u8_array(
+        &self,
+        target: u32,
+        level: i32,
+        internalformat: u32,
+        width: i32,
+        height: i32,
+        border: i32,
+        data: &[u8],
+    ) {
+        self.gl.compressed_tex_image_2d_with_u8_array(
+            target,
+            level,
+            internalformat,
+            width,
+            height,
+            border,
+            data,
+        );
+    }
+
+    fn tex_image_2d_with_u32_and_u32_and_html_video_element(
+        &self,
+        target: u32,
+        level: i32,
+        internalformat: i32,
+        format: u32,
+        type_: u32,
+        source: &HtmlVideoElement,
+    ) -> Result<(), JsValue> {
+        self.gl
+            .tex_image_2d_with_u32_and_u32_and_html_video_element(
+                target,
+                level,
+                internalformat,
+                format,
+                type_,
+                source,
+            )
+    }
+
+    fn tex_storage_3d(
+        &self,
+        target: u32,
+        levels: i32,
+        internalformat: u32,
+        width: i32,
+        height: i32,
+        depth: i32,
+    ) {
+        self.gl
+            .tex_storage_3d(target, levels, internalformat, width, height, depth);
+    }
+
+    fn tex_parameteri(&self, target: u32, pname: u32, param: i32) {
+        self.gl.tex_parameteri(target, pname, param);
+    }
+
+    fn tex_parameterf(&self, target: u32, pname: u32, param: f32) {
+        self.gl.tex_parameterf(target, pname, param);
+    }
+
+    fn generate_mipmap(&self, target: u32) {
+        self.gl.generate_mipmap(target);
+    }
+
+    fn delete_texture(&self, texture: Option<&G::Texture>) {
+        self.gl.delete_texture(texture);
+        // deleting a texture unbinds it from every unit
+        if let Some(texture) = texture {
+            for (_, _, bound) in self.shadow.borrow_mut().textures.iter_mut() {
+                if bound.as_ref() == Some(texture) {
+                    *bound = None;
+                }
+            }
+        }
+    }
+
+    #[track_caller]
+    fn create_framebuffer(&self) -> Option<G::Framebuffer> {
+        self.gl.create_framebuffer()
+    }
+
+    fn bind_framebuffer(&self, target: u32, framebuffer: Option<&G::Framebuffer>) {
+        let mut shadow = self.shadow.borrow_mut();
+        let framebuffer_ = framebuffer.cloned();
+        match target {
+            WebGl2RenderingContext::DRAW_FRAMEBUFFER => {
+                self.set(&mut shadow.draw_framebuffer, framebuffer_, |gl| {
+                    gl.bind_framebuffer(target, framebuffer);
+                });
+            }
+            WebGl2RenderingContext::READ_FRAMEBUFFER => {
+                self.set(&mut shadow.read_framebuffer, framebuffer_, |gl| {
+                    gl.bind_framebuffer(target, framebuffer);
+                });
+            }
+            _ => {
+                // FRAMEBUFFER binds both
+                if shadow.draw_framebuffer.as_ref() == Some(&framebuffer_)
+                    && shadow.read_framebuffer.as_ref() == Some(&framebuffer_)
+                {
+                    self.elided();
+                } else {
+                    self.gl.bind_framebuffer(target, framebuffer);
+                    shadow.draw_framebuffer = Some(framebuffer_.clone());
+                    shadow.read_framebuffer = Some(framebuffer_);
+                    self.issued();
+                }
+            }
+        }
+    }
+
+    fn get_draw_framebuffer_binding(&self) -> Option<G::Framebuffer> {
+        // answered from the cache when possible, since a GL query stalls the pipeline
+        match &self.shadow.borrow().draw_framebuffer {
+            Some(framebuffer) => framebuffer.clone(),
+            None => self.gl.get_draw_framebuffer_binding(),
+        }
+    }
+
+    fn framebuffer_texture_2d(
+        &self,
+        target: u32,
+        attachment: u32,
+        textarget: u32,
+        texture: Option<&G::Texture>,
+        level: i32,
+    ) {
+        self.gl
+            .framebuffer_texture_2d(target, attachment, textarget, texture, level);
+    }
+
+    fn framebuffer_renderbuffer(
+        &self,
+        target: u32,
+        attachment: u32,
+        renderbuffertarget: u32,
+        renderbuffer: Option<&G::Renderbuffer>,
+    ) {
+        self.gl
+            .framebuffer_renderbuffer(target, attachment, renderbuffertarget, renderbuffer);
+    }
+
+    fn framebuffer_texture_multiview_ovr(
+        &self,
+        target: u32,
+        attachment: u32,
+        texture: Option<&G::Texture>,
+        level: i32,
+        base_view_index: i32,
+        num_views: i32,
+    ) {
+        self.gl.framebuffer_texture_multiview_ovr(
+            target,
+            attachment,
+            texture,
+            level,
+            base_view_index,
+            num_views,
+        );
+    }
+
+    fn check_framebuffer_status(&self, target: u32) -> u32 {
+        self.gl.check_framebuffer_status(target)
+    }
+
+    fn blit_framebuffer(
+        &self,
+        src_x0: i32,
+        src_y0: i32,
+        src_x1: i32,
+        src_y1: i32,
+        dst_x0: i32,
+        dst_y0: i32,
+        dst_x1: i32,
+        dst_y1: i32,
+        mask: u32,
+        filter: u32,
+    ) {
+        self.gl.blit_framebuffer(
+            src_x0, src_y0, src_x1, src_y1, dst_x0, dst_y0, dst_x1, dst_y1, mask, filter,
+        );
+    }
+
+    fn delete_framebuffer(&self, framebuffer: Option<&G::Framebuffer>) {
+        self.gl.delete_framebuffer(framebuffer);
+        // deleting a bound framebuffer reverts that binding to the default one
+        if let Some(framebuffer) = framebuffer {
+            let shadow = &mut *self.shadow.borrow_mut();
+            for binding in [&mut shadow.draw_framebuffer, &mut shadow.read_framebuffer] {
+                if binding.as_ref() == Some(&Some(framebuffer.clone())) {
+                    *binding = Some(None);
+                }
+            }
+        }
+    }
+
+    #[track_caller]
+    fn create_renderbuffer(&self) -> Option<G::Renderbuffer> {
+        self.gl.create_renderbuffer()
+    }
+
+    fn bind_renderbuffer(&self, target: u32, renderbuffer: Option<&G::Renderbuffer>) {
+        self.gl.bind_renderbuffer(target, renderbuffer);
+    }
+
+    fn get_renderbuffer_binding(&self) -> Option<G::Renderbuffer> {
+        self.gl.get_renderbuffer_binding()
+    }
+
+    fn renderbuffer_storage(&self, target: u32, internalformat: u32, width: i32, height: i32) {
+        self.gl
+            .renderbuffer_storage(target, internalformat, width, height);
+    }
+
+    fn renderbuffer_storage_multisample(
+        &self,
+        target: u32,
+        samples: i32,
+        internalformat: u32,
+        width: i32,
+        height: i32,
+    ) {
+        self.gl
+            .renderbuffer_storage_multisample(target, samples, internalformat, width, height);
+    }
+
+    fn delete_renderbuffer(&self, renderbuffer: Option<&G::Renderbuffer>) {
+        self.gl.delete_renderbuffer(renderbuffer);
+    }
+
+    fn clear_color(&self, red: f32, green: f32, blue: f32, alpha: f32) {
+        self.gl.clear_color(red, green, blue, alpha);
+    }
+
+    fn clear(&self, mask: u32) {
+        self.gl.clear(mask);
+    }
+
+    fn viewport(&self, x: i32, y: i32, width: i32, height: i32) {
+        let mut shadow = self.shadow.borrow_mut();
+        self.set(&mut shadow.viewport, [x, y, width, height], |gl| {
+            gl.viewport(x, y, width, height);
+        });
+    }
+
+    fn get_viewport(&self) -> [i32; 4] {
+        match self.shadow.borrow().viewport {
+            Some(viewport) => viewport,
+            None => self.gl.get_viewport(),
+        }
+    }
+
+    fn enable(&self, cap: u32) {
+        self.set_capability(cap, true);
+    }
+
+    fn disable(&self, cap: u32) {
+        self.set_capability(cap, false);
+    }
+
+    fn blend_func(&self, sfactor: u32, dfactor: u32) {
+        let mut shadow = self.shadow.borrow_mut();
+        self.set(&mut shadow.blend_func, (sfactor, dfactor), |gl| {
+            gl.blend_func(sfactor, dfactor);
+        });
+    }
+
+    fn depth_func(&self, func: u32) {
+        let mut shadow = self.shadow.borrow_mut();
+        self.set(&mut shadow.depth_func, func, |gl| gl.depth_func(func));
+    }
+
+    fn depth_mask(&self, flag: bool) {
+        let mut shadow = self.shadow.borrow_mut();
+        self.set(&mut shadow.depth_mask, flag, |gl| gl.depth_mask(flag));
+    }
+
+    fn has_extension(&self, name: &str) -> bool {
+        self.gl.has_extension(name)
+    }
+
+    #[track_caller]
+    fn create_query(&self) -> Option<G::Query> {
+        self.gl.create_query()
+    }
+
+    fn begin_query(&self, target: u32, query: &G::Query) {
+        self.gl.begin_query(target, query);
+    }
+
+    fn end_query(&self, target: u32) {
+        self.gl.end_query(target);
+    }
+
+    fn get_query_result_available(&self, query: &G::Query) -> bool {
+        self.gl.get_query_result_available(query)
+    }
+
+    fn get_query_result(&self, query: &G::Query) -> u64 {
+        self.gl.get_query_result(query)
+    }
+
+    fn delete_query(&self, query: Option<&G::Query>) {
+        self.gl.delete_query(query);
+    }
+
+    fn get_gpu_disjoint(&self) -> bool {
+        self.gl.get_gpu_disjoint()
+    }
+
+    fn draw_arrays(&self, mode: u32, first: i32, count: i32) {
+        self.gl.draw_arrays(mode, first, count);
+    }
+
+    fn draw_elements_with_i32(&self, mode: u32, count: i32, type_: u32, offset: i32) {
+        self.gl.draw_elements_with_i32(mode, count, type_, offset);
+    }
+
+    fn draw_arrays_instanced(&self, mode: u32, first: i32, count: i32, instance_count: i32) {
+        self.gl
+            .draw_arrays_instanced(mode, first, count, instance_count);
+    }
+
+    fn draw_elements_instanced_with_i32(
+        &self,
+        mode: u32,
+        count: i32,
+        type_: u32,
+        offset: i32,
+        instance_count: i32,
+    ) {
+        self.gl
+            .draw_elements_instanced_with_i32(mode, count, type_, offset, instance_count);
+    }
+}
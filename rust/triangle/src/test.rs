@@ -1,7 +1,8 @@
 use crate::to_mat4;
-use cgmath::*;
+use glam::vec4;
 
 #[test]
+#[allow(clippy::excessive_precision)]
 fn test1() {
     let proj = [
         0.9027383923530579,
@@ -51,3 +52,2783 @@ fn test1() {
     let origin = vec4(0.0, 0.0, 0.0, 1.0);
     println!("mvp*origin = {:?}", mvp * origin);
 }
+
+#[test]
+fn poster_draws_with_recording_backend() {
+    use crate::recording::{GlCall, RecordingGl};
+    use crate::DrawLogic;
+    use web_sys::WebGl2RenderingContext as GL;
+
+    let gl = RecordingGl::new();
+    let mut draw_logic = DrawLogic::new(&gl).unwrap();
+
+    let poster_texture = gl
+        .calls()
+        .iter()
+        .find_map(|call| match call {
+            GlCall::CreateTexture(handle) => Some(*handle),
+            _ => None,
+        })
+        .unwrap();
+
+    gl.take_calls();
+    draw_logic
+        .draw_view(&gl, &crate::camera::CameraBlock::identity())
+        .unwrap();
+    let calls = gl.take_calls();
+
+    let bind = calls
+        .iter()
+        .position(|call| {
+            *call
+                == GlCall::BindTexture {
+                    target: GL::TEXTURE_2D,
+                    texture: Some(poster_texture),
+                }
+        })
+        .unwrap();
+    assert_eq!(calls[bind - 1], GlCall::ActiveTexture(GL::TEXTURE0));
+    assert!(calls.contains(&GlCall::DrawElements {
+        mode: GL::TRIANGLES,
+        count: 6,
+        type_: GL::UNSIGNED_BYTE,
+        offset: 0,
+    }));
+    assert!(calls.contains(&GlCall::DrawArrays {
+        mode: GL::TRIANGLES,
+        first: 0,
+        count: 3,
+    }));
+}
+
+#[test]
+fn xyrgb_layout_matches_hand_written_pointers() {
+    use crate::objects::XYRGB;
+    use crate::vertex_layout::VertexLayout;
+    use web_sys::WebGl2RenderingContext as GL;
+
+    let format = XYRGB::format();
+    assert_eq!(format.stride, 12);
+    let rgb = &format.attributes[1];
+    assert_eq!(
+        (
+            rgb.name,
+            rgb.gl_type,
+            rgb.components,
+            rgb.normalized,
+            rgb.offset
+        ),
+        ("rgb", GL::UNSIGNED_BYTE, 3, true, 8)
+    );
+
+    let bytes = XYRGB::to_bytes(&[(1.0, -1.0, 0xff, 0, 0x80).into()]);
+    let mut expected = Vec::new();
+    expected.extend_from_slice(&1.0f32.to_le_bytes());
+    expected.extend_from_slice(&(-1.0f32).to_le_bytes());
+    expected.extend_from_slice(&[0xff, 0, 0x80, 0]);
+    assert_eq!(bytes, expected);
+}
+
+#[test]
+fn position_normal_uv_layout() {
+    use crate::recording::{GlCall, RecordingGl};
+    use crate::vertex_layout::{vertex_layout, VertexLayout};
+    use glam::{Vec2, Vec3};
+    use web_sys::WebGl2RenderingContext as GL;
+
+    vertex_layout! {
+        struct PositionNormalUv {
+            position: Vec3,
+            normal: Vec3,
+            uv: Vec2,
+        }
+    }
+
+    let gl = RecordingGl::new();
+    PositionNormalUv::format().vertex_attrib_pointers(&gl, &[4, 5, 6]);
+    let pointers: Vec<_> = gl
+        .calls()
+        .into_iter()
+        .filter_map(|call| match call {
+            GlCall::VertexAttribPointer {
+                index,
+                size,
+                type_,
+                stride,
+                offset,
+                ..
+            } => Some((index, size, type_, stride, offset)),
+            _ => None,
+        })
+        .collect();
+    assert_eq!(
+        pointers,
+        [
+            (4, 3, GL::FLOAT, 32, 0),
+            (5, 3, GL::FLOAT, 32, 12),
+            (6, 2, GL::FLOAT, 32, 24)
+        ]
+    );
+}
+
+#[test]
+fn index_buffer_picks_draw_type() {
+    use crate::gl_thin::IndexBuffer;
+    use crate::recording::{GlCall, RecordingGl};
+    use web_sys::WebGl2RenderingContext as GL;
+
+    let gl = RecordingGl::new();
+
+    // sierpinski(.., 6) is 4^6 tetrahedra of 12 vertices each
+    let level_6: Vec<u32> = (0..4u32.pow(6) * 12).collect();
+    let indices = IndexBuffer::new_bound_narrowest(&gl, &level_6, GL::STATIC_DRAW).unwrap();
+    assert_eq!(indices.index_type, GL::UNSIGNED_SHORT);
+
+    let huge: Vec<u32> = (0..70_000).collect();
+    let huge = IndexBuffer::new_bound_narrowest(&gl, &huge, GL::STATIC_DRAW).unwrap();
+    gl.take_calls();
+    huge.draw_elements(&gl, GL::TRIANGLES);
+    assert_eq!(
+        gl.calls(),
+        [GlCall::DrawElements {
+            mode: GL::TRIANGLES,
+            count: 70_000,
+            type_: GL::UNSIGNED_INT,
+            offset: 0
+        }]
+    );
+
+    let small = IndexBuffer::new_bound(&gl, &[0u16, 1, 2], GL::STATIC_DRAW).unwrap();
+    assert_eq!(
+        (small.index_type, small.index_count),
+        (GL::UNSIGNED_SHORT, 3)
+    );
+}
+
+#[test]
+fn half_float_payload() {
+    use crate::gl_thin::ToBufferPayload;
+    use half::f16;
+
+    let payload = [f16::from_f32(1.0), f16::from_f32(-2.0)].to_buffer_payload();
+    assert_eq!(payload, [0x00, 0x3c, 0x00, 0xc0]);
+    assert_eq!([-1i16, 2].to_buffer_payload(), [0xff, 0xff, 0x02, 0x00]);
+}
+
+#[test]
+fn growable_buffer_reallocates_only_when_outgrown() {
+    use crate::gl_thin::HomogeneousGlBuffer;
+    use crate::recording::{GlCall, RecordingGl};
+    use web_sys::WebGl2RenderingContext as GL;
+
+    let gl = RecordingGl::new();
+    let mut buffer =
+        HomogeneousGlBuffer::<f32, _>::with_capacity(&gl, 4, GL::ARRAY_BUFFER, GL::DYNAMIC_DRAW)
+            .unwrap();
+    assert_eq!((buffer.len(), buffer.capacity()), (0, 4));
+
+    gl.take_calls();
+    buffer.upload(&gl, &[1.0, 2.0, 3.0]).unwrap();
+    assert!(!gl
+        .take_calls()
+        .iter()
+        .any(|call| matches!(call, GlCall::BufferDataSize { .. })));
+    assert_eq!(buffer.len(), 3);
+
+    buffer.upload(&gl, &[0.0; 5]).unwrap();
+    assert!(gl.take_calls().contains(&GlCall::BufferDataSize {
+        target: GL::ARRAY_BUFFER,
+        size: 8 * 4,
+        usage: GL::DYNAMIC_DRAW
+    }));
+    assert_eq!((buffer.len(), buffer.capacity()), (5, 8));
+
+    buffer.update(&gl, 6, &[7.0]).unwrap();
+    assert_eq!(
+        gl.take_calls().last(),
+        Some(&GlCall::BufferSubData {
+            target: GL::ARRAY_BUFFER,
+            offset: 6 * 4,
+            data: 7.0f32.to_le_bytes().to_vec()
+        })
+    );
+    assert_eq!(buffer.len(), 7);
+    assert!(buffer.update(&gl, 7, &[0.0, 0.0]).is_err());
+}
+
+#[test]
+fn ring_buffer_rotates() {
+    use crate::gl_thin::{ResourceKind, ResourceRegistry, RingGlBuffer};
+    use crate::recording::RecordingGl;
+    use web_sys::WebGl2RenderingContext as GL;
+
+    let gl = RecordingGl::new();
+    let mut ring =
+        RingGlBuffer::<f32, _>::new_triple(&gl, 16, GL::ARRAY_BUFFER, GL::STREAM_DRAW).unwrap();
+    let seen: Vec<_> = (0..4)
+        .map(|frame| ring.next_frame(&gl, &[frame as f32]).unwrap().buffer)
+        .collect();
+    assert_ne!(seen[0], seen[1]);
+    assert_ne!(seen[1], seen[2]);
+    assert_eq!(seen[0], seen[3]);
+    assert_eq!(ring.current().len(), 1);
+
+    // running out on the last buffer of the ring gives back the first two
+    let gl = ResourceRegistry::new(RecordingGl::new());
+    gl.inner().fail_creations_after(2);
+    assert!(
+        RingGlBuffer::<f32, _>::new_triple(&gl, 16, GL::ARRAY_BUFFER, GL::STREAM_DRAW).is_err()
+    );
+    assert_eq!(gl.live_count(ResourceKind::Buffer), 0);
+}
+
+#[test]
+fn draw_logic_releases_everything_it_creates() {
+    use crate::gl_thin::{ResourceKind, ResourceRegistry};
+    use crate::recording::RecordingGl;
+    use crate::DrawLogic;
+
+    let gl = ResourceRegistry::new(RecordingGl::new());
+    let draw_logic = DrawLogic::new(&gl).unwrap();
+    assert_eq!(gl.live_count(ResourceKind::Program), 3);
+    // the poster's placeholder and the pyramid's white stand-in
+    assert_eq!(gl.live_count(ResourceKind::Texture), 2);
+    assert_eq!(gl.live_count(ResourceKind::VertexArray), 3);
+    // triangle, poster vertices, poster indices, pyramid vertices, pyramid indices,
+    // camera uniforms, light uniforms
+    assert_eq!(gl.live_count(ResourceKind::Buffer), 7);
+
+    draw_logic.release(&gl);
+    let report = gl.leak_report();
+    assert!(report.is_empty(), "{report}");
+}
+
+#[test]
+fn draw_logic_recreates_after_context_loss() {
+    use crate::gl_thin::{ResourceKind, ResourceRegistry};
+    use crate::recording::{GlCall, RecordingGl};
+    use crate::DrawLogic;
+
+    let gl = ResourceRegistry::new(RecordingGl::new());
+    let mut draw_logic = DrawLogic::new(&gl).unwrap();
+
+    // the old handles died with the context; nothing may be deleted through the new one
+    gl.forget_all();
+    gl.inner().take_calls();
+    draw_logic.recreate(&gl).unwrap();
+    assert!(!gl.inner().calls().iter().any(|call| matches!(
+        call,
+        GlCall::DeleteBuffer(_)
+            | GlCall::DeleteVertexArray(_)
+            | GlCall::DeleteProgram(_)
+            | GlCall::DeleteTexture(_)
+    )));
+    assert_eq!(gl.live_count(ResourceKind::Program), 3);
+    assert_eq!(gl.live_count(ResourceKind::Texture), 2);
+    assert_eq!(gl.live_count(ResourceKind::VertexArray), 3);
+    assert_eq!(gl.live_count(ResourceKind::Buffer), 7);
+
+    draw_logic.release(&gl);
+    let report = gl.leak_report();
+    assert!(report.is_empty(), "{report}");
+}
+
+#[test]
+fn leak_report_names_creation_site() {
+    use crate::gl_thin::{HomogeneousGlBuffer, ResourceKind, ResourceRegistry};
+    use crate::recording::{GlCall, RecordingGl};
+    use web_sys::WebGl2RenderingContext as GL;
+
+    let gl = ResourceRegistry::new(RecordingGl::new());
+    let line = line!() + 1;
+    let buffer = HomogeneousGlBuffer::new_bound(&gl, &[1.0f32], GL::ARRAY_BUFFER, GL::STATIC_DRAW);
+    let buffer = buffer.unwrap().buffer;
+
+    let report = gl.leak_report();
+    assert_eq!(report.leaks.len(), 1);
+    assert_eq!(report.leaks[0].kind, ResourceKind::Buffer);
+    if cfg!(debug_assertions) {
+        let site = report.leaks[0].site.unwrap();
+        assert_eq!((site.file(), site.line()), (file!(), line));
+    }
+
+    gl.release_all();
+    assert_eq!(gl.live_count(ResourceKind::Buffer), 0);
+    assert_eq!(
+        gl.inner().calls().last(),
+        Some(&GlCall::DeleteBuffer(Some(buffer)))
+    );
+}
+
+#[test]
+fn camera_uploaded_once_per_view() {
+    use crate::camera::{CameraBlock, CAMERA_BINDING};
+    use crate::recording::{GlCall, RecordingGl};
+    use crate::DrawLogic;
+    use glam::{vec3, Mat4};
+    use web_sys::WebGl2RenderingContext as GL;
+
+    let gl = RecordingGl::new();
+    let mut draw_logic = DrawLogic::new(&gl).unwrap();
+    // every program attaches its Camera block to the shared binding point
+    let block_bindings = gl
+        .calls()
+        .into_iter()
+        .filter(|call| {
+            matches!(call, GlCall::UniformBlockBinding { uniform_block_binding, .. } if *uniform_block_binding == CAMERA_BINDING)
+        })
+        .count();
+    assert_eq!(block_bindings, 3);
+
+    gl.take_calls();
+    let projection = Mat4::perspective_rh_gl(1.0, 1.0, 0.1, 100.0);
+    for eye_index in 0..2 {
+        let eye = vec3(eye_index as f32 * 0.06 - 0.03, 1.6, 0.0);
+        let camera = CameraBlock::new(
+            &projection,
+            &Mat4::from_translation(eye).inverse(),
+            eye,
+            eye_index,
+        );
+        draw_logic.draw_view(&gl, &camera).unwrap();
+    }
+    let calls = gl.take_calls();
+
+    let camera_uploads: Vec<_> = calls
+        .iter()
+        .filter_map(|call| match call {
+            GlCall::BufferSubData { target, data, .. } if *target == GL::UNIFORM_BUFFER => {
+                Some(data)
+            }
+            _ => None,
+        })
+        .collect();
+    assert_eq!(camera_uploads.len(), 2);
+    assert_eq!(camera_uploads[1].len(), 208);
+    assert_eq!(camera_uploads[1][204..208], 1i32.to_le_bytes());
+    assert_eq!(camera_uploads[1][192..196], 0.03f32.to_le_bytes());
+
+    // the objects only push their model matrix
+    let model_uploads = calls
+        .iter()
+        .filter(|call| matches!(call, GlCall::UniformMatrix4fv { .. }))
+        .count();
+    assert_eq!(model_uploads, 6);
+}
+
+#[test]
+fn instanced_triangles_draw_in_one_call() {
+    use crate::objects::{GradientTriangles, InstanceData};
+    use crate::recording::{GlCall, RecordingGl};
+    use crate::shaders::registry::ProgramRegistry;
+    use glam::{Mat4, Vec3};
+    use web_sys::WebGl2RenderingContext as GL;
+
+    let gl = RecordingGl::new();
+    let instances: Vec<InstanceData> = (0..100)
+        .map(|i| InstanceData::new(Mat4::from_translation(Vec3::new(i as f32, 0.0, -1.0))))
+        .collect();
+    let programs = ProgramRegistry::new();
+    let mut triangles = GradientTriangles::new(&gl, &programs, &instances).unwrap();
+
+    let setup = gl.take_calls();
+    // 4 columns of the model matrix plus the tint advance per instance, the vertex attributes do not
+    let divisors: Vec<_> = setup
+        .iter()
+        .filter_map(|call| match call {
+            GlCall::VertexAttribDivisor { index, divisor } => Some((*index, *divisor)),
+            _ => None,
+        })
+        .collect();
+    let model = triangles.shader.sal_model;
+    assert_eq!(
+        divisors[..4],
+        [(model, 1), (model + 1, 1), (model + 2, 1), (model + 3, 1)]
+    );
+    assert_eq!(divisors[4], (triangles.shader.sal_tint, 1));
+    assert_eq!(divisors.len(), 5);
+    assert!(setup.contains(&GlCall::VertexAttribPointer {
+        index: model + 3,
+        size: 4,
+        type_: GL::FLOAT,
+        normalized: false,
+        stride: 80,
+        offset: 48,
+    }));
+
+    triangles.draw(&gl);
+    let calls = gl.take_calls();
+    assert!(calls.contains(&GlCall::DrawArraysInstanced {
+        mode: GL::TRIANGLES,
+        first: 0,
+        count: 3,
+        instance_count: 100,
+    }));
+    assert!(!calls
+        .iter()
+        .any(|call| matches!(call, GlCall::UniformMatrix4fv { .. })));
+
+    triangles.set_instances(&gl, &instances[..10]);
+    triangles.draw(&gl);
+    assert!(gl.take_calls().contains(&GlCall::DrawArraysInstanced {
+        mode: GL::TRIANGLES,
+        first: 0,
+        count: 3,
+        instance_count: 10,
+    }));
+
+    triangles.release(&gl);
+}
+
+#[test]
+fn state_cache_elides_redundant_binds() {
+    use crate::recording::{GlCall, RecordingGl};
+    use crate::state_cache::{StateCache, StateStats};
+    use crate::DrawLogic;
+
+    let gl = StateCache::new(RecordingGl::new());
+    let mut draw_logic = DrawLogic::new(&gl).unwrap();
+    let camera = crate::camera::CameraBlock::identity();
+
+    draw_logic.draw_view(&gl, &camera).unwrap();
+    gl.take_frame_stats();
+    gl.inner().take_calls();
+
+    // the texture unit and depth function carry over from the previous view;
+    // the programs, VAOs and the poster's and pyramid's textures alternate so they must be issued again,
+    // as must the depth test, which is off between views
+    draw_logic.draw_view(&gl, &camera).unwrap();
+    assert_eq!(
+        gl.take_frame_stats(),
+        StateStats {
+            issued: 12,
+            elided: 3
+        }
+    );
+    let calls = gl.inner().take_calls();
+    assert!(!calls
+        .iter()
+        .any(|call| matches!(call, GlCall::ActiveTexture(_))));
+
+    draw_logic.release(&gl);
+}
+
+#[test]
+fn lit_meshes_are_drawn_with_the_depth_test_on() {
+    use crate::recording::{GlCall, RecordingGl};
+    use crate::DrawLogic;
+    use web_sys::WebGl2RenderingContext as GL;
+
+    let gl = RecordingGl::new();
+    let mut draw_logic = DrawLogic::new(&gl).unwrap();
+    gl.take_calls();
+    draw_logic
+        .draw_view(&gl, &crate::camera::CameraBlock::identity())
+        .unwrap();
+
+    // whether the depth test was on for each draw, by the program it used
+    let pyramid = *draw_logic.pyramid.shader.program;
+    let (mut depth_test, mut program) = (false, None);
+    let mut lit_draws = Vec::new();
+    for call in gl.take_calls() {
+        match call {
+            GlCall::Enable(GL::DEPTH_TEST) => depth_test = true,
+            GlCall::Disable(GL::DEPTH_TEST) => depth_test = false,
+            GlCall::DepthFunc(func) => assert_eq!(func, GL::LESS),
+            GlCall::UseProgram(used) => program = used,
+            GlCall::DrawElements { .. } if program == Some(pyramid) => lit_draws.push(depth_test),
+            _ => {}
+        }
+    }
+    assert_eq!(lit_draws, [true]);
+    // and off again for whatever comes next
+    assert!(!depth_test);
+
+    draw_logic.release(&gl);
+}
+
+#[test]
+fn state_cache_tracks_framebuffers_and_capabilities() {
+    use crate::backend::GlBackend;
+    use crate::recording::{GlCall, RecordingGl};
+    use crate::state_cache::StateCache;
+    use web_sys::WebGl2RenderingContext as GL;
+
+    let gl = StateCache::new(RecordingGl::new());
+    let framebuffer = 7;
+
+    gl.bind_framebuffer(GL::FRAMEBUFFER, Some(&framebuffer));
+    gl.bind_framebuffer(GL::DRAW_FRAMEBUFFER, Some(&framebuffer));
+    gl.bind_framebuffer(GL::READ_FRAMEBUFFER, None);
+    gl.bind_framebuffer(GL::FRAMEBUFFER, Some(&framebuffer));
+    gl.enable(GL::DEPTH_TEST);
+    gl.enable(GL::DEPTH_TEST);
+    gl.disable(GL::BLEND);
+    gl.depth_mask(true);
+    gl.depth_mask(true);
+    gl.viewport(0, 0, 10, 10);
+    gl.viewport(0, 0, 10, 10);
+
+    assert_eq!(
+        gl.inner().take_calls(),
+        [
+            GlCall::BindFramebuffer {
+                target: GL::FRAMEBUFFER,
+                framebuffer: Some(framebuffer)
+            },
+            GlCall::BindFramebuffer {
+                target: GL::READ_FRAMEBUFFER,
+                framebuffer: None
+            },
+            GlCall::BindFramebuffer {
+                target: GL::FRAMEBUFFER,
+                framebuffer: Some(framebuffer)
+            },
+            GlCall::Enable(GL::DEPTH_TEST),
+            GlCall::Disable(GL::BLEND),
+            GlCall::DepthMask(true),
+            GlCall::Viewport {
+                x: 0,
+                y: 0,
+                width: 10,
+                height: 10
+            },
+        ]
+    );
+
+    gl.invalidate();
+    gl.enable(GL::DEPTH_TEST);
+    assert_eq!(gl.inner().take_calls(), [GlCall::Enable(GL::DEPTH_TEST)]);
+
+    // a program made after deleting the one in use may reuse its handle
+    let program = 3;
+    gl.use_program(Some(&program));
+    gl.delete_program(Some(&program));
+    gl.use_program(Some(&program));
+    assert_eq!(
+        gl.inner().take_calls(),
+        [
+            GlCall::UseProgram(Some(program)),
+            GlCall::DeleteProgram(Some(program)),
+            GlCall::UseProgram(Some(program)),
+        ]
+    );
+}
+
+#[test]
+fn msaa_render_target_resolves_and_restores_bindings() {
+    use crate::backend::GlBackend;
+    use crate::gl_thin::{
+        AttachmentStorage, RenderTarget, RenderTargetDescription, ResourceKind, ResourceRegistry,
+    };
+    use crate::recording::{GlCall, RecordingGl};
+    use web_sys::WebGl2RenderingContext as GL;
+
+    let gl = ResourceRegistry::new(RecordingGl::new());
+    let xr_framebuffer = 99;
+    gl.bind_framebuffer(GL::FRAMEBUFFER, Some(&xr_framebuffer));
+    gl.viewport(0, 0, 1832, 1920);
+
+    assert!(RenderTarget::new(
+        &gl,
+        &RenderTargetDescription {
+            color: AttachmentStorage::Texture,
+            ..RenderTargetDescription::multisampled(256, 256, 4)
+        }
+    )
+    .is_err());
+
+    let msaa = RenderTarget::new(&gl, &RenderTargetDescription::multisampled(256, 256, 4)).unwrap();
+    let resolved = RenderTarget::new(&gl, &RenderTargetDescription::sampled(256, 256)).unwrap();
+    assert!(msaa.color_texture().is_none());
+    assert!(resolved.color_texture().is_some());
+    assert_eq!(gl.get_draw_framebuffer_binding(), Some(xr_framebuffer));
+
+    let setup = gl.inner().take_calls();
+    assert!(setup.contains(&GlCall::RenderbufferStorageMultisample {
+        target: GL::RENDERBUFFER,
+        samples: 4,
+        internalformat: GL::RGBA8,
+        width: 256,
+        height: 256,
+    }));
+
+    let drawn_into = msaa.draw_with(&gl, |gl| gl.get_draw_framebuffer_binding());
+    assert_eq!(drawn_into, Some(msaa.framebuffer));
+    assert_eq!(gl.get_draw_framebuffer_binding(), Some(xr_framebuffer));
+    assert_eq!(gl.get_viewport(), [0, 0, 1832, 1920]);
+
+    msaa.resolve_into(&gl, &resolved).unwrap();
+    assert!(gl.inner().calls().contains(&GlCall::BlitFramebuffer {
+        src: [0, 0, 256, 256],
+        dst: [0, 0, 256, 256],
+        mask: GL::COLOR_BUFFER_BIT,
+        filter: GL::LINEAR,
+    }));
+    assert_eq!(gl.get_draw_framebuffer_binding(), Some(xr_framebuffer));
+
+    msaa.release(&gl);
+    resolved.release(&gl);
+
+    // the framebuffer and both attachments are blamed on whoever made the target
+    let line = line!() + 1;
+    let target = RenderTarget::new(&gl, &RenderTargetDescription::multisampled(64, 64, 4)).unwrap();
+    let report = gl.leak_report();
+    assert_eq!(report.leaks.len(), 3);
+    for leak in &report.leaks {
+        let site = leak.site.unwrap();
+        assert_eq!((site.file(), site.line()), (file!(), line), "{leak:?}");
+    }
+    target.release(&gl);
+
+    // running out halfway: the color renderbuffer is made, the depth one is not
+    let (texture, renderbuffer) = (gl.create_texture(), gl.create_renderbuffer());
+    gl.bind_texture(GL::TEXTURE_2D, texture.as_ref());
+    gl.bind_renderbuffer(GL::RENDERBUFFER, renderbuffer.as_ref());
+    gl.inner().fail_creations_after(2);
+    assert!(RenderTarget::new(&gl, &RenderTargetDescription::multisampled(256, 256, 4)).is_err());
+    assert_eq!(gl.get_draw_framebuffer_binding(), Some(xr_framebuffer));
+    assert_eq!(gl.get_texture_binding_2d(), texture);
+    assert_eq!(gl.get_renderbuffer_binding(), renderbuffer);
+    gl.delete_texture(texture.as_ref());
+    gl.delete_renderbuffer(renderbuffer.as_ref());
+    for kind in [
+        ResourceKind::Framebuffer,
+        ResourceKind::Renderbuffer,
+        ResourceKind::Texture,
+    ] {
+        assert_eq!(gl.live_count(kind), 0, "{kind:?}");
+    }
+}
+
+#[test]
+fn profiler_times_nested_scopes_on_cpu_and_gpu() {
+    use crate::backend::TIME_ELAPSED_EXT;
+    use crate::profiler::{overlay_bars, Profiler, TIMER_QUERY_EXTENSION};
+    use crate::recording::{GlCall, RecordingGl};
+    use std::cell::Cell;
+    use std::rc::Rc;
+
+    let gl = RecordingGl::new().with_extensions(&[TIMER_QUERY_EXTENSION]);
+    let now = Rc::new(Cell::new(0.0));
+    let clock = now.clone();
+    let mut profiler = Profiler::with_clock(&gl, Box::new(move || clock.get()));
+    assert!(profiler.has_gpu_timer());
+
+    // disabled: no queries, no clock
+    profiler.begin_frame(&gl, 0.0);
+    profiler.scope(&gl, "eye", || ());
+    profiler.end_frame(&gl);
+    assert!(gl.take_calls().is_empty());
+    assert!(profiler.scopes().is_empty());
+
+    profiler.set_enabled(true);
+    gl.set_query_result(2_000_000);
+    for frame in 0..3 {
+        profiler.begin_frame(&gl, f64::from(frame) * 14.0);
+        profiler.begin_scope(&gl, "eye");
+        now.set(now.get() + 1.0);
+        profiler.scope(&gl, "poster", || now.set(now.get() + 3.0));
+        profiler.end_scope(&gl);
+        profiler.end_frame(&gl);
+    }
+
+    // the eye's query is paused around the poster's, since timer queries can not overlap
+    let calls = gl.take_calls();
+    let timer_calls: Vec<_> = calls
+        .iter()
+        .filter_map(|call| match call {
+            GlCall::BeginQuery { target, .. } => Some((true, *target)),
+            GlCall::EndQuery(target) => Some((false, *target)),
+            _ => None,
+        })
+        .collect();
+    assert_eq!(timer_calls.len(), 3 * 6);
+    assert!(timer_calls
+        .iter()
+        .all(|(_, target)| *target == TIME_ELAPSED_EXT));
+    assert!(timer_calls.chunks(2).all(|pair| pair[0].0 && !pair[1].0));
+
+    let eye = profiler.scope_stats("eye").unwrap();
+    let poster = profiler.scope_stats("poster").unwrap();
+    assert_eq!(eye.cpu_ms.mean(), 4.0);
+    assert_eq!(poster.cpu_ms.mean(), 3.0);
+    // GPU results lag a frame: the third frame's queries have not been read yet
+    assert_eq!(eye.gpu_ms.len(), 2);
+    // two segments of 2ms each
+    assert_eq!(eye.gpu_ms.mean(), 4.0);
+    assert_eq!(poster.gpu_ms.mean(), 2.0);
+    assert_eq!(profiler.frame_interval_ms.mean(), 14.0);
+
+    let report = profiler.report();
+    assert!(report
+        .to_string()
+        .contains("poster: cpu 3.00ms, gpu (self) 2.00ms"));
+    // interval and cpu rows, a row per scope, and the budget line
+    assert_eq!(overlay_bars(&report, 14.0).len(), 2 + 2 * 2 + 1);
+
+    profiler.release(&gl);
+    let deleted = gl
+        .take_calls()
+        .iter()
+        .filter(|call| matches!(call, GlCall::DeleteQuery(_)))
+        .count();
+    let created = calls
+        .iter()
+        .filter(|call| matches!(call, GlCall::CreateQuery(_)))
+        .count();
+    assert_eq!(deleted, created);
+}
+
+#[test]
+fn texture_pixels_convert_every_image_kind() {
+    use crate::texture::{ColorSpace, TextureFormat, TexturePixels};
+    use image::{DynamicImage, GrayImage, ImageBuffer, Rgb, Rgba, RgbaImage};
+
+    // 3x2 RGB: 9 byte rows padded to 12, and the height is not the width
+    let rgb = DynamicImage::ImageRgb8(ImageBuffer::from_fn(3, 2, |x, y| {
+        Rgb([x as u8, y as u8, 7])
+    }));
+    let pixels = TexturePixels::from_image(&rgb, ColorSpace::Linear, false);
+    assert_eq!((pixels.width, pixels.height), (3, 2));
+    assert_eq!(pixels.format, TextureFormat::Rgb8);
+    assert_eq!(pixels.bytes.len(), 24);
+    assert_eq!(&pixels.bytes[12..21], &[0, 1, 7, 1, 1, 7, 2, 1, 7]);
+    assert_eq!(&pixels.bytes[21..24], &[0, 0, 0]);
+
+    // transparency survives
+    let rgba = DynamicImage::ImageRgba8(RgbaImage::from_pixel(2, 5, Rgba([1, 2, 3, 4])));
+    let pixels = TexturePixels::from_image(&rgba, ColorSpace::Srgb, false);
+    assert_eq!(pixels.format, TextureFormat::Srgb8Alpha8);
+    assert_eq!((pixels.width, pixels.height), (2, 5));
+    assert_eq!(&pixels.bytes[..4], &[1, 2, 3, 4]);
+
+    // gray is expanded
+    let gray = DynamicImage::ImageLuma8(GrayImage::from_pixel(4, 1, image::Luma([9])));
+    let pixels = TexturePixels::from_image(&gray, ColorSpace::Linear, true);
+    assert_eq!(pixels.format, TextureFormat::Rgb8);
+    assert_eq!(&pixels.bytes[..3], &[9, 9, 9]);
+
+    // 16 bit channels are scaled down
+    let deep = DynamicImage::ImageRgba16(ImageBuffer::from_pixel(
+        1,
+        1,
+        Rgba([0xffff, 0, 0x8080, 0xffff]),
+    ));
+    let pixels = TexturePixels::from_image(&deep, ColorSpace::Linear, false);
+    assert_eq!(pixels.format, TextureFormat::Rgba8);
+    assert_eq!(pixels.bytes, vec![0xff, 0, 0x80, 0xff]);
+
+    // SRGB8 cannot have generated mipmaps, so mipmapped sRGB gains an opaque alpha channel
+    assert_eq!(
+        TexturePixels::from_image(&rgb, ColorSpace::Srgb, false).format,
+        TextureFormat::Srgb8
+    );
+    let pixels = TexturePixels::from_image(&rgb, ColorSpace::Srgb, true);
+    assert_eq!(pixels.format, TextureFormat::Srgb8Alpha8);
+    assert_eq!(&pixels.bytes[..8], &[0, 0, 7, 0xff, 1, 0, 7, 0xff]);
+}
+
+#[test]
+fn texture_upload_applies_sampler() {
+    use crate::backend::TEXTURE_MAX_ANISOTROPY_EXT;
+    use crate::recording::{GlCall, RecordingGl};
+    use crate::texture::{ColorSpace, SamplerDescription, Texture, ANISOTROPY_EXTENSION};
+    use image::{DynamicImage, RgbaImage};
+    use web_sys::WebGl2RenderingContext as GL;
+
+    let image = DynamicImage::ImageRgba8(RgbaImage::new(8, 2));
+
+    let gl = RecordingGl::new();
+    let sampler = SamplerDescription::nearest().with_anisotropy(4.0);
+    let texture = Texture::from_image(&gl, &image, ColorSpace::Srgb, &sampler).unwrap();
+    assert_eq!((texture.width, texture.height), (8, 2));
+    let calls = gl.take_calls();
+    assert!(calls.contains(&GlCall::TexImage2d {
+        target: GL::TEXTURE_2D,
+        level: 0,
+        internalformat: GL::SRGB8_ALPHA8 as i32,
+        width: 8,
+        height: 2,
+        format: GL::RGBA,
+        type_: GL::UNSIGNED_BYTE,
+        byte_len: 64,
+    }));
+    assert!(calls.contains(&GlCall::TexParameteri {
+        target: GL::TEXTURE_2D,
+        pname: GL::TEXTURE_MIN_FILTER,
+        param: GL::NEAREST as i32,
+    }));
+    // no mipmaps wanted, and no anisotropy without the extension
+    assert!(!calls.iter().any(|call| matches!(
+        call,
+        GlCall::GenerateMipmap(_) | GlCall::TexParameterf { .. }
+    )));
+    texture.release(&gl);
+
+    let gl = RecordingGl::new().with_extensions(&[ANISOTROPY_EXTENSION]);
+    let sampler = SamplerDescription::default().with_anisotropy(4.0);
+    Texture::from_image(&gl, &image, ColorSpace::Linear, &sampler)
+        .unwrap()
+        .release(&gl);
+    let calls = gl.take_calls();
+    assert!(calls.contains(&GlCall::TexParameterf {
+        target: GL::TEXTURE_2D,
+        pname: TEXTURE_MAX_ANISOTROPY_EXT,
+        param: 4.0,
+    }));
+    assert!(calls.contains(&GlCall::GenerateMipmap(GL::TEXTURE_2D)));
+}
+
+/// A single-image KTX2 file with the given levels and a basic data format descriptor.
+fn ktx2_file(
+    vk_format: u32,
+    size: (u32, u32),
+    supercompression: u32,
+    (color_model, transfer, channel_ids): (u8, u8, &[u8]),
+    levels: &[&[u8]],
+) -> Vec<u8> {
+    let level_index_end = 80 + 24 * levels.len();
+    let block_size = 24 + 16 * channel_ids.len();
+    let dfd_len = 4 + block_size;
+
+    let mut bytes = crate::ktx2::KTX2_IDENTIFIER.to_vec();
+    let header = [vk_format, 1, size.0, size.1, 0, 0, 1, levels.len() as u32];
+    for field in header.into_iter().chain([supercompression]) {
+        bytes.extend(field.to_le_bytes());
+    }
+    for field in [level_index_end as u32, dfd_len as u32, 0, 0] {
+        bytes.extend(field.to_le_bytes());
+    }
+    bytes.extend([0; 16]); // no supercompression global data
+
+    let mut offset = (level_index_end + dfd_len) as u64;
+    for level in levels {
+        for field in [offset, level.len() as u64, level.len() as u64] {
+            bytes.extend(field.to_le_bytes());
+        }
+        offset += level.len() as u64;
+    }
+
+    bytes.extend((dfd_len as u32).to_le_bytes());
+    bytes.extend([0; 4]); // vendor and descriptor type
+    bytes.extend([2, 0]); // version
+    bytes.extend((block_size as u16).to_le_bytes());
+    bytes.extend([color_model, 1, transfer, 0]);
+    bytes.extend([0; 12]); // texel block dimensions and bytes per plane
+    for id in channel_ids {
+        let mut sample = [0; 16];
+        sample[3] = *id;
+        bytes.extend(sample);
+    }
+
+    for level in levels {
+        bytes.extend(*level);
+    }
+    bytes
+}
+
+#[test]
+fn ktx2_container_and_transcode_target() {
+    use crate::ktx2::{CompressedFormat, CompressionSupport, Ktx2, Ktx2Encoding};
+
+    // UASTC RGBA in sRGB, 8x4 with two levels
+    let bytes = ktx2_file(0, (8, 4), 0, (166, 2, &[3]), &[&[1; 32], &[2; 16]]);
+    let ktx2 = Ktx2::parse(&bytes).unwrap();
+    assert_eq!((ktx2.width(), ktx2.height()), (8, 4));
+    assert_eq!(ktx2.encoding(), Ok(Ktx2Encoding::Uastc));
+    assert!(ktx2.is_srgb());
+    assert!(ktx2.has_alpha());
+    assert_eq!(ktx2.levels.len(), 2);
+    assert_eq!(ktx2.level_data(1), &[2; 16]);
+
+    // ETC1S is recognized by its supercompression, and is opaque without an AAA sample
+    let bytes = ktx2_file(0, (4, 4), 1, (163, 1, &[0]), &[&[0; 8]]);
+    let ktx2 = Ktx2::parse(&bytes).unwrap();
+    assert_eq!(ktx2.encoding(), Ok(Ktx2Encoding::Etc1s));
+    assert!(!ktx2.is_srgb());
+    assert!(!ktx2.has_alpha());
+
+    // raw ETC2 payloads keep their vkFormat
+    let bytes = ktx2_file(152, (4, 4), 0, (0, 2, &[]), &[&[0; 16]]);
+    assert_eq!(
+        Ktx2::parse(&bytes).unwrap().encoding(),
+        Ok(Ktx2Encoding::Raw(Some(CompressedFormat::Etc2Rgba)))
+    );
+
+    assert!(Ktx2::parse(b"\x89PNG\r\n\x1a\n").is_err());
+    let mut truncated = ktx2_file(37, (2, 2), 0, (0, 1, &[]), &[&[0; 16]]);
+    truncated.truncate(truncated.len() - 1);
+    assert!(Ktx2::parse(&truncated).is_err());
+    // a level count whose index would not fit in memory, let alone in the file
+    let mut huge = ktx2_file(37, (2, 2), 0, (0, 1, &[]), &[&[0; 16]]);
+    huge[40..44].copy_from_slice(&u32::MAX.to_le_bytes());
+    assert_eq!(
+        Ktx2::parse(&huge).err(),
+        Some("KTX2 level index is truncated")
+    );
+
+    let everything = CompressionSupport {
+        astc: true,
+        etc: true,
+        s3tc: true,
+        s3tc_srgb: true,
+    };
+    assert_eq!(
+        everything.choose(true, true),
+        Some(CompressedFormat::Astc4x4)
+    );
+    let mobile = CompressionSupport {
+        etc: true,
+        ..Default::default()
+    };
+    assert_eq!(mobile.choose(false, false), Some(CompressedFormat::Etc2Rgb));
+    let desktop = CompressionSupport {
+        s3tc: true,
+        ..Default::default()
+    };
+    assert_eq!(desktop.choose(true, false), Some(CompressedFormat::Bc3));
+    // no sRGB S3TC without its extension, so fall back to RGBA8
+    assert_eq!(desktop.choose(true, true), None);
+    assert_eq!(CompressionSupport::default().choose(false, false), None);
+}
+
+#[test]
+fn ktx2_textures_upload_compressed_levels() {
+    use crate::ktx2::{CompressedFormat, Ktx2Transcoder, ASTC_EXTENSION};
+    use crate::recording::{GlCall, RecordingGl};
+    use crate::texture::{SamplerDescription, Texture, TextureFormat};
+    use wasm_bindgen::JsValue;
+    use web_sys::WebGl2RenderingContext as GL;
+
+    /// pretends to transcode by producing levels of the right size
+    struct FakeTranscoder;
+    impl Ktx2Transcoder for FakeTranscoder {
+        fn transcode(
+            &self,
+            _ktx2: &[u8],
+            target: Option<CompressedFormat>,
+        ) -> Result<Vec<Vec<u8>>, JsValue> {
+            let target = target.expect("a compressed target");
+            Ok(vec![
+                vec![0; target.level_byte_len(8, 8)],
+                vec![0; target.level_byte_len(4, 4)],
+            ])
+        }
+    }
+
+    // a Basis texture on a context with ASTC; two of the four levels, so the chain is cut short
+    let bytes = ktx2_file(0, (8, 8), 0, (166, 1, &[0]), &[&[0; 16], &[0; 16]]);
+    let gl = RecordingGl::new().with_extensions(&[ASTC_EXTENSION]);
+    let texture = Texture::from_ktx2(
+        &gl,
+        &bytes,
+        &SamplerDescription::default(),
+        Some(&FakeTranscoder),
+    )
+    .unwrap();
+    assert_eq!(
+        texture.format,
+        TextureFormat::Compressed {
+            format: CompressedFormat::Astc4x4,
+            srgb: false
+        }
+    );
+    let calls = gl.take_calls();
+    let uploads: Vec<_> = calls
+        .iter()
+        .filter_map(|call| match call {
+            GlCall::CompressedTexImage2d {
+                level,
+                internalformat,
+                width,
+                byte_len,
+                ..
+            } => Some((*level, *internalformat, *width, *byte_len)),
+            _ => None,
+        })
+        .collect();
+    assert_eq!(uploads, vec![(0, 0x93B0, 8, 64), (1, 0x93B0, 4, 16)]);
+    assert!(calls.contains(&GlCall::TexParameteri {
+        target: GL::TEXTURE_2D,
+        pname: GL::TEXTURE_MAX_LEVEL,
+        param: 1,
+    }));
+    assert!(!calls.contains(&GlCall::GenerateMipmap(GL::TEXTURE_2D)));
+    texture.release(&gl);
+
+    // raw RGBA8 uploads as an ordinary texture
+    let rgba = ktx2_file(43, (2, 2), 0, (0, 2, &[]), &[&[0; 16]]);
+    let gl = RecordingGl::new();
+    let texture = Texture::from_ktx2(&gl, &rgba, &SamplerDescription::nearest(), None).unwrap();
+    assert_eq!(texture.format, TextureFormat::Srgb8Alpha8);
+    assert!(gl.calls().contains(&GlCall::TexImage2d {
+        target: GL::TEXTURE_2D,
+        level: 0,
+        internalformat: GL::SRGB8_ALPHA8 as i32,
+        width: 2,
+        height: 2,
+        format: GL::RGBA,
+        type_: GL::UNSIGNED_BYTE,
+        byte_len: 16,
+    }));
+}
+
+/// serves fixed bytes per URL, and counts requests
+#[derive(Default)]
+struct FakeFetch {
+    files: std::collections::HashMap<String, Vec<u8>>,
+    requests: std::rc::Rc<std::cell::Cell<usize>>,
+}
+
+impl crate::assets::Fetch for FakeFetch {
+    fn fetch(
+        &self,
+        url: &str,
+    ) -> futures::future::LocalBoxFuture<'static, Result<Vec<u8>, String>> {
+        self.requests.set(self.requests.get() + 1);
+        let result = self
+            .files
+            .get(url)
+            .cloned()
+            .ok_or_else(|| format!("{url}: HTTP 404"));
+        Box::pin(futures::future::ready(result))
+    }
+}
+
+/// a 3x2 PNG, encoded with the `image` crate
+fn png_bytes() -> Vec<u8> {
+    let image = image::DynamicImage::ImageRgba8(image::RgbaImage::new(3, 2));
+    let mut bytes = std::io::Cursor::new(Vec::new());
+    image.write_to(&mut bytes, image::ImageFormat::Png).unwrap();
+    bytes.into_inner()
+}
+
+#[test]
+fn asset_loader_caches_by_url_and_tracks_state() {
+    use crate::assets::{AssetKind, AssetLoader, AssetState};
+    use futures::executor::block_on;
+
+    let fetch = FakeFetch::default();
+    let requests = fetch.requests.clone();
+    let mut files = std::collections::HashMap::new();
+    files.insert("poster.png".to_string(), png_bytes());
+    files.insert("flat.frag".to_string(), b"void main() {}".to_vec());
+    files.insert("garbage.png".to_string(), b"not a png".to_vec());
+    let loader = AssetLoader::with_fetch(FakeFetch { files, ..fetch });
+
+    let task = loader.start("poster.png", AssetKind::Image).unwrap();
+    assert!(matches!(
+        loader.state("poster.png"),
+        Some(AssetState::Pending)
+    ));
+    assert_eq!(loader.pending_count(), 1);
+    // already requested, so no second fetch
+    assert!(loader.start("poster.png", AssetKind::Image).is_none());
+    block_on(task);
+    let image = loader.get("poster.png").unwrap();
+    let image = image.as_image().unwrap();
+    assert_eq!((image.width(), image.height()), (3, 2));
+    assert!(loader.start("poster.png", AssetKind::Image).is_none());
+    assert_eq!(requests.get(), 1);
+
+    block_on(loader.start("flat.frag", AssetKind::Text).unwrap());
+    assert_eq!(
+        loader.get("flat.frag").unwrap().as_text().unwrap().as_ref(),
+        "void main() {}"
+    );
+
+    block_on(loader.start("missing.png", AssetKind::Image).unwrap());
+    assert!(
+        matches!(loader.state("missing.png"), Some(AssetState::Failed(e)) if e.contains("404"))
+    );
+    block_on(loader.start("garbage.png", AssetKind::Image).unwrap());
+    assert!(matches!(
+        loader.state("garbage.png"),
+        Some(AssetState::Failed(_))
+    ));
+    assert_eq!(loader.pending_count(), 0);
+    assert!(loader.state("never-requested.png").is_none());
+
+    // forgetting a failure allows a retry
+    loader.forget("missing.png");
+    assert!(loader.start("missing.png", AssetKind::Image).is_some());
+}
+
+#[test]
+fn draw_logic_swaps_placeholder_for_loaded_poster() {
+    use crate::assets::{AssetKind, AssetLoader};
+    use crate::gl_thin::{ResourceKind, ResourceRegistry};
+    use crate::objects::POSTER_URL;
+    use crate::recording::{GlCall, RecordingGl};
+    use crate::DrawLogic;
+    use futures::executor::block_on;
+
+    let mut files = std::collections::HashMap::new();
+    files.insert(POSTER_URL.to_string(), png_bytes());
+    let loader = AssetLoader::with_fetch(FakeFetch {
+        files,
+        ..Default::default()
+    });
+
+    let gl = ResourceRegistry::new(RecordingGl::new());
+    let mut draw_logic = DrawLogic::new(&gl).unwrap();
+    let task = loader.start(POSTER_URL, AssetKind::Image).unwrap();
+
+    // still pending: the placeholder stays
+    draw_logic.use_assets(&gl, &loader);
+    gl.inner().take_calls();
+    draw_logic.draw(&gl, 0.0);
+    assert!(!gl
+        .inner()
+        .take_calls()
+        .iter()
+        .any(|call| matches!(call, GlCall::TexImage2d { .. })));
+
+    block_on(task);
+    draw_logic.use_assets(&gl, &loader);
+    let calls = gl.inner().take_calls();
+    assert!(calls.iter().any(|call| matches!(
+        call,
+        GlCall::TexImage2d {
+            width: 3,
+            height: 2,
+            ..
+        }
+    )));
+    assert!(calls
+        .iter()
+        .any(|call| matches!(call, GlCall::DeleteTexture(_))));
+    // the poster image, in place of the placeholder, and the pyramid's white stand-in
+    assert_eq!(gl.live_count(ResourceKind::Texture), 2);
+
+    // only once
+    draw_logic.use_assets(&gl, &loader);
+    assert!(gl.inner().take_calls().is_empty());
+
+    // the image survives a lost context
+    gl.forget_all();
+    draw_logic.recreate(&gl).unwrap();
+    assert!(gl.inner().take_calls().iter().any(|call| matches!(
+        call,
+        GlCall::TexImage2d {
+            width: 3,
+            height: 2,
+            ..
+        }
+    )));
+
+    draw_logic.release(&gl);
+    let report = gl.leak_report();
+    assert!(report.is_empty(), "{report}");
+}
+
+#[test]
+fn video_frame_gate_skips_unchanged_frames() {
+    use crate::video::FrameGate;
+    use std::cell::Cell;
+    use std::rc::Rc;
+    use web_sys::HtmlMediaElement;
+
+    const READY: u16 = HtmlMediaElement::HAVE_ENOUGH_DATA;
+
+    // polling: upload whenever the playback position moved
+    let mut gate = FrameGate::polling();
+    assert!(!gate.should_upload(HtmlMediaElement::HAVE_METADATA, 0.0));
+    assert!(gate.should_upload(READY, 0.0));
+    assert!(!gate.should_upload(READY, 0.0)); // paused
+    assert!(gate.should_upload(READY, 0.04));
+    assert!(gate.should_upload(READY, 7.0)); // seeked
+    gate.reset();
+    assert!(gate.should_upload(READY, 7.0));
+
+    // with requestVideoFrameCallback: upload only when a frame was presented
+    let presented = Rc::new(Cell::new(false));
+    let mut gate = FrameGate::with_callback(presented.clone());
+    assert!(gate.should_upload(READY, 0.0)); // the first frame, whenever it was presented
+    assert!(!gate.should_upload(READY, 0.01)); // playing, but between video frames
+    presented.set(true);
+    assert!(gate.should_upload(READY, 0.04));
+    assert!(!presented.get());
+    assert!(!gate.should_upload(READY, 0.05));
+}
+
+/// one em is 1000 units; every glyph is half an em wide, and "AV" kerns together by a tenth
+struct FakeMetrics;
+
+impl crate::text::GlyphMetrics for FakeMetrics {
+    fn glyph_index(&self, c: char) -> Option<u16> {
+        (c != '€').then_some(c as u16)
+    }
+
+    fn advance(&self, _glyph: u16) -> f32 {
+        0.5
+    }
+
+    fn kerning(&self, left: u16, right: u16) -> f32 {
+        if (left, right) == ('A' as u16, 'V' as u16) {
+            -0.1
+        } else {
+            0.0
+        }
+    }
+
+    fn ascender(&self) -> f32 {
+        0.8
+    }
+
+    fn descender(&self) -> f32 {
+        -0.2
+    }
+
+    fn line_gap(&self) -> f32 {
+        0.0
+    }
+}
+
+#[test]
+fn text_layout_kerns_and_wraps() {
+    use crate::text::{layout_text, TextStyle};
+
+    let xs = |layout: &crate::text::TextLayout, line: usize| -> Vec<f32> {
+        layout
+            .glyphs
+            .iter()
+            .filter(|g| g.line == line)
+            .map(|g| g.x)
+            .collect()
+    };
+
+    let style = TextStyle {
+        size: 2.0,
+        ..TextStyle::default()
+    };
+    let layout = layout_text(&FakeMetrics, "AVA V", &style);
+    // the space is not a glyph; "AV" is pulled together by 0.1 em
+    assert_eq!(xs(&layout, 0), vec![0.0, 0.8, 1.8, 3.8]);
+    assert_eq!(layout.lines, 1);
+    assert_eq!(layout.width, 4.8);
+    assert_eq!(layout.glyphs[0].baseline, -1.6);
+
+    // "\n" breaks, and a missing character becomes the box
+    let layout = layout_text(&FakeMetrics, "a€\nb", &TextStyle::default());
+    assert_eq!(layout.lines, 2);
+    assert_eq!(layout.glyphs[1].glyph, 0);
+    assert_eq!(
+        (layout.glyphs[2].line, layout.glyphs[2].baseline),
+        (1, -1.8)
+    );
+    assert_eq!(layout.height, 2.0);
+
+    // wrapping at spaces: 3 words of 1 em, 2 fit on a 2.6 em line
+    let layout = layout_text(&FakeMetrics, "ab cd ef", &TextStyle::wrapped(2.6));
+    assert_eq!(layout.lines, 2);
+    assert_eq!(xs(&layout, 0), vec![0.0, 0.5, 1.5, 2.0]);
+    assert_eq!(xs(&layout, 1), vec![0.0, 0.5]);
+    assert_eq!(layout.width, 2.5);
+
+    // a word wider than the line is broken between characters
+    let layout = layout_text(&FakeMetrics, "x abcdefg", &TextStyle::wrapped(1.6));
+    assert_eq!(layout.lines, 4);
+    assert_eq!(xs(&layout, 1), vec![0.0, 0.5, 1.0]);
+    assert_eq!(xs(&layout, 3), vec![0.0]);
+    assert!(layout.width <= 1.6);
+}
+
+#[test]
+fn shelf_packer_fills_rows_then_gives_up() {
+    use crate::text::ShelfPacker;
+
+    let mut packer = ShelfPacker::new(16, 16, 1);
+    assert_eq!(packer.pack(7, 5), Some([0, 0]));
+    assert_eq!(packer.pack(7, 4), Some([8, 0]));
+    // no room left on the first shelf
+    assert_eq!(packer.pack(3, 3), Some([0, 6]));
+    // the shortest shelf that fits
+    assert_eq!(packer.pack(3, 2), Some([4, 6]));
+    assert_eq!(packer.pack(17, 1), None);
+    assert_eq!(packer.pack(4, 7), None);
+    assert_eq!(packer.pack(4, 5), Some([0, 10]));
+}
+
+const DEJAVU_SANS: &[u8] = include_bytes!("../../../webroot/assets/DejaVuSans.ttf");
+
+#[test]
+fn font_reads_metrics_kerning_and_outlines() {
+    use crate::font::Font;
+    use crate::text::GlyphAtlas;
+
+    let font = Font::parse(DEJAVU_SANS.to_vec()).unwrap();
+    assert_eq!(font.units_per_em, 2048);
+    assert!(font.ascender > 0 && font.descender < 0);
+
+    let a = font.glyph_index('A').unwrap();
+    let v = font.glyph_index('V').unwrap();
+    assert!(font.kerning(a, v) < 0);
+    assert!(font.glyph_index('\u{10FFFF}').is_none());
+    assert!(font.glyph_index('é').is_some()); // a composite glyph
+
+    let space = font.glyph_index(' ').unwrap();
+    assert!(font.advance(space) > 0);
+    assert!(font.rasterize(space, 32.0).is_none());
+
+    // 'O' is a ring: solid on its left stroke, empty in the middle
+    let o = font
+        .rasterize(font.glyph_index('O').unwrap(), 32.0)
+        .unwrap();
+    let middle_row = &o.coverage[(o.height / 2 * o.width) as usize..][..o.width as usize];
+    let leftmost_ink = middle_row.iter().position(|c| *c == 255).unwrap();
+    assert_eq!(middle_row[o.width as usize / 2], 0);
+    assert!(leftmost_ink < o.width as usize / 4);
+    assert!(o.top > 20 && o.top < 28, "{}", o.top);
+
+    let e_acute = font
+        .rasterize(font.glyph_index('é').unwrap(), 32.0)
+        .unwrap();
+    let e = font
+        .rasterize(font.glyph_index('e').unwrap(), 32.0)
+        .unwrap();
+    assert!(e_acute.top > e.top);
+    let ink =
+        |bitmap: &crate::font::GlyphBitmap| bitmap.coverage.iter().map(|c| *c as u32).sum::<u32>();
+    assert!(ink(&e_acute) > ink(&e));
+
+    // the atlas keeps each glyph once
+    let mut atlas = GlyphAtlas::new(128, 128, 32.0);
+    let placed = atlas.glyph(&font, a).unwrap();
+    assert_eq!(atlas.generation, 1);
+    assert_eq!(atlas.glyph(&font, a), Some(placed));
+    assert_eq!(atlas.generation, 1);
+    assert!(atlas.glyph(&font, space).is_none());
+    assert!(atlas.coverage.contains(&255));
+}
+
+#[test]
+fn draw_logic_writes_message_once_font_loads() {
+    use crate::assets::{AssetKind, AssetLoader};
+    use crate::gl_thin::ResourceRegistry;
+    use crate::recording::{GlCall, RecordingGl};
+    use crate::text::FONT_URL;
+    use crate::DrawLogic;
+    use futures::executor::block_on;
+    use web_sys::WebGl2RenderingContext as GL;
+
+    let mut files = std::collections::HashMap::new();
+    files.insert(FONT_URL.to_string(), DEJAVU_SANS.to_vec());
+    let loader = AssetLoader::with_fetch(FakeFetch {
+        files,
+        ..Default::default()
+    });
+
+    let gl = ResourceRegistry::new(RecordingGl::new());
+    let mut draw_logic = DrawLogic::new(&gl).unwrap();
+    // kept until there is a font to write it with
+    draw_logic.set_message(&gl, "Hi");
+    block_on(loader.start(FONT_URL, AssetKind::Binary).unwrap());
+    draw_logic.use_assets(&gl, &loader);
+    let calls = gl.inner().take_calls();
+    assert!(calls.iter().any(|call| matches!(
+        call,
+        GlCall::TexImage2d {
+            internalformat,
+            width: 512,
+            format: GL::RED,
+            ..
+        } if *internalformat == GL::R8 as i32
+    )));
+
+    draw_logic.draw(&gl, 0.0);
+    let calls = gl.inner().take_calls();
+    let blend = calls
+        .iter()
+        .position(|call| *call == GlCall::Enable(GL::BLEND))
+        .unwrap();
+    // two glyph quads
+    assert!(calls[blend..]
+        .iter()
+        .any(|call| matches!(call, GlCall::DrawElements { count: 12, .. })));
+
+    // an unchanged message is not rebuilt
+    draw_logic.set_message(&gl, "Hi");
+    assert!(gl.inner().take_calls().is_empty());
+    draw_logic.set_message(&gl, "Hi there");
+    assert_eq!(draw_logic.message(), "Hi there");
+
+    gl.forget_all();
+    draw_logic.recreate(&gl).unwrap();
+    draw_logic.release(&gl);
+    let report = gl.leak_report();
+    assert!(report.is_empty(), "{report}");
+}
+
+#[test]
+fn reflected_program_reports_every_mismatch_and_types_uniforms() {
+    use crate::recording::{GlCall, RecordingGl};
+    use crate::reflection::{
+        GlslType, InterfaceMismatch, ReflectedProgram, ShaderInterface, TextureUnit,
+    };
+    use crate::shaders::simple_shader_program;
+    use glam::{Mat4, Vec3};
+
+    const VS: &str = "#version 300 es
+        layout(std140) uniform Camera { mat4 view_projection; } camera;
+        layout(location = 0) in vec2 xy;
+        in vec3 normal; // a comment; with a semicolon
+        uniform mat4 model;
+        uniform highp float lights[4];
+        void main() { gl_Position = camera.view_projection * model * vec4(xy, 0.0, 1.0); }";
+    const FS: &str = "#version 300 es
+        precision highp float;
+        in vec3 normal2;
+        uniform sampler2D tex;
+        uniform mat4 model;
+        out vec4 color;
+        void main() { color = texture(tex, vec2(0.0)); }";
+
+    let gl = RecordingGl::new();
+    let program = simple_shader_program(&gl, VS, FS).unwrap();
+    let reflected = ReflectedProgram::reflect(&gl, program);
+    let names: Vec<_> = reflected
+        .attributes
+        .iter()
+        .map(|a| a.name.as_str())
+        .collect();
+    assert_eq!(names, ["xy", "normal"]);
+    let uniforms: Vec<_> = reflected
+        .uniforms
+        .iter()
+        .map(|u| (u.name.as_str(), u.type_, u.size))
+        .collect();
+    assert_eq!(
+        uniforms,
+        [
+            ("model", GlslType::Mat4, 1),
+            ("lights", GlslType::Float, 4),
+            ("tex", GlslType::Sampler2D, 1)
+        ]
+    );
+
+    // the declaration that matches the shader
+    let matching = ShaderInterface {
+        attributes: &[("xy", GlslType::Vec2), ("normal", GlslType::Vec3)],
+        uniforms: &[
+            ("model", GlslType::Mat4),
+            ("lights", GlslType::Float),
+            ("tex", GlslType::Sampler2D),
+        ],
+    };
+    assert!(reflected.mismatches(&matching).is_empty());
+
+    // a misspelling, a wrong type and a forgotten uniform, all reported at once
+    let wrong = ShaderInterface {
+        attributes: &[("xy", GlslType::Vec3), ("norml", GlslType::Vec3)],
+        uniforms: &[("model", GlslType::Mat4), ("tex", GlslType::Sampler2D)],
+    };
+    let mismatches = reflected.mismatches(&wrong);
+    assert_eq!(
+        mismatches,
+        [
+            InterfaceMismatch::AttributeType {
+                name: "xy".into(),
+                expected: GlslType::Vec3,
+                found: GlslType::Vec2,
+            },
+            InterfaceMismatch::MissingAttribute {
+                name: "norml".into(),
+                expected: GlslType::Vec3,
+            },
+            InterfaceMismatch::UndeclaredAttribute {
+                name: "normal".into(),
+                found: GlslType::Vec3,
+            },
+            InterfaceMismatch::UndeclaredUniform {
+                name: "lights".into(),
+                found: GlslType::Float,
+            },
+        ]
+    );
+    assert_eq!(
+        mismatches[0].to_string(),
+        "attribute xy is vec2, expected vec3"
+    );
+
+    // setters only for the Rust types that fit the GLSL type
+    assert!(reflected.uniform::<Vec3>("model").is_err());
+    assert!(reflected.uniform::<TextureUnit>("model").is_err());
+    assert!(reflected.uniform::<Mat4>("modle").is_err());
+    assert!(reflected.attribute("norml").is_err());
+    let model = reflected.uniform::<Mat4>("model").unwrap();
+    let tex = reflected.uniform::<TextureUnit>("tex").unwrap();
+    gl.take_calls();
+    model.set(&gl, &Mat4::IDENTITY);
+    tex.set(&gl, &TextureUnit(3));
+    let calls = gl.take_calls();
+    assert!(matches!(&calls[0], GlCall::UniformMatrix4fv { data, .. } if data[0] == 1.0));
+    assert!(matches!(calls[1], GlCall::Uniform1i { x: 3, .. }));
+    assert_eq!(gl.uniform_name(*tex.location()).as_deref(), Some("tex"));
+}
+
+#[test]
+fn preprocessor_expands_includes_and_variants_and_maps_lines_back() {
+    use crate::shaders::preprocess::{embedded_variant, ShaderDefines, ShaderLibrary};
+
+    let mut library = ShaderLibrary::new();
+    library.insert("common.glsl", "precision highp float;");
+    library.insert(
+        "lit.glsl",
+        "#include \"common.glsl\"\n#ifdef SHADOWS\nuniform sampler2D shadow;\n#endif",
+    );
+    library.insert(
+        "model.vert",
+        "#version 300 es
+#include \"common.glsl\"
+#include \"lit.glsl\"
+#ifndef INSTANCED
+uniform mat4 model;
+#else
+in mat4 model;
+#endif
+#if __VERSION__ >= 300
+in vec2 xy;
+#endif
+void main() { gl_Position = model * vec4(xy * SCALE, 0.0, 1.0); }",
+    );
+
+    let defines = ShaderDefines::new()
+        .with("INSTANCED")
+        .with_value("SCALE", "2.0");
+    let variant = library.preprocess("model.vert", &defines).unwrap();
+    // the version goes first and common.glsl is only included once
+    assert_eq!(
+        variant.text,
+        "#version 300 es
+#define INSTANCED
+#define SCALE 2.0
+precision highp float;
+in mat4 model;
+#if __VERSION__ >= 300
+in vec2 xy;
+#endif
+void main() { gl_Position = model * vec4(xy * SCALE, 0.0, 1.0); }
+"
+    );
+    assert_eq!(variant.location(1), None);
+    assert_eq!(variant.location(4), Some(("common.glsl", 1)));
+    assert_eq!(variant.location(5), Some(("model.vert", 7)));
+    assert_eq!(variant.location(9), Some(("model.vert", 12)));
+    assert_eq!(variant.location(10), None);
+
+    let plain = library
+        .preprocess("model.vert", &ShaderDefines::new())
+        .unwrap();
+    assert!(plain.text.contains("uniform mat4 model;"));
+    assert!(!plain.text.contains("in mat4 model;"));
+    assert!(!plain.text.contains("shadow"));
+
+    // variants are cached by their define set, whatever order it was built in
+    let shadowed = ShaderDefines::new().with("SHADOWS").with("INSTANCED");
+    let first = library.variant("model.vert", &shadowed).unwrap();
+    let again = library
+        .variant(
+            "model.vert",
+            &ShaderDefines::new().with("INSTANCED").with("SHADOWS"),
+        )
+        .unwrap();
+    assert!(std::rc::Rc::ptr_eq(&first, &again));
+    assert!(first.text.contains("uniform sampler2D shadow;"));
+    library.variant("model.vert", &defines).unwrap();
+    assert_eq!(library.cached_variants(), 2);
+
+    library.insert("broken.vert", "#include \"missing.glsl\"");
+    let error = library.variant("broken.vert", &defines).unwrap_err();
+    assert_eq!(
+        error.to_string(),
+        "broken.vert:1: no shader source named \"missing.glsl\""
+    );
+    library.insert("open.vert", "#ifdef A\n");
+    let error = library.preprocess("open.vert", &defines).unwrap_err();
+    assert_eq!((error.line, error.message.as_str()), (1, "missing #endif"));
+
+    // every embedded shader is written against the shared includes
+    let instanced =
+        embedded_variant("texture.vert", &ShaderDefines::new().with("INSTANCED")).unwrap();
+    assert!(instanced
+        .text
+        .starts_with("#version 300 es\n#define INSTANCED\nprecision highp float;\n"));
+    assert!(instanced.text.contains("uniform Camera"));
+    assert!(instanced.text.contains("in mat4 model;"));
+}
+
+#[test]
+fn shader_errors_parse_driver_logs_and_point_at_the_source() {
+    use crate::gl_thin::ResourceRegistry;
+    use crate::recording::RecordingGl;
+    use crate::shaders::diagnostics::{
+        parse_info_log, LogEntry, Severity, ShaderError, ShaderStage,
+    };
+    use crate::shaders::preprocess::{embedded_variant, ShaderDefines};
+    use crate::shaders::{simple_shader_program, variant_program};
+
+    let entry = |severity, line, column, message: &str| LogEntry {
+        severity,
+        line,
+        column,
+        message: message.into(),
+    };
+    // ANGLE, which Chrome, Edge and the Quest browser all compile through
+    assert_eq!(
+        parse_info_log(
+            "ERROR: 0:12: 'xy' : undeclared identifier\n\
+             WARNING: 0:3: 'foo' : extension is not supported\n\
+             ERROR: 2 compilation errors.  No code generated.\n\0"
+        ),
+        [
+            entry(
+                Severity::Error,
+                Some(12),
+                None,
+                "'xy' : undeclared identifier"
+            ),
+            entry(
+                Severity::Warning,
+                Some(3),
+                None,
+                "'foo' : extension is not supported"
+            ),
+        ]
+    );
+    // Mesa, as Firefox on Linux reports it
+    assert_eq!(
+        parse_info_log("0:7(14): error: `colour' undeclared\n0:9(1): warning: unused"),
+        [
+            entry(Severity::Error, Some(7), Some(14), "`colour' undeclared"),
+            entry(Severity::Warning, Some(9), Some(1), "unused"),
+        ]
+    );
+    // older Adreno drivers, NVIDIA and Mali
+    assert_eq!(
+        parse_info_log(
+            "ERROR: 0:4: error(#143) Undeclared identifier: uv\n\
+             ERROR: error(#273) 1 compilation errors.  No code generated"
+        ),
+        [entry(
+            Severity::Error,
+            Some(4),
+            None,
+            "Undeclared identifier: uv"
+        )]
+    );
+    assert_eq!(
+        parse_info_log("0(5) : error C1008: undefined variable \"tint\""),
+        [entry(
+            Severity::Error,
+            Some(5),
+            None,
+            "undefined variable \"tint\""
+        )]
+    );
+    assert_eq!(
+        parse_info_log("0:2: L0002: Undeclared variable 'rgb'"),
+        [entry(
+            Severity::Error,
+            Some(2),
+            None,
+            "L0002: Undeclared variable 'rgb'"
+        )]
+    );
+    // a link log has no locations at all
+    let error = ShaderError::link("Varyings with the same name but different type: 'uv'");
+    assert_eq!(
+        error.to_string(),
+        "program failed to link\nerror: Varyings with the same name but different type: 'uv'\n"
+    );
+
+    // a typo in an include, caught by a driver that reports no column
+    let gl = ResourceRegistry::new(RecordingGl::new());
+    let texture_vert = embedded_variant("texture.vert", &ShaderDefines::new()).unwrap();
+    let call = texture_vert
+        .text
+        .lines()
+        .position(|line| line.contains("gl_Position"))
+        .unwrap()
+        + 1;
+    gl.inner().fail_compile(
+        "model_to_clip",
+        &format!("ERROR: 0:{call}: 'model_to_clip' : no matching overloaded function found"),
+    );
+    let error = variant_program(
+        &gl,
+        "texture.vert",
+        "texture.frag",
+        &ShaderDefines::new(),
+        &[],
+    )
+    .unwrap_err();
+    assert_eq!(error.stage, ShaderStage::Vertex);
+    assert_eq!(error.file.as_deref(), Some("texture.vert"));
+    let first = error.first_error().unwrap();
+    assert_eq!(
+        (first.file.as_deref(), first.line),
+        (Some("texture.vert"), Some(15))
+    );
+    assert_eq!(
+        error.to_string(),
+        "vertex shader texture.vert failed to compile
+texture.vert:15: error: 'model_to_clip' : no matching overloaded function found
+15 |     gl_Position = model_to_clip(model, xy);
+   |                   ^^^^^^^^^^^^^
+"
+    );
+    // the failed shader objects are not left behind
+    let report = gl.leak_report();
+    assert!(report.is_empty(), "{report}");
+
+    // a column, where the driver gives one
+    let gl = RecordingGl::new();
+    gl.fail_compile("colour", "0:3(15): error: `colour' undeclared");
+    let error = simple_shader_program(
+        &gl,
+        "#version 300 es\nvoid main() {}",
+        "#version 300 es\nout vec4 color;\nvoid main() { colour = vec4(1.0); }",
+    )
+    .unwrap_err();
+    assert_eq!(
+        error.to_string(),
+        "fragment shader source failed to compile
+source:3: error: `colour' undeclared
+3 | void main() { colour = vec4(1.0); }
+  |               ^
+"
+    );
+}
+
+/// Rewrite the GLSL ES 3.00 that WebGL takes into the Vulkan-flavoured GLSL 4.50 that naga reads:
+/// explicit locations and bindings, a separate texture and sampler for every `sampler*`,
+/// `mat4` attributes (which naga will not take as inputs) split into `NAME_column0..3`,
+/// and `gl_VertexIndex` for `gl_VertexID`.
+/// Lines are kept where they were, so naga's line numbers still map back through the preprocessor.
+/// Everything else is left alone, so whatever 4.50 allows and ES 3.00 does not goes through.
+fn vulkan_glsl(source: &str, stage: naga::ShaderStage) -> String {
+    let mut out = Vec::new();
+    let mut depth = 0;
+    let (mut binding, mut input, mut output) = (0, 0, 0);
+    // each sampler's name and type
+    let mut samplers: Vec<(String, String)> = Vec::new();
+    let mut matrices: Vec<String> = Vec::new();
+    let mut main_pending = false;
+    for line in source.lines() {
+        let code = line.split("//").next().unwrap_or("");
+        let trimmed = code.trim();
+        let (layout, declaration) = match trimmed
+            .strip_prefix("layout(")
+            .and_then(|rest| rest.split_once(')'))
+        {
+            Some((qualifiers, rest)) => (format!("{qualifiers}, "), rest.trim_start()),
+            None => (String::new(), trimmed),
+        };
+        let words: Vec<&str> = declaration
+            .trim_end_matches(';')
+            .split_whitespace()
+            .collect();
+        let mut rewritten = match (depth, words.as_slice()) {
+            (_, ["#version", ..]) => "#version 450 core".to_string(),
+            // there are no combined sampler types to give a precision to
+            (0, ["precision", _, type_]) if type_.starts_with("sampler") => String::new(),
+            (0, ["uniform", type_, name]) if type_.starts_with("sampler") => {
+                let dimension = &type_["sampler".len()..];
+                samplers.push((name.to_string(), type_.to_string()));
+                binding += 2;
+                format!(
+                    "layout(binding={}) uniform texture{dimension} {name}; layout(binding={}) uniform sampler {name}_sampler;",
+                    binding - 2,
+                    binding - 1
+                )
+            }
+            (0, ["uniform", ..]) => {
+                binding += 1;
+                format!("layout({layout}binding={}) {declaration}", binding - 1)
+            }
+            (0, ["in", "mat4", name]) if stage == naga::ShaderStage::Vertex => {
+                matrices.push(name.to_string());
+                let columns: Vec<_> = (0..4)
+                    .map(|column| {
+                        format!(
+                            "layout(location={}) in vec4 {name}_column{column};",
+                            input + column
+                        )
+                    })
+                    .collect();
+                input += 4;
+                format!("{} mat4 {name};", columns.join(" "))
+            }
+            (0, ["in", ..]) => {
+                input += 1;
+                format!("layout({layout}location={}) {declaration}", input - 1)
+            }
+            (0, ["out", ..]) => {
+                output += 1;
+                format!("layout({layout}location={}) {declaration}", output - 1)
+            }
+            _ => {
+                let mut line = replace_identifier(line, "gl_VertexID", "gl_VertexIndex");
+                for (name, type_) in &samplers {
+                    line = replace_identifier(
+                        &line,
+                        name,
+                        &format!("{type_}({name}, {name}_sampler)"),
+                    );
+                }
+                line
+            }
+        };
+        depth += code.matches('{').count() as i32 - code.matches('}').count() as i32;
+        main_pending |= trimmed.starts_with("void main");
+        if main_pending && code.contains('{') {
+            main_pending = false;
+            for name in &matrices {
+                rewritten.push_str(&format!(
+                    " {name} = mat4({name}_column0, {name}_column1, {name}_column2, {name}_column3);"
+                ));
+            }
+        }
+        out.push(rewritten);
+    }
+    out.join("\n")
+}
+
+fn replace_identifier(line: &str, name: &str, replacement: &str) -> String {
+    let is_word = |c: char| c.is_ascii_alphanumeric() || c == '_';
+    let mut rval = String::new();
+    let mut rest = line;
+    while let Some(at) = rest.find(name) {
+        let before = rest[..at].chars().next_back();
+        let after = rest[at + name.len()..].chars().next();
+        rval.push_str(&rest[..at]);
+        if before.is_some_and(is_word) || after.is_some_and(is_word) {
+            rval.push_str(name);
+        } else {
+            rval.push_str(replacement);
+        }
+        rest = &rest[at + name.len()..];
+    }
+    rval.push_str(rest);
+    rval
+}
+
+/// Parse and validate every embedded shader with naga, and check the wrappers' interfaces against them.
+///
+/// naga reads GLSL 4.50 rather than the GLSL ES 3.00 WebGL compiles, so what is validated is
+/// [`vulkan_glsl`]'s rewrite of each shader. Errors that only exist in ES 3.00 pass here:
+/// implicit conversions (`float x = 1;`), missing precision qualifiers, `texture2D` and the like
+/// still need a browser (or `glslangValidator -S frag` on the preprocessed source) to be caught.
+#[test]
+fn embedded_shaders_pass_naga_validation_and_match_their_wrappers() {
+    use crate::gl_thin::ResourceRegistry;
+    use crate::material::LightingModel;
+    use crate::recording::RecordingGl;
+    use crate::reflection::GlslType;
+    use crate::shaders::preprocess::{ShaderDefines, ShaderLibrary, EMBEDDED_SOURCES};
+    use crate::shaders::registry::ProgramRegistry;
+    use crate::shaders::{
+        BarShader, GradientShader, InstancedGradientShader, InstancedTextureShader,
+        LayerCopyShader, LitShader, TextShader, TextureShader, PROGRAMS,
+    };
+    use naga::front::glsl::{Frontend, Options};
+    use naga::valid::{Capabilities, ValidationFlags, Validator};
+    use naga::{AddressSpace, Binding, ScalarKind, ShaderStage, TypeInner};
+
+    let embedded = ShaderLibrary::embedded();
+    let compile_from = |library: &ShaderLibrary,
+                        name: &str,
+                        defines: &ShaderDefines|
+     -> Result<naga::Module, String> {
+        let stage = if name.ends_with(".vert") {
+            ShaderStage::Vertex
+        } else {
+            ShaderStage::Fragment
+        };
+        let source = library.variant(name, defines).map_err(|e| e.to_string())?;
+        let glsl = vulkan_glsl(&source.text, stage);
+        let module = Frontend::default()
+            .parse(&Options::from(stage), &glsl)
+            .map_err(|errors| {
+                let errors: Vec<_> = errors
+                    .errors
+                    .iter()
+                    .map(|error| {
+                        let line = error.meta.location(&glsl).line_number;
+                        let (file, line) = source.location(line).unwrap_or((name, line));
+                        format!("{file}:{line}: {}", error.kind)
+                    })
+                    .collect();
+                errors.join("\n")
+            })?;
+        Validator::new(ValidationFlags::all(), Capabilities::all())
+            .validate(&module)
+            .map_err(|error| format!("{name} {defines:?}\n{}", error.emit_to_string(&glsl)))?;
+        Ok(module)
+    };
+    let compile = |name: &str, defines: &ShaderDefines| compile_from(&embedded, name, defines);
+
+    // a shader that is wrong in any GLSL must fail, and point at its own line
+    let mut broken = ShaderLibrary::embedded();
+    broken.insert(
+        "broken.frag",
+        "#include \"common.glsl\"\n\nout vec4 color;\n\nvoid main() {\n    colour = vec4(1.0);\n}\n",
+    );
+    let error = compile_from(&broken, "broken.frag", &ShaderDefines::new()).unwrap_err();
+    assert!(error.starts_with("broken.frag:6: "), "{error}");
+
+    // every shader on its own, so a new one is checked the day it is added.
+    // The MULTIVIEW variants are left out: naga knows neither OVR_multiview2 nor gl_ViewID_OVR.
+    for (name, _) in EMBEDDED_SOURCES {
+        if name.ends_with(".vert") || name.ends_with(".frag") {
+            if let Err(e) = compile(name, &ShaderDefines::new()) {
+                panic!("{e}");
+            }
+        }
+    }
+    if let Err(e) = compile(
+        "texture.frag",
+        &ShaderDefines::new().with_value("ALPHA_CUTOFF", "0.5"),
+    ) {
+        panic!("{e}");
+    }
+
+    let glsl_type = |module: &naga::Module, ty| {
+        let name = match module.types[ty].inner {
+            TypeInner::Scalar(scalar) => match scalar.kind {
+                ScalarKind::Sint => "int".to_string(),
+                ScalarKind::Uint => "uint".to_string(),
+                ScalarKind::Bool => "bool".to_string(),
+                _ => "float".to_string(),
+            },
+            TypeInner::Vector { size, scalar } => {
+                let prefix = match scalar.kind {
+                    ScalarKind::Sint => "i",
+                    ScalarKind::Uint => "u",
+                    ScalarKind::Bool => "b",
+                    _ => "",
+                };
+                format!("{prefix}vec{}", size as u8)
+            }
+            TypeInner::Matrix { columns, rows, .. } if columns == rows => {
+                format!("mat{}", columns as u8)
+            }
+            TypeInner::Image { dim, arrayed, .. } => {
+                format!("sampler{dim:?}{}", if arrayed { "Array" } else { "" })
+                    .replace("D2", "2D")
+                    .replace("D3", "3D")
+            }
+            _ => String::new(),
+        };
+        GlslType::from_glsl(&name)
+    };
+
+    // the names and types each wrapper looks up, against what the sources declare
+    for program in PROGRAMS {
+        let defines = program.defines();
+        let vertex = compile(program.vertex, &defines).unwrap();
+        let fragment = compile(program.fragment, &defines).unwrap();
+        let mut attributes = Vec::new();
+        for argument in &vertex.entry_points[0].function.arguments {
+            let Some(Binding::Location { .. }) = argument.binding else {
+                continue;
+            };
+            let name = argument.name.as_deref().unwrap_or("");
+            match name.split_once("_column") {
+                Some((matrix, "0")) => attributes.push((matrix.to_string(), Some(GlslType::Mat4))),
+                Some(_) => {}
+                None => attributes.push((name.to_string(), glsl_type(&vertex, argument.ty))),
+            }
+        }
+        let mut uniforms = Vec::new();
+        for module in [&vertex, &fragment] {
+            for (_, global) in module.global_variables.iter() {
+                let name = global.name.clone().unwrap_or_default();
+                let block = matches!(module.types[global.ty].inner, TypeInner::Struct { .. });
+                let sampler = matches!(module.types[global.ty].inner, TypeInner::Sampler { .. });
+                let uniform = matches!(global.space, AddressSpace::Uniform | AddressSpace::Handle);
+                let entry = (name, glsl_type(module, global.ty));
+                if uniform && !block && !sampler && !uniforms.contains(&entry) {
+                    uniforms.push(entry);
+                }
+            }
+        }
+        attributes.sort_by(|a, b| a.0.cmp(&b.0));
+        uniforms.sort_by(|a, b| a.0.cmp(&b.0));
+        let expected = |list: &[(&str, GlslType)]| {
+            let mut list: Vec<_> = list
+                .iter()
+                .map(|(name, type_)| (name.to_string(), Some(*type_)))
+                .collect();
+            list.sort_by(|a, b| a.0.cmp(&b.0));
+            list
+        };
+        let which = format!(
+            "{} + {} {:?}",
+            program.vertex, program.fragment, program.defines
+        );
+        assert_eq!(
+            attributes,
+            expected(program.interface.attributes),
+            "{which}"
+        );
+        assert_eq!(uniforms, expected(program.interface.uniforms), "{which}");
+    }
+
+    // and every wrapper finds what it looks up
+    let gl = ResourceRegistry::new(RecordingGl::new());
+    let programs = ProgramRegistry::new();
+    GradientShader::new(&gl, &programs).unwrap().release(&gl);
+    InstancedGradientShader::new(&gl, &programs)
+        .unwrap()
+        .release(&gl);
+    TextureShader::new(&gl, &programs).unwrap().release(&gl);
+    let alpha_tested = ShaderDefines::new().with_value("ALPHA_CUTOFF", "0.5");
+    TextureShader::variant(&gl, &programs, &alpha_tested)
+        .unwrap()
+        .release(&gl);
+    InstancedTextureShader::new(&gl, &programs)
+        .unwrap()
+        .release(&gl);
+    BarShader::new(&gl, &programs).unwrap().release(&gl);
+    TextShader::new(&gl, &programs).unwrap().release(&gl);
+    LayerCopyShader::new(&gl, &programs).unwrap().release(&gl);
+    for lighting in [
+        LightingModel::BlinnPhong,
+        LightingModel::MetallicRoughness,
+        LightingModel::Unlit,
+    ] {
+        LitShader::new(&gl, &programs, lighting)
+            .unwrap()
+            .release(&gl);
+    }
+}
+
+#[test]
+fn multiview_draws_both_eyes_in_one_pass_and_copies_each_layer_out() {
+    use crate::backend::{GlBackend, MULTIVIEW_EXTENSION};
+    use crate::camera::CameraBlock;
+    use crate::gl_thin::{MultiviewTarget, ResourceKind, ResourceRegistry};
+    use crate::recording::{GlCall, RecordingGl};
+    use crate::DrawLogic;
+    use glam::{vec3, Mat4};
+    use web_sys::WebGl2RenderingContext as GL;
+
+    let projection = Mat4::perspective_rh_gl(1.0, 1.0, 0.1, 100.0);
+    let camera = |eye_index: i32| {
+        let eye = vec3(eye_index as f32 * 0.06 - 0.03, 1.6, 0.0);
+        CameraBlock::new(
+            &projection,
+            &Mat4::from_translation(eye).inverse(),
+            eye,
+            eye_index,
+        )
+    };
+    let cameras = [camera(0), camera(1)];
+    let viewports = [[0, 0, 800, 900], [800, 0, 800, 900]];
+    let draws = |calls: &[GlCall]| {
+        calls
+            .iter()
+            .filter(|call| {
+                matches!(
+                    call,
+                    GlCall::DrawArrays { .. }
+                        | GlCall::DrawElements { .. }
+                        | GlCall::DrawArraysInstanced { .. }
+                )
+            })
+            .count()
+    };
+
+    // without the extension there is no target, and draw_xr falls back to a pass per eye
+    let gl = RecordingGl::new();
+    assert_eq!(
+        MultiviewTarget::new(&gl, 800, 900, 2).err(),
+        Some("OVR_multiview2 is not supported")
+    );
+    let mut draw_logic = DrawLogic::new(&gl).unwrap();
+    gl.take_calls();
+    draw_logic.draw_view(&gl, &cameras[0]).unwrap();
+    let draws_per_view = draws(&gl.take_calls());
+
+    let gl = ResourceRegistry::new(RecordingGl::new().with_extensions(&[MULTIVIEW_EXTENSION]));
+    let mut draw_logic = DrawLogic::new(&gl).unwrap();
+    let layer = gl.create_framebuffer().unwrap();
+    gl.inner().take_calls();
+    draw_logic
+        .draw_multiview(&gl, &cameras, Some(&layer), &viewports)
+        .unwrap();
+    let calls = gl.inner().take_calls();
+
+    // a two-layer color and depth array, both attached as two views
+    let storage: Vec<_> = calls
+        .iter()
+        .filter_map(|call| match call {
+            GlCall::TexStorage3d {
+                target,
+                internalformat,
+                width,
+                height,
+                depth,
+                ..
+            } => Some((*target, *internalformat, *width, *height, *depth)),
+            _ => None,
+        })
+        .collect();
+    assert_eq!(
+        storage,
+        [
+            (GL::TEXTURE_2D_ARRAY, GL::RGBA8, 800, 900, 2),
+            (GL::TEXTURE_2D_ARRAY, GL::DEPTH_COMPONENT24, 800, 900, 2)
+        ]
+    );
+    let attachments: Vec<_> = calls
+        .iter()
+        .filter_map(|call| match call {
+            GlCall::FramebufferTextureMultiviewOvr {
+                attachment,
+                base_view_index,
+                num_views,
+                ..
+            } => Some((*attachment, *base_view_index, *num_views)),
+            _ => None,
+        })
+        .collect();
+    assert_eq!(
+        attachments,
+        [(GL::COLOR_ATTACHMENT0, 0, 2), (GL::DEPTH_ATTACHMENT, 0, 2)]
+    );
+
+    // one upload holding both cameras, then the scene once, then a copy per eye
+    let camera_uploads: Vec<_> = calls
+        .iter()
+        .filter_map(|call| match call {
+            GlCall::BufferSubData { target, data, .. } if *target == GL::UNIFORM_BUFFER => {
+                Some(data)
+            }
+            _ => None,
+        })
+        .collect();
+    assert_eq!(camera_uploads.len(), 1);
+    assert_eq!(camera_uploads[0].len(), 2 * 208);
+    assert_eq!(camera_uploads[0][208 + 204..], 1i32.to_le_bytes());
+    assert_eq!(draws(&calls), draws_per_view + 2);
+    let copy_start = calls
+        .iter()
+        .rposition(|call| {
+            matches!(call, GlCall::BindFramebuffer { framebuffer, .. } if *framebuffer == Some(layer))
+        })
+        .unwrap();
+    let copies: Vec<_> = calls[copy_start..]
+        .iter()
+        .filter_map(|call| match call {
+            GlCall::Viewport {
+                x,
+                y,
+                width,
+                height,
+            } => Some([*x, *y, *width, *height]),
+            GlCall::DrawArrays { first, count, .. } => {
+                assert_eq!((*first, *count), (0, 3));
+                None
+            }
+            _ => None,
+        })
+        .collect();
+    assert_eq!(copies, viewports);
+    assert_eq!(draws(&calls[copy_start..]), 2);
+    // the variants draw from the same VAOs, so their attributes are bound where the originals' are
+    let shader = &draw_logic.gradient_triangle.shader;
+    let variant = shader.multiview.as_ref().unwrap();
+    assert_eq!(
+        (variant.sal_xy, variant.sal_rgb),
+        (shader.sal_xy, shader.sal_rgb)
+    );
+    assert!(calls.contains(&GlCall::BindAttribLocation {
+        program: *variant.program,
+        index: 1,
+        name: "rgb".to_string(),
+    }));
+
+    // the next frame reuses the target and the programs
+    draw_logic
+        .draw_multiview(&gl, &cameras, Some(&layer), &viewports)
+        .unwrap();
+    let calls = gl.inner().take_calls();
+    assert!(!calls
+        .iter()
+        .any(|call| matches!(call, GlCall::TexStorage3d { .. } | GlCall::LinkProgram(_))));
+    // the scene's three programs, their multiview variants and the copy
+    assert_eq!(gl.live_count(ResourceKind::Program), 7);
+
+    draw_logic.release(&gl);
+    gl.delete_framebuffer(Some(&layer));
+    let report = gl.leak_report();
+    assert!(report.is_empty(), "{report}");
+
+    // running out of objects halfway through a target leaves none of them behind
+    let gl = ResourceRegistry::new(RecordingGl::new().with_extensions(&[MULTIVIEW_EXTENSION]));
+    gl.inner().fail_creations_after(2);
+    assert_eq!(
+        MultiviewTarget::new(&gl, 800, 900, 2).err(),
+        Some("failed to create texture")
+    );
+    let report = gl.leak_report();
+    assert!(report.is_empty(), "{report}");
+
+    // a restored context gets another go at the single pass
+    let gl = RecordingGl::new().with_extensions(&[MULTIVIEW_EXTENSION]);
+    let mut draw_logic = DrawLogic::new(&gl).unwrap();
+    draw_logic.multiview_failed = true;
+    draw_logic.recreate(&gl).unwrap();
+    assert!(!draw_logic.multiview_failed);
+}
+
+#[test]
+fn lights_are_uploaded_once_per_frame_and_materials_once_per_draw() {
+    use crate::camera::CameraBlock;
+    use crate::gl_thin::{ResourceKind, ResourceRegistry};
+    use crate::lighting::{DirectionalLight, Lights, LIGHTS_BINDING};
+    use crate::material::{LightingModel, Material};
+    use crate::objects::{pyramid, LitMesh};
+    use crate::recording::{GlCall, RecordingGl};
+    use crate::shaders::registry::ProgramRegistry;
+    use crate::DrawLogic;
+    use glam::{vec3, vec4, Mat3, Mat4, Vec3};
+    use web_sys::WebGl2RenderingContext as GL;
+
+    // std140: counts after the arrays, colors scaled, directions normalized, extra lights dropped
+    let sun = DirectionalLight {
+        direction: vec3(0.0, -2.0, 0.0),
+        color: vec3(1.0, 0.5, 0.25),
+        intensity: 2.0,
+    };
+    let lights = Lights {
+        ambient: Vec3::splat(0.1),
+        directional: vec![sun; 3],
+        point: Vec::new(),
+    };
+    let bytes = lights.to_block().to_std140_bytes();
+    assert_eq!(bytes.len(), 224);
+    assert_eq!(bytes[4..8], (-1.0f32).to_le_bytes());
+    assert_eq!(bytes[16..20], 2.0f32.to_le_bytes());
+    assert_eq!(bytes[24..28], 0.5f32.to_le_bytes());
+    assert_eq!(bytes[192..196], 0.1f32.to_le_bytes());
+    assert_eq!(bytes[204..208], 2i32.to_le_bytes());
+    assert_eq!(bytes[208..212], 0i32.to_le_bytes());
+
+    // every face of the pyramid winds counter-clockwise seen from outside
+    let (vertices, indices) = pyramid();
+    for triangle in indices.chunks_exact(3) {
+        let [a, b, c] = [0, 1, 2].map(|i| vertices[triangle[i] as usize].position);
+        let normal = vertices[triangle[0] as usize].normal;
+        assert!((b - a).cross(c - a).dot(normal) > 0.0);
+        assert!(((a + b + c) / 3.0).dot(normal) > 0.0);
+    }
+
+    // one upload and binding for both eyes; the lit program's block points at it
+    let gl = RecordingGl::new();
+    let mut draw_logic = DrawLogic::new(&gl).unwrap();
+    assert_eq!(
+        gl.take_calls()
+            .iter()
+            .filter(|call| matches!(call, GlCall::UniformBlockBinding { uniform_block_binding, .. } if *uniform_block_binding == LIGHTS_BINDING))
+            .count(),
+        1
+    );
+    draw_logic.upload_lights(&gl);
+    for eye_index in 0..2 {
+        let camera = CameraBlock::new(&Mat4::IDENTITY, &Mat4::IDENTITY, Vec3::ZERO, eye_index);
+        draw_logic.draw_view(&gl, &camera).unwrap();
+    }
+    let calls = gl.take_calls();
+    let light_bindings = calls
+        .iter()
+        .filter(
+            |call| matches!(call, GlCall::BindBufferBase { index, .. } if *index == LIGHTS_BINDING),
+        )
+        .count();
+    assert_eq!(light_bindings, 1);
+    let uploads = calls
+        .iter()
+        .filter(|call| matches!(call, GlCall::BufferSubData { target, .. } if *target == GL::UNIFORM_BUFFER))
+        .count();
+    // the lights, then a camera per eye
+    assert_eq!(uploads, 3);
+    draw_logic.release(&gl);
+
+    // the material and a normal matrix that undoes non-uniform scale
+    let gl = ResourceRegistry::new(RecordingGl::new());
+    let mut material =
+        Material::new(vec4(0.5, 0.25, 1.0, 1.0)).with_lighting(LightingModel::MetallicRoughness);
+    material.roughness = 0.75;
+    material.metallic = 1.0;
+    material.emissive = vec3(0.0, 0.0, 0.5);
+    let mesh = LitMesh::new(&gl, &ProgramRegistry::new(), &vertices, &indices, material).unwrap();
+    gl.inner().take_calls();
+    let model = Mat4::from_scale(vec3(2.0, 1.0, 1.0));
+    mesh.draw(&gl, &model.to_cols_array(), Default::default());
+    let calls = gl.inner().take_calls();
+    let shader = &mesh.shader;
+    let at = |location: &_| Some(*location);
+    for expected in [
+        GlCall::Uniform4fv {
+            location: at(shader.sul_base_color.location()),
+            data: vec![0.5, 0.25, 1.0, 1.0],
+        },
+        GlCall::Uniform1f {
+            location: at(shader.sul_roughness.as_ref().unwrap().location()),
+            x: 0.75,
+        },
+        GlCall::Uniform1f {
+            location: at(shader.sul_metallic.as_ref().unwrap().location()),
+            x: 1.0,
+        },
+        GlCall::Uniform3fv {
+            location: at(shader.sul_emissive.as_ref().unwrap().location()),
+            data: vec![0.0, 0.0, 0.5],
+        },
+        GlCall::UniformMatrix3fv {
+            location: at(shader.sul_normal_matrix.as_ref().unwrap().location()),
+            transpose: false,
+            data: Mat3::from_diagonal(vec3(0.5, 1.0, 1.0))
+                .to_cols_array()
+                .to_vec(),
+        },
+        GlCall::Enable(GL::CULL_FACE),
+        GlCall::Disable(GL::CULL_FACE),
+    ] {
+        assert!(calls.contains(&expected), "{expected:?}");
+    }
+    // the white stand-in is released with the mesh
+    assert_eq!(gl.live_count(ResourceKind::Texture), 1);
+    mesh.release(&gl);
+    let report = gl.leak_report();
+    assert!(report.is_empty(), "{report}");
+}
+
+#[test]
+fn identical_programs_are_linked_once_and_deleted_with_their_last_user() {
+    use crate::gl_thin::{ResourceKind, ResourceRegistry};
+    use crate::objects::SohmahPoster;
+    use crate::recording::{GlCall, RecordingGl};
+    use crate::shaders::preprocess::ShaderDefines;
+    use crate::shaders::registry::ProgramRegistry;
+    use crate::shaders::TextureShader;
+    use std::cell::Cell;
+
+    // every reading is 2.5ms after the last, so every link takes 2.5ms
+    let now = Cell::new(0.0);
+    let programs = ProgramRegistry::with_clock(Box::new(move || {
+        now.set(now.get() + 2.5);
+        now.get()
+    }));
+    let gl = ResourceRegistry::new(RecordingGl::new());
+    let mut posters: Vec<_> = (0..10)
+        .map(|_| SohmahPoster::new(&gl, &programs).unwrap())
+        .collect();
+    let links = |gl: &ResourceRegistry<RecordingGl>| {
+        gl.inner()
+            .take_calls()
+            .iter()
+            .filter(|call| matches!(call, GlCall::LinkProgram(_)))
+            .count()
+    };
+    assert_eq!(links(&gl), 1);
+    assert_eq!(gl.live_count(ResourceKind::Program), 1);
+    assert_eq!(posters[0].shader.program.users(), 10);
+    assert_eq!(*posters[0].shader.program, *posters[9].shader.program);
+
+    // a different variant is a different program
+    let mut variant = TextureShader::variant(
+        &gl,
+        &programs,
+        &ShaderDefines::new().with_value("ALPHA_CUTOFF", "0.5"),
+    )
+    .unwrap();
+    assert_eq!(links(&gl), 1);
+    assert_eq!(programs.live_programs(), 2);
+    // as are the multiview twins: the variant's, and one all ten posters share
+    variant.enable_multiview(&gl, &programs).unwrap();
+    for poster in &mut posters {
+        poster.enable_multiview(&gl, &programs).unwrap();
+    }
+    assert_eq!(links(&gl), 2);
+    variant.release(&gl);
+
+    // the program outlives every poster but the last
+    let last = posters.pop().unwrap();
+    for poster in posters {
+        poster.release(&gl);
+    }
+    // the poster program and its twin
+    assert_eq!(gl.live_count(ResourceKind::Program), 2);
+    assert_eq!(last.shader.program.users(), 1);
+    last.release(&gl);
+    assert_eq!(gl.live_count(ResourceKind::Program), 0);
+    assert_eq!(programs.live_programs(), 0);
+    let report = gl.leak_report();
+    assert!(report.is_empty(), "{report}");
+
+    // gone, so linked again next time; every compile is on record with its time
+    SohmahPoster::new(&gl, &programs).unwrap().release(&gl);
+    assert_eq!(links(&gl), 1);
+    let compiles = programs.compiles();
+    assert_eq!(compiles.len(), 5);
+    assert!(compiles.iter().all(|compile| compile.milliseconds == 2.5));
+    assert_eq!(
+        compiles[1].key.to_string(),
+        "texture.vert + texture.frag [ALPHA_CUTOFF=0.5]"
+    );
+    let report = programs.to_string();
+    assert!(
+        report.starts_with("5 programs linked in 12.5 ms (0 in use)\n"),
+        "{report}"
+    );
+    assert!(report.contains("    2.5 ms  texture.vert + texture.frag [MULTIVIEW]\n"));
+
+    // dropping the last handle rather than releasing it leaves the program behind
+    drop(TextureShader::new(&gl, &programs).unwrap());
+    assert_eq!(programs.live_programs(), 0);
+    let report = gl.leak_report();
+    assert_eq!(report.leaks.len(), 1);
+    assert_eq!(report.leaks[0].kind, ResourceKind::Program);
+}
+
+#[test]
+fn scene_propagates_transforms_and_recomputes_only_what_moved() {
+    use crate::camera::CameraBlock;
+    use crate::recording::{GlCall, RecordingGl};
+    use crate::scene::{Scene, Transform};
+    use crate::DrawLogic;
+    use glam::{vec3, Mat4, Quat, Vec3};
+    use std::f32::consts::FRAC_PI_2;
+
+    // parent TRS applies to the child's: scaled, then turned, then moved
+    let mut scene: Scene<&str> = Scene::new();
+    let root = scene.add(
+        None,
+        "root",
+        Transform::from_translation(vec3(1.0, 0.0, 0.0))
+            .with_rotation(Quat::from_rotation_z(FRAC_PI_2))
+            .with_scale(Vec3::splat(2.0)),
+        None,
+    );
+    let arm = scene.add(
+        Some(root),
+        "arm",
+        Transform::from_translation(vec3(1.0, 0.0, 0.0)),
+        Some("arm"),
+    );
+    let hand = scene.add(Some(arm), "hand", Transform::IDENTITY, Some("hand"));
+    let other = scene.add(None, "other", Transform::IDENTITY, Some("other"));
+    assert_eq!(scene.update_world_matrices(), 4);
+    let origin = scene.node(hand).world().transform_point3(Vec3::ZERO);
+    assert!(origin.abs_diff_eq(vec3(1.0, 2.0, 0.0), 1e-6), "{origin}");
+    let decomposed = Transform::from_mat4(scene.node(root).world());
+    assert!(decomposed.scale.abs_diff_eq(Vec3::splat(2.0), 1e-6));
+
+    // nothing moved; moving the arm moves the hand but not the root or the other tree
+    assert_eq!(scene.update_world_matrices(), 0);
+    scene.set_local(arm, Transform::from_translation(vec3(0.0, 1.0, 0.0)));
+    assert_eq!(scene.update_world_matrices(), 2);
+    let origin = scene.node(hand).world().transform_point3(Vec3::ZERO);
+    assert!(origin.abs_diff_eq(vec3(-1.0, 0.0, 0.0), 1e-6), "{origin}");
+
+    // reparenting keeps the local transform, and cycles are refused
+    assert!(scene.set_parent(root, Some(hand)).is_err());
+    assert!(scene.set_parent(arm, Some(arm)).is_err());
+    scene.set_parent(arm, Some(other)).unwrap();
+    assert_eq!(scene.node(root).children(), &[]);
+    assert_eq!(scene.update_world_matrices(), 2);
+    let origin = scene.node(hand).world().transform_point3(Vec3::ZERO);
+    assert!(origin.abs_diff_eq(vec3(0.0, 1.0, 0.0), 1e-6), "{origin}");
+
+    // depth first, parents before children, siblings in order
+    let order: Vec<&str> = scene
+        .walk()
+        .map(|id| scene.node(id).name.as_str())
+        .collect();
+    assert_eq!(order, ["root", "other", "arm", "hand"]);
+    let drawn: Vec<&str> = scene.drawables().map(|(name, _)| *name).collect();
+    assert_eq!(drawn, ["other", "arm", "hand"]);
+    assert_eq!(scene.find("hand"), Some(hand));
+
+    // moving the stage moves everything on it
+    let model_of_first_draw = |gl: &RecordingGl| {
+        gl.take_calls()
+            .into_iter()
+            .find_map(|call| match call {
+                GlCall::UniformMatrix4fv { data, .. } => Some(data),
+                _ => None,
+            })
+            .unwrap()
+    };
+    let gl = RecordingGl::new();
+    let mut draw_logic = DrawLogic::new(&gl).unwrap();
+    let camera = CameraBlock::new(&Mat4::IDENTITY, &Mat4::IDENTITY, Vec3::ZERO, 0);
+    gl.take_calls();
+    draw_logic.draw_view(&gl, &camera).unwrap();
+    let before = model_of_first_draw(&gl);
+    assert_eq!(before[12..15], [-0.3, 0.0, -1.0]);
+    let stage = draw_logic.scene.find("stage").unwrap();
+    draw_logic
+        .scene
+        .set_local(stage, Transform::from_translation(vec3(0.0, 0.5, -2.0)));
+    draw_logic.draw_view(&gl, &camera).unwrap();
+    let after = model_of_first_draw(&gl);
+    assert_eq!(after[12..15], [-0.3, 0.5, -2.0]);
+}
+
+const TABLETOP_GLB: &[u8] = include_bytes!("../../../webroot/assets/models/tabletop.glb");
+const CRATE_GLTF: &[u8] = include_bytes!("../../../webroot/assets/models/crate.gltf");
+const CRATE_BIN: &[u8] = include_bytes!("../../../webroot/assets/models/crate.bin");
+const CRATE_PNG: &[u8] = include_bytes!("../../../webroot/assets/models/crate.png");
+
+#[test]
+fn gltf_import_reads_meshes_materials_textures_and_nodes() {
+    use crate::model::GltfFile;
+    use crate::scene::{Scene, Transform};
+    use base64::Engine;
+    use glam::{vec2, vec3, vec4, Vec3};
+    use std::rc::Rc;
+    use web_sys::WebGl2RenderingContext as GL;
+
+    // a GLB: everything in its binary chunk
+    let file = GltfFile::parse(TABLETOP_GLB).unwrap();
+    assert!(file.external_uris().is_empty());
+    let data = file
+        .import(|uri| Err(format!("{uri} should not be needed")))
+        .unwrap();
+    let [table, sign] = &data.meshes[..] else {
+        panic!("expected two meshes");
+    };
+    let box_ = &table.primitives[0];
+    assert_eq!((box_.vertices.len(), box_.indices.len()), (24, 36));
+    for triangle in box_.indices.chunks_exact(3) {
+        let [a, b, c] = [0, 1, 2].map(|i| box_.vertices[triangle[i] as usize].position);
+        let normal = box_.vertices[triangle[0] as usize].normal;
+        assert!((b - a).cross(c - a).dot(normal) > 0.0);
+    }
+    // neither indices nor normals: a vertex per corner, each with its face's normal
+    let quad = &sign.primitives[0];
+    assert_eq!(quad.indices, (0..6).collect::<Vec<u32>>());
+    assert!(quad.vertices.iter().all(|vertex| vertex.normal == Vec3::Z));
+
+    let wood = data.material(box_.material);
+    assert_eq!(wood.name, "wood");
+    assert_eq!(wood.base_color, vec4(1.0, 0.9, 0.8, 1.0));
+    assert_eq!((wood.roughness, wood.metallic), (0.8, 0.0));
+    assert_eq!(wood.emissive, vec3(0.1, 0.0, 0.0));
+    assert!(!wood.unlit);
+    // KHR_texture_transform: scaled by (2, 3), a quarter turn, then offset by (0.5, 0)
+    let uv = wood.uv_transform.transform_point2(vec2(1.0, 0.0));
+    assert!(uv.abs_diff_eq(vec2(0.5, -2.0), 1e-6), "{uv}");
+    let texture = data.textures[wood.base_color_texture.unwrap()];
+    assert_eq!(
+        [texture.sampler.min_filter, texture.sampler.mag_filter],
+        [GL::NEAREST; 2]
+    );
+    assert_eq!(
+        [texture.sampler.wrap_s, texture.sampler.wrap_t],
+        [GL::REPEAT, GL::MIRRORED_REPEAT]
+    );
+    assert_eq!(data.images[texture.image].width(), 4);
+    let glow = data.material(quad.material);
+    assert!(glow.unlit);
+    assert_eq!(glow.base_color, vec4(1.0, 0.5, 0.0, 1.0));
+
+    // the sign hangs off the (flattened) table, turned a quarter round
+    let mut scene = Scene::new();
+    let root = data.add_to_scene(&mut scene, None, "tabletop", Transform::IDENTITY, |mesh| {
+        mesh
+    });
+    scene.update_world_matrices();
+    let sign = scene.find("sign").unwrap();
+    assert_eq!(scene.node(sign).parent(), scene.find("table"));
+    assert_eq!(
+        scene.node(scene.find("table").unwrap()).parent(),
+        Some(root)
+    );
+    let world = scene.node(sign).world();
+    assert!(world
+        .transform_point3(Vec3::ZERO)
+        .abs_diff_eq(vec3(0.0, 1.0, 0.0), 1e-6));
+    assert!(world
+        .transform_vector3(Vec3::X)
+        .abs_diff_eq(vec3(0.0, 0.0, -1.0), 1e-6));
+    let meshes: Vec<usize> = scene.drawables().map(|(mesh, _)| *mesh).collect();
+    assert_eq!(meshes, [0, 1]);
+
+    // JSON with its buffer and image in files of their own
+    let file = GltfFile::parse(CRATE_GLTF).unwrap();
+    assert_eq!(file.external_uris(), ["crate.bin", "crate.png"]);
+    let external = |uri: &str| -> Result<Rc<[u8]>, String> {
+        match uri {
+            "crate.bin" => Ok(CRATE_BIN.into()),
+            "crate.png" => Ok(CRATE_PNG.into()),
+            _ => Err(format!("{uri}: HTTP 404")),
+        }
+    };
+    let data = file.import(external).unwrap();
+    assert_eq!(data.meshes[0].primitives[0].indices.len(), 36);
+    let planks = data.material(Some(0));
+    // glTF's defaults for what the file leaves out
+    assert_eq!((planks.roughness, planks.metallic), (1.0, 0.0));
+    let texture = data.textures[planks.base_color_texture.unwrap()];
+    assert_eq!(texture.sampler.min_filter, GL::LINEAR_MIPMAP_LINEAR);
+    assert_eq!(texture.sampler.wrap_s, GL::CLAMP_TO_EDGE);
+    assert_eq!(data.images[texture.image].width(), 8);
+    assert_eq!(data.roots, [0, 1]);
+    let lid = &data.nodes[1];
+    assert_eq!(lid.name, "lid");
+    assert!(lid.local.scale.abs_diff_eq(vec3(1.0, 0.25, 1.0), 1e-6));
+    assert!(lid
+        .local
+        .translation
+        .abs_diff_eq(vec3(0.0, 0.75, 0.0), 1e-6));
+    let missing = file.import(|uri| Err(format!("{uri}: HTTP 404")));
+    assert!(missing.is_err_and(|e| e.contains("crate.bin")));
+
+    // a data URI, a triangle strip and a line to skip, with the default material
+    let corners: Vec<u8> = [
+        [0.0f32, 0.0, 0.0],
+        [1.0, 0.0, 0.0],
+        [0.0, 1.0, 0.0],
+        [1.0, 1.0, 0.0],
+    ]
+    .iter()
+    .flatten()
+    .flat_map(|x| x.to_le_bytes())
+    .collect();
+    let json = format!(
+        r#"{{
+            "asset": {{"version": "2.0"}},
+            "buffers": [{{"byteLength": 48, "uri": "data:application/octet-stream;base64,{}"}}],
+            "bufferViews": [{{"buffer": 0, "byteLength": 48}}],
+            "accessors": [{{"bufferView": 0, "componentType": 5126, "count": 4, "type": "VEC3",
+                            "min": [0, 0, 0], "max": [1, 1, 0]}}],
+            "meshes": [{{"primitives": [{{"attributes": {{"POSITION": 0}}, "mode": 5}},
+                                        {{"attributes": {{"POSITION": 0}}, "mode": 1}}]}}],
+            "nodes": [{{"mesh": 0}}]
+        }}"#,
+        base64::engine::general_purpose::STANDARD.encode(&corners)
+    );
+    let file = GltfFile::parse(json.as_bytes()).unwrap();
+    assert!(file.external_uris().is_empty());
+    let data = file.import(external).unwrap();
+    let [strip] = &data.meshes[0].primitives[..] else {
+        panic!("the line should have been skipped");
+    };
+    // both triangles face the same way
+    assert_eq!(strip.vertices.len(), 6);
+    assert!(strip.vertices.iter().all(|vertex| vertex.normal == Vec3::Z));
+    assert_eq!(data.material(strip.material).metallic, 1.0);
+    // no scene, so the node nobody parents is the root
+    assert_eq!(data.roots, [0]);
+}
+
+#[test]
+fn draw_logic_loads_models_onto_the_stage() {
+    use crate::assets::{AssetKind, AssetLoader};
+    use crate::gl_thin::{ResourceKind, ResourceRegistry};
+    use crate::model::{GltfFile, ModelLoad};
+    use crate::recording::{GlCall, RecordingGl};
+    use crate::scene::Transform;
+    use crate::shaders::UNLIT_PROGRAM;
+    use crate::DrawLogic;
+    use futures::executor::block_on;
+    use glam::{vec3, Mat4, Vec3};
+
+    let gl = ResourceRegistry::new(RecordingGl::new());
+    let mut draw_logic = DrawLogic::new(&gl).unwrap();
+    let programs = gl.live_count(ResourceKind::Program);
+    let textures = gl.live_count(ResourceKind::Texture);
+
+    // the files next to the model are fetched relative to it, and dropped once imported
+    let fetch = FakeFetch::default();
+    let requests = fetch.requests.clone();
+    let mut files = std::collections::HashMap::new();
+    files.insert("models/crate.gltf".to_string(), CRATE_GLTF.to_vec());
+    files.insert("models/crate.bin".to_string(), CRATE_BIN.to_vec());
+    files.insert("models/crate.png".to_string(), CRATE_PNG.to_vec());
+    files.insert("broken/crate.gltf".to_string(), CRATE_GLTF.to_vec());
+    let assets = AssetLoader::with_fetch(FakeFetch { files, ..fetch });
+    for url in ["models/crate.gltf", "models/crate.bin", "models/crate.png"] {
+        block_on(assets.start(url, AssetKind::Binary).unwrap());
+    }
+    let on_the_floor = Transform::from_translation(vec3(0.0, -0.5, 0.0));
+    draw_logic.load_model("models/crate.gltf", on_the_floor);
+    draw_logic.use_assets(&gl, &assets);
+    assert_eq!(requests.get(), 3);
+    assert!(assets.state("models/crate.bin").is_none());
+    let root = draw_logic.scene.find("models/crate.gltf").unwrap();
+    assert_eq!(
+        draw_logic.scene.node(root).parent(),
+        draw_logic.scene.find("stage")
+    );
+    assert_eq!(draw_logic.scene.node(root).children().len(), 2);
+    // lit with the pyramid's program; the crate's texture and a white stand-in
+    assert_eq!(gl.live_count(ResourceKind::Program), programs);
+    assert_eq!(gl.live_count(ResourceKind::Texture), textures + 2);
+
+    // a failed load is forgotten too, so loading it again fetches it again
+    let mut load = ModelLoad::new("broken/crate.gltf");
+    for url in ["broken/crate.gltf", "broken/crate.bin", "broken/crate.png"] {
+        block_on(assets.start(url, AssetKind::Binary).unwrap());
+    }
+    let failed = load.poll(&assets).unwrap();
+    assert!(failed.is_err_and(|e| e.contains("broken/crate.bin")));
+    for url in ["broken/crate.gltf", "broken/crate.bin", "broken/crate.png"] {
+        assert!(assets.state(url).is_none(), "{url}");
+    }
+
+    // an unlit model links the unlit program
+    let tabletop = GltfFile::parse(TABLETOP_GLB)
+        .unwrap()
+        .import(|uri| Err(uri.to_string()))
+        .unwrap();
+    draw_logic
+        .add_model(&gl, tabletop, "tabletop", Transform::IDENTITY)
+        .unwrap();
+    assert_eq!(gl.live_count(ResourceKind::Program), programs + 1);
+    let links: Vec<_> = draw_logic
+        .programs
+        .compiles()
+        .into_iter()
+        .map(|compile| compile.key.vertex)
+        .collect();
+    assert!(links.contains(&UNLIT_PROGRAM.vertex));
+
+    // the crate's box is drawn for both of its nodes, the tabletop's two meshes once each
+    let camera = crate::camera::CameraBlock::new(&Mat4::IDENTITY, &Mat4::IDENTITY, Vec3::ZERO, 0);
+    let draws = |gl: &RecordingGl| {
+        gl.take_calls()
+            .iter()
+            .filter(|call| matches!(call, GlCall::DrawElements { .. }))
+            .count()
+    };
+    gl.inner().take_calls();
+    draw_logic.draw_view(&gl, &camera).unwrap();
+    let with_models = draws(gl.inner());
+    draw_logic
+        .scene
+        .set_local(root, on_the_floor.with_scale(Vec3::ZERO));
+    draw_logic.draw_view(&gl, &camera).unwrap();
+    // still drawn, just shrunk to nothing; the count is the same
+    assert_eq!(draws(gl.inner()), with_models);
+    let stage_only = {
+        let gl = RecordingGl::new();
+        let mut plain = DrawLogic::new(&gl).unwrap();
+        gl.take_calls();
+        plain.draw_view(&gl, &camera).unwrap();
+        draws(&gl)
+    };
+    assert_eq!(with_models, stage_only + 4);
+
+    draw_logic.release(&gl);
+    let report = gl.leak_report();
+    assert!(report.is_empty(), "{report}");
+}
@@ -0,0 +1,11 @@
+#include "common.glsl"
+
+in vec2 uv2;
+in vec4 rgba2;
+// glyph coverage in the red channel
+uniform sampler2D atlas;
+out vec4 color;
+
+void main() {
+    color = vec4(rgba2.rgb, rgba2.a * texture(atlas, uv2).r);
+}
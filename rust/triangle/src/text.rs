@@ -0,0 +1,563 @@
+use crate::backend::GlBackend;
+use crate::camera::ViewMode;
+use crate::font::Font;
+use crate::gl_thin::{IndexBuffer, InterleavedGlBuffer};
+use crate::reflection::TextureUnit;
+use crate::shaders::registry::ProgramRegistry;
+use crate::shaders::TextShader;
+use crate::texture::{SamplerDescription, Texture, TextureFormat, TexturePixels};
+use crate::vertex_layout::{vertex_layout, Normalized};
+use std::collections::HashMap;
+use wasm_bindgen::JsValue;
+use web_sys::WebGl2RenderingContext;
+
+/// where the page serves the font from, relative to the page
+pub const FONT_URL: &str = "assets/DejaVuSans.ttf";
+
+/// What [`layout_text`] needs to know about a font, in ems.
+/// [`Font`] is the real one; tests substitute their own.
+pub trait GlyphMetrics {
+    /// `None` for characters the font has no glyph for
+    fn glyph_index(&self, c: char) -> Option<u16>;
+    fn advance(&self, glyph: u16) -> f32;
+    fn kerning(&self, left: u16, right: u16) -> f32;
+    /// above the baseline, positive
+    fn ascender(&self) -> f32;
+    /// below the baseline, negative
+    fn descender(&self) -> f32;
+    fn line_gap(&self) -> f32;
+}
+
+impl GlyphMetrics for Font {
+    fn glyph_index(&self, c: char) -> Option<u16> {
+        Font::glyph_index(self, c)
+    }
+
+    fn advance(&self, glyph: u16) -> f32 {
+        Font::advance(self, glyph) as f32 / self.units_per_em as f32
+    }
+
+    fn kerning(&self, left: u16, right: u16) -> f32 {
+        Font::kerning(self, left, right) as f32 / self.units_per_em as f32
+    }
+
+    fn ascender(&self) -> f32 {
+        self.ascender as f32 / self.units_per_em as f32
+    }
+
+    fn descender(&self) -> f32 {
+        self.descender as f32 / self.units_per_em as f32
+    }
+
+    fn line_gap(&self) -> f32 {
+        self.line_gap as f32 / self.units_per_em as f32
+    }
+}
+
+/// How [`layout_text`] sets a string. Lengths are in model units.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct TextStyle {
+    /// the em size
+    pub size: f32,
+    /// multiplies the font's line height
+    pub line_spacing: f32,
+    /// wrap lines that would get wider than this
+    pub max_width: Option<f32>,
+    pub color: [u8; 4],
+}
+
+impl Default for TextStyle {
+    /// 1 unit to the em, unwrapped, white; scale it with the model matrix
+    fn default() -> Self {
+        Self {
+            size: 1.0,
+            line_spacing: 1.0,
+            max_width: None,
+            color: [0xff; 4],
+        }
+    }
+}
+
+impl TextStyle {
+    pub fn wrapped(max_width: f32) -> Self {
+        Self {
+            max_width: Some(max_width),
+            ..Self::default()
+        }
+    }
+}
+
+/// One glyph of a [`TextLayout`]: the pen position on its line's baseline.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct PlacedGlyph {
+    pub glyph: u16,
+    pub x: f32,
+    pub baseline: f32,
+    pub line: usize,
+}
+
+/// Glyph positions for a string, with the top left corner of the text at the origin,
+/// x to the right and y up (so every baseline is negative).
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct TextLayout {
+    /// in reading order; spaces are left out
+    pub glyphs: Vec<PlacedGlyph>,
+    pub lines: usize,
+    /// of the widest line
+    pub width: f32,
+    pub height: f32,
+}
+
+/// Lay out `text` in lines: `'\n'` starts a new one, and with a `max_width` lines wrap at spaces
+/// (or between characters, for a word that is too wide on its own).
+/// Kerning applies between the glyphs of a word. Characters missing from the font get glyph 0, the box.
+pub fn layout_text(metrics: &impl GlyphMetrics, text: &str, style: &TextStyle) -> TextLayout {
+    let size = style.size;
+    let line_height =
+        (metrics.ascender() - metrics.descender() + metrics.line_gap()) * size * style.line_spacing;
+    let max_width = style.max_width.unwrap_or(f32::INFINITY);
+    let space = metrics
+        .glyph_index(' ')
+        .map_or(0.25, |glyph| metrics.advance(glyph))
+        * size;
+    let baseline = |line: usize| -metrics.ascender() * size - line as f32 * line_height;
+
+    let mut layout = TextLayout::default();
+    let mut line = 0;
+    for (p, paragraph) in text.split('\n').enumerate() {
+        if p > 0 {
+            line += 1;
+        }
+        let mut pen = 0.0;
+        for (w, word) in paragraph.split([' ', '\t']).enumerate() {
+            // each glyph's offset from the start of the word, and its advance
+            let mut shaped: Vec<(u16, f32, f32)> = Vec::new();
+            let mut word_width = 0.0;
+            let mut previous = None;
+            for c in word.chars().filter(|c| !c.is_control()) {
+                let glyph = metrics.glyph_index(c).unwrap_or(0);
+                if let Some(previous) = previous {
+                    word_width += metrics.kerning(previous, glyph) * size;
+                }
+                let advance = metrics.advance(glyph) * size;
+                shaped.push((glyph, word_width, advance));
+                word_width += advance;
+                previous = Some(glyph);
+            }
+
+            let start = if w == 0 { pen } else { pen + space };
+            let mut word_x = if pen > 0.0 && start + word_width > max_width {
+                line += 1;
+                0.0
+            } else {
+                start
+            };
+            let starts_line = word_x == 0.0;
+            for (glyph, offset, advance) in shaped {
+                let mut x = word_x + offset;
+                if starts_line && x > 0.0 && x + advance > max_width {
+                    line += 1;
+                    word_x = -offset;
+                    x = 0.0;
+                }
+                layout.glyphs.push(PlacedGlyph {
+                    glyph,
+                    x,
+                    baseline: baseline(line),
+                    line,
+                });
+                layout.width = layout.width.max(x + advance);
+            }
+            pen = word_x + word_width;
+        }
+    }
+    layout.lines = line + 1;
+    layout.height = layout.lines as f32 * line_height;
+    layout
+}
+
+//
+
+/// Packs rectangles into rows ("shelves") as tall as the tallest rectangle that started them.
+/// Good enough for glyphs, which are all about the same height.
+#[derive(Debug, Clone)]
+pub struct ShelfPacker {
+    width: u32,
+    height: u32,
+    /// empty pixels to the right of and below each rectangle
+    padding: u32,
+    shelves: Vec<Shelf>,
+}
+
+#[derive(Debug, Clone, Copy)]
+struct Shelf {
+    y: u32,
+    height: u32,
+    used: u32,
+}
+
+impl ShelfPacker {
+    pub fn new(width: u32, height: u32, padding: u32) -> Self {
+        Self {
+            width,
+            height,
+            padding,
+            shelves: Vec::new(),
+        }
+    }
+
+    /// The top left corner for a `width` x `height` rectangle, or `None` if there is no room left.
+    pub fn pack(&mut self, width: u32, height: u32) -> Option<[u32; 2]> {
+        let (w, h) = (width + self.padding, height + self.padding);
+        let room = self.width;
+        // the shortest shelf it fits on wastes the least
+        if let Some(shelf) = self
+            .shelves
+            .iter_mut()
+            .filter(|shelf| shelf.height >= h && room - shelf.used >= w)
+            .min_by_key(|shelf| shelf.height)
+        {
+            shelf.used += w;
+            return Some([shelf.used - w, shelf.y]);
+        }
+        let y = self
+            .shelves
+            .last()
+            .map_or(0, |shelf| shelf.y + shelf.height);
+        if w > self.width || y + h > self.height {
+            return None;
+        }
+        self.shelves.push(Shelf {
+            y,
+            height: h,
+            used: w,
+        });
+        Some([0, y])
+    }
+}
+
+/// Where a glyph's coverage is in a [`GlyphAtlas`], and how it sits relative to the pen, in atlas pixels.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct AtlasGlyph {
+    pub x: u32,
+    pub y: u32,
+    pub width: u32,
+    pub height: u32,
+    /// from the pen position to the left edge
+    pub left: i32,
+    /// from the baseline up to the top edge
+    pub top: i32,
+}
+
+/// Single channel coverage for the glyphs drawn so far, rasterized on first use.
+pub struct GlyphAtlas {
+    pub width: u32,
+    pub height: u32,
+    /// Glyphs are rasterized at this size. Text drawn much bigger gets blurry.
+    pub pixels_per_em: f32,
+    /// top row first
+    pub coverage: Vec<u8>,
+    packer: ShelfPacker,
+    /// `None` for glyphs with no outline, or that did not fit
+    glyphs: HashMap<u16, Option<AtlasGlyph>>,
+    /// bumped whenever [`Self::coverage`] changes
+    pub generation: u32,
+}
+
+impl GlyphAtlas {
+    /// `width` has to be a multiple of 4, so the rows need no padding when uploaded.
+    pub fn new(width: u32, height: u32, pixels_per_em: f32) -> Self {
+        assert_eq!(width % 4, 0, "atlas width {width} is not a multiple of 4");
+        Self {
+            width,
+            height,
+            pixels_per_em,
+            coverage: vec![0; (width * height) as usize],
+            packer: ShelfPacker::new(width, height, 1),
+            glyphs: HashMap::new(),
+            generation: 0,
+        }
+    }
+
+    /// Rasterize `glyph` into the atlas unless it is there already.
+    /// `None` for glyphs with nothing to draw, and once the atlas is full.
+    pub fn glyph(&mut self, font: &Font, glyph: u16) -> Option<AtlasGlyph> {
+        if let Some(placed) = self.glyphs.get(&glyph) {
+            return *placed;
+        }
+        let placed = font
+            .rasterize(glyph, self.pixels_per_em)
+            .and_then(|bitmap| {
+                let [x, y] = self.packer.pack(bitmap.width, bitmap.height)?;
+                for (row, src) in bitmap
+                    .coverage
+                    .chunks_exact(bitmap.width as usize)
+                    .enumerate()
+                {
+                    let start = (y as usize + row) * self.width as usize + x as usize;
+                    self.coverage[start..start + src.len()].copy_from_slice(src);
+                }
+                self.generation += 1;
+                Some(AtlasGlyph {
+                    x,
+                    y,
+                    width: bitmap.width,
+                    height: bitmap.height,
+                    left: bitmap.left,
+                    top: bitmap.top,
+                })
+            });
+        self.glyphs.insert(glyph, placed);
+        placed
+    }
+
+    pub fn pixels(&self) -> TexturePixels {
+        TexturePixels {
+            width: self.width,
+            height: self.height,
+            format: TextureFormat::R8,
+            bytes: self.coverage.clone(),
+        }
+    }
+}
+
+vertex_layout! {
+    /// One corner of a glyph quad. 20 bytes:
+    /// the position in model units, where in the [`GlyphAtlas`] it samples, and the text color.
+    #[derive(Debug, Clone, Copy, PartialEq)]
+    pub struct TextVertex {
+        pub xy: [f32; 2],
+        pub uv: [f32; 2],
+        pub rgba: Normalized<[u8; 4]>,
+    }
+}
+
+/// A quad per visible glyph of `layout` (4 vertices, 6 indices), adding glyphs to `atlas` as needed.
+/// `size` must be the em size `layout` was made with.
+pub fn text_mesh(
+    font: &Font,
+    atlas: &mut GlyphAtlas,
+    layout: &TextLayout,
+    size: f32,
+    color: [u8; 4],
+) -> (Vec<TextVertex>, Vec<u32>) {
+    let scale = size / atlas.pixels_per_em;
+    let (atlas_width, atlas_height) = (atlas.width as f32, atlas.height as f32);
+    let mut vertices = Vec::with_capacity(4 * layout.glyphs.len());
+    let mut indices = Vec::with_capacity(6 * layout.glyphs.len());
+    for placed in &layout.glyphs {
+        let Some(glyph) = atlas.glyph(font, placed.glyph) else {
+            continue;
+        };
+        let left = placed.x + glyph.left as f32 * scale;
+        let top = placed.baseline + glyph.top as f32 * scale;
+        let right = left + glyph.width as f32 * scale;
+        let bottom = top - glyph.height as f32 * scale;
+        let u0 = glyph.x as f32 / atlas_width;
+        let v0 = glyph.y as f32 / atlas_height;
+        let u1 = (glyph.x + glyph.width) as f32 / atlas_width;
+        let v1 = (glyph.y + glyph.height) as f32 / atlas_height;
+
+        let first = vertices.len() as u32;
+        vertices.extend(
+            [
+                ([left, bottom], [u0, v1]),
+                ([right, bottom], [u1, v1]),
+                ([left, top], [u0, v0]),
+                ([right, top], [u1, v0]),
+            ]
+            .map(|(xy, uv)| TextVertex {
+                xy,
+                uv,
+                rgba: Normalized(color),
+            }),
+        );
+        indices.extend([0, 1, 2, 2, 1, 3].map(|i| first + i));
+    }
+    (vertices, indices)
+}
+
+//
+
+/// A string set and uploaded by a [`TextRenderer`], ready to draw.
+pub struct TextLabel<G: GlBackend = WebGl2RenderingContext> {
+    text: String,
+    pub style: TextStyle,
+    /// of the laid out text, in model units
+    pub width: f32,
+    pub height: f32,
+    vertices: InterleavedGlBuffer<TextVertex, G>,
+    indices: IndexBuffer<G>,
+    vao: G::VertexArray,
+}
+
+impl<G: GlBackend> TextLabel<G> {
+    pub fn text(&self) -> &str {
+        &self.text
+    }
+
+    pub fn release(self, gl: &G) {
+        self.vertices.release(gl);
+        self.indices.release(gl);
+        gl.delete_vertex_array(Some(&self.vao));
+    }
+}
+
+/// Draws [`TextLabel`]s in the scene with one font, whose glyphs share one atlas texture.
+pub struct TextRenderer<G: GlBackend = WebGl2RenderingContext> {
+    pub font: Font,
+    pub atlas: GlyphAtlas,
+    texture: Texture<G>,
+    /// the [`GlyphAtlas::generation`] `texture` was uploaded from
+    uploaded: u32,
+    pub shader: TextShader<G>,
+}
+
+impl<G: GlBackend> TextRenderer<G> {
+    /// 48 pixels to the em in a 512x512 atlas: plenty for Latin text at arm's length
+    pub fn new(gl: &G, programs: &ProgramRegistry<G>, font: Font) -> Result<Self, JsValue> {
+        let atlas = GlyphAtlas::new(512, 512, 48.0);
+        let texture =
+            Texture::from_pixels(gl, &atlas.pixels(), &SamplerDescription::linear_clamped())?;
+        Ok(Self {
+            font,
+            uploaded: atlas.generation,
+            atlas,
+            texture,
+            shader: TextShader::new(gl, programs)?,
+        })
+    }
+
+    /// Lay out and upload `text`.
+    pub fn label(
+        &mut self,
+        gl: &G,
+        text: &str,
+        style: &TextStyle,
+    ) -> Result<TextLabel<G>, JsValue> {
+        let layout = layout_text(&self.font, text, style);
+        let (vertices, indices) = text_mesh(
+            &self.font,
+            &mut self.atlas,
+            &layout,
+            style.size,
+            style.color,
+        );
+        self.upload_atlas(gl)?;
+
+        let vao = gl
+            .create_vertex_array()
+            .ok_or_else(|| JsValue::from_str("failed to create vao"))?;
+        gl.bind_vertex_array(Some(&vao));
+        let vertices = InterleavedGlBuffer::new_bound(
+            gl,
+            &vertices,
+            WebGl2RenderingContext::ARRAY_BUFFER,
+            WebGl2RenderingContext::STATIC_DRAW,
+        )?;
+        vertices.vertex_attrib_pointers(
+            gl,
+            &[self.shader.sal_xy, self.shader.sal_uv, self.shader.sal_rgba],
+        );
+        let indices =
+            IndexBuffer::new_bound_narrowest(gl, &indices, WebGl2RenderingContext::STATIC_DRAW)?;
+        gl.bind_vertex_array(None);
+
+        Ok(TextLabel {
+            text: text.to_string(),
+            style: *style,
+            width: layout.width,
+            height: layout.height,
+            vertices,
+            indices,
+            vao,
+        })
+    }
+
+    /// Replace the text of `label`, keeping its style. Does nothing if the text is unchanged.
+    pub fn set_text(
+        &mut self,
+        gl: &G,
+        label: &mut TextLabel<G>,
+        text: &str,
+    ) -> Result<(), JsValue> {
+        if label.text == text {
+            return Ok(());
+        }
+        let new = self.label(gl, text, &label.style)?;
+        std::mem::replace(label, new).release(gl);
+        Ok(())
+    }
+
+    /// Upload the atlas again if glyphs were added since the last upload.
+    fn upload_atlas(&mut self, gl: &G) -> Result<(), JsValue> {
+        if self.uploaded == self.atlas.generation {
+            return Ok(());
+        }
+        let texture = Texture::from_pixels(
+            gl,
+            &self.atlas.pixels(),
+            &SamplerDescription::linear_clamped(),
+        )?;
+        std::mem::replace(&mut self.texture, texture).release(gl);
+        self.uploaded = self.atlas.generation;
+        Ok(())
+    }
+
+    /// Alpha blended over whatever is behind it, without writing depth,
+    /// so draw labels after the opaque objects they overlap.
+    pub fn draw(&self, gl: &G, label: &TextLabel<G>, model: &[f32; 16], mode: ViewMode) {
+        let tex_index = 0;
+        self.texture.bind(gl, tex_index);
+
+        gl.enable(WebGl2RenderingContext::BLEND);
+        gl.blend_func(
+            WebGl2RenderingContext::SRC_ALPHA,
+            WebGl2RenderingContext::ONE_MINUS_SRC_ALPHA,
+        );
+        gl.depth_mask(false);
+        self.shader.for_view(mode).draw(
+            gl,
+            &label.indices,
+            &label.vao,
+            model,
+            TextureUnit(tex_index),
+        );
+        gl.depth_mask(true);
+        gl.disable(WebGl2RenderingContext::BLEND);
+    }
+
+    /// Make [`ViewMode::Multiview`] available to [`Self::draw`].
+    pub fn enable_multiview(
+        &mut self,
+        gl: &G,
+        programs: &ProgramRegistry<G>,
+    ) -> Result<(), JsValue> {
+        self.shader.enable_multiview(gl, programs)
+    }
+
+    /// Build the shader and atlas texture again on a restored context.
+    /// Labels need [`Self::recreate_label`] too.
+    pub fn recreate(&mut self, gl: &G, programs: &ProgramRegistry<G>) -> Result<(), JsValue> {
+        self.shader = TextShader::new(gl, programs)?;
+        self.texture = Texture::from_pixels(
+            gl,
+            &self.atlas.pixels(),
+            &SamplerDescription::linear_clamped(),
+        )?;
+        self.uploaded = self.atlas.generation;
+        Ok(())
+    }
+
+    /// Build `label`'s buffers again on a restored context.
+    /// The old handles died with the lost context, so they are dropped instead of released.
+    pub fn recreate_label(&mut self, gl: &G, label: &mut TextLabel<G>) -> Result<(), JsValue> {
+        *label = self.label(gl, &label.text, &label.style)?;
+        Ok(())
+    }
+
+    pub fn release(self, gl: &G) {
+        self.texture.release(gl);
+        self.shader.release(gl);
+    }
+}
@@ -0,0 +1,15 @@
+#include "camera.glsl"
+
+in vec2 xy;
+in vec2 uv;
+in vec4 rgba;
+uniform mat4 model;
+out vec2 uv2;
+out vec4 rgba2;
+
+void main()
+{
+    gl_Position = model_to_clip(model, xy);
+    uv2 = uv;
+    rgba2 = rgba;
+}
@@ -1,9 +1,15 @@
-#version 300 es
-precision highp float;
+#include "common.glsl"
+
 in vec2 uv;
+in vec4 tint2;
 uniform sampler2D tex;
 out vec4 color;
 
 void main() {
-    color = texture(tex, uv);
+    color = texture(tex, uv) * tint2;
+#ifdef ALPHA_CUTOFF
+    if (color.a < ALPHA_CUTOFF) {
+        discard;
+    }
+#endif
 }
@@ -0,0 +1,412 @@
+use crate::backend::{GlBackend, TEXTURE_MAX_ANISOTROPY_EXT};
+use crate::ktx2::{CompressedFormat, CompressionSupport, Ktx2, Ktx2Encoding, Ktx2Transcoder};
+use image::DynamicImage;
+use std::borrow::Cow;
+use wasm_bindgen::JsValue;
+use web_sys::WebGl2RenderingContext;
+
+/// the extension that provides [`TEXTURE_MAX_ANISOTROPY_EXT`]
+pub const ANISOTROPY_EXTENSION: &str = "EXT_texture_filter_anisotropic";
+
+/// How the texels of an image should be interpreted when sampled.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ColorSpace {
+    /// the bytes are the values the shader sees (normal maps, masks, data)
+    Linear,
+    /// the bytes are sRGB encoded (photos, posters, anything painted) and GL decodes them when sampling
+    Srgb,
+}
+
+/// The internal formats a [`Texture`] can have. Every [`DynamicImage`] is converted to one of these.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TextureFormat {
+    /// single channel coverage or masks, e.g. a glyph atlas; never chosen for an image
+    R8,
+    Rgb8,
+    Rgba8,
+    Srgb8,
+    Srgb8Alpha8,
+    /// from a KTX2 file; [`format`](Self::format) and [`channels`](Self::channels) describe the decoded texels
+    Compressed {
+        format: CompressedFormat,
+        srgb: bool,
+    },
+}
+
+impl TextureFormat {
+    /// Pick the format for `image`.
+    /// Images without alpha stay 3 channel, except that sRGB textures which need mipmaps get an opaque alpha channel,
+    /// because WebGL2 can only generate mipmaps for color-renderable formats and `SRGB8` is not one.
+    pub fn for_image(image: &DynamicImage, color_space: ColorSpace, mipmaps: bool) -> Self {
+        let alpha = image.color().has_alpha();
+        match color_space {
+            ColorSpace::Linear if alpha => TextureFormat::Rgba8,
+            ColorSpace::Linear => TextureFormat::Rgb8,
+            ColorSpace::Srgb if alpha || mipmaps => TextureFormat::Srgb8Alpha8,
+            ColorSpace::Srgb => TextureFormat::Srgb8,
+        }
+    }
+
+    pub fn internal_format(self) -> u32 {
+        match self {
+            TextureFormat::R8 => WebGl2RenderingContext::R8,
+            TextureFormat::Rgb8 => WebGl2RenderingContext::RGB8,
+            TextureFormat::Rgba8 => WebGl2RenderingContext::RGBA8,
+            TextureFormat::Srgb8 => WebGl2RenderingContext::SRGB8,
+            TextureFormat::Srgb8Alpha8 => WebGl2RenderingContext::SRGB8_ALPHA8,
+            TextureFormat::Compressed { format, srgb } => format.internal_format(srgb),
+        }
+    }
+
+    /// the `format` argument of `texImage2D`; the type is always `UNSIGNED_BYTE`
+    pub fn format(self) -> u32 {
+        match self.channels() {
+            1 => WebGl2RenderingContext::RED,
+            3 => WebGl2RenderingContext::RGB,
+            _ => WebGl2RenderingContext::RGBA,
+        }
+    }
+
+    pub fn channels(self) -> usize {
+        match self {
+            TextureFormat::R8 => 1,
+            TextureFormat::Rgb8 | TextureFormat::Srgb8 => 3,
+            TextureFormat::Rgba8 | TextureFormat::Srgb8Alpha8 => 4,
+            TextureFormat::Compressed { format, .. } if format.has_alpha() => 4,
+            TextureFormat::Compressed { .. } => 3,
+        }
+    }
+
+    pub fn is_srgb(self) -> bool {
+        matches!(
+            self,
+            TextureFormat::Srgb8
+                | TextureFormat::Srgb8Alpha8
+                | TextureFormat::Compressed { srgb: true, .. }
+        )
+    }
+}
+
+/// An image converted to 8 bit channels and laid out the way `texImage2D` reads it.
+#[derive(Debug, Clone, PartialEq)]
+pub struct TexturePixels {
+    pub width: u32,
+    pub height: u32,
+    pub format: TextureFormat,
+    /// Rows are padded to a multiple of 4 bytes to match the default `UNPACK_ALIGNMENT`.
+    pub bytes: Vec<u8>,
+}
+
+impl TexturePixels {
+    /// Convert any [`DynamicImage`]: gray images are expanded to RGB(A),
+    /// and 16 bit and floating point channels are scaled (and clamped) to 8 bits.
+    pub fn from_image(image: &DynamicImage, color_space: ColorSpace, mipmaps: bool) -> Self {
+        let format = TextureFormat::for_image(image, color_space, mipmaps);
+        let width = image.width();
+        let height = image.height();
+        let tight = match format.channels() {
+            3 => image.to_rgb8().into_raw(),
+            _ => image.to_rgba8().into_raw(),
+        };
+
+        let row_len = width as usize * format.channels();
+        let stride = Self::row_stride(width, format);
+        let bytes = if stride == row_len {
+            tight
+        } else {
+            let mut padded = vec![0; stride * height as usize];
+            for (src, dst) in tight
+                .chunks_exact(row_len)
+                .zip(padded.chunks_exact_mut(stride))
+            {
+                dst[..row_len].copy_from_slice(src);
+            }
+            padded
+        };
+
+        Self {
+            width,
+            height,
+            format,
+            bytes,
+        }
+    }
+
+    /// bytes from the start of one row to the start of the next
+    pub fn row_stride(width: u32, format: TextureFormat) -> usize {
+        (width as usize * format.channels()).next_multiple_of(4)
+    }
+}
+
+/// Filtering and wrapping for a [`Texture`]. The filters and wrap modes are GL enums.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct SamplerDescription {
+    pub min_filter: u32,
+    pub mag_filter: u32,
+    pub wrap_s: u32,
+    pub wrap_t: u32,
+    /// Only applied when the context has [`ANISOTROPY_EXTENSION`]; GL clamps it to what the hardware supports.
+    pub max_anisotropy: Option<f32>,
+}
+
+impl Default for SamplerDescription {
+    /// trilinear and repeating
+    fn default() -> Self {
+        Self {
+            min_filter: WebGl2RenderingContext::LINEAR_MIPMAP_LINEAR,
+            mag_filter: WebGl2RenderingContext::LINEAR,
+            wrap_s: WebGl2RenderingContext::REPEAT,
+            wrap_t: WebGl2RenderingContext::REPEAT,
+            max_anisotropy: None,
+        }
+    }
+}
+
+impl SamplerDescription {
+    /// Bilinear without mipmaps, clamped to the edge: for images drawn at about their own size.
+    pub fn linear_clamped() -> Self {
+        Self {
+            min_filter: WebGl2RenderingContext::LINEAR,
+            mag_filter: WebGl2RenderingContext::LINEAR,
+            wrap_s: WebGl2RenderingContext::CLAMP_TO_EDGE,
+            wrap_t: WebGl2RenderingContext::CLAMP_TO_EDGE,
+            max_anisotropy: None,
+        }
+    }
+
+    /// Point sampling without mipmaps, clamped to the edge: for pixel art and lookup tables.
+    pub fn nearest() -> Self {
+        Self {
+            min_filter: WebGl2RenderingContext::NEAREST,
+            mag_filter: WebGl2RenderingContext::NEAREST,
+            ..Self::linear_clamped()
+        }
+    }
+
+    pub fn with_anisotropy(self, max_anisotropy: f32) -> Self {
+        Self {
+            max_anisotropy: Some(max_anisotropy),
+            ..self
+        }
+    }
+
+    /// whether the minification filter reads mip levels, so the texture needs them generated
+    pub fn uses_mipmaps(&self) -> bool {
+        matches!(
+            self.min_filter,
+            WebGl2RenderingContext::NEAREST_MIPMAP_NEAREST
+                | WebGl2RenderingContext::LINEAR_MIPMAP_NEAREST
+                | WebGl2RenderingContext::NEAREST_MIPMAP_LINEAR
+                | WebGl2RenderingContext::LINEAR_MIPMAP_LINEAR
+        )
+    }
+
+    /// set these parameters on the texture bound to `TEXTURE_2D`
+    fn apply<G: GlBackend>(&self, gl: &G) {
+        let target = WebGl2RenderingContext::TEXTURE_2D;
+        for (pname, param) in [
+            (WebGl2RenderingContext::TEXTURE_MIN_FILTER, self.min_filter),
+            (WebGl2RenderingContext::TEXTURE_MAG_FILTER, self.mag_filter),
+            (WebGl2RenderingContext::TEXTURE_WRAP_S, self.wrap_s),
+            (WebGl2RenderingContext::TEXTURE_WRAP_T, self.wrap_t),
+        ] {
+            gl.tex_parameteri(target, pname, param as i32);
+        }
+        if let Some(max_anisotropy) = self.max_anisotropy {
+            if gl.has_extension(ANISOTROPY_EXTENSION) {
+                gl.tex_parameterf(target, TEXTURE_MAX_ANISOTROPY_EXT, max_anisotropy);
+            }
+        }
+    }
+}
+
+/// A 2D texture together with what was uploaded into it.
+pub struct Texture<G: GlBackend = WebGl2RenderingContext> {
+    pub texture: G::Texture,
+    pub width: u32,
+    pub height: u32,
+    pub format: TextureFormat,
+    pub sampler: SamplerDescription,
+}
+
+impl<G: GlBackend> Texture<G> {
+    /// Convert `image` (see [`TexturePixels::from_image`]) and upload it.
+    /// Mipmaps are generated when the sampler's minification filter needs them.
+    #[track_caller]
+    pub fn from_image(
+        gl: &G,
+        image: &DynamicImage,
+        color_space: ColorSpace,
+        sampler: &SamplerDescription,
+    ) -> Result<Self, JsValue> {
+        let pixels = TexturePixels::from_image(image, color_space, sampler.uses_mipmaps());
+        Self::from_pixels(gl, &pixels, sampler)
+    }
+
+    /// Upload already converted pixels. Leaves the texture bound to `TEXTURE_2D` on the active unit.
+    #[track_caller]
+    pub fn from_pixels(
+        gl: &G,
+        pixels: &TexturePixels,
+        sampler: &SamplerDescription,
+    ) -> Result<Self, JsValue> {
+        let texture = gl
+            .create_texture()
+            .ok_or_else(|| JsValue::from_str("failed to create texture"))?;
+        gl.bind_texture(WebGl2RenderingContext::TEXTURE_2D, Some(&texture));
+        if let Err(e) = gl.tex_image_2d_with_u8_array(
+            WebGl2RenderingContext::TEXTURE_2D,
+            0,
+            pixels.format.internal_format() as i32,
+            pixels.width.try_into().unwrap(),
+            pixels.height.try_into().unwrap(),
+            0,
+            pixels.format.format(),
+            WebGl2RenderingContext::UNSIGNED_BYTE,
+            Some(&pixels.bytes),
+        ) {
+            gl.delete_texture(Some(&texture));
+            return Err(e);
+        }
+        sampler.apply(gl);
+        if sampler.uses_mipmaps() {
+            gl.generate_mipmap(WebGl2RenderingContext::TEXTURE_2D);
+        }
+
+        Ok(Self {
+            texture,
+            width: pixels.width,
+            height: pixels.height,
+            format: pixels.format,
+            sampler: *sampler,
+        })
+    }
+
+    /// Upload a KTX2 file. Payloads already in a GPU format are uploaded as they are (and fail if the
+    /// context cannot sample them). Basis Universal payloads go through `transcoder` to the best format
+    /// [`CompressionSupport::choose`] finds, or to RGBA8 when the context has no compressed formats.
+    ///
+    /// Compressed textures cannot have mipmaps generated, so when the file stores fewer levels than
+    /// the sampler wants, `TEXTURE_MAX_LEVEL` is lowered to keep the texture complete.
+    #[track_caller]
+    pub fn from_ktx2(
+        gl: &G,
+        bytes: &[u8],
+        sampler: &SamplerDescription,
+        transcoder: Option<&dyn Ktx2Transcoder>,
+    ) -> Result<Self, JsValue> {
+        let ktx2 = Ktx2::parse(bytes)?;
+        let srgb = ktx2.is_srgb();
+        let support = CompressionSupport::query(gl);
+        let (target, levels): (_, Vec<Cow<[u8]>>) = match ktx2.encoding()? {
+            Ktx2Encoding::Raw(format) => {
+                if format.is_some_and(|format| !support.supports(format, srgb)) {
+                    return Err(JsValue::from(format!(
+                        "this context cannot sample {format:?} textures"
+                    )));
+                }
+                let levels = (0..ktx2.levels.len())
+                    .map(|level| Cow::Borrowed(ktx2.level_data(level)))
+                    .collect();
+                (format, levels)
+            }
+            Ktx2Encoding::Etc1s | Ktx2Encoding::Uastc => {
+                let transcoder = transcoder
+                    .ok_or_else(|| JsValue::from("Basis Universal textures need a transcoder"))?;
+                let target = support.choose(ktx2.has_alpha(), srgb);
+                let levels = transcoder.transcode(bytes, target)?;
+                (target, levels.into_iter().map(Cow::Owned).collect())
+            }
+        };
+        let format = match target {
+            Some(format) => TextureFormat::Compressed { format, srgb },
+            None if srgb => TextureFormat::Srgb8Alpha8,
+            None => TextureFormat::Rgba8,
+        };
+
+        let texture = gl
+            .create_texture()
+            .ok_or_else(|| JsValue::from_str("failed to create texture"))?;
+        gl.bind_texture(WebGl2RenderingContext::TEXTURE_2D, Some(&texture));
+        if let Err(e) = Self::upload_levels(gl, &ktx2, target, format, &levels) {
+            gl.delete_texture(Some(&texture));
+            return Err(e);
+        }
+        sampler.apply(gl);
+        if sampler.uses_mipmaps() {
+            let full_chain = 32 - ktx2.width().max(ktx2.height()).leading_zeros();
+            if ktx2.header.level_count == 0 && target.is_none() {
+                gl.generate_mipmap(WebGl2RenderingContext::TEXTURE_2D);
+            } else if (levels.len() as u32) < full_chain {
+                gl.tex_parameteri(
+                    WebGl2RenderingContext::TEXTURE_2D,
+                    WebGl2RenderingContext::TEXTURE_MAX_LEVEL,
+                    levels.len() as i32 - 1,
+                );
+            }
+        }
+
+        Ok(Self {
+            texture,
+            width: ktx2.width(),
+            height: ktx2.height(),
+            format,
+            sampler: *sampler,
+        })
+    }
+
+    fn upload_levels(
+        gl: &G,
+        ktx2: &Ktx2,
+        target: Option<CompressedFormat>,
+        format: TextureFormat,
+        levels: &[Cow<[u8]>],
+    ) -> Result<(), JsValue> {
+        for (level, data) in levels.iter().enumerate() {
+            let width = (ktx2.width() >> level).max(1);
+            let height = (ktx2.height() >> level).max(1);
+            match target {
+                Some(compressed) => {
+                    if data.len() != compressed.level_byte_len(width, height) {
+                        return Err(JsValue::from(format!(
+                            "KTX2 level {level} has {} bytes, {compressed:?} needs {}",
+                            data.len(),
+                            compressed.level_byte_len(width, height)
+                        )));
+                    }
+                    gl.compressed_tex_image_2d_with_u8_array(
+                        WebGl2RenderingContext::TEXTURE_2D,
+                        level as i32,
+                        format.internal_format(),
+                        width as i32,
+                        height as i32,
+                        0,
+                        data,
+                    );
+                }
+                None => gl.tex_image_2d_with_u8_array(
+                    WebGl2RenderingContext::TEXTURE_2D,
+                    level as i32,
+                    format.internal_format() as i32,
+                    width as i32,
+                    height as i32,
+                    0,
+                    WebGl2RenderingContext::RGBA,
+                    WebGl2RenderingContext::UNSIGNED_BYTE,
+                    Some(data),
+                )?,
+            }
+        }
+        Ok(())
+    }
+
+    /// Bind to `TEXTURE_2D` on texture unit `unit` (which also becomes the active unit).
+    pub fn bind(&self, gl: &G, unit: u32) {
+        gl.active_texture(WebGl2RenderingContext::TEXTURE0 + unit);
+        gl.bind_texture(WebGl2RenderingContext::TEXTURE_2D, Some(&self.texture));
+    }
+
+    pub fn release(self, gl: &G) {
+        gl.delete_texture(Some(&self.texture));
+    }
+}
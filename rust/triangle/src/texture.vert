@@ -1,11 +1,23 @@
-#version 300 es
+#include "camera.glsl"
+
 in vec2 xy;
-uniform mat4 mvp;
+#ifdef INSTANCED
+in mat4 model;
+in vec4 tint;
+#else
+uniform mat4 model;
+#endif
 out vec2 uv;
+out vec4 tint2;
 
 void main()
 {
-    gl_Position = mvp*vec4(xy,0.0, 1.0);
+    gl_Position = model_to_clip(model, xy);
     uv = (xy+1.0)*0.5;
     uv = vec2(uv.x, 1.0-uv.y);
+#ifdef INSTANCED
+    tint2 = tint;
+#else
+    tint2 = vec4(1.0);
+#endif
 }
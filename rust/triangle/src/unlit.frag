@@ -0,0 +1,14 @@
+#include "common.glsl"
+
+in vec2 uv2;
+// linear RGBA, multiplied with the texture
+uniform vec4 base_color;
+uniform sampler2D base_color_texture;
+out vec4 color;
+
+// glTF's KHR_materials_unlit: the base color as it is, whatever the lights
+void main() {
+    vec4 albedo = base_color*texture(base_color_texture, uv2);
+    // the framebuffer is not sRGB, so encode here
+    color = vec4(pow(albedo.rgb, vec3(1.0/2.2)), albedo.a);
+}
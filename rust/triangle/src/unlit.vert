@@ -0,0 +1,14 @@
+#include "camera.glsl"
+
+in vec3 position;
+in vec2 uv;
+uniform mat4 model;
+// e.g. glTF's KHR_texture_transform; the identity for most materials
+uniform mat3 uv_transform;
+out vec2 uv2;
+
+void main()
+{
+    uv2 = (uv_transform*vec3(uv, 1.0)).xy;
+    gl_Position = camera.view_projection*model*vec4(position, 1.0);
+}
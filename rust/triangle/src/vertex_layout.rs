@@ -0,0 +1,347 @@
+use crate::backend::GlBackend;
+use crate::gl_thin::GlType;
+use half::f16;
+use web_sys::WebGl2RenderingContext;
+
+/// How one field of an interleaved vertex is laid out in the GL buffer.
+/// These are the arguments for `vertexAttribPointer`, minus the stride which is shared by all fields.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct AttributeFormat {
+    /// the name of the rust field, which is usually also the name of the shader attribute
+    pub name: &'static str,
+    /// [`WebGl2RenderingContext::FLOAT`], [`WebGl2RenderingContext::UNSIGNED_BYTE`], etc.
+    pub gl_type: u32,
+    /// 1 through 4, per column
+    pub components: i32,
+    /// 1 except for matrices, which GL treats as one attribute per column at consecutive locations
+    pub columns: i32,
+    pub normalized: bool,
+    /// byte offset of this field from the start of the vertex
+    pub offset: usize,
+}
+
+impl AttributeFormat {
+    /// byte size of the whole field, before padding
+    pub fn size(&self) -> usize {
+        let scalar = match self.gl_type {
+            WebGl2RenderingContext::BYTE | WebGl2RenderingContext::UNSIGNED_BYTE => 1,
+            WebGl2RenderingContext::SHORT
+            | WebGl2RenderingContext::UNSIGNED_SHORT
+            | WebGl2RenderingContext::HALF_FLOAT => 2,
+            _ => 4,
+        };
+        scalar * usize::try_from(self.components * self.columns).unwrap()
+    }
+}
+
+/// The layout of every field in a vertex type, and the byte size of one whole vertex.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct VertexFormat {
+    pub attributes: Vec<AttributeFormat>,
+    pub stride: usize,
+}
+
+impl VertexFormat {
+    /// Configure the current VAO to pull each field from the buffer bound to `ARRAY_BUFFER`.
+    /// # params
+    /// * `locations` - the shader attribute location for each field, in the order the fields were declared
+    pub fn vertex_attrib_pointers<G: GlBackend>(&self, gl: &G, locations: &[u32]) {
+        self.vertex_attrib_pointers_with_divisor(gl, locations, 0);
+    }
+
+    /// Like [`Self::vertex_attrib_pointers`] but for per-instance data:
+    /// the attributes advance once every `divisor` instances instead of once per vertex.
+    /// A matrix field's location is that of its first column.
+    pub fn vertex_attrib_pointers_with_divisor<G: GlBackend>(
+        &self,
+        gl: &G,
+        locations: &[u32],
+        divisor: u32,
+    ) {
+        assert_eq!(
+            self.attributes.len(),
+            locations.len(),
+            "one shader attribute location is required for each vertex field"
+        );
+        let stride = self.stride.try_into().unwrap();
+        for (attribute, location) in self.attributes.iter().zip(locations) {
+            let column_size = attribute.size() / usize::try_from(attribute.columns).unwrap();
+            for column in 0..attribute.columns {
+                let location = *location + u32::try_from(column).unwrap();
+                let offset = attribute.offset + column_size * usize::try_from(column).unwrap();
+                gl.vertex_attrib_pointer_with_i32(
+                    location,
+                    attribute.components,
+                    attribute.gl_type,
+                    attribute.normalized,
+                    stride,
+                    offset.try_into().unwrap(),
+                );
+                gl.enable_vertex_attrib_array(location);
+                if divisor != 0 {
+                    gl.vertex_attrib_divisor(location, divisor);
+                }
+            }
+        }
+    }
+}
+
+/// A vertex type made of [`VertexComponent`] fields.
+/// Use [`vertex_layout!`] to declare the struct and implement this trait in one go.
+///
+/// Each field starts on a 4-byte boundary (WebGL requires attribute offsets to be aligned),
+/// so a `[u8; 3]` field occupies 4 bytes.
+pub trait VertexLayout {
+    fn format() -> VertexFormat;
+
+    /// append exactly `format().stride` bytes to `dst`
+    fn write_vertex(&self, dst: &mut Vec<u8>);
+
+    /// the bytes to upload for a whole buffer of vertices
+    fn to_bytes(vertices: &[Self]) -> Vec<u8>
+    where
+        Self: Sized,
+    {
+        let mut rval = Vec::with_capacity(Self::format().stride * vertices.len());
+        for vertex in vertices {
+            vertex.write_vertex(&mut rval);
+        }
+        rval
+    }
+}
+
+/// Something that can be a single field of a vertex: a scalar, an array of scalars, or a glam vector.
+pub trait VertexComponent {
+    /// [`WebGl2RenderingContext::FLOAT`], [`WebGl2RenderingContext::UNSIGNED_BYTE`], etc.
+    fn gl_type() -> u32;
+    /// per column
+    fn components() -> i32;
+    /// 1 except for matrices
+    fn columns() -> i32 {
+        1
+    }
+    /// ask GL to map integers onto 0..1 (or -1..1) instead of converting them directly to float
+    fn normalized() -> bool {
+        false
+    }
+    /// byte size before padding
+    fn size() -> usize;
+
+    fn write_le_bytes(&self, dst: &mut Vec<u8>);
+}
+
+/// The scalar types GL can read out of a vertex buffer.
+pub trait VertexScalar: GlType + Copy {
+    fn write_le_bytes(self, dst: &mut Vec<u8>);
+}
+
+macro_rules! vertex_scalar {
+    ($($t:ty),*) => {
+        $(
+            impl VertexScalar for $t {
+                fn write_le_bytes(self, dst: &mut Vec<u8>) {
+                    dst.extend_from_slice(&self.to_le_bytes());
+                }
+            }
+        )*
+    };
+}
+
+vertex_scalar!(f32, f16, u8, u16, u32, i8, i16, i32);
+
+impl<T: VertexScalar> VertexComponent for T {
+    fn gl_type() -> u32 {
+        T::my_type()
+    }
+
+    fn components() -> i32 {
+        1
+    }
+
+    fn size() -> usize {
+        size_of::<T>()
+    }
+
+    fn write_le_bytes(&self, dst: &mut Vec<u8>) {
+        VertexScalar::write_le_bytes(*self, dst);
+    }
+}
+
+impl<T: VertexScalar, const N: usize> VertexComponent for [T; N] {
+    fn gl_type() -> u32 {
+        T::my_type()
+    }
+
+    fn components() -> i32 {
+        N.try_into().unwrap()
+    }
+
+    fn size() -> usize {
+        N * size_of::<T>()
+    }
+
+    fn write_le_bytes(&self, dst: &mut Vec<u8>) {
+        for x in self {
+            VertexScalar::write_le_bytes(*x, dst);
+        }
+    }
+}
+
+macro_rules! vertex_glam_vector {
+    ($($t:ty => $n:literal),*) => {
+        $(
+            impl VertexComponent for $t {
+                fn gl_type() -> u32 {
+                    WebGl2RenderingContext::FLOAT
+                }
+
+                fn components() -> i32 {
+                    $n
+                }
+
+                fn size() -> usize {
+                    $n * size_of::<f32>()
+                }
+
+                fn write_le_bytes(&self, dst: &mut Vec<u8>) {
+                    self.to_array().write_le_bytes(dst);
+                }
+            }
+        )*
+    };
+}
+
+vertex_glam_vector!(glam::Vec2 => 2, glam::Vec3 => 3, glam::Vec4 => 4);
+
+/// Occupies 4 consecutive attribute locations, one per column (e.g. `in mat4 model;`).
+impl VertexComponent for glam::Mat4 {
+    fn gl_type() -> u32 {
+        WebGl2RenderingContext::FLOAT
+    }
+
+    fn components() -> i32 {
+        4
+    }
+
+    fn columns() -> i32 {
+        4
+    }
+
+    fn size() -> usize {
+        16 * size_of::<f32>()
+    }
+
+    fn write_le_bytes(&self, dst: &mut Vec<u8>) {
+        self.to_cols_array().write_le_bytes(dst);
+    }
+}
+
+/// Wrap an integer field so GL maps it onto 0..1 (or -1..1 for signed types), e.g. `u8` colors.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct Normalized<T>(pub T);
+
+impl<T: VertexComponent> VertexComponent for Normalized<T> {
+    fn gl_type() -> u32 {
+        T::gl_type()
+    }
+
+    fn components() -> i32 {
+        T::components()
+    }
+
+    fn columns() -> i32 {
+        T::columns()
+    }
+
+    fn normalized() -> bool {
+        true
+    }
+
+    fn size() -> usize {
+        T::size()
+    }
+
+    fn write_le_bytes(&self, dst: &mut Vec<u8>) {
+        self.0.write_le_bytes(dst);
+    }
+}
+
+/// round a field size up to the next 4-byte boundary
+#[must_use]
+pub const fn padded_size(size: usize) -> usize {
+    size.div_ceil(4) * 4
+}
+
+/// Accumulates [`AttributeFormat`]s with their offsets. Used by [`vertex_layout!`].
+#[derive(Default)]
+pub struct VertexFormatBuilder {
+    attributes: Vec<AttributeFormat>,
+    offset: usize,
+}
+
+impl VertexFormatBuilder {
+    #[must_use]
+    pub fn field<C: VertexComponent>(mut self, name: &'static str) -> Self {
+        self.attributes.push(AttributeFormat {
+            name,
+            gl_type: C::gl_type(),
+            components: C::components(),
+            columns: C::columns(),
+            normalized: C::normalized(),
+            offset: self.offset,
+        });
+        self.offset += padded_size(C::size());
+        self
+    }
+
+    #[must_use]
+    pub fn build(self) -> VertexFormat {
+        VertexFormat {
+            attributes: self.attributes,
+            stride: self.offset,
+        }
+    }
+}
+
+/// Append one field's bytes plus its padding. Used by [`vertex_layout!`].
+pub fn write_field<C: VertexComponent>(field: &C, dst: &mut Vec<u8>) {
+    field.write_le_bytes(dst);
+    dst.resize(dst.len() + padded_size(C::size()) - C::size(), 0);
+}
+
+/// Declare a vertex struct whose fields are all [`VertexComponent`]s and implement [`VertexLayout`] for it.
+/// ```ignore
+/// vertex_layout! {
+///     pub struct XYRGB {
+///         pub xy: [f32; 2],
+///         pub rgb: Normalized<[u8; 3]>,
+///     }
+/// }
+/// ```
+macro_rules! vertex_layout {
+    (
+        $(#[$meta:meta])*
+        $vis:vis struct $name:ident {
+            $( $(#[$field_meta:meta])* $field_vis:vis $field:ident : $ty:ty ),* $(,)?
+        }
+    ) => {
+        $(#[$meta])*
+        $vis struct $name {
+            $( $(#[$field_meta])* $field_vis $field: $ty ),*
+        }
+
+        impl $crate::vertex_layout::VertexLayout for $name {
+            fn format() -> $crate::vertex_layout::VertexFormat {
+                $crate::vertex_layout::VertexFormatBuilder::default()
+                    $( .field::<$ty>(stringify!($field)) )*
+                    .build()
+            }
+
+            fn write_vertex(&self, dst: &mut Vec<u8>) {
+                $( $crate::vertex_layout::write_field(&self.$field, dst); )*
+            }
+        }
+    };
+}
+
+pub(crate) use vertex_layout;
@@ -0,0 +1,251 @@
+use crate::backend::GlBackend;
+use js_sys::{Function, Promise, Reflect};
+use std::cell::{Cell, RefCell};
+use std::rc::Rc;
+use wasm_bindgen::prelude::*;
+use wasm_bindgen::JsCast;
+use web_sys::{HtmlMediaElement, HtmlVideoElement, WebGl2RenderingContext};
+
+/// Decides when a video has a frame worth uploading.
+///
+/// Where the browser has `requestVideoFrameCallback`, the callback raises a flag for every presented frame.
+/// Elsewhere the playback position is polled, which uploads once per animation frame while playing
+/// (the position advances even between video frames) but not at all while paused.
+#[derive(Debug, Default)]
+pub struct FrameGate {
+    /// raised by the `requestVideoFrameCallback` callback; `None` when polling
+    presented: Option<Rc<Cell<bool>>>,
+    /// the playback position of the last upload
+    uploaded_time: Option<f64>,
+}
+
+impl FrameGate {
+    pub fn polling() -> Self {
+        Self::default()
+    }
+
+    pub fn with_callback(presented: Rc<Cell<bool>>) -> Self {
+        Self {
+            presented: Some(presented),
+            uploaded_time: None,
+        }
+    }
+
+    /// Whether to upload now. `ready_state` and `current_time` are the video element's.
+    pub fn should_upload(&mut self, ready_state: u16, current_time: f64) -> bool {
+        if ready_state < HtmlMediaElement::HAVE_CURRENT_DATA {
+            return false;
+        }
+        let upload = match &self.presented {
+            // the first frame may have been presented before the callback was registered
+            Some(presented) => presented.replace(false) || self.uploaded_time.is_none(),
+            None => self.uploaded_time != Some(current_time),
+        };
+        if upload {
+            self.uploaded_time = Some(current_time);
+        }
+        upload
+    }
+
+    /// Upload at the next opportunity, e.g. into a new texture after the context was restored.
+    pub fn reset(&mut self) {
+        self.uploaded_time = None;
+    }
+}
+
+/// The self-registering `requestVideoFrameCallback` closure and the switch that stops it.
+struct FrameCallback {
+    closure: Rc<RefCell<Option<Closure<dyn FnMut(f64, JsValue)>>>>,
+    stopped: Rc<Cell<bool>>,
+}
+
+impl FrameCallback {
+    /// `None` if the browser has no `requestVideoFrameCallback`
+    fn start(video: &HtmlVideoElement, presented: Rc<Cell<bool>>) -> Option<Self> {
+        let request: Function = Reflect::get(video, &"requestVideoFrameCallback".into())
+            .ok()?
+            .dyn_into()
+            .ok()?;
+
+        let closure: Rc<RefCell<Option<Closure<dyn FnMut(f64, JsValue)>>>> =
+            Rc::new(RefCell::new(None));
+        let stopped = Rc::new(Cell::new(false));
+        {
+            let closure2 = closure.clone();
+            let stopped = stopped.clone();
+            let video = video.clone();
+            let request = request.clone();
+            *closure.borrow_mut() = Some(Closure::new(move |_now: f64, _metadata: JsValue| {
+                if stopped.get() {
+                    return;
+                }
+                presented.set(true);
+                let _ = request.call1(&video, closure2.borrow().as_ref().unwrap().as_ref());
+            }));
+        }
+        request
+            .call1(video, closure.borrow().as_ref().unwrap().as_ref())
+            .ok()?;
+        Some(Self { closure, stopped })
+    }
+
+    fn stop(self) {
+        self.stopped.set(true);
+        // breaks the closure's reference to itself
+        self.closure.borrow_mut().take();
+    }
+}
+
+/// A texture that shows whatever an `HtmlVideoElement` is playing.
+/// Call [`Self::update`] once per frame; it only uploads when there is a new frame.
+pub struct VideoTexture<G: GlBackend = WebGl2RenderingContext> {
+    pub video: HtmlVideoElement,
+    texture: G::Texture,
+    gate: FrameGate,
+    frame_callback: Option<FrameCallback>,
+}
+
+impl<G: GlBackend> VideoTexture<G> {
+    #[track_caller]
+    pub fn new(gl: &G, video: HtmlVideoElement) -> Result<Self, JsValue> {
+        let texture = Self::create_texture(gl)?;
+        let presented = Rc::new(Cell::new(false));
+        let frame_callback = FrameCallback::start(&video, presented.clone());
+        let gate = match frame_callback {
+            Some(_) => FrameGate::with_callback(presented),
+            None => FrameGate::polling(),
+        };
+        Ok(Self {
+            video,
+            texture,
+            gate,
+            frame_callback,
+        })
+    }
+
+    /// A new muted, looping `<video>` (not attached to the document) which starts loading `url`.
+    /// The server has to allow cross-origin use for the frames to be uploadable.
+    #[track_caller]
+    pub fn from_url(gl: &G, url: &str) -> Result<Self, JsValue> {
+        let video: HtmlVideoElement = crate::helper::document()?
+            .create_element("video")?
+            .dyn_into()?;
+        video.set_cross_origin(Some("anonymous"));
+        video.set_muted(true);
+        video.set_loop(true);
+        video.set_attribute("playsinline", "")?;
+        video.set_preload("auto");
+        video.set_src(url);
+        Self::new(gl, video)
+    }
+
+    /// bilinear, clamped, no mipmaps: regenerating them every frame would cost more than it saves
+    #[track_caller]
+    fn create_texture(gl: &G) -> Result<G::Texture, JsValue> {
+        let texture = gl
+            .create_texture()
+            .ok_or_else(|| JsValue::from_str("failed to create texture"))?;
+        gl.bind_texture(WebGl2RenderingContext::TEXTURE_2D, Some(&texture));
+        for (pname, param) in [
+            (
+                WebGl2RenderingContext::TEXTURE_MIN_FILTER,
+                WebGl2RenderingContext::LINEAR,
+            ),
+            (
+                WebGl2RenderingContext::TEXTURE_MAG_FILTER,
+                WebGl2RenderingContext::LINEAR,
+            ),
+            (
+                WebGl2RenderingContext::TEXTURE_WRAP_S,
+                WebGl2RenderingContext::CLAMP_TO_EDGE,
+            ),
+            (
+                WebGl2RenderingContext::TEXTURE_WRAP_T,
+                WebGl2RenderingContext::CLAMP_TO_EDGE,
+            ),
+        ] {
+            gl.tex_parameteri(WebGl2RenderingContext::TEXTURE_2D, pname, param as i32);
+        }
+        Ok(texture)
+    }
+
+    /// Upload the current frame if it is new. Returns whether it uploaded.
+    /// Leaves the texture bound to `TEXTURE_2D` on the active unit when it does.
+    pub fn update(&mut self, gl: &G) -> Result<bool, JsValue> {
+        if !self
+            .gate
+            .should_upload(self.video.ready_state(), self.video.current_time())
+        {
+            return Ok(false);
+        }
+        gl.bind_texture(WebGl2RenderingContext::TEXTURE_2D, Some(&self.texture));
+        gl.tex_image_2d_with_u32_and_u32_and_html_video_element(
+            WebGl2RenderingContext::TEXTURE_2D,
+            0,
+            WebGl2RenderingContext::RGBA as i32,
+            WebGl2RenderingContext::RGBA,
+            WebGl2RenderingContext::UNSIGNED_BYTE,
+            &self.video,
+        )?;
+        Ok(true)
+    }
+
+    /// Bind to `TEXTURE_2D` on texture unit `unit` (which also becomes the active unit).
+    pub fn bind(&self, gl: &G, unit: u32) {
+        gl.active_texture(WebGl2RenderingContext::TEXTURE0 + unit);
+        gl.bind_texture(WebGl2RenderingContext::TEXTURE_2D, Some(&self.texture));
+    }
+
+    /// width over height, or 1 until the video's metadata has loaded
+    pub fn aspect(&self) -> f32 {
+        match (self.video.video_width(), self.video.video_height()) {
+            (_, 0) | (0, _) => 1.0,
+            (width, height) => width as f32 / height as f32,
+        }
+    }
+
+    /// Resolves when playback starts, or rejects (e.g. because the browser wants a user gesture first).
+    pub fn play(&self) -> Result<Promise, JsValue> {
+        self.video.play()
+    }
+
+    pub fn pause(&self) -> Result<(), JsValue> {
+        self.video.pause()
+    }
+
+    pub fn is_paused(&self) -> bool {
+        self.video.paused()
+    }
+
+    /// jump to `seconds` from the start
+    pub fn seek(&self, seconds: f64) {
+        self.video.set_current_time(seconds);
+    }
+
+    /// the playback position in seconds
+    pub fn current_time(&self) -> f64 {
+        self.video.current_time()
+    }
+
+    /// in seconds; NaN until the metadata has loaded
+    pub fn duration(&self) -> f64 {
+        self.video.duration()
+    }
+
+    /// A new texture on a restored context, filled at the next [`Self::update`].
+    /// The element keeps playing throughout.
+    pub fn recreate(&mut self, gl: &G) -> Result<(), JsValue> {
+        self.texture = Self::create_texture(gl)?;
+        self.gate.reset();
+        Ok(())
+    }
+
+    /// Pause the video and delete the texture.
+    pub fn release(self, gl: &G) {
+        if let Some(frame_callback) = self.frame_callback {
+            frame_callback.stop();
+        }
+        let _ = self.video.pause();
+        gl.delete_texture(Some(&self.texture));
+    }
+}
@@ -0,0 +1,268 @@
+#!/usr/bin/env python3
+"""Writes the sample models the triangle crate's tests import (and the page can load):
+
+* tabletop.glb - one binary file: a textured box (KHR_texture_transform, embedded PNG)
+  with an unlit sign (KHR_materials_unlit, no indices, no normals) as its child
+* crate.gltf - JSON with its buffer and texture in crate.bin and crate.png next to it
+
+Run it from anywhere; the files are written next to this script.
+"""
+
+import json
+import math
+import os
+import struct
+import zlib
+
+HERE = os.path.dirname(os.path.abspath(__file__))
+
+FLOAT, UNSIGNED_SHORT, UNSIGNED_INT = 5126, 5123, 5125
+ARRAY_BUFFER, ELEMENT_ARRAY_BUFFER = 34962, 34963
+NEAREST, LINEAR, LINEAR_MIPMAP_LINEAR = 9728, 9729, 9987
+REPEAT, MIRRORED_REPEAT, CLAMP_TO_EDGE = 10497, 33648, 33071
+
+
+def png(width, height, pixel):
+    """An RGBA PNG of pixel(x, y) -> (r, g, b, a)."""
+    rows = b"".join(
+        b"\0" + b"".join(bytes(pixel(x, y)) for x in range(width)) for y in range(height)
+    )
+
+    def chunk(kind, data):
+        body = kind + data
+        return struct.pack(">I", len(data)) + body + struct.pack(">I", zlib.crc32(body))
+
+    header = struct.pack(">IIBBBBB", width, height, 8, 6, 0, 0, 0)
+    return (
+        b"\x89PNG\r\n\x1a\n"
+        + chunk(b"IHDR", header)
+        + chunk(b"IDAT", zlib.compress(rows))
+        + chunk(b"IEND", b"")
+    )
+
+
+def checker(a, b):
+    return lambda x, y: a if (x + y) % 2 == 0 else b
+
+
+def cube():
+    """24 vertices (a normal per face) and 36 counter-clockwise indices, one unit across."""
+    positions, normals, uvs, indices = [], [], [], []
+    for axis in range(3):
+        for sign in (1, -1):
+            normal = [0, 0, 0]
+            normal[axis] = sign
+            # u x v points along +axis
+            u_axis, v_axis = (axis + 1) % 3, (axis + 2) % 3
+            if sign < 0:
+                u_axis, v_axis = v_axis, u_axis
+            base = len(positions)
+            for u, v in ((0, 0), (1, 0), (1, 1), (0, 1)):
+                p = [0.0, 0.0, 0.0]
+                p[axis] = 0.5 * sign
+                p[u_axis] = u - 0.5
+                p[v_axis] = v - 0.5
+                positions.append(p)
+                normals.append([float(n) for n in normal])
+                uvs.append([float(u), float(1 - v)])
+            indices += [base, base + 1, base + 2, base, base + 2, base + 3]
+    return positions, normals, uvs, indices
+
+
+class Buffer:
+    """Packs bufferViews (each 4-byte aligned) and their accessors."""
+
+    def __init__(self):
+        self.data = bytearray()
+        self.views = []
+        self.accessors = []
+
+    def view(self, data, target=None):
+        while len(self.data) % 4:
+            self.data.append(0)
+        view = {"buffer": 0, "byteOffset": len(self.data), "byteLength": len(data)}
+        if target is not None:
+            view["target"] = target
+        self.data += data
+        self.views.append(view)
+        return len(self.views) - 1
+
+    def floats(self, rows, kind, bounds=False):
+        data = b"".join(struct.pack("<%df" % len(row), *row) for row in rows)
+        accessor = {
+            "bufferView": self.view(data, ARRAY_BUFFER),
+            "componentType": FLOAT,
+            "count": len(rows),
+            "type": kind,
+        }
+        if bounds:
+            accessor["min"] = [min(row[i] for row in rows) for i in range(len(rows[0]))]
+            accessor["max"] = [max(row[i] for row in rows) for i in range(len(rows[0]))]
+        self.accessors.append(accessor)
+        return len(self.accessors) - 1
+
+    def indices(self, indices, component_type):
+        fmt = "<%dH" if component_type == UNSIGNED_SHORT else "<%dI"
+        data = struct.pack(fmt % len(indices), *indices)
+        self.accessors.append(
+            {
+                "bufferView": self.view(data, ELEMENT_ARRAY_BUFFER),
+                "componentType": component_type,
+                "count": len(indices),
+                "type": "SCALAR",
+            }
+        )
+        return len(self.accessors) - 1
+
+
+def tabletop():
+    buffer = Buffer()
+    positions, normals, uvs, indices = cube()
+    box = {
+        "attributes": {
+            "POSITION": buffer.floats(positions, "VEC3", bounds=True),
+            "NORMAL": buffer.floats(normals, "VEC3"),
+            "TEXCOORD_0": buffer.floats(uvs, "VEC2"),
+        },
+        "indices": buffer.indices(indices, UNSIGNED_SHORT),
+        "material": 0,
+    }
+    # two triangles, no index buffer and no normals
+    quad = [[-0.5, -0.5, 0.0], [0.5, -0.5, 0.0], [0.5, 0.5, 0.0],
+            [-0.5, -0.5, 0.0], [0.5, 0.5, 0.0], [-0.5, 0.5, 0.0]]
+    sign = {
+        "attributes": {"POSITION": buffer.floats(quad, "VEC3", bounds=True)},
+        "material": 1,
+    }
+    wood = png(4, 4, checker((200, 150, 100, 255), (120, 80, 40, 255)))
+    image_view = buffer.view(wood)
+    half_turn = math.sqrt(0.5)
+    document = {
+        "asset": {"version": "2.0", "generator": "make_samples.py"},
+        "extensionsUsed": ["KHR_texture_transform", "KHR_materials_unlit"],
+        "scene": 0,
+        "scenes": [{"name": "tabletop", "nodes": [0]}],
+        "nodes": [
+            {
+                "name": "table",
+                "mesh": 0,
+                "translation": [0.0, 0.5, 0.0],
+                "scale": [2.0, 0.1, 1.0],
+                "children": [1],
+            },
+            {
+                "name": "sign",
+                "mesh": 1,
+                "translation": [0.0, 5.0, 0.0],
+                "rotation": [0.0, half_turn, 0.0, half_turn],
+            },
+        ],
+        "meshes": [
+            {"name": "box", "primitives": [box]},
+            {"name": "sign", "primitives": [sign]},
+        ],
+        "materials": [
+            {
+                "name": "wood",
+                "pbrMetallicRoughness": {
+                    "baseColorFactor": [1.0, 0.9, 0.8, 1.0],
+                    "baseColorTexture": {
+                        "index": 0,
+                        "extensions": {
+                            "KHR_texture_transform": {
+                                "offset": [0.5, 0.0],
+                                "rotation": math.pi / 2,
+                                "scale": [2.0, 3.0],
+                            }
+                        },
+                    },
+                    "metallicFactor": 0.0,
+                    "roughnessFactor": 0.8,
+                },
+                "emissiveFactor": [0.1, 0.0, 0.0],
+            },
+            {
+                "name": "glow",
+                "pbrMetallicRoughness": {"baseColorFactor": [1.0, 0.5, 0.0, 1.0]},
+                "extensions": {"KHR_materials_unlit": {}},
+            },
+        ],
+        "textures": [{"sampler": 0, "source": 0}],
+        "samplers": [
+            {
+                "magFilter": NEAREST,
+                "minFilter": NEAREST,
+                "wrapS": REPEAT,
+                "wrapT": MIRRORED_REPEAT,
+            }
+        ],
+        "images": [{"name": "wood", "bufferView": image_view, "mimeType": "image/png"}],
+        "accessors": buffer.accessors,
+        "bufferViews": buffer.views,
+        "buffers": [{"byteLength": len(buffer.data)}],
+    }
+
+    text = json.dumps(document, separators=(",", ":")).encode()
+    text += b" " * (-len(text) % 4)
+    binary = bytes(buffer.data) + b"\0" * (-len(buffer.data) % 4)
+    chunks = (
+        struct.pack("<II", len(text), 0x4E4F534A) + text
+        + struct.pack("<II", len(binary), 0x004E4942) + binary
+    )
+    with open(os.path.join(HERE, "tabletop.glb"), "wb") as f:
+        f.write(struct.pack("<III", 0x46546C67, 2, 12 + len(chunks)) + chunks)
+
+
+def crate():
+    buffer = Buffer()
+    positions, normals, uvs, indices = cube()
+    box = {
+        "attributes": {
+            "POSITION": buffer.floats(positions, "VEC3", bounds=True),
+            "NORMAL": buffer.floats(normals, "VEC3"),
+            "TEXCOORD_0": buffer.floats(uvs, "VEC2"),
+        },
+        "indices": buffer.indices(indices, UNSIGNED_INT),
+        "material": 0,
+    }
+    document = {
+        "asset": {"version": "2.0", "generator": "make_samples.py"},
+        "scenes": [{"nodes": [0, 1]}],
+        "nodes": [
+            {"name": "crate", "mesh": 0},
+            {"name": "lid", "mesh": 0, "matrix": [
+                1.0, 0.0, 0.0, 0.0,
+                0.0, 0.25, 0.0, 0.0,
+                0.0, 0.0, 1.0, 0.0,
+                0.0, 0.75, 0.0, 1.0,
+            ]},
+        ],
+        "meshes": [{"name": "box", "primitives": [box]}],
+        "materials": [
+            {
+                "name": "planks",
+                "pbrMetallicRoughness": {
+                    "baseColorTexture": {"index": 0},
+                    "metallicFactor": 0.0,
+                },
+            }
+        ],
+        "textures": [{"sampler": 0, "source": 0}],
+        "samplers": [{"wrapS": CLAMP_TO_EDGE, "wrapT": CLAMP_TO_EDGE}],
+        "images": [{"uri": "crate.png"}],
+        "accessors": buffer.accessors,
+        "bufferViews": buffer.views,
+        "buffers": [{"uri": "crate.bin", "byteLength": len(buffer.data)}],
+    }
+    with open(os.path.join(HERE, "crate.gltf"), "w") as f:
+        json.dump(document, f, indent=2)
+        f.write("\n")
+    with open(os.path.join(HERE, "crate.bin"), "wb") as f:
+        f.write(buffer.data)
+    with open(os.path.join(HERE, "crate.png"), "wb") as f:
+        f.write(png(8, 8, lambda x, y: (160, 110, 60, 255) if y % 4 else (90, 60, 30, 255)))
+
+
+if __name__ == "__main__":
+    tabletop()
+    crate()